@@ -0,0 +1,82 @@
+//! `rdapx abuse`: registrar abuse mailboxes for a batch of domains, one row
+//! per distinct (registrar, email, phone) with the domains it covers, ready
+//! for preparing mass abuse reports.
+
+use crate::table::{Cell, Column, Tabular};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// One registrar abuse contact and the domains that route to it.
+#[derive(Serialize, Debug)]
+pub struct Mailbox {
+    pub registrar: Option<String>,
+    pub abuse_email: Option<String>,
+    pub abuse_phone: Option<String>,
+    pub count: usize,
+    pub domains: Vec<String>,
+}
+
+impl Tabular for Mailbox {
+    const COLUMNS: &'static [Column] = &[
+        Column("REGISTRAR", 36),
+        Column("ABUSE EMAIL", 36),
+        Column("ABUSE PHONE", 18),
+        Column("COUNT", -5),
+    ];
+    const CSV_HEADER: &'static [&'static str] = &[
+        "registrar",
+        "abuse_email",
+        "abuse_phone",
+        "count",
+        "domains",
+    ];
+
+    fn table_row(&self) -> Vec<Cell> {
+        vec![
+            Cell::or_dash(self.registrar.as_deref()),
+            Cell::or_dash(self.abuse_email.as_deref()),
+            Cell::or_dash(self.abuse_phone.as_deref()),
+            self.count.to_string().into(),
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        let opt = |v: &Option<String>| v.clone().unwrap_or_default();
+        vec![
+            opt(&self.registrar),
+            opt(&self.abuse_email),
+            opt(&self.abuse_phone),
+            self.count.to_string(),
+            self.domains.join(" "),
+        ]
+    }
+}
+
+type Key = (Option<String>, Option<String>, Option<String>);
+
+/// Group looked-up domains by abuse contact, largest groups first.
+pub fn consolidate<'a>(results: impl IntoIterator<Item = (&'a str, Value)>) -> Vec<Mailbox> {
+    let mut groups: BTreeMap<Key, Vec<String>> = BTreeMap::new();
+    for (query, json) in results {
+        let key = (
+            crate::fields::registrar(&json),
+            crate::fields::abuse_email(&json).map(|e| e.to_lowercase()),
+            crate::fields::abuse_phone(&json),
+        );
+        groups.entry(key).or_default().push(query.to_string());
+    }
+    let mut boxes: Vec<Mailbox> = groups
+        .into_iter()
+        .map(|((registrar, abuse_email, abuse_phone), domains)| Mailbox {
+            registrar,
+            abuse_email,
+            abuse_phone,
+            count: domains.len(),
+            domains,
+        })
+        .collect();
+    // stable: ties stay in registrar order
+    boxes.sort_by_key(|b| std::cmp::Reverse(b.count));
+    boxes
+}
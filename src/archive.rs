@@ -0,0 +1,180 @@
+//! `--archive run.warc.gz`: capture every registry exchange as WARC 1.1
+//! `request`/`response` records so the raw responses behind a run can be
+//! replayed and audited with standard WARC tooling. Files ending in `.gz` get
+//! one gzip member per record, as usual for `.warc.gz`.
+
+use flate2::{write::GzEncoder, Compression};
+use reqwest::header::HeaderMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+static ARCHIVE: OnceLock<Archive> = OnceLock::new();
+static SEQ: AtomicU64 = AtomicU64::new(0);
+
+struct Archive {
+    file: Mutex<File>,
+    gzip: bool,
+}
+
+/// Open (truncate) the archive and write its `warcinfo` record.
+pub fn init(path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let archive = Archive {
+        file: Mutex::new(File::create(path)?),
+        gzip: path.extension().is_some_and(|e| e == "gz"),
+    };
+    let info = format!(
+        "software: rdapx/{}\r\nformat: WARC File Format 1.1\r\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    let filename = path
+        .file_name()
+        .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+    archive.write(
+        &[
+            ("WARC-Type", "warcinfo"),
+            ("WARC-Filename", &filename),
+            ("Content-Type", "application/warc-fields"),
+        ],
+        info.as_bytes(),
+    )?;
+    let _ = ARCHIVE.set(archive);
+    Ok(())
+}
+
+/// True when `--archive` was given.
+pub fn active() -> bool {
+    ARCHIVE.get().is_some()
+}
+
+/// Record one GET exchange. Bodies are stored decoded (reqwest undoes
+/// `Content-Encoding`), so that header is dropped and `Content-Length` rewritten.
+pub fn record(url: &str, status: reqwest::StatusCode, headers: &HeaderMap, body: &[u8]) {
+    let Some(archive) = ARCHIVE.get() else {
+        return;
+    };
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return;
+    };
+    let target = parsed.query().map_or_else(
+        || parsed.path().to_string(),
+        |q| format!("{}?{q}", parsed.path()),
+    );
+    let request = format!(
+        "GET {target} HTTP/1.1\r\nHost: {}\r\nUser-Agent: rdapx/{}\r\n\
+         Accept: application/rdap+json, application/json\r\n\r\n",
+        parsed.port().map_or_else(
+            || parsed.host_str().unwrap_or_default().to_string(),
+            |port| format!("{}:{port}", parsed.host_str().unwrap_or_default())
+        ),
+        env!("CARGO_PKG_VERSION")
+    );
+    let mut response = format!(
+        "HTTP/1.1 {} {}\r\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default()
+    );
+    for (name, value) in headers {
+        if name == "content-encoding" || name == "content-length" || name == "transfer-encoding" {
+            continue;
+        }
+        let _ = write!(
+            response,
+            "{name}: {}\r\n",
+            String::from_utf8_lossy(value.as_bytes())
+        );
+    }
+    let _ = write!(response, "Content-Length: {}\r\n\r\n", body.len());
+    let mut block = response.into_bytes();
+    block.extend_from_slice(body);
+
+    let request_id = record_id(url);
+    let result = archive
+        .write(
+            &[
+                ("WARC-Type", "request"),
+                ("WARC-Record-ID", &request_id),
+                ("WARC-Target-URI", url),
+                ("Content-Type", "application/http;msgtype=request"),
+            ],
+            request.as_bytes(),
+        )
+        .and_then(|()| {
+            archive.write(
+                &[
+                    ("WARC-Type", "response"),
+                    ("WARC-Target-URI", url),
+                    ("WARC-Concurrent-To", &request_id),
+                    ("Content-Type", "application/http;msgtype=response"),
+                ],
+                &block,
+            )
+        });
+    if let Err(e) = result {
+        eprintln!("archive write failed: {e}");
+    }
+}
+
+fn record_id(seed: &str) -> String {
+    let nanos = crate::deterministic::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let mut h = blake3::Hasher::new();
+    h.update(seed.as_bytes());
+    h.update(&nanos.to_le_bytes());
+    h.update(&SEQ.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    let hex = h.finalize().to_hex();
+    format!(
+        "<urn:uuid:{}-{}-4{}-8{}-{}>",
+        &hex[..8],
+        &hex[8..12],
+        &hex[13..16],
+        &hex[17..20],
+        &hex[20..32]
+    )
+}
+
+impl Archive {
+    fn write(&self, fields: &[(&str, &str)], block: &[u8]) -> io::Result<()> {
+        // WARC-Type first, then the record ID and date, then the rest
+        let mut rec = String::from("WARC/1.1\r\n");
+        let (kind, rest) = fields
+            .split_first()
+            .expect("record fields start with WARC-Type");
+        let _ = write!(rec, "{}: {}\r\n", kind.0, kind.1);
+        if !rest.iter().any(|(k, _)| *k == "WARC-Record-ID") {
+            let _ = write!(rec, "WARC-Record-ID: {}\r\n", record_id(""));
+        }
+        let _ = write!(
+            rec,
+            "WARC-Date: {}\r\n",
+            crate::format_timestamp(crate::now_secs())
+        );
+        for (k, v) in rest {
+            let _ = write!(rec, "{k}: {v}\r\n");
+        }
+        let _ = write!(rec, "Content-Length: {}\r\n\r\n", block.len());
+        let mut bytes = rec.into_bytes();
+        bytes.extend_from_slice(block);
+        bytes.extend_from_slice(b"\r\n\r\n");
+
+        if self.gzip {
+            let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+            gz.write_all(&bytes)?;
+            bytes = gz.finish()?;
+        }
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        file.write_all(&bytes)?;
+        file.flush()
+    }
+}
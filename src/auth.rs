@@ -0,0 +1,88 @@
+//! Named secrets (bearer tokens, OIDC tokens, webhook secrets) kept in the OS
+//! keyring under the `rdapx` service rather than in plaintext config.
+
+use std::error::Error;
+use std::sync::OnceLock;
+
+/// Bearer token sent on every request (`--auth`).
+static BEARER: OnceLock<String> = OnceLock::new();
+
+/// Load the secret named by `--auth`; a missing secret is an error.
+pub fn init(name: &str) -> Result<(), Box<dyn Error>> {
+    let token = get(name)?.ok_or_else(|| format!("no secret named '{name}'"))?;
+    let _ = BEARER.set(token);
+    Ok(())
+}
+
+pub fn bearer() -> Option<&'static str> {
+    BEARER.get().map(String::as_str)
+}
+
+/// Secret names are keyring accounts; keep them to a predictable charset.
+fn check_name(name: &str) -> Result<(), Box<dyn Error>> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+    {
+        return Err(format!("invalid secret name '{name}' (use letters, digits, - _ . :)").into());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "keychain")]
+fn entry(name: &str) -> Result<keyring::Entry, Box<dyn Error>> {
+    check_name(name)?;
+    Ok(keyring::Entry::new(
+        crate::crypt::KEYCHAIN_SERVICE,
+        &format!("secret:{name}"),
+    )?)
+}
+
+#[cfg(feature = "keychain")]
+pub fn set(name: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    entry(name)?
+        .set_password(value)
+        .map_err(|e| format!("keyring: {e}").into())
+}
+
+/// `Ok(None)` when no secret of that name is stored.
+#[cfg(feature = "keychain")]
+pub fn get(name: &str) -> Result<Option<String>, Box<dyn Error>> {
+    match entry(name)?.get_password() {
+        Ok(v) => Ok(Some(v)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("keyring: {e}").into()),
+    }
+}
+
+/// Returns whether a secret was removed.
+#[cfg(feature = "keychain")]
+pub fn delete(name: &str) -> Result<bool, Box<dyn Error>> {
+    match entry(name)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(format!("keyring: {e}").into()),
+    }
+}
+
+#[cfg(not(feature = "keychain"))]
+const UNSUPPORTED: &str = "this build has no keyring support (enable the `keychain` feature)";
+
+#[cfg(not(feature = "keychain"))]
+pub fn set(name: &str, _value: &str) -> Result<(), Box<dyn Error>> {
+    check_name(name)?;
+    Err(UNSUPPORTED.into())
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn get(name: &str) -> Result<Option<String>, Box<dyn Error>> {
+    check_name(name)?;
+    Err(UNSUPPORTED.into())
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn delete(name: &str) -> Result<bool, Box<dyn Error>> {
+    check_name(name)?;
+    Err(UNSUPPORTED.into())
+}
@@ -0,0 +1,126 @@
+//! ASN context for `get --asn-context`: the containing registration block and
+//! sibling ASNs held by the same registrant. Siblings come from the org's
+//! entity object when the RIR lists its `autnums` (ARIN does), otherwise from
+//! probing adjacent ASNs and keeping those with the same registrant.
+
+use crate::model::{AutnumContext, AutnumSummary};
+use crate::{fetch_url, fields, FetchOpts};
+use serde_json::Value;
+
+fn registrant(json: &Value) -> Option<&Value> {
+    fields::entities_with_role(json, "registrant").next()
+}
+
+fn handle(entity: &Value) -> Option<String> {
+    entity
+        .get("handle")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+/// Base of an RDAP URL: everything before `/autnum/`.
+fn base_of(url: &str) -> Option<&str> {
+    url.rfind("/autnum/").map(|at| &url[..=at])
+}
+
+pub async fn context(
+    client: &reqwest::Client,
+    url: &str,
+    json: &Value,
+    asn: u32,
+    neighbors: u32,
+    opts: &FetchOpts<'_>,
+) -> AutnumContext {
+    let org_entity = registrant(json);
+    let org = org_entity.and_then(handle);
+    let mut ctx = AutnumContext {
+        block: AutnumSummary::from_rdap(json),
+        delegation: crate::dataset::Delegations::load().ok().and_then(|db| {
+            db.lookup(&asn.to_string())
+                .map(|d| d.to_json(&format!("AS{asn}")))
+        }),
+        org_name: org_entity.and_then(|e| fields::vcard_prop(e, "fn")),
+        org: org.clone(),
+        siblings: Vec::new(),
+        source: "neighbors".into(),
+    };
+    let (Some(org), Some(base)) = (org, base_of(url)) else {
+        return ctx;
+    };
+
+    let entity_url = format!("{base}entity/{org}");
+    if let Ok(entity) = fetch_url(client, &entity_url, &org, opts).await {
+        if let Some(list) = entity.get("autnums").and_then(Value::as_array) {
+            ctx.siblings = list
+                .iter()
+                .filter_map(AutnumSummary::from_rdap)
+                .filter(|a| !(a.start..=a.end).contains(&asn))
+                .collect();
+            ctx.source = "entity".into();
+            return ctx;
+        }
+    }
+
+    let lo = asn.saturating_sub(neighbors);
+    let hi = asn.saturating_add(neighbors);
+    for n in (lo..=hi).filter(|&n| n != asn) {
+        if ctx
+            .block
+            .as_ref()
+            .is_some_and(|b| (b.start..=b.end).contains(&n))
+        {
+            continue; // same registration as the queried ASN
+        }
+        let neighbor_url = format!("{base}autnum/{n}");
+        let Ok(other) = fetch_url(client, &neighbor_url, &format!("AS{n}"), opts).await else {
+            continue;
+        };
+        if registrant(&other).and_then(handle).as_deref() == Some(org.as_str()) {
+            if let Some(a) = AutnumSummary::from_rdap(&other) {
+                ctx.siblings.push(a);
+            }
+        }
+    }
+    ctx
+}
+
+/// Human-readable block and sibling list for table output.
+pub fn print_context(ctx: &AutnumContext) {
+    use colored::Colorize;
+    let range = |a: &AutnumSummary| {
+        if a.start == a.end {
+            format!("AS{}", a.start)
+        } else {
+            format!("AS{}-AS{}", a.start, a.end)
+        }
+    };
+    if let Some(block) = &ctx.block {
+        println!("{} {} {}", "Block:".blue().bold(), range(block), block.name);
+    }
+    if let Some(d) = &ctx.delegation {
+        let get = |k: &str| d.get(k).and_then(Value::as_str).unwrap_or("-");
+        println!(
+            "{} {} {} allocated {}",
+            "RIR:".blue().bold(),
+            get("rir"),
+            get("country"),
+            get("allocated")
+        );
+    }
+    if let Some(org) = &ctx.org {
+        println!(
+            "{} {org} {}",
+            "Org:".blue().bold(),
+            ctx.org_name.as_deref().unwrap_or("")
+        );
+    }
+    println!(
+        "{} ({} via {})",
+        "Siblings:".yellow().bold(),
+        ctx.siblings.len(),
+        ctx.source
+    );
+    for s in &ctx.siblings {
+        println!("  {} {}", range(s), s.name);
+    }
+}
@@ -0,0 +1,166 @@
+//! Availability sweeps: expand base names across TLDs and check each domain
+//! with an RDAP `HEAD` (404 means unregistered), rate limited per registry.
+
+use crate::ratelimit::HostLimiter;
+use crate::Format;
+use futures::stream::{self, StreamExt};
+use serde_json::{Map, Value};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Available,
+    Registered,
+    Unknown,
+}
+
+impl Status {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Available => "available",
+            Self::Registered => "registered",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [Self::Available, Self::Registered, Self::Unknown]
+            .into_iter()
+            .find(|v| v.as_str() == s)
+    }
+}
+
+/// RDAP URL for a domain in any TLD, at the server it routes to.
+pub fn domain_url(domain: &str) -> String {
+    crate::classify_to_url(crate::Kind::Domain, domain)
+}
+
+async fn check(client: &reqwest::Client, limiter: &HostLimiter, domain: &str) -> Status {
+    let url = domain_url(domain);
+    limiter.wait(&crate::url_host(&url)).await;
+    let mut resp = client.head(&url).send().await;
+    // Some servers don't implement HEAD; fall back to GET.
+    if matches!(&resp, Ok(r) if matches!(r.status().as_u16(), 405 | 501)) {
+        limiter.wait(&crate::url_host(&url)).await;
+        resp = client.get(&url).send().await;
+    }
+    match resp.map(|r| r.status().as_u16()) {
+        Ok(404) => Status::Available,
+        Ok(200..=299) => Status::Registered,
+        _ => Status::Unknown,
+    }
+}
+
+/// Check each domain, at most `per_second` requests to any one registry,
+/// handing `(index, status)` to `done` as results arrive.
+pub async fn check_each<E>(
+    client: &reqwest::Client,
+    domains: &[String],
+    concurrency: usize,
+    per_second: u32,
+    mut done: impl FnMut(usize, Status) -> Result<(), E>,
+) -> Result<(), E> {
+    let limiter = HostLimiter::new(per_second);
+    let results = stream::iter(domains.iter().enumerate())
+        .map(|(i, domain)| {
+            let limiter = &limiter;
+            async move { (i, check(client, limiter, domain).await) }
+        })
+        .buffer_unordered(concurrency.max(1));
+    let mut results = std::pin::pin!(results);
+    while let Some((i, status)) = results.next().await {
+        done(i, status)?;
+    }
+    Ok(())
+}
+
+/// [`check_each`] collected in input order.
+pub async fn check_all(
+    client: &reqwest::Client,
+    domains: &[String],
+    concurrency: usize,
+    per_second: u32,
+) -> Vec<Status> {
+    let mut statuses = vec![Status::Unknown; domains.len()];
+    let _ = check_each(client, domains, concurrency, per_second, |i, s| {
+        statuses[i] = s;
+        Ok::<_, std::convert::Infallible>(())
+    })
+    .await;
+    statuses
+}
+
+/// Check every `name.tld` combination; returns one row per name with a
+/// status per TLD, in input order.
+pub async fn sweep(
+    client: &reqwest::Client,
+    names: &[String],
+    tlds: &[String],
+    concurrency: usize,
+    per_second: u32,
+) -> Vec<(String, Vec<Status>)> {
+    let domains: Vec<String> = names
+        .iter()
+        .flat_map(|n| tlds.iter().map(move |t| format!("{n}.{t}")))
+        .collect();
+    let statuses = check_all(client, &domains, concurrency, per_second).await;
+    names
+        .iter()
+        .zip(statuses.chunks(tlds.len().max(1)))
+        .map(|(n, s)| (n.clone(), s.to_vec()))
+        .collect()
+}
+
+/// JSON object for one matrix row: `{"name": "acme", "com": "registered", ...}`.
+pub fn row_json(name: &str, tlds: &[String], statuses: &[Status]) -> Value {
+    let mut obj = Map::new();
+    obj.insert("name".into(), Value::String(name.to_string()));
+    for (tld, status) in tlds.iter().zip(statuses) {
+        obj.insert(tld.clone(), Value::String(status.as_str().into()));
+    }
+    Value::Object(obj)
+}
+
+/// Print the sweep as a matrix (table/CSV) or one record per name.
+pub fn print_matrix(rows: &[(String, Vec<Status>)], tlds: &[String], fmt: Format) {
+    use colored::Colorize;
+    use std::fmt::Write;
+    match fmt {
+        Format::Table => {
+            let width = rows.iter().map(|(n, _)| n.len()).max().unwrap_or(4).max(4);
+            let mut header = format!("{:width$}", "NAME");
+            for tld in tlds {
+                let _ = write!(header, "  {:<10}", format!(".{tld}"));
+            }
+            println!("{}", header.blue().bold());
+            for (name, statuses) in rows {
+                print!("{name:width$}");
+                for s in statuses {
+                    let cell = format!("  {:<10}", s.as_str());
+                    match s {
+                        Status::Available => print!("{}", cell.green()),
+                        Status::Registered => print!("{}", cell.red()),
+                        Status::Unknown => print!("{}", cell.yellow()),
+                    }
+                }
+                println!();
+            }
+        }
+        Format::Csv => {
+            println!("name,{}", tlds.join(","));
+            for (name, statuses) in rows {
+                let cells: Vec<&str> = statuses.iter().map(|s| s.as_str()).collect();
+                println!("{},{}", crate::model::csv_escape(name), cells.join(","));
+            }
+        }
+        Format::Pretty | Format::Json | Format::Msgpack | Format::Cbor | Format::Ical => {
+            let fmt = if matches!(fmt, Format::Pretty) {
+                Format::Json
+            } else {
+                fmt
+            };
+            for (name, statuses) in rows {
+                crate::output(&row_json(name, tlds, statuses), fmt);
+            }
+        }
+    }
+}
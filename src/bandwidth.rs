@@ -0,0 +1,74 @@
+//! Per-registry download accounting for the bulk summary and `--max-bytes`.
+//! Counts response body bytes as received by the client (after content
+//! decoding), for every request the run makes.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+static BYTES: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+fn lock() -> std::sync::MutexGuard<'static, BTreeMap<String, u64>> {
+    BYTES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+pub fn record(url: &str, bytes: usize) {
+    let host = crate::url_host(url);
+    *lock().entry(host).or_default() += bytes as u64;
+}
+
+pub fn total() -> u64 {
+    lock().values().sum()
+}
+
+pub fn by_host(host: &str) -> u64 {
+    lock().get(host).copied().unwrap_or(0)
+}
+
+/// Parse a byte size: `500`, `64KB`, `2GB`, `1.5GiB` (KB/MB/GB are powers
+/// of 1000, KiB/MiB/GiB powers of 1024).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let t = s.trim();
+    let split = t
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(t.len());
+    let (num, unit) = t.split_at(split);
+    let n: f64 = num
+        .parse()
+        .map_err(|_| format!("invalid size '{s}' (expected e.g. 500MB, 2GB)"))?;
+    let mult: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" | "k" => 1_000,
+        "mb" | "m" => 1_000_000,
+        "gb" | "g" => 1_000_000_000,
+        "tb" | "t" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        other => return Err(format!("unknown size unit '{other}' in '{s}'")),
+    };
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    Ok((n * mult as f64) as u64)
+}
+
+/// Short human-readable size (`812 B`, `4.2 MB`).
+pub fn human(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut v = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while v >= 1000.0 && unit + 1 < UNITS.len() {
+        v /= 1000.0;
+        unit += 1;
+    }
+    format!("{v:.1} {}", UNITS[unit])
+}
@@ -0,0 +1,100 @@
+//! Response bodies parsed as they arrive: chunks go over a bounded channel
+//! to a blocking JSON parser, so a large response is never held as raw bytes
+//! next to its parsed `Value`. The bytes are only kept when `--archive`
+//! needs them. Large bodies are parsed with `--fields` applied (see
+//! [`crate::projection`]), and no body may exceed [`MAX_BODY`].
+
+use hyper::body::Bytes;
+use serde::de::{Deserialize, DeserializeSeed};
+use serde_json::Value;
+use std::error::Error;
+use std::io::{self, BufReader, Read};
+use tokio::sync::mpsc;
+
+/// Chunks in flight between the download and the parser.
+const CHUNKS: usize = 16;
+
+/// Bodies are cut off with an error past this size.
+const MAX_BODY: usize = 128 << 20;
+
+/// A parsed body, plus its bytes when they are archived.
+pub struct Body {
+    pub json: serde_json::Result<Value>,
+    pub raw: Option<Vec<u8>>,
+    pub len: usize,
+    /// Parsed with `--fields` applied, so not the complete response
+    pub projected: bool,
+}
+
+/// Download and parse `resp` chunk by chunk; network errors and oversized
+/// bodies end the read.
+pub async fn json(mut resp: reqwest::Response) -> Result<Body, Box<dyn Error + Send + Sync>> {
+    let projection = crate::projection::for_body(resp.content_length());
+    let projected = projection.is_some();
+    let (tx, rx) = mpsc::channel(CHUNKS);
+    let parser = tokio::task::spawn_blocking(move || {
+        let chunks = BufReader::new(Chunks {
+            rx,
+            cur: Bytes::new(),
+        });
+        let mut de = serde_json::Deserializer::from_reader(chunks);
+        let json = match projection {
+            Some(projection) => projection.deserialize(&mut de),
+            None => Value::deserialize(&mut de),
+        };
+        json.and_then(|json| de.end().map(|()| json))
+    });
+    let mut raw = crate::archive::active().then(Vec::new);
+    let mut len = 0;
+    let read = async {
+        while let Some(chunk) = resp.chunk().await? {
+            len += chunk.len();
+            if len > MAX_BODY {
+                return Err(format!(
+                    "{}: response body exceeds {} MiB",
+                    resp.url(),
+                    MAX_BODY >> 20
+                )
+                .into());
+            }
+            if let Some(raw) = raw.as_mut() {
+                raw.extend_from_slice(&chunk);
+            }
+            // the parser already gave up on invalid JSON
+            if tx.send(chunk).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+    .await;
+    drop(tx);
+    let json = parser
+        .await
+        .unwrap_or_else(|e| Err(serde_json::Error::io(io::Error::other(e))));
+    read.map(|()| Body {
+        json,
+        raw,
+        len,
+        projected,
+    })
+}
+
+struct Chunks {
+    rx: mpsc::Receiver<Bytes>,
+    cur: Bytes,
+}
+
+impl Read for Chunks {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.cur.is_empty() {
+            match self.rx.blocking_recv() {
+                Some(chunk) => self.cur = chunk,
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.cur.len());
+        buf[..n].copy_from_slice(&self.cur.split_to(n));
+        Ok(n)
+    }
+}
@@ -0,0 +1,1205 @@
+//! Query routing: which RDAP server answers a query, and at what URL.
+//!
+//! Every lookup classifies its query and builds the server URL several times
+//! (cache key, history, rate accounting, output), so the decision is made
+//! once per distinct query and remembered in `<cache>/bootstrap/routes.ndjson`
+//! for later runs, up to [`MAX_ROUTES`] queries. The file is started over
+//! when the bootstrap files or `servers.toml` change.
+//! `rdapx bootstrap resolve` prints it for scripting.
+//!
+//! Servers come from the IANA RDAP bootstrap registries (RFC 9224): the
+//! longest matching TLD suffix, IP prefix, or ASN range, and for entity
+//! handles the object tag after their last hyphen (`ABC123-ARIN`, RFC 8521).
+//! The files are kept under `<cache>/bootstrap` and refreshed daily before
+//! the first lookup; until they have been fetched, or for queries they don't
+//! cover, the built-in endpoints are used. Entries in `servers.toml` win over
+//! both.
+//!
+//! The IANA bootstrap registry files can be taken from an internal mirror or
+//! pinned local copies (`[bootstrap]` in the config), checked against pinned
+//! checksums, with IANA as the fallback; `rdapx bootstrap sources` shows
+//! which source each file would be loaded from.
+
+use crate::checkpoint::Journal;
+use crate::table::{Cell, Column, Tabular};
+use colored::Color;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Route {
+    pub query: String,
+    pub kind: String,
+    pub normalized: String,
+    pub url: String,
+    pub server: String,
+    /// `servers.toml`, `bootstrap`, or `built-in`
+    pub source: String,
+}
+
+/// Routes kept in memory and on disk; past this the map starts over.
+const MAX_ROUTES: usize = 10_000;
+
+/// What the stored routes were computed from.
+#[derive(Serialize, Deserialize, PartialEq, Eq)]
+struct RoutesFrom {
+    /// SHA-256 of `servers.toml` (empty when there is none)
+    servers: String,
+    /// Modification times of the bootstrap files, Unix seconds (0 if missing)
+    bootstrap: Vec<u64>,
+}
+
+impl RoutesFrom {
+    fn current(dir: &Path) -> Self {
+        let servers = servers_path()
+            .and_then(std::fs::read)
+            .map(|raw| crate::integrity::hex(&Sha256::digest(raw)))
+            .unwrap_or_default();
+        let bootstrap = FILES
+            .iter()
+            .map(|f| {
+                std::fs::metadata(dir.join(f))
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map_or(0, |d| d.as_secs())
+            })
+            .collect();
+        Self { servers, bootstrap }
+    }
+}
+
+#[derive(Default)]
+struct Routes {
+    map: HashMap<String, Route>,
+    /// Where new routes are recorded; `None` when the file can't be written
+    journal: Option<Journal<Route>>,
+}
+
+impl Routes {
+    /// The stored routes, if they were computed from the current data.
+    fn load() -> Self {
+        let Ok(dir) = dir() else {
+            return Self::default();
+        };
+        let path = dir.join("routes.ndjson");
+        let from = RoutesFrom::current(&dir);
+        let opened = std::fs::create_dir_all(&dir)
+            .map_err(Box::from)
+            .and_then(|()| Journal::<Route>::open(&path, &from, |_| String::new()))
+            .or_else(|_| {
+                let _ = std::fs::remove_file(&path);
+                Journal::open(&path, &from, |_| String::new())
+            });
+        let Ok((journal, stored)) = opened else {
+            return Self::default();
+        };
+        Self {
+            map: stored
+                .into_iter()
+                .take(MAX_ROUTES)
+                .map(|r| (r.query.clone(), r))
+                .collect(),
+            journal: Some(journal),
+        }
+    }
+
+    /// Forget every route, here and on disk.
+    fn reset(&mut self) {
+        if let Ok(path) = dir().map(|d| d.join("routes.ndjson")) {
+            let _ = std::fs::remove_file(path);
+        }
+        *self = Self::load();
+    }
+}
+
+static ROUTES: OnceLock<Mutex<Routes>> = OnceLock::new();
+
+fn compute(q: &str) -> Route {
+    let (kind, normalized) = crate::normalize(q);
+    let source = lookup(kind, &normalized).map_or("built-in", |(_, source)| source);
+    let url = crate::classify_to_url(kind, &normalized);
+    Route {
+        query: q.to_string(),
+        kind: kind.as_str().to_string(),
+        server: crate::url_host(&url),
+        normalized,
+        url,
+        source: source.to_string(),
+    }
+}
+
+/// Route for `q`, computed on first use.
+pub fn resolve(q: &str) -> Route {
+    let routes = ROUTES.get_or_init(|| Mutex::new(Routes::load()));
+    let mut routes = routes.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(route) = routes.map.get(q) {
+        return route.clone();
+    }
+    if routes.map.len() >= MAX_ROUTES {
+        routes.reset();
+    }
+    let route = compute(q);
+    if let Some(journal) = routes.journal.as_mut() {
+        if journal.append(&route).is_err() {
+            routes.journal = None;
+        }
+    }
+    routes.map.insert(q.to_string(), route.clone());
+    route
+}
+
+/* -------------------------- Bootstrap sources --------------------------- */
+
+/// IANA RDAP bootstrap registry files (RFC 9224, RFC 8521 for object tags).
+pub const FILES: [&str; 5] = [
+    "dns.json",
+    "ipv4.json",
+    "ipv6.json",
+    "asn.json",
+    "object-tags.json",
+];
+
+const IANA_BASE: &str = "https://data.iana.org/rdap/";
+
+/// A bootstrap file as loaded, and where it came from.
+#[derive(Serialize, Debug)]
+pub struct Loaded {
+    pub file: &'static str,
+    /// Mirror URL, local path, or the IANA URL
+    pub source: String,
+    pub sha256: String,
+    pub publication: Option<String>,
+    pub services: usize,
+    /// Sources tried first and why they were passed over
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+    #[serde(skip)]
+    pub body: Vec<u8>,
+}
+
+impl Tabular for Loaded {
+    const COLUMNS: &'static [Column] = &[
+        Column("FILE", 16),
+        Column("SERVICES", -8),
+        Column("PUBLICATION", 22),
+        Column("SOURCE", 0),
+    ];
+    const CSV_HEADER: &'static [&'static str] =
+        &["file", "services", "publication", "source", "sha256"];
+
+    fn table_row(&self) -> Vec<Cell> {
+        vec![
+            self.file.into(),
+            self.services.to_string().into(),
+            Cell::or_dash(self.publication.as_deref()),
+            self.source.as_str().into(),
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.file.to_string(),
+            self.services.to_string(),
+            self.publication.clone().unwrap_or_default(),
+            self.source.clone(),
+            self.sha256.clone(),
+        ]
+    }
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("https://") || source.starts_with("http://")
+}
+
+fn join(base: &str, file: &str) -> String {
+    if is_url(base) {
+        format!("{}/{file}", base.trim_end_matches('/'))
+    } else {
+        Path::new(base).join(file).display().to_string()
+    }
+}
+
+async fn read(client: &reqwest::Client, location: &str) -> Result<Vec<u8>, String> {
+    if is_url(location) {
+        let resp = client
+            .get(location)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| e.to_string())?;
+        Ok(resp.bytes().await.map_err(|e| e.to_string())?.to_vec())
+    } else {
+        std::fs::read(location).map_err(|e| e.to_string())
+    }
+}
+
+/// Checksum (when pinned) and shape checks for one copy of a bootstrap file.
+fn check(bytes: &[u8], pinned: Option<&String>) -> Result<(Value, String), String> {
+    let sha = crate::integrity::hex(&Sha256::digest(bytes));
+    if let Some(want) = pinned.filter(|w| !w.eq_ignore_ascii_case(&sha)) {
+        return Err(format!("checksum mismatch (expected {want}, got {sha})"));
+    }
+    let data: Value = serde_json::from_slice(bytes).map_err(|e| format!("not JSON: {e}"))?;
+    if !data.get("services").is_some_and(Value::is_array) {
+        return Err("not an RDAP bootstrap file (no \"services\")".to_string());
+    }
+    Ok((data, sha))
+}
+
+/// Load `file` from the configured `[bootstrap] sources` in order, then from
+/// IANA unless `iana_fallback = false`. Copies from configured sources must
+/// match their pinned `sha256`; a source that fails or mismatches is skipped
+/// with its reason recorded.
+pub async fn load(client: &reqwest::Client, file: &'static str) -> Result<Loaded, Box<dyn Error>> {
+    let cfg = &crate::config::get().bootstrap;
+    let pinned = cfg.sha256.get(file);
+    let mut candidates: Vec<(String, Option<&String>)> = cfg
+        .sources
+        .iter()
+        .map(|s| (join(s, file), pinned))
+        .collect();
+    if cfg.iana_fallback {
+        candidates.push((format!("{IANA_BASE}{file}"), None));
+    }
+    let mut skipped = Vec::new();
+    for (location, pinned) in candidates {
+        match read(client, &location).await.and_then(|bytes| {
+            let (data, sha256) = check(&bytes, pinned)?;
+            Ok((data, sha256, bytes))
+        }) {
+            Ok((data, sha256, body)) => {
+                return Ok(Loaded {
+                    file,
+                    source: location,
+                    sha256,
+                    publication: data
+                        .get("publication")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                    services: data["services"].as_array().map_or(0, Vec::len),
+                    skipped,
+                    body,
+                });
+            }
+            Err(reason) => skipped.push(format!("{location}: {reason}")),
+        }
+    }
+    if skipped.is_empty() {
+        return Err(
+            format!("{file}: no bootstrap sources configured and iana_fallback is off").into(),
+        );
+    }
+    Err(format!(
+        "{file}: every bootstrap source failed: {}",
+        skipped.join("; ")
+    )
+    .into())
+}
+
+/* ------------------------------ Registry -------------------------------- */
+
+/// Local server mappings that take precedence over the bootstrap data:
+/// `servers.toml` next to `config.toml`, or `$RDAPX_SERVERS`.
+///
+/// ```toml
+/// [tlds]
+/// test = "https://rdap.example-registry.test/"
+/// "corp.example" = "https://rdap.internal.example/"
+///
+/// [prefixes]
+/// "10.0.0.0/8" = "https://rdap.internal.example/"
+/// "fd00::/8" = "https://rdap.internal.example/"
+///
+/// [asns]
+/// "64512-65534" = "https://rdap.internal.example/"
+///
+/// [tags]
+/// CORP = "https://rdap.internal.example/"
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ServersFile {
+    tlds: BTreeMap<String, String>,
+    prefixes: BTreeMap<String, String>,
+    asns: BTreeMap<String, String>,
+    tags: BTreeMap<String, String>,
+}
+
+static OVERRIDES: OnceLock<Registry> = OnceLock::new();
+
+fn servers_path() -> std::io::Result<PathBuf> {
+    if let Some(p) = std::env::var_os("RDAPX_SERVERS") {
+        return Ok(PathBuf::from(p));
+    }
+    Ok(crate::project::config_dir()?.join("servers.toml"))
+}
+
+/// Load `servers.toml` once at startup; a missing file means no overrides.
+pub fn init() -> Result<(), Box<dyn Error>> {
+    let path = servers_path()?;
+    let raw = match std::fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("{}: {e}", path.display()).into()),
+    };
+    let file: ServersFile = toml::from_str(&raw).map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut overrides = Registry::default();
+    for (table, target, entries) in [
+        ("tlds", "dns.json", &file.tlds),
+        ("prefixes", "ipv4.json", &file.prefixes),
+        ("asns", "asn.json", &file.asns),
+        ("tags", "object-tags.json", &file.tags),
+    ] {
+        for (entry, url) in entries {
+            let url = if url.ends_with('/') {
+                url.clone()
+            } else {
+                format!("{url}/")
+            };
+            if !overrides.insert(target, entry, url) {
+                return Err(format!("{}: [{table}] {entry:?} is not valid", path.display()).into());
+            }
+        }
+    }
+    let _ = OVERRIDES.set(overrides);
+    Ok(())
+}
+
+/// Bootstrap files older than this are fetched again before the next lookup.
+const MAX_AGE: Duration = Duration::from_secs(86_400);
+
+/// Service entries of the bootstrap files, each with the preferred base
+/// URL (HTTPS when offered, with a trailing slash).
+#[derive(Default)]
+struct Registry {
+    /// TLD or multi-label suffix -> base URL
+    dns: HashMap<String, String>,
+    v4: Vec<(u32, u32, String)>,
+    v6: Vec<(u128, u32, String)>,
+    asn: Vec<(u32, u32, String)>,
+    /// Object tag (upper case) -> base URL
+    tags: HashMap<String, String>,
+    /// Preferred base URL -> the service's other base URLs, in listed order
+    alternates: HashMap<String, Vec<String>>,
+}
+
+static REGISTRY: OnceLock<RwLock<Arc<Registry>>> = OnceLock::new();
+
+/// Where fetched bootstrap files are kept.
+pub fn dir() -> std::io::Result<PathBuf> {
+    Ok(crate::project::cache_dir()?.join("bootstrap"))
+}
+
+fn registry() -> Arc<Registry> {
+    let lock = REGISTRY.get_or_init(|| RwLock::new(Arc::new(Registry::from_disk())));
+    lock.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Swap in freshly fetched data; routes computed from the old data are
+/// dropped, including the stored ones.
+fn replace(registry: Registry) {
+    let lock = REGISTRY.get_or_init(|| RwLock::new(Arc::default()));
+    *lock.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(registry);
+    if let Some(routes) = ROUTES.get() {
+        routes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .reset();
+    }
+}
+
+/// Base URLs of a service entry with trailing slashes, preferred first:
+/// HTTPS before plain HTTP, otherwise in listed order.
+fn bases(urls: &Value) -> Vec<String> {
+    let mut urls: Vec<String> = urls
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(|url| {
+            if url.ends_with('/') {
+                url.to_string()
+            } else {
+                format!("{url}/")
+            }
+        })
+        .collect();
+    urls.sort_by_key(|u| !u.starts_with("https://"));
+    urls.dedup();
+    urls
+}
+
+/// `(entries, base URLs)` for each service of a bootstrap file. Object tag
+/// services lead with the operator's contact addresses, which are skipped.
+fn services(data: &Value) -> impl Iterator<Item = (Vec<&str>, Vec<String>)> {
+    data.get("services")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|s| {
+            let s = s.as_array()?;
+            let s = &s[s.len().saturating_sub(2)..];
+            let entries = s
+                .first()?
+                .as_array()?
+                .iter()
+                .filter_map(Value::as_str)
+                .collect();
+            let bases = bases(s.get(1)?);
+            (!bases.is_empty()).then_some((entries, bases))
+        })
+}
+
+/// Network address and length of a `net/len` entry.
+fn prefix(entry: &str) -> Option<(IpAddr, u32)> {
+    let (addr, len) = entry.split_once('/')?;
+    Some((addr.parse().ok()?, len.parse().ok()?))
+}
+
+/// Whether `addr` lies in `net/len` for `bits`-wide addresses.
+const fn in_prefix(addr: u128, net: u128, len: u32, bits: u32) -> bool {
+    if len == 0 {
+        return true;
+    }
+    let shift = bits - len;
+    (addr >> shift) == (net >> shift)
+}
+
+impl Registry {
+    fn alternates_for(&self, url: &str) -> Vec<String> {
+        self.alternates
+            .iter()
+            .filter(|(base, _)| url.starts_with(base.as_str()))
+            .max_by_key(|(base, _)| base.len())
+            .map(|(base, alts)| {
+                alts.iter()
+                    .map(|alt| format!("{alt}{}", &url[base.len()..]))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn from_disk() -> Self {
+        let mut reg = Self::default();
+        let Ok(dir) = dir() else {
+            return reg;
+        };
+        for file in FILES {
+            let data = std::fs::read(dir.join(file))
+                .ok()
+                .and_then(|b| serde_json::from_slice::<Value>(&b).ok());
+            if let Some(data) = data {
+                reg.add(file, &data);
+            }
+        }
+        reg
+    }
+
+    fn add(&mut self, file: &str, data: &Value) {
+        for (entries, mut bases) in services(data) {
+            let url = bases.remove(0);
+            for entry in entries {
+                self.insert(file, entry, url.clone());
+            }
+            if !bases.is_empty() {
+                self.alternates.insert(url, bases);
+            }
+        }
+    }
+
+    /// Map one entry of `file`'s kind (TLD, `net/len`, or ASN range) to a
+    /// base URL; `false` when the entry doesn't parse.
+    fn insert(&mut self, file: &str, entry: &str, url: String) -> bool {
+        match file {
+            "dns.json" => {
+                self.dns.insert(entry.trim_matches('.').to_lowercase(), url);
+            }
+            "object-tags.json" => {
+                let tag = entry.trim().to_uppercase();
+                if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    return false;
+                }
+                self.tags.insert(tag, url);
+            }
+            "asn.json" => {
+                let (lo, hi) = entry.split_once('-').unwrap_or((entry, entry));
+                let (Ok(lo), Ok(hi)) = (lo.trim().parse(), hi.trim().parse()) else {
+                    return false;
+                };
+                self.asn.push((lo, hi, url));
+            }
+            _ => match prefix(entry) {
+                Some((IpAddr::V4(net), len)) if len <= 32 => {
+                    self.v4.push((u32::from(net), len, url));
+                }
+                Some((IpAddr::V6(net), len)) if len <= 128 => {
+                    self.v6.push((u128::from(net), len, url));
+                }
+                _ => return false,
+            },
+        }
+        true
+    }
+
+    /// Base URL of the server for a classified query, if the bootstrap data
+    /// covers it.
+    fn base_for(&self, kind: crate::Kind, normalized: &str) -> Option<String> {
+        match kind {
+            crate::Kind::Domain => {
+                let name = normalized.trim_end_matches('.').to_lowercase();
+                // longest registered suffix wins (`co.uk` before `uk`)
+                let labels: Vec<&str> = name.split('.').collect();
+                (0..labels.len())
+                    .map(|i| labels[i..].join("."))
+                    .find_map(|suffix| self.dns.get(&suffix).cloned())
+            }
+            crate::Kind::Asn => {
+                let n: u32 = normalized.parse().ok()?;
+                self.asn
+                    .iter()
+                    .find(|(lo, hi, _)| (*lo..=*hi).contains(&n))
+                    .map(|(_, _, url)| url.clone())
+            }
+            crate::Kind::Entity => {
+                let (_, tag) = normalized.rsplit_once('-')?;
+                self.tags.get(&tag.to_uppercase()).cloned()
+            }
+            crate::Kind::Ip => {
+                let addr = normalized.split('/').next()?.parse::<IpAddr>().ok()?;
+                match addr {
+                    IpAddr::V4(a) => self
+                        .v4
+                        .iter()
+                        .filter(|(net, len, _)| {
+                            in_prefix(u128::from(u32::from(a)), u128::from(*net), *len, 32)
+                        })
+                        .max_by_key(|(_, len, _)| *len)
+                        .map(|(_, _, url)| url.clone()),
+                    IpAddr::V6(a) => self
+                        .v6
+                        .iter()
+                        .filter(|(net, len, _)| in_prefix(u128::from(a), *net, *len, 128))
+                        .max_by_key(|(_, len, _)| *len)
+                        .map(|(_, _, url)| url.clone()),
+                }
+            }
+        }
+    }
+}
+
+/// Base URL for a classified query from `servers.toml`, else from the
+/// bootstrap registries; `None` when neither covers it.
+pub fn base_for(kind: crate::Kind, normalized: &str) -> Option<String> {
+    lookup(kind, normalized).map(|(base, _)| base)
+}
+
+fn lookup(kind: crate::Kind, normalized: &str) -> Option<(String, &'static str)> {
+    OVERRIDES
+        .get()
+        .and_then(|o| o.base_for(kind, normalized))
+        .map(|b| (b, "servers.toml"))
+        .or_else(|| {
+            registry()
+                .base_for(kind, normalized)
+                .map(|b| (b, "bootstrap"))
+        })
+}
+
+/// Whether names are registered directly under `suffix` (lower case): it
+/// is a zone listed in `servers.toml` or the DNS bootstrap registry.
+pub fn is_dns_suffix(suffix: &str) -> bool {
+    OVERRIDES.get().is_some_and(|o| o.dns.contains_key(suffix))
+        || registry().dns.contains_key(suffix)
+}
+
+/// Whether `tag` is an entity handle object tag listed in `servers.toml`,
+/// the object tag registry, or one of the RIRs' own.
+pub fn is_object_tag(tag: &str) -> bool {
+    let tag = tag.to_uppercase();
+    OVERRIDES.get().is_some_and(|o| o.tags.contains_key(&tag))
+        || registry().tags.contains_key(&tag)
+        || crate::fanout::RIR_TAGS.contains(&tag.as_str())
+}
+
+/// The same request at the other base URLs the bootstrap data lists for the
+/// service behind `url`, in order; empty for a single-URL service or a URL
+/// that didn't come from bootstrap data. When several bases prefix `url`
+/// (`https://host/` and `https://host/rdap/`), the longest one is the service.
+pub fn alternates(url: &str) -> Vec<String> {
+    registry().alternates_for(url)
+}
+
+/// Kinds of service entry listed by `rdapx servers list`.
+#[derive(clap::ValueEnum, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceKind {
+    Tld,
+    Ipv4,
+    Ipv6,
+    Asn,
+    /// Entity handle object tag (RFC 8521)
+    Tag,
+}
+
+impl ServiceKind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Tld => "tld",
+            Self::Ipv4 => "ipv4",
+            Self::Ipv6 => "ipv6",
+            Self::Asn => "asn",
+            Self::Tag => "tag",
+        }
+    }
+}
+
+/// One known RDAP service entry and where it is configured.
+#[derive(Serialize, Debug)]
+pub struct Service {
+    pub kind: ServiceKind,
+    /// TLD, `net/len`, ASN range, or `*` for a built-in default
+    pub entry: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alternates: Vec<String>,
+    /// `servers.toml`, `bootstrap`, or `built-in`
+    pub source: &'static str,
+}
+
+impl Tabular for Service {
+    const COLUMNS: &'static [Column] = &[
+        Column("KIND", 6),
+        Column("ENTRY", 28),
+        Column("URL", 48),
+        Column("SOURCE", 0),
+    ];
+    const CSV_HEADER: &'static [&'static str] = &["kind", "entry", "url", "alternates", "source"];
+
+    fn table_row(&self) -> Vec<Cell> {
+        let url = if self.alternates.is_empty() {
+            self.url.clone()
+        } else {
+            format!("{} (+{})", self.url, self.alternates.len())
+        };
+        vec![
+            self.kind.as_str().into(),
+            self.entry.as_str().into(),
+            url.into(),
+            self.source.into(),
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.kind.as_str().to_string(),
+            self.entry.clone(),
+            self.url.clone(),
+            self.alternates.join(" "),
+            self.source.to_string(),
+        ]
+    }
+}
+
+impl Registry {
+    fn list(&self, source: &'static str) -> Vec<Service> {
+        let service = |kind, entry: String, url: &String| Service {
+            kind,
+            entry,
+            url: url.clone(),
+            alternates: self.alternates.get(url).cloned().unwrap_or_default(),
+            source,
+        };
+        let mut dns: Vec<_> = self.dns.iter().collect();
+        dns.sort();
+        let mut v4: Vec<_> = self.v4.iter().collect();
+        v4.sort_by_key(|(net, len, _)| (*net, *len));
+        let mut v6: Vec<_> = self.v6.iter().collect();
+        v6.sort_by_key(|(net, len, _)| (*net, *len));
+        let mut asn: Vec<_> = self.asn.iter().collect();
+        asn.sort_by_key(|(lo, hi, _)| (*lo, *hi));
+        let mut tags: Vec<_> = self.tags.iter().collect();
+        tags.sort();
+
+        let mut out: Vec<Service> = dns
+            .into_iter()
+            .map(|(tld, url)| service(ServiceKind::Tld, tld.clone(), url))
+            .collect();
+        out.extend(v4.into_iter().map(|(net, len, url)| {
+            let net = std::net::Ipv4Addr::from(*net);
+            service(ServiceKind::Ipv4, format!("{net}/{len}"), url)
+        }));
+        out.extend(v6.into_iter().map(|(net, len, url)| {
+            let net = std::net::Ipv6Addr::from(*net);
+            service(ServiceKind::Ipv6, format!("{net}/{len}"), url)
+        }));
+        out.extend(asn.into_iter().map(|(lo, hi, url)| {
+            let range = if lo == hi {
+                lo.to_string()
+            } else {
+                format!("{lo}-{hi}")
+            };
+            service(ServiceKind::Asn, range, url)
+        }));
+        out.extend(
+            tags.into_iter()
+                .map(|(tag, url)| service(ServiceKind::Tag, tag.clone(), url)),
+        );
+        out
+    }
+}
+
+/// Every service entry from `servers.toml` and the bootstrap data, in that
+/// (precedence) order.
+pub fn services_list() -> Vec<Service> {
+    let mut out = OVERRIDES
+        .get()
+        .map(|o| o.list("servers.toml"))
+        .unwrap_or_default();
+    out.extend(registry().list("bootstrap"));
+    out
+}
+
+/// Outcome of `GET <base>help` for `rdapx servers check`.
+#[derive(Serialize, Debug)]
+pub struct Probe {
+    pub url: String,
+    pub status: Option<u16>,
+    pub latency_ms: u128,
+    /// `rdapConformance` of the help response
+    pub conformance: Vec<String>,
+    /// 2xx with a JSON body
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Tabular for Probe {
+    const COLUMNS: &'static [Column] = &[
+        Column("SERVER", 52),
+        Column("STATUS", 7),
+        Column("LATENCY", -9),
+        Column("CONFORMANCE", 0),
+    ];
+    const CSV_HEADER: &'static [&'static str] = &[
+        "url",
+        "status",
+        "latency_ms",
+        "healthy",
+        "conformance",
+        "error",
+    ];
+
+    fn table_row(&self) -> Vec<Cell> {
+        let status = match self.status {
+            Some(code) if self.healthy => Cell::colored(code.to_string(), Color::Green),
+            Some(code) => Cell::colored(code.to_string(), Color::Yellow),
+            None => Cell::colored("error", Color::Red),
+        };
+        let detail = self
+            .error
+            .clone()
+            .unwrap_or_else(|| self.conformance.join(", "));
+        vec![
+            self.url.as_str().into(),
+            status,
+            format!("{}ms", self.latency_ms).into(),
+            detail.into(),
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.url.clone(),
+            self.status.map(|s| s.to_string()).unwrap_or_default(),
+            self.latency_ms.to_string(),
+            self.healthy.to_string(),
+            self.conformance.join(" "),
+            self.error.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+pub async fn probe(client: &reqwest::Client, base: String) -> Probe {
+    let started = std::time::Instant::now();
+    let help = format!("{}/help", base.trim_end_matches('/'));
+    let mut probe = Probe {
+        url: base,
+        status: None,
+        latency_ms: 0,
+        conformance: Vec::new(),
+        healthy: false,
+        error: None,
+    };
+    let result = match client.get(&help).send().await {
+        Ok(resp) => {
+            probe.status = Some(resp.status().as_u16());
+            let ok = resp.status().is_success();
+            resp.json::<Value>()
+                .await
+                .map(|body| (ok, body))
+                .map_err(|e| format!("not an RDAP response: {e}"))
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    probe.latency_ms = crate::deterministic::elapsed(started).as_millis();
+    match result {
+        Ok((ok, body)) => {
+            probe.conformance = body
+                .get("rdapConformance")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect();
+            probe.healthy = ok;
+            if !ok {
+                probe.error = Some(format!("HTTP {}", probe.status.unwrap_or_default()));
+            }
+        }
+        Err(e) => probe.error = Some(e),
+    }
+    probe
+}
+
+/// When the files were last checked, and why refreshing them failed.
+static LAST_CHECK: tokio::sync::Mutex<Option<(Instant, Option<String>)>> =
+    tokio::sync::Mutex::const_new(None);
+static STALE_NOTE: std::sync::Once = std::sync::Once::new();
+
+/// After a failed refresh, how long lookups keep using the cached copy
+/// before trying again.
+const RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// Make sure current bootstrap data is in place before a lookup: files
+/// missing or older than a day are fetched again, so a long-running `serve`
+/// picks up new data too. When that fails the previous copy (or the
+/// built-in endpoints) keeps serving, with a note, unless `--strict` makes
+/// it an error; the fetch is retried after a few minutes.
+pub async fn ensure(client: &reqwest::Client) -> Result<(), crate::strict::Violation> {
+    let failed = {
+        let mut last = LAST_CHECK.lock().await;
+        let fresh = all_fresh();
+        match last.as_mut() {
+            Some((_, failed)) if fresh => *failed = None,
+            Some((at, _)) if at.elapsed() < RETRY_AFTER => {}
+            _ => *last = Some((Instant::now(), refresh_if_stale(client).await.err())),
+        }
+        last.as_ref().and_then(|(_, failed)| failed.clone())
+    };
+    if let Some(e) = failed {
+        crate::strict::check(crate::strict::Condition::Stale, || {
+            format!("bootstrap data not refreshed ({e}); not using what is cached")
+        })?;
+        STALE_NOTE.call_once(|| {
+            eprintln!("Note: bootstrap data not refreshed ({e}); using what is cached");
+        });
+    }
+    Ok(())
+}
+
+fn all_fresh() -> bool {
+    dir().is_ok_and(|dir| FILES.iter().all(|f| is_fresh(&dir.join(f))))
+}
+
+fn is_fresh(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .is_ok_and(|m| crate::deterministic::age(m) <= MAX_AGE)
+}
+
+async fn refresh_if_stale(client: &reqwest::Client) -> Result<(), String> {
+    if all_fresh() {
+        return Ok(());
+    }
+    let (_, mut failed) = refresh(client).await?;
+    match failed.len() {
+        0 => Ok(()),
+        1 => Err(failed.remove(0)),
+        n => Err(format!("{} (and {} more)", failed.remove(0), n - 1)),
+    }
+}
+
+/// Fetch all bootstrap files from their sources and store them,
+/// returning what was loaded and why the rest failed. Files that fail keep
+/// their previous copy.
+pub async fn refresh(client: &reqwest::Client) -> Result<(Vec<Loaded>, Vec<String>), String> {
+    let dir = dir().map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+    let mut loaded = Vec::new();
+    let mut failed = Vec::new();
+    for file in FILES {
+        let result = load(client, file).await.map_err(|e| e.to_string());
+        match result {
+            Ok(l) => {
+                let path = dir.join(file);
+                let tmp = path.with_extension("json.tmp");
+                std::fs::write(&tmp, &l.body)
+                    .and_then(|()| std::fs::rename(&tmp, &path))
+                    .map_err(|e| format!("{}: {e}", path.display()))?;
+                loaded.push(l);
+            }
+            Err(e) => failed.push(e),
+        }
+    }
+    if !loaded.is_empty() {
+        replace(Registry::from_disk());
+    }
+    Ok((loaded, failed))
+}
+
+/// State of one stored bootstrap file, for `rdapx bootstrap status`.
+#[derive(Serialize, Debug)]
+pub struct FileStatus {
+    pub file: &'static str,
+    pub path: PathBuf,
+    /// When the local copy was last written (RFC 3339); `None` if missing
+    pub updated: Option<String>,
+    pub age_secs: Option<u64>,
+    pub publication: Option<String>,
+    pub services: usize,
+    /// `fresh`, `stale` (refetched before the next lookup), or `missing`
+    pub state: &'static str,
+}
+
+impl Tabular for FileStatus {
+    const COLUMNS: &'static [Column] = &[
+        Column("FILE", 16),
+        Column("SERVICES", -8),
+        Column("PUBLICATION", 22),
+        Column("UPDATED", 22),
+        Column("AGE", -5),
+        Column("STATE", 0),
+    ];
+    const CSV_HEADER: &'static [&'static str] = &[
+        "file",
+        "services",
+        "publication",
+        "updated",
+        "age_secs",
+        "state",
+        "path",
+    ];
+
+    fn table_row(&self) -> Vec<Cell> {
+        let color = match self.state {
+            "fresh" => Color::Green,
+            "stale" => Color::Yellow,
+            _ => Color::Red,
+        };
+        vec![
+            self.file.into(),
+            self.services.to_string().into(),
+            Cell::or_dash(self.publication.as_deref()),
+            Cell::or_dash(self.updated.as_deref()),
+            self.age_secs
+                .map_or_else(|| "-".to_string(), crate::short_age)
+                .into(),
+            Cell::colored(self.state, color),
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.file.to_string(),
+            self.services.to_string(),
+            self.publication.clone().unwrap_or_default(),
+            self.updated.clone().unwrap_or_default(),
+            self.age_secs.map(|a| a.to_string()).unwrap_or_default(),
+            self.state.to_string(),
+            self.path.display().to_string(),
+        ]
+    }
+}
+
+/// Status of the stored bootstrap files.
+pub fn status() -> std::io::Result<Vec<FileStatus>> {
+    let dir = dir()?;
+    Ok(FILES
+        .iter()
+        .map(|&file| {
+            let path = dir.join(file);
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            let data = std::fs::read(&path)
+                .ok()
+                .and_then(|b| serde_json::from_slice::<Value>(&b).ok());
+            let age = modified.map(crate::deterministic::age);
+            FileStatus {
+                file,
+                updated: modified
+                    .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| crate::format_timestamp(d.as_secs())),
+                age_secs: age.map(|a| a.as_secs()),
+                publication: data
+                    .as_ref()
+                    .and_then(|d| d.get("publication"))
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                services: data
+                    .as_ref()
+                    .and_then(|d| d.get("services"))
+                    .and_then(Value::as_array)
+                    .map_or(0, Vec::len),
+                state: match age {
+                    None => "missing",
+                    Some(a) if a <= MAX_AGE => "fresh",
+                    Some(_) => "stale",
+                },
+                path,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Registry;
+    use crate::Kind;
+    use serde_json::json;
+
+    fn registry() -> Registry {
+        let mut reg = Registry::default();
+        reg.add(
+            "dns.json",
+            &json!({"services": [
+                [["uk"], ["https://uk.test/"]],
+                [["co.uk"], ["https://co-uk.test"]],
+                [["com", "net"], ["http://com.test/", "https://com.test/"]],
+            ]}),
+        );
+        reg.add(
+            "ipv4.json",
+            &json!({"services": [
+                [["10.0.0.0/8"], ["https://ten.test/"]],
+                [["10.1.0.0/16"], ["https://ten-one.test/"]],
+            ]}),
+        );
+        reg.add(
+            "ipv6.json",
+            &json!({"services": [[["2001:db8::/32"], ["https://doc.test/"]]]}),
+        );
+        reg.add(
+            "asn.json",
+            &json!({"services": [[["100-200", "300"], ["https://asn.test/"]]]}),
+        );
+        reg.add(
+            "object-tags.json",
+            &json!({"services": [[["ops@arin.test"], ["ARIN"], ["https://arin.test/"]]]}),
+        );
+        reg
+    }
+
+    fn base(kind: Kind, q: &str) -> Option<String> {
+        registry().base_for(kind, q)
+    }
+
+    #[test]
+    fn domains_take_the_longest_suffix() {
+        assert_eq!(
+            base(Kind::Domain, "example.co.uk").as_deref(),
+            Some("https://co-uk.test/")
+        );
+        assert_eq!(
+            base(Kind::Domain, "example.uk").as_deref(),
+            Some("https://uk.test/")
+        );
+        assert_eq!(
+            base(Kind::Domain, "Example.COM.").as_deref(),
+            Some("https://com.test/")
+        );
+        assert_eq!(base(Kind::Domain, "example.org"), None);
+    }
+
+    #[test]
+    fn addresses_take_the_longest_prefix() {
+        assert_eq!(
+            base(Kind::Ip, "10.1.2.3").as_deref(),
+            Some("https://ten-one.test/")
+        );
+        assert_eq!(
+            base(Kind::Ip, "10.1.0.0/24").as_deref(),
+            Some("https://ten-one.test/")
+        );
+        assert_eq!(
+            base(Kind::Ip, "10.2.0.1").as_deref(),
+            Some("https://ten.test/")
+        );
+        assert_eq!(base(Kind::Ip, "11.0.0.1"), None);
+        assert_eq!(
+            base(Kind::Ip, "2001:db8::1").as_deref(),
+            Some("https://doc.test/")
+        );
+        assert_eq!(base(Kind::Ip, "2001:db9::1"), None);
+    }
+
+    #[test]
+    fn a_zero_length_prefix_matches_everything() {
+        let mut reg = registry();
+        reg.add(
+            "ipv4.json",
+            &json!({"services": [[["0.0.0.0/0"], ["https://any.test/"]]]}),
+        );
+        assert_eq!(
+            reg.base_for(Kind::Ip, "192.0.2.1").as_deref(),
+            Some("https://any.test/")
+        );
+        assert_eq!(
+            reg.base_for(Kind::Ip, "10.1.2.3").as_deref(),
+            Some("https://ten-one.test/")
+        );
+    }
+
+    #[test]
+    fn asns_match_ranges_and_single_numbers() {
+        assert_eq!(base(Kind::Asn, "100").as_deref(), Some("https://asn.test/"));
+        assert_eq!(base(Kind::Asn, "200").as_deref(), Some("https://asn.test/"));
+        assert_eq!(base(Kind::Asn, "300").as_deref(), Some("https://asn.test/"));
+        assert_eq!(base(Kind::Asn, "201"), None);
+        assert_eq!(base(Kind::Asn, "AS100"), None);
+    }
+
+    #[test]
+    fn entities_route_by_object_tag() {
+        assert_eq!(
+            base(Kind::Entity, "ABC123-arin").as_deref(),
+            Some("https://arin.test/")
+        );
+        assert_eq!(base(Kind::Entity, "ABC123-RIPE"), None);
+        assert_eq!(base(Kind::Entity, "ABC123"), None);
+    }
+
+    #[test]
+    fn alternates_come_from_the_longest_matching_base() {
+        let mut reg = registry();
+        reg.add(
+            "dns.json",
+            &json!({"services": [
+                [["org"], ["https://shared.test/", "https://org-b.test/"]],
+                [["info"], ["https://shared.test/rdap/", "https://info-b.test/"]],
+            ]}),
+        );
+        assert_eq!(
+            reg.alternates_for("https://shared.test/rdap/domain/example.info"),
+            ["https://info-b.test/domain/example.info"]
+        );
+        assert_eq!(
+            reg.alternates_for("https://shared.test/domain/example.org"),
+            ["https://org-b.test/domain/example.org"]
+        );
+        assert_eq!(
+            reg.alternates_for("https://com.test/domain/example.com"),
+            ["http://com.test/domain/example.com"]
+        );
+        assert!(reg
+            .alternates_for("https://uk.test/domain/example.uk")
+            .is_empty());
+    }
+}
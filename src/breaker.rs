@@ -0,0 +1,148 @@
+//! Per-registry circuit breaker for long-running modes (`serve`).
+//!
+//! A registry that answers 429 or 5xx, or can't be reached, accumulates
+//! failures; after [`THRESHOLD`] in a row its circuit opens for a cooldown
+//! that doubles with each further failure (capped at [`MAX_COOLDOWN`]). A
+//! `Retry-After` header opens it immediately for that long. While open,
+//! requests to the host fail fast with [`Open`].
+//!
+//! State is kept in `<data dir>/rdapx/breaker.json` and reloaded on start,
+//! so a restarted daemon doesn't immediately re-hammer a registry that was
+//! rate-limiting it. Other commands never enable the breaker.
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+
+const THRESHOLD: u32 = 5;
+const BASE_COOLDOWN: u64 = 30;
+const MAX_COOLDOWN: u64 = 3600;
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy)]
+struct HostState {
+    failures: u32,
+    /// Unix seconds; 0 when closed
+    open_until: u64,
+}
+
+struct Breaker {
+    path: PathBuf,
+    hosts: BTreeMap<String, HostState>,
+}
+
+static BREAKER: OnceLock<Mutex<Breaker>> = OnceLock::new();
+
+/// Enable the breaker, restoring state saved by a previous process.
+pub fn init() -> io::Result<()> {
+    let path = crate::project::data_dir()?.join("breaker.json");
+    let mut hosts: BTreeMap<String, HostState> = match fs::read_to_string(&path) {
+        Ok(raw) => serde_json::from_str(&raw).unwrap_or_default(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e),
+    };
+    let now = crate::deterministic::wall_secs();
+    hosts.retain(|_, s| s.failures > 0 || s.open_until > now);
+    for (host, s) in &hosts {
+        if s.open_until > now {
+            eprintln!(
+                "Note: circuit for {host} still open for {}s",
+                s.open_until - now
+            );
+        }
+    }
+    let _ = BREAKER.set(Mutex::new(Breaker { path, hosts }));
+    Ok(())
+}
+
+fn breaker() -> Option<MutexGuard<'static, Breaker>> {
+    BREAKER
+        .get()
+        .map(|m| m.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// A request refused because the registry's circuit is open.
+#[derive(Debug)]
+pub struct Open {
+    pub host: String,
+    /// Seconds until the circuit closes
+    pub wait: u64,
+}
+
+impl std::fmt::Display for Open {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "circuit open for {} after repeated failures; retrying in {}s",
+            self.host, self.wait
+        )
+    }
+}
+
+impl std::error::Error for Open {}
+
+/// Refuse `url` while its host's circuit is open.
+pub fn check(url: &str) -> Result<(), Open> {
+    let Some(b) = breaker() else {
+        return Ok(());
+    };
+    let host = crate::url_host(url);
+    let now = crate::deterministic::wall_secs();
+    match b.hosts.get(&host) {
+        Some(s) if s.open_until > now => Err(Open {
+            wait: s.open_until - now,
+            host,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Record the outcome of a request to `url`: `None` for no response at all.
+pub fn record(url: &str, response: Option<(StatusCode, &HeaderMap)>) {
+    let Some(mut b) = breaker() else {
+        return;
+    };
+    let host = crate::url_host(url);
+    let failed = response
+        .is_none_or(|(code, _)| code == StatusCode::TOO_MANY_REQUESTS || code.is_server_error());
+    let before = b.hosts.get(&host).copied().unwrap_or_default();
+    if failed {
+        let failures = before.failures + 1;
+        let retry_after = response
+            .and_then(|(_, h)| h.get(RETRY_AFTER))
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok());
+        let cooldown = retry_after.or_else(|| {
+            (failures >= THRESHOLD).then(|| {
+                BASE_COOLDOWN
+                    .saturating_mul(1 << (failures - THRESHOLD).min(16))
+                    .min(MAX_COOLDOWN)
+            })
+        });
+        let open_until =
+            cooldown.map_or(before.open_until, |c| crate::deterministic::wall_secs() + c);
+        b.hosts.insert(
+            host,
+            HostState {
+                failures,
+                open_until,
+            },
+        );
+    } else {
+        if before.failures == 0 {
+            return;
+        }
+        b.hosts.remove(&host);
+    }
+    let _ = save(&b);
+}
+
+fn save(b: &Breaker) -> io::Result<()> {
+    let tmp = b.path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(&b.hosts)?)?;
+    fs::rename(tmp, &b.path)
+}
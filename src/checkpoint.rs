@@ -0,0 +1,94 @@
+//! Resumable-run state (`--resume FILE` on `search` and `squat`) as an
+//! append-only journal: a header line naming the run, then one JSON line per
+//! step. Appending keeps each save proportional to the step, not to the
+//! whole run; a line cut short by an interruption is ignored on load.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+
+/// Replace `path` with `bytes` through a temporary sibling, so readers (and
+/// interruptions) never see a half-written file.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)
+}
+
+/// An open journal taking steps of type `S`.
+pub struct Journal<S> {
+    file: File,
+    steps: PhantomData<S>,
+}
+
+impl<S: Serialize + DeserializeOwned> Journal<S> {
+    /// Open the journal at `path` for the run `header`, returning the steps
+    /// recorded so far (none for a new file). A journal of another run is an
+    /// error, worded by `describe` from its header.
+    pub fn open<H>(
+        path: &Path,
+        header: &H,
+        describe: impl FnOnce(&H) -> String,
+    ) -> Result<(Self, Vec<S>), Box<dyn Error>>
+    where
+        H: Serialize + DeserializeOwned + PartialEq,
+    {
+        let at = |e: &dyn std::fmt::Display| format!("{}: {e}", path.display());
+        let steps = match fs::read_to_string(path) {
+            Ok(raw) => {
+                let mut lines = raw.lines();
+                let stored: H =
+                    serde_json::from_str(lines.next().unwrap_or_default()).map_err(|e| at(&e))?;
+                if stored != *header {
+                    return Err(format!(
+                        "{} belongs to {}; remove it to start over",
+                        path.display(),
+                        describe(&stored)
+                    )
+                    .into());
+                }
+                let lines: Vec<&str> = lines.collect();
+                let mut steps = Vec::with_capacity(lines.len());
+                for (i, line) in lines.iter().enumerate() {
+                    match serde_json::from_str(line) {
+                        Ok(step) => steps.push(step),
+                        // only the last line can be torn
+                        Err(_) if i + 1 == lines.len() => {}
+                        Err(e) => return Err(at(&e).into()),
+                    }
+                }
+                steps
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut line = serde_json::to_vec(header)?;
+                line.push(b'\n');
+                write_atomic(path, &line).map_err(|e| at(&e))?;
+                Vec::new()
+            }
+            Err(e) => return Err(at(&e).into()),
+        };
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|e| at(&e))?;
+        let journal = Self {
+            file,
+            steps: PhantomData,
+        };
+        Ok((journal, steps))
+    }
+
+    /// Record one step.
+    pub fn append(&mut self, step: &S) -> Result<(), Box<dyn Error>> {
+        let mut line = serde_json::to_vec(step)?;
+        line.push(b'\n');
+        // one write per line, so a torn line can only be the last
+        self.file.write_all(&line)?;
+        Ok(())
+    }
+}
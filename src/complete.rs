@@ -0,0 +1,99 @@
+//! Dynamic completion: `rdapx __complete <kind>` prints candidates one per
+//! line, and [`hook`] returns the shell glue appended to the generated
+//! completion script so query and portfolio arguments use it.
+
+use crate::{cache_dir, list_cache, portfolio, read_cache_file, CacheEntry, Shell};
+use clap::ValueEnum;
+use std::collections::BTreeSet;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum CompleteKind {
+    /// Queries found in the cache
+    Queries,
+    /// Portfolio names
+    Portfolios,
+}
+
+/// Candidates of `kind` starting with `prefix`, sorted and deduplicated.
+pub fn candidates(kind: CompleteKind, prefix: &str) -> Vec<String> {
+    let all: BTreeSet<String> = match kind {
+        CompleteKind::Queries => cache_dir()
+            .map(|dir| list_cache(&dir))
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|p| read_cache_file(p))
+            .filter_map(|raw| serde_json::from_str::<CacheEntry>(&raw).ok())
+            .map(|e| e.query)
+            .filter(|q| !q.is_empty())
+            .collect(),
+        CompleteKind::Portfolios => portfolio::load()
+            .map(|all| all.into_keys().collect())
+            .unwrap_or_default(),
+    };
+    all.into_iter().filter(|c| c.starts_with(prefix)).collect()
+}
+
+const BASH: &str = r#"
+_rdapx_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" sub="" action="" i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            get|changed|portfolio) [[ -z "$sub" ]] && sub="${COMP_WORDS[i]}" ;;
+            add|remove|list|check) [[ "$sub" == portfolio && -z "$action" ]] && action="${COMP_WORDS[i]}" ;;
+        esac
+    done
+    if [[ "$cur" != -* ]]; then
+        if [[ "$sub" == get || "$sub" == changed ]]; then
+            COMPREPLY=($(compgen -W "$(rdapx __complete queries 2>/dev/null)" -- "$cur"))
+            return 0
+        fi
+        if [[ "$sub" == portfolio && -n "$action" && "${COMP_WORDS[COMP_CWORD-1]}" == "$action" ]]; then
+            COMPREPLY=($(compgen -W "$(rdapx __complete portfolios 2>/dev/null)" -- "$cur"))
+            return 0
+        fi
+    fi
+    _rdapx "$@"
+}
+complete -F _rdapx_dynamic -o bashdefault -o default rdapx
+"#;
+
+const ZSH: &str = r#"
+_rdapx_dynamic() {
+    local sub="" action="" w
+    for w in ${words[2,CURRENT-1]}; do
+        case $w in
+            get|changed|portfolio) [[ -z $sub ]] && sub=$w ;;
+            add|remove|list|check) [[ $sub == portfolio && -z $action ]] && action=$w ;;
+        esac
+    done
+    if [[ $PREFIX != -* ]]; then
+        if [[ $sub == get || $sub == changed ]]; then
+            compadd -- ${(f)"$(rdapx __complete queries 2>/dev/null)"}
+            return
+        fi
+        if [[ $sub == portfolio && -n $action && ${words[CURRENT-1]} == $action ]]; then
+            compadd -- ${(f)"$(rdapx __complete portfolios 2>/dev/null)"}
+            return
+        fi
+    fi
+    _rdapx "$@"
+}
+compdef _rdapx_dynamic rdapx
+"#;
+
+const FISH: &str = r#"
+complete -c rdapx -n "__fish_seen_subcommand_from get changed" -f -a "(rdapx __complete queries 2>/dev/null)"
+complete -c rdapx -n "__fish_seen_subcommand_from portfolio; and __fish_seen_subcommand_from add remove list check" -f -a "(rdapx __complete portfolios 2>/dev/null)"
+"#;
+
+/// Shell glue wiring the dynamic candidates into the generated script.
+/// PowerShell allows one native completer per command, so it only gets the
+/// static completions.
+pub const fn hook(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => BASH,
+        Shell::Zsh => ZSH,
+        Shell::Fish => FISH,
+        Shell::Powershell => "",
+    }
+}
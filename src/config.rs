@@ -0,0 +1,162 @@
+//! User configuration from `<config dir>/rdapx/config.toml`, the project's
+//! `.rdapx/config.toml` inside a project (see `rdapx init`), or `$RDAPX_CONFIG`.
+//!
+//! ```toml
+//! [history]
+//! keep_last = 50      # keep at most N most recent snapshots per object
+//! keep_monthly = true # also keep the first and last snapshot of each month
+//! dedupe = true       # drop snapshots identical to the previous one
+//!
+//! [cache.ttl]          # seconds; overrides --cache-ttl per object class
+//! domain = 86400
+//! ip = 604800
+//! notfound = 3600      # cache 404s for this long (not cached by default)
+//!
+//! [search]             # politeness between search pages (`--delay` overrides)
+//! delay_ms = 1000
+//! hosts = { "rdap.verisign.com" = 2000 }
+//!
+//! [bootstrap]          # where the IANA RDAP bootstrap files come from
+//! sources = ["https://mirror.example.internal/rdap/", "/etc/rdapx/bootstrap"]
+//! iana_fallback = true # try data.iana.org after them (the default)
+//! sha256 = { "dns.json" = "9f86d081884c7d65..." }
+//!
+//! [diff]               # left out of diff-runs / changed (`--ignore-path` adds)
+//! ignore = ["events[eventAction=last update of RDAP database]", "notices", "order:links"]
+//! ```
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub history: RetentionPolicy,
+    pub cache: CacheConfig,
+    pub search: SearchConfig,
+    pub bootstrap: BootstrapConfig,
+    pub diff: DiffConfig,
+}
+
+/// Noise to leave out when comparing versions of an object.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiffConfig {
+    /// Ignore rules, as for `--ignore-path`
+    pub ignore: Vec<String>,
+}
+
+/// Alternative sources for the IANA bootstrap registry files, tried in order.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct BootstrapConfig {
+    /// Mirror base URLs (`https://...`) or directories of pinned copies
+    pub sources: Vec<String>,
+    /// Expected SHA-256 (hex) per file name, checked for `sources` copies
+    pub sha256: BTreeMap<String, String>,
+    /// Fall back to IANA when every configured source fails
+    pub iana_fallback: bool,
+}
+
+impl Default for BootstrapConfig {
+    fn default() -> Self {
+        Self {
+            sources: Vec::new(),
+            sha256: BTreeMap::new(),
+            iana_fallback: true,
+        }
+    }
+}
+
+/// Minimum time between search page requests to the same server.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    pub delay_ms: Option<u64>,
+    /// Per-host overrides of `delay_ms`
+    pub hosts: BTreeMap<String, u64>,
+}
+
+impl SearchConfig {
+    pub fn delay_for(&self, host: &str) -> Option<u64> {
+        self.hosts.get(host).copied().or(self.delay_ms)
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub ttl: TtlConfig,
+}
+
+/// Per object class cache TTLs in seconds; unset classes use `--cache-ttl`.
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct TtlConfig {
+    pub domain: Option<u64>,
+    pub ip: Option<u64>,
+    pub autnum: Option<u64>,
+    pub nameserver: Option<u64>,
+    pub entity: Option<u64>,
+    /// Negative caching of 404 responses
+    pub notfound: Option<u64>,
+}
+
+impl TtlConfig {
+    /// TTL for an RDAP object class path segment (`domain`, `ip`, ...).
+    pub fn for_class(&self, class: &str) -> Option<u64> {
+        match class {
+            "domain" => self.domain,
+            "ip" => self.ip,
+            "autnum" => self.autnum,
+            "nameserver" => self.nameserver,
+            "entity" => self.entity,
+            _ => None,
+        }
+    }
+}
+
+/// History retention rules; an empty policy keeps everything.
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionPolicy {
+    pub keep_last: Option<usize>,
+    pub keep_monthly: bool,
+    pub dedupe: bool,
+}
+
+impl RetentionPolicy {
+    pub const fn is_empty(&self) -> bool {
+        self.keep_last.is_none() && !self.keep_monthly && !self.dedupe
+    }
+}
+
+pub fn config_path() -> io::Result<PathBuf> {
+    if let Some(p) = std::env::var_os("RDAPX_CONFIG") {
+        return Ok(PathBuf::from(p));
+    }
+    Ok(crate::project::config_dir()?.join("config.toml"))
+}
+
+/// Load the config file once at startup; a missing file means defaults.
+pub fn init() -> Result<(), Box<dyn Error>> {
+    let path = config_path()?;
+    let cfg = match fs::read_to_string(&path) {
+        Ok(raw) => toml::from_str(&raw).map_err(|e| format!("{}: {e}", path.display()))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Config::default(),
+        Err(e) => return Err(format!("{}: {e}", path.display()).into()),
+    };
+    let _ = CONFIG.set(cfg);
+    Ok(())
+}
+
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
@@ -0,0 +1,140 @@
+//! `rdapx contacts`: flatten every entity's jCard into one row per contact
+//! role, with the object it was attached to, for spreadsheets and CRMs.
+
+use crate::fields::vcard_prop;
+use crate::table::{Cell, Column, Tabular};
+use serde::Serialize;
+use serde_json::Value;
+
+/// One entity contact in one role.
+#[derive(Serialize, Debug)]
+pub struct Contact {
+    pub query: String,
+    /// Object the entity hangs off, e.g. `domain example.com` or
+    /// `entity 292` for an abuse contact under its registrar
+    pub source: String,
+    pub role: String,
+    pub handle: Option<String>,
+    pub name: Option<String>,
+    pub org: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<String>,
+}
+
+impl Tabular for Contact {
+    const COLUMNS: &'static [Column] = &[
+        Column("SOURCE", 24),
+        Column("ROLE", 14),
+        Column("NAME", 28),
+        Column("EMAIL", 32),
+        Column("PHONE", 0),
+    ];
+    const CSV_HEADER: &'static [&'static str] = &[
+        "query", "source", "role", "handle", "name", "org", "email", "phone", "address",
+    ];
+
+    fn table_row(&self) -> Vec<Cell> {
+        vec![
+            self.source.as_str().into(),
+            self.role.as_str().into(),
+            Cell::or_dash(self.name.as_deref()),
+            Cell::or_dash(self.email.as_deref()),
+            Cell::or_dash(self.phone.as_deref()),
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        let opt = |v: &Option<String>| v.clone().unwrap_or_default();
+        vec![
+            self.query.clone(),
+            self.source.clone(),
+            self.role.clone(),
+            opt(&self.handle),
+            opt(&self.name),
+            opt(&self.org),
+            opt(&self.email),
+            opt(&self.phone),
+            opt(&self.address),
+        ]
+    }
+}
+
+/// `objectClassName` plus the object's name, handle as a fallback.
+fn describe(obj: &Value) -> String {
+    let class = obj
+        .get("objectClassName")
+        .and_then(Value::as_str)
+        .unwrap_or("object");
+    let id = ["ldhName", "handle", "name"]
+        .iter()
+        .find_map(|k| obj.get(*k).and_then(Value::as_str))
+        .unwrap_or("-");
+    format!("{class} {id}")
+}
+
+/// Postal address: the `adr` components, or its `label` parameter when the
+/// structured value is empty (common in redacted responses).
+fn address(entity: &Value) -> Option<String> {
+    vcard_prop(entity, "adr").or_else(|| {
+        entity
+            .get("vcardArray")?
+            .get(1)?
+            .as_array()?
+            .iter()
+            .find(|p| p.get(0).and_then(Value::as_str) == Some("adr"))?
+            .get(1)?
+            .get("label")?
+            .as_str()
+            .map(|l| l.replace('\n', ", "))
+    })
+}
+
+fn collect(query: &str, parent: &Value, out: &mut Vec<Contact>) {
+    let source = describe(parent);
+    for entity in parent
+        .get("entities")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let roles: Vec<&str> = entity
+            .get("roles")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        let handle = entity
+            .get("handle")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let phone = vcard_prop(entity, "tel").map(|t| t.trim_start_matches("tel:").to_string());
+        let roles = if roles.is_empty() { vec![""] } else { roles };
+        // parents kept by `--roles` only for their nested contacts get no row
+        for role in roles
+            .into_iter()
+            .filter(|r| crate::projection::wants_role(r))
+        {
+            out.push(Contact {
+                query: query.to_string(),
+                source: source.clone(),
+                role: role.to_string(),
+                handle: handle.clone(),
+                name: vcard_prop(entity, "fn"),
+                org: vcard_prop(entity, "org"),
+                email: vcard_prop(entity, "email"),
+                phone: phone.clone(),
+                address: address(entity),
+            });
+        }
+        collect(query, entity, out);
+    }
+}
+
+/// Every contact in `json`, nested entities included, in document order.
+pub fn extract(query: &str, json: &Value) -> Vec<Contact> {
+    let mut out = Vec::new();
+    collect(query, json, &mut out);
+    out
+}
@@ -0,0 +1,134 @@
+//! Optional encryption at rest for cache and history entries (`--cache-key`).
+//!
+//! Entries are sealed line by line with XChaCha20-Poly1305 so history files
+//! stay appendable: a sealed line is `enc1:` followed by base64 of the 24-byte
+//! nonce and the ciphertext. Plain lines are still read, so turning the key on
+//! doesn't invalidate an existing cache; without the key, sealed entries are
+//! treated as missing.
+
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const PREFIX: &str = "enc1:";
+const NONCE_LEN: usize = 24;
+
+/// Keychain service and account holding the base64 cache key.
+#[cfg(feature = "keychain")]
+pub const KEYCHAIN_SERVICE: &str = "rdapx";
+#[cfg(feature = "keychain")]
+const KEYCHAIN_ACCOUNT: &str = "cache-key";
+
+static CIPHER: OnceLock<XChaCha20Poly1305> = OnceLock::new();
+
+/// Load the key from `source` — `keychain` or a key file path — creating a
+/// new random key there on first use.
+pub fn init(source: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let Some(source) = source else {
+        return Ok(());
+    };
+    let encoded = if source == "keychain" {
+        keychain_key()?
+    } else {
+        file_key(Path::new(source))?
+    };
+    let key = B64
+        .decode(encoded.trim())
+        .ok()
+        .filter(|k| k.len() == 32)
+        .ok_or("cache key must be 32 bytes of base64")?;
+    let cipher = XChaCha20Poly1305::new_from_slice(&key).map_err(|e| e.to_string())?;
+    let _ = CIPHER.set(cipher);
+    Ok(())
+}
+
+fn new_key() -> String {
+    B64.encode(XChaCha20Poly1305::generate_key(&mut OsRng))
+}
+
+fn file_key(path: &Path) -> Result<String, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(raw) => Ok(raw),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let key = new_key();
+            write_private(path, &key)?;
+            eprintln!("Note: created new cache key at {}", path.display());
+            Ok(key)
+        }
+        Err(e) => Err(format!("{}: {e}", path.display()).into()),
+    }
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(contents.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    fs::write(path, contents)
+}
+
+#[cfg(feature = "keychain")]
+fn keychain_key() -> Result<String, Box<dyn Error>> {
+    let entry = keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT)
+        .map_err(|e| format!("keychain: {e}"))?;
+    match entry.get_password() {
+        Ok(key) => Ok(key),
+        Err(keyring::Error::NoEntry) => {
+            let key = new_key();
+            entry
+                .set_password(&key)
+                .map_err(|e| format!("keychain: {e}"))?;
+            eprintln!("Note: stored new cache key in the OS keychain");
+            Ok(key)
+        }
+        Err(e) => Err(format!("keychain: {e}").into()),
+    }
+}
+
+#[cfg(not(feature = "keychain"))]
+fn keychain_key() -> Result<String, Box<dyn Error>> {
+    Err("this build has no keychain support (enable the `keychain` feature)".into())
+}
+
+/// Seal one line of JSON; returns it unchanged when no key is configured.
+pub fn seal(plain: &str) -> String {
+    let Some(cipher) = CIPHER.get() else {
+        return plain.to_string();
+    };
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    // encryption only fails for plaintexts beyond the AEAD length limit
+    let Ok(ct) = cipher.encrypt(&nonce, plain.as_bytes()) else {
+        return plain.to_string();
+    };
+    let mut blob = nonce.to_vec();
+    blob.extend_from_slice(&ct);
+    format!("{PREFIX}{}", B64.encode(blob))
+}
+
+/// Open a line written by [`seal`]. Plain lines pass through; sealed lines
+/// without the right key yield `None`.
+pub fn open(line: &str) -> Option<String> {
+    let Some(encoded) = line.trim_end().strip_prefix(PREFIX) else {
+        return Some(line.to_string());
+    };
+    let blob = B64.decode(encoded).ok()?;
+    if blob.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ct) = blob.split_at(NONCE_LEN);
+    let plain = CIPHER.get()?.decrypt(XNonce::from_slice(nonce), ct).ok()?;
+    String::from_utf8(plain).ok()
+}
@@ -0,0 +1,384 @@
+//! Reference datasets fetched from IANA and the RIRs, kept under `<cache>/datasets`.
+
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const REGISTRAR_IDS_URL: &str =
+    "https://www.iana.org/assignments/registrar-ids/registrar-ids-1.csv";
+const REGISTRAR_IDS_FILE: &str = "registrar-ids.csv";
+
+/// RIR short name and its delegated-extended statistics file.
+const RIR_DELEGATIONS: [(&str, &str); 5] = [
+    (
+        "afrinic",
+        "https://ftp.afrinic.net/pub/stats/afrinic/delegated-afrinic-extended-latest",
+    ),
+    (
+        "apnic",
+        "https://ftp.apnic.net/stats/apnic/delegated-apnic-extended-latest",
+    ),
+    (
+        "arin",
+        "https://ftp.arin.net/pub/stats/arin/delegated-arin-extended-latest",
+    ),
+    (
+        "lacnic",
+        "https://ftp.lacnic.net/pub/stats/lacnic/delegated-lacnic-extended-latest",
+    ),
+    (
+        "ripe",
+        "https://ftp.ripe.net/pub/stats/ripencc/delegated-ripencc-extended-latest",
+    ),
+];
+
+/// IANA updates these registries a few times a month; a week-old copy is fine.
+const DATASET_MAX_AGE: Duration = Duration::from_secs(7 * 86_400);
+
+/// IANA Registrar ID -> row of the registrar registry, keyed by `snake_case` header.
+pub type RegistrarIds = HashMap<String, Map<String, Value>>;
+
+pub fn datasets_dir() -> io::Result<PathBuf> {
+    let p = crate::cache_dir()?.join("datasets");
+    fs::create_dir_all(&p)?;
+    Ok(p)
+}
+
+fn is_fresh(path: &Path, max_age: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(crate::deterministic::age)
+        .is_some_and(|age| age <= max_age)
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<String, Box<dyn Error>> {
+    let resp = client.get(url).send().await?.error_for_status()?;
+    Ok(resp.text().await?)
+}
+
+/// Return the dataset body, downloading it when the local copy is missing or
+/// stale. A stale copy is still used when the download fails.
+async fn fetch_cached(
+    client: &reqwest::Client,
+    url: &str,
+    file: &str,
+) -> Result<String, Box<dyn Error>> {
+    let path = datasets_dir()?.join(file);
+    if is_fresh(&path, DATASET_MAX_AGE) {
+        return Ok(fs::read_to_string(&path)?);
+    }
+    match download(client, url).await {
+        Ok(body) => {
+            fs::write(&path, &body)?;
+            Ok(body)
+        }
+        Err(e) => {
+            let body = fs::read_to_string(&path).map_err(|_| e.to_string())?;
+            crate::strict::check(crate::strict::Condition::Stale, || {
+                format!("{file}: download failed ({e}); not using the outdated local copy")
+            })?;
+            eprintln!("Note: {file}: download failed ({e}); using the outdated local copy");
+            Ok(body)
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum DatasetName {
+    /// RIR delegated-extended statistics (IP/ASN -> RIR, country, date)
+    RirDelegations,
+    /// IANA registrar IDs registry
+    RegistrarIds,
+}
+
+fn delegation_file(rir: &str) -> String {
+    format!("delegated-{rir}-extended-latest")
+}
+
+/// Force-download a dataset, returning `(file, bytes)` per stored file.
+pub async fn pull(
+    client: &reqwest::Client,
+    name: DatasetName,
+) -> Result<Vec<(String, usize)>, Box<dyn Error>> {
+    let sources: Vec<(String, &str)> = match name {
+        DatasetName::RirDelegations => RIR_DELEGATIONS
+            .iter()
+            .map(|(rir, url)| (delegation_file(rir), *url))
+            .collect(),
+        DatasetName::RegistrarIds => vec![(REGISTRAR_IDS_FILE.to_string(), REGISTRAR_IDS_URL)],
+    };
+    let dir = datasets_dir()?;
+    let mut out = Vec::new();
+    for (file, url) in sources {
+        let body = download(client, url).await?;
+        fs::write(dir.join(&file), &body)?;
+        out.push((file, body.len()));
+    }
+    Ok(out)
+}
+
+/// Locally stored dataset files as `(file, bytes, age)`.
+pub fn list() -> io::Result<Vec<(String, u64, Duration)>> {
+    let mut out = Vec::new();
+    for e in fs::read_dir(datasets_dir()?)?.flatten() {
+        let Ok(meta) = e.metadata() else { continue };
+        let age = meta
+            .modified()
+            .ok()
+            .map(crate::deterministic::age)
+            .unwrap_or_default();
+        out.push((
+            e.file_name().to_string_lossy().into_owned(),
+            meta.len(),
+            age,
+        ));
+    }
+    out.sort();
+    Ok(out)
+}
+
+/* ---------------------------- Registrar IDs ----------------------------- */
+
+pub async fn registrar_ids(client: &reqwest::Client) -> Result<RegistrarIds, Box<dyn Error>> {
+    let body = fetch_cached(client, REGISTRAR_IDS_URL, REGISTRAR_IDS_FILE).await?;
+    Ok(parse_registrar_ids(&body))
+}
+
+/// Parse the registry CSV generically by header so richer exports (e.g. with
+/// abuse contact columns) are picked up without code changes.
+fn parse_registrar_ids(body: &str) -> RegistrarIds {
+    let mut lines = body.lines().filter(|l| !l.trim().is_empty());
+    let Some(header) = lines.next() else {
+        return RegistrarIds::new();
+    };
+    let keys: Vec<String> = split_csv_line(header)
+        .iter()
+        .map(|h| snake_key(h))
+        .collect();
+
+    let mut out = RegistrarIds::new();
+    for line in lines {
+        let cells = split_csv_line(line);
+        let Some(id) = cells.first().map(|c| c.trim().to_string()) else {
+            continue;
+        };
+        let row: Map<String, Value> = keys
+            .iter()
+            .zip(cells)
+            .filter(|(_, v)| !v.trim().is_empty())
+            .map(|(k, v)| (k.clone(), Value::from(v.trim())))
+            .collect();
+        out.insert(id, row);
+    }
+    out
+}
+
+/// Attach the registry row for the response's IANA Registrar ID under
+/// `_enrichment.registrar`. Responses without a registrar ID are left alone.
+pub fn enrich_registrar(json: &mut Value, ids: &RegistrarIds) {
+    if let Some(row) = iana_registrar_id(json).and_then(|id| ids.get(&id)) {
+        crate::attach_under(json, "_enrichment", "registrar", Value::Object(row.clone()));
+    }
+}
+
+fn iana_registrar_id(json: &Value) -> Option<String> {
+    json.get("entities")?
+        .as_array()?
+        .iter()
+        .filter(|e| {
+            e.get("roles")
+                .and_then(Value::as_array)
+                .is_some_and(|r| r.iter().any(|r| r == "registrar"))
+        })
+        .filter_map(|e| e.get("publicIds").and_then(Value::as_array))
+        .flatten()
+        .find(|p| p.get("type").and_then(Value::as_str) == Some("IANA Registrar ID"))
+        .and_then(|p| p.get("identifier"))
+        .and_then(|id| match id {
+            Value::String(s) => Some(s.trim().to_string()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+}
+
+/* ---------------------------- RIR delegations --------------------------- */
+
+#[derive(Debug, Clone)]
+pub struct Delegation {
+    pub rir: String,
+    pub country: String,
+    /// Allocation date as `YYYY-MM-DD` (empty when the RIR omits it)
+    pub date: String,
+    pub status: String,
+    range: String,
+}
+
+impl Delegation {
+    pub fn to_json(&self, query: &str) -> Value {
+        json!({
+            "query": query,
+            "rir": self.rir,
+            "country": self.country,
+            "allocated": self.date,
+            "status": self.status,
+            "range": self.range,
+        })
+    }
+}
+
+/// Inclusive `[start, end]` ranges sorted by start, pointing into `records`.
+type Ranges<T> = Vec<(T, T, usize)>;
+
+#[derive(Debug, Default)]
+pub struct Delegations {
+    v4: Ranges<u32>,
+    v6: Ranges<u128>,
+    asn: Ranges<u32>,
+    records: Vec<Delegation>,
+}
+
+impl Delegations {
+    /// Parse the locally pulled delegation files; never touches the network.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let dir = datasets_dir()?;
+        let mut db = Self::default();
+        let mut found = false;
+        for (rir, _) in RIR_DELEGATIONS {
+            if let Ok(body) = fs::read_to_string(dir.join(delegation_file(rir))) {
+                found = true;
+                db.parse(rir, &body);
+            }
+        }
+        if !found {
+            return Err("no RIR delegation data; run `rdapx dataset pull rir-delegations`".into());
+        }
+        db.v4.sort_unstable();
+        db.v6.sort_unstable();
+        db.asn.sort_unstable();
+        Ok(db)
+    }
+
+    /// `registry|cc|type|start|value|date|status[|...]`; header, summary and
+    /// comment lines are skipped.
+    fn parse(&mut self, rir: &str, body: &str) {
+        for line in body.lines() {
+            let f: Vec<&str> = line.split('|').collect();
+            if line.starts_with('#') || f.len() < 7 || f[1] == "*" {
+                continue;
+            }
+            let (kind, start, value) = (f[2], f[3], f[4]);
+            let Ok(value) = value.parse::<u64>() else {
+                continue;
+            };
+            let idx = self.records.len();
+            let ok = match kind {
+                "ipv4" => start.parse::<std::net::Ipv4Addr>().is_ok_and(|a| {
+                    let lo = u32::from(a);
+                    let span = u32::try_from(value.saturating_sub(1)).unwrap_or(u32::MAX);
+                    self.v4.push((lo, lo.saturating_add(span), idx));
+                    true
+                }),
+                "ipv6" => start.parse::<std::net::Ipv6Addr>().is_ok_and(|a| {
+                    let lo = u128::from(a);
+                    let host_bits = 128u32.saturating_sub(u32::try_from(value).unwrap_or(128));
+                    let mask = u128::MAX.checked_shr(128 - host_bits).unwrap_or(0);
+                    self.v6.push((lo, lo | mask, idx));
+                    true
+                }),
+                "asn" => start.parse::<u32>().is_ok_and(|lo| {
+                    let span = u32::try_from(value.saturating_sub(1)).unwrap_or(u32::MAX);
+                    self.asn.push((lo, lo.saturating_add(span), idx));
+                    true
+                }),
+                _ => false,
+            };
+            if ok {
+                self.records.push(Delegation {
+                    rir: rir.to_string(),
+                    country: f[1].to_string(),
+                    date: format_date(f[5]),
+                    status: f[6].to_string(),
+                    range: if kind == "ipv6" {
+                        format!("{start}/{value}")
+                    } else {
+                        format!("{start}+{value}")
+                    },
+                });
+            }
+        }
+    }
+
+    /// Look up an IP (optionally with `/len`) or ASN (`AS123` or `123`).
+    pub fn lookup(&self, query: &str) -> Option<&Delegation> {
+        let q = query.trim();
+        let addr = q.split('/').next().unwrap_or(q);
+        let idx = match addr.parse::<IpAddr>() {
+            Ok(IpAddr::V4(a)) => find(&self.v4, u32::from(a)),
+            Ok(IpAddr::V6(a)) => find(&self.v6, u128::from(a)),
+            Err(_) => {
+                let num = q
+                    .strip_prefix("AS")
+                    .or_else(|| q.strip_prefix("as"))
+                    .unwrap_or(q);
+                find(&self.asn, num.parse().ok()?)
+            }
+        }?;
+        self.records.get(idx)
+    }
+}
+
+fn find<T: Ord + Copy>(ranges: &Ranges<T>, x: T) -> Option<usize> {
+    let i = ranges.partition_point(|r| r.0 <= x).checked_sub(1)?;
+    let (_, end, idx) = ranges[i];
+    (x <= end).then_some(idx)
+}
+
+fn format_date(d: &str) -> String {
+    if d.len() == 8 && d.bytes().all(|b| b.is_ascii_digit()) {
+        format!("{}-{}-{}", &d[..4], &d[4..6], &d[6..])
+    } else {
+        String::new()
+    }
+}
+
+/* ------------------------------- CSV utils ------------------------------ */
+
+fn snake_key(header: &str) -> String {
+    let mut key = String::new();
+    for c in header.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            key.push(c.to_ascii_lowercase());
+        } else if !key.ends_with('_') {
+            key.push('_');
+        }
+    }
+    key.trim_matches('_').to_string()
+}
+
+/// Split one CSV record, honouring double-quoted cells and `""` escapes.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cur = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                cur.push('"');
+                chars.next();
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => cells.push(std::mem::take(&mut cur)),
+            _ => cur.push(c),
+        }
+    }
+    cells.push(cur);
+    cells
+}
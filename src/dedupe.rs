@@ -0,0 +1,74 @@
+//! `bulk --dedupe-output`: content-addressed output for runs where many
+//! responses repeat (IPs inside one network, thin records sharing registrar
+//! boilerplate).
+//!
+//! * A response identical to an earlier one is replaced by a reference
+//!   record holding only its rdapx annotations plus
+//!   `_meta: {"query": ..., "duplicate_of": "<hash>"}`. Full records carry
+//!   their `_meta.content_hash`.
+//! * Large shared blocks (`notices`, `remarks`, and each entity) are written
+//!   once as `{"_blob": "<hash>", "value": ...}` lines and replaced by
+//!   `{"_ref": "<hash>"}` wherever they appear, including the first record.
+
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+
+/// Blocks smaller than this (serialized bytes) stay inline.
+const MIN_BLOB: usize = 256;
+
+fn hash(v: &Value) -> String {
+    blake3::hash(v.to_string().as_bytes()).to_hex()[..32].to_string()
+}
+
+#[derive(Default)]
+pub struct Deduper {
+    bodies: HashSet<String>,
+    blobs: HashSet<String>,
+}
+
+impl Deduper {
+    /// Returns blob definitions to write first, then the record to emit: the
+    /// rewritten response, or a reference keeping only the rdapx annotations.
+    pub fn process(&mut self, query: &str, mut json: Value) -> (Vec<Value>, Value) {
+        let Value::Object(obj) = &mut json else {
+            return (Vec::new(), json);
+        };
+        let body: Map<String, Value> = obj
+            .iter()
+            .filter(|(k, _)| !k.starts_with('_'))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        let h = hash(&Value::Object(body));
+        if !self.bodies.insert(h.clone()) {
+            obj.retain(|k, _| k.starts_with('_'));
+            crate::attach_meta(&mut json, "query", Value::from(query));
+            crate::attach_meta(&mut json, "duplicate_of", Value::from(h));
+            return (Vec::new(), json);
+        }
+        let mut blobs = Vec::new();
+        for key in ["notices", "remarks"] {
+            if let Some(block) = obj.get_mut(key) {
+                self.extract(block, &mut blobs);
+            }
+        }
+        if let Some(entities) = obj.get_mut("entities").and_then(Value::as_array_mut) {
+            for e in entities {
+                self.extract(e, &mut blobs);
+            }
+        }
+        crate::attach_meta(&mut json, "content_hash", Value::from(h));
+        (blobs, json)
+    }
+
+    /// Swap a large block for a `_ref`, queueing its definition the first time.
+    fn extract(&mut self, block: &mut Value, blobs: &mut Vec<Value>) {
+        if block.to_string().len() < MIN_BLOB {
+            return;
+        }
+        let h = hash(block);
+        let value = std::mem::replace(block, json!({ "_ref": h }));
+        if self.blobs.insert(h.clone()) {
+            blobs.push(json!({ "_blob": h, "value": value }));
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! `--deterministic`: reproducible output for golden-file tests. The clock is
+//! frozen at `SOURCE_DATE_EPOCH` (the Unix epoch when unset) for timestamps,
+//! cache ages and TTLs, so fixtures never expire; WARC record IDs derive from
+//! that fixed time and a counter rather than the wall clock; measured
+//! durations read as zero; and lookups run one at a time so results come out
+//! in input order instead of completion order. What gets persisted (cache
+//! and history `fetched_at`) is still stamped with the wall clock.
+
+use std::error::Error;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Frozen Unix time, set only under `--deterministic`.
+static FROZEN: OnceLock<u64> = OnceLock::new();
+
+pub fn init(enabled: bool) -> Result<(), Box<dyn Error>> {
+    if !enabled {
+        return Ok(());
+    }
+    let secs = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(v) => v
+            .trim()
+            .parse()
+            .map_err(|_| format!("SOURCE_DATE_EPOCH: expected Unix seconds, got '{v}'"))?,
+        Err(_) => 0,
+    };
+    let _ = FROZEN.set(secs);
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    FROZEN.get().is_some()
+}
+
+/// The current time, or the frozen one.
+pub fn now() -> SystemTime {
+    FROZEN
+        .get()
+        .map_or_else(SystemTime::now, |s| UNIX_EPOCH + Duration::from_secs(*s))
+}
+
+/// Real Unix seconds, for rate-limit pacing, the circuit breaker and stored
+/// cache entries, which must follow the wall clock even when output time is
+/// frozen.
+pub fn wall_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Time since `then`; zero when `then` is in the (possibly frozen) future.
+pub fn age(then: SystemTime) -> Duration {
+    now().duration_since(then).unwrap_or_default()
+}
+
+/// Time since `start` for reporting; zero when frozen. Pacing and timeouts
+/// keep using real `Instant`s.
+pub fn elapsed(start: Instant) -> Duration {
+    if enabled() {
+        Duration::ZERO
+    } else {
+        start.elapsed()
+    }
+}
+
+/// How many lookups to run at once: `n`, or one at a time when
+/// deterministic so results come out in input order.
+pub fn concurrency(n: usize) -> usize {
+    if enabled() {
+        1
+    } else {
+        n
+    }
+}
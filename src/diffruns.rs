@@ -0,0 +1,572 @@
+//! `rdapx diff-runs`: join two bulk NDJSON outputs on a key and report which
+//! objects were added or removed and which fields changed, so periodic
+//! collections can produce a change report directly.
+//!
+//! Records are flattened to dotted paths (`entities.0.handle`) and compared
+//! leaf by leaf, leaving out `_meta`, which holds per-run timings and hashes.
+//!
+//! `--diff-format` (here and on `rdapx changed`) renders the changes for
+//! reading, with each field as a JSON pointer (`/status/2: + serverHold`):
+//! a colored unified view, old and new side by side, or one JSON document
+//! per change.
+//!
+//! Ignore rules (`--ignore-path`, `[diff] ignore` in the config) drop noise
+//! from both versions before they are compared: `notices` (or a dotted
+//! prefix such as `_rdapx.tags`) removes a member,
+//! `events[eventAction=last update of RDAP database]` the matching array
+//! elements, `entities.vcardArray` a member of every element, and
+//! `order:links` compares an array regardless of element order.
+
+use crate::table::{Cell, Column};
+use clap::ValueEnum;
+use colored::Color;
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Kind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Changed => "changed",
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffFormat {
+    /// `-`/`+` lines per field, red and green
+    Unified,
+    /// Field, old value, and new value in columns
+    SideBySide,
+    /// One `{key, change, path, old, new}` document per line
+    Json,
+}
+
+/// One line of the change report; `field` is empty for added/removed objects.
+#[derive(Serialize, Debug)]
+pub struct Change {
+    pub key: String,
+    #[serde(rename = "change")]
+    pub kind: Kind,
+    pub field: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl crate::table::Tabular for Change {
+    const COLUMNS: &'static [Column] = &[
+        Column("KEY", 32),
+        Column("CHANGE", 8),
+        Column("FIELD", 40),
+        Column("OLD -> NEW", 0),
+    ];
+    const CSV_HEADER: &'static [&'static str] = &["key", "change", "field", "old", "new"];
+
+    fn table_row(&self) -> Vec<Cell> {
+        let color = match self.kind {
+            Kind::Added => Color::Green,
+            Kind::Removed => Color::Red,
+            Kind::Changed => Color::Yellow,
+        };
+        let values = if self.kind == Kind::Changed {
+            format!("{} -> {}", cell(self.old.as_ref()), cell(self.new.as_ref()))
+        } else {
+            String::new()
+        };
+        vec![
+            self.key.as_str().into(),
+            Cell::colored(self.kind.as_str(), color),
+            self.field.as_str().into(),
+            values.into(),
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.key.clone(),
+            self.kind.as_str().to_string(),
+            self.field.clone(),
+            cell(self.old.as_ref()),
+            cell(self.new.as_ref()),
+        ]
+    }
+}
+
+/// Value at a dotted path, also looking inside `rdap` for v2 envelopes.
+/// Bulk records only carry their query under `_meta` (or top level for
+/// `--emit-not-found`), so `query` falls back to the object's own name.
+pub fn record_key(record: &Value, key: &str) -> Option<String> {
+    let at = |v: &Value, path: &str| {
+        path.split('.')
+            .try_fold(v, |v, p| v.get(p))
+            .and_then(|v| match v {
+                Value::String(s) => Some(s.clone()),
+                Value::Null => None,
+                other => Some(other.to_string()),
+            })
+    };
+    let inner = record.get("rdap").unwrap_or(record);
+    let mut found = at(record, key).or_else(|| at(inner, key));
+    if found.is_none() && key == "query" {
+        found = at(record, "_meta.query")
+            .or_else(|| at(inner, "ldhName").map(|s| s.to_lowercase()))
+            .or_else(|| at(inner, "handle"));
+    }
+    found
+}
+
+fn flatten(prefix: &str, v: &Value, out: &mut BTreeMap<String, Value>) {
+    let join = |k: &str| {
+        if prefix.is_empty() {
+            k.to_string()
+        } else {
+            format!("{prefix}.{k}")
+        }
+    };
+    match v {
+        Value::Object(map) if !map.is_empty() => {
+            for (k, v) in map {
+                flatten(&join(k), v, out);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, v) in items.iter().enumerate() {
+                flatten(&join(&i.to_string()), v, out);
+            }
+        }
+        _ => {
+            out.insert(prefix.to_string(), v.clone());
+        }
+    }
+}
+
+/// rdapx's per-run annotations, never compared.
+const META: &str = "_meta";
+
+fn under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Key every parseable line; later duplicates of a key replace earlier ones.
+/// Returns the records and how many lines had no key.
+pub fn index(lines: &[String], key: &str) -> (BTreeMap<String, Value>, usize) {
+    let mut out = BTreeMap::new();
+    let mut unkeyed = 0;
+    for line in lines {
+        let Ok(record) = serde_json::from_str::<Value>(line) else {
+            unkeyed += 1;
+            continue;
+        };
+        // `--dedupe-output` blob definitions aren't objects of their own
+        if record.get("_blob").is_some() {
+            continue;
+        }
+        match record_key(&record, key) {
+            Some(k) => {
+                out.insert(k, record);
+            }
+            None => unkeyed += 1,
+        }
+    }
+    (out, unkeyed)
+}
+
+/// Field-level changes between two versions of one object.
+pub fn fields(key: &str, before: &Value, after: &Value) -> Vec<Change> {
+    let (mut a, mut b) = (BTreeMap::new(), BTreeMap::new());
+    flatten("", before, &mut a);
+    flatten("", after, &mut b);
+    let mut paths: Vec<&String> = a.keys().chain(b.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    paths
+        .into_iter()
+        .filter(|path| !under(path, META) && a.get(*path) != b.get(*path))
+        .map(|path| Change {
+            key: key.to_string(),
+            kind: Kind::Changed,
+            field: path.clone(),
+            old: a.get(path).cloned(),
+            new: b.get(path).cloned(),
+        })
+        .collect()
+}
+
+pub fn diff(old: &BTreeMap<String, Value>, new: &BTreeMap<String, Value>) -> Vec<Change> {
+    let mut out = Vec::new();
+    for (key, before) in old {
+        let Some(after) = new.get(key) else {
+            out.push(Change {
+                key: key.clone(),
+                kind: Kind::Removed,
+                field: String::new(),
+                old: Some(before.clone()),
+                new: None,
+            });
+            continue;
+        };
+        out.extend(fields(key, before, after));
+    }
+    for (key, after) in new {
+        if !old.contains_key(key) {
+            out.push(Change {
+                key: key.clone(),
+                kind: Kind::Added,
+                field: String::new(),
+                old: None,
+                new: Some(after.clone()),
+            });
+        }
+    }
+    out
+}
+
+/// Leaf value as a table/CSV cell; whole objects are summarized.
+pub fn cell(v: Option<&Value>) -> String {
+    match v {
+        None => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Object(_)) => "{…}".to_string(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// JSON pointer (RFC 6901) for a dotted field path.
+pub fn pointer(field: &str) -> String {
+    let mut out = String::new();
+    for part in field.split('.').filter(|p| !p.is_empty()) {
+        out.push('/');
+        out.push_str(&part.replace('~', "~0").replace('/', "~1"));
+    }
+    out
+}
+
+/// Print `changes` grouped by key (in the order given) in `format`.
+pub fn render(changes: &[Change], format: DiffFormat) -> Result<(), serde_json::Error> {
+    if format == DiffFormat::Json {
+        for c in changes {
+            let doc = json!({
+                "key": c.key,
+                "change": c.kind,
+                "path": pointer(&c.field),
+                "old": c.old,
+                "new": c.new,
+            });
+            println!("{}", serde_json::to_string(&doc)?);
+        }
+        return Ok(());
+    }
+    let width = changes
+        .iter()
+        .map(|c| pointer(&c.field).len())
+        .max()
+        .unwrap_or(0)
+        .clamp(4, 48);
+    let old_width = changes
+        .iter()
+        .map(|c| cell(c.old.as_ref()).chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(3, 40);
+    let mut current: Option<&str> = None;
+    for c in changes {
+        if current != Some(c.key.as_str()) {
+            current = Some(&c.key);
+            match c.kind {
+                Kind::Added => println!("{}", format!("+++ {} (added)", c.key).green().bold()),
+                Kind::Removed => println!("{}", format!("--- {} (removed)", c.key).red().bold()),
+                Kind::Changed => println!("{}", format!("@@ {} @@", c.key).cyan().bold()),
+            }
+            if format == DiffFormat::SideBySide && c.kind == Kind::Changed {
+                println!(
+                    "  {}",
+                    format!("{:<width$}  {:<old_width$}  {}", "PATH", "OLD", "NEW").bold()
+                );
+            }
+        }
+        if c.kind != Kind::Changed {
+            continue;
+        }
+        let path = pointer(&c.field);
+        let (old, new) = (cell(c.old.as_ref()), cell(c.new.as_ref()));
+        if format == DiffFormat::SideBySide {
+            let old = if c.old.is_some() { old } else { "-".into() };
+            let new = if c.new.is_some() { new } else { "-".into() };
+            println!(
+                "  {path:<width$}  {}  {}",
+                format!("{old:<old_width$}").red(),
+                new.green()
+            );
+            continue;
+        }
+        if c.old.is_some() {
+            println!("{}", format!("{path}: - {old}").red());
+        }
+        if c.new.is_some() {
+            println!("{}", format!("{path}: + {new}").green());
+        }
+    }
+    Ok(())
+}
+
+/// Array elements a rule segment selects.
+#[derive(Clone, Debug)]
+enum Filter {
+    All,
+    Eq(String, String),
+}
+
+#[derive(Clone, Debug)]
+struct Segment {
+    name: String,
+    filter: Option<Filter>,
+}
+
+/// One ignore rule: drop what `path` selects, or with `order:` sort the
+/// array it names.
+#[derive(Clone, Debug)]
+pub struct IgnoreRule {
+    path: Vec<Segment>,
+    order: bool,
+}
+
+impl IgnoreRule {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let (order, path) = s
+            .strip_prefix("order:")
+            .map_or((false, s), |rest| (true, rest.trim()));
+        // split on dots outside brackets (filter values may contain dots)
+        let mut parts = vec![String::new()];
+        let mut depth = 0usize;
+        for c in path.chars() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth = depth.saturating_sub(1),
+                '.' if depth == 0 => {
+                    parts.push(String::new());
+                    continue;
+                }
+                _ => {}
+            }
+            parts.last_mut().unwrap_or_else(|| unreachable!()).push(c);
+        }
+        let mut segments = Vec::new();
+        for part in &parts {
+            let (name, filter) = match part.split_once('[') {
+                Some((name, rest)) => {
+                    let inner = rest
+                        .strip_suffix(']')
+                        .ok_or_else(|| format!("{s}: unclosed '[' in '{part}'"))?;
+                    let filter = match inner.split_once('=') {
+                        _ if inner == "*" => Filter::All,
+                        Some((k, v)) if !k.trim().is_empty() => {
+                            Filter::Eq(k.trim().to_string(), v.trim().to_string())
+                        }
+                        _ => return Err(format!("{s}: expected [*] or [field=value] in '{part}'")),
+                    };
+                    (name, Some(filter))
+                }
+                None => (part.as_str(), None),
+            };
+            if name.is_empty() {
+                return Err(format!("{s}: empty field name"));
+            }
+            segments.push(Segment {
+                name: name.to_string(),
+                filter,
+            });
+        }
+        if order && segments.last().is_some_and(|seg| seg.filter.is_some()) {
+            return Err(format!("{s}: order: takes an array path without a filter"));
+        }
+        Ok(Self {
+            path: segments,
+            order,
+        })
+    }
+
+    /// Apply the rule to `v` in place.
+    pub fn apply(&self, v: &mut Value) {
+        // v2 envelopes keep the object under `rdap` and rdapx's `_` members
+        // beside it
+        let annotation = self
+            .path
+            .first()
+            .is_some_and(|seg| seg.name.starts_with('_'));
+        let target = match v.get_mut("rdap") {
+            Some(inner) if inner.is_object() && !annotation => inner,
+            _ => v,
+        };
+        self.walk(target, &self.path);
+    }
+
+    fn walk(&self, v: &mut Value, path: &[Segment]) {
+        if let Value::Array(items) = v {
+            for item in items {
+                self.walk(item, path);
+            }
+            return;
+        }
+        let (Some(seg), Some(obj)) = (path.first(), v.as_object_mut()) else {
+            return;
+        };
+        let last = path.len() == 1;
+        match (&seg.filter, last) {
+            (None, true) if self.order => {
+                if let Some(Value::Array(items)) = obj.get_mut(&seg.name) {
+                    items.sort_by_cached_key(Value::to_string);
+                }
+            }
+            (None | Some(Filter::All), true) => {
+                obj.remove(&seg.name);
+            }
+            (Some(Filter::Eq(k, want)), true) => {
+                if let Some(Value::Array(items)) = obj.get_mut(&seg.name) {
+                    items.retain(|item| !matches(item, k, want));
+                }
+            }
+            (None | Some(Filter::All), false) => {
+                if let Some(child) = obj.get_mut(&seg.name) {
+                    self.walk(child, &path[1..]);
+                }
+            }
+            (Some(Filter::Eq(k, want)), false) => {
+                if let Some(Value::Array(items)) = obj.get_mut(&seg.name) {
+                    for item in items.iter_mut().filter(|item| matches(item, k, want)) {
+                        self.walk(item, &path[1..]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn matches(item: &Value, key: &str, want: &str) -> bool {
+    match item.get(key) {
+        Some(Value::String(s)) => s == want,
+        Some(other) => serde_json::from_str::<Value>(want).is_ok_and(|w| w == *other),
+        None => false,
+    }
+}
+
+/// Rules from `[diff] ignore` in the config followed by `extra`.
+pub fn ignore_rules(extra: &[IgnoreRule]) -> Result<Vec<IgnoreRule>, String> {
+    let mut rules = crate::config::get()
+        .diff
+        .ignore
+        .iter()
+        .map(|r| IgnoreRule::parse(r).map_err(|e| format!("[diff] ignore: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    rules.extend_from_slice(extra);
+    Ok(rules)
+}
+
+/// Apply every rule to `v` in place.
+pub fn strip(v: &mut Value, rules: &[IgnoreRule]) {
+    for rule in rules {
+        rule.apply(v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pointer, IgnoreRule};
+    use serde_json::{json, Value};
+
+    fn apply(rule: &str, mut v: Value) -> Value {
+        IgnoreRule::parse(rule).unwrap().apply(&mut v);
+        v
+    }
+
+    #[test]
+    fn pointer_escapes_rfc_6901_characters() {
+        assert_eq!(pointer("events.eventDate"), "/events/eventDate");
+        assert_eq!(pointer("a/b.c~d"), "/a~1b/c~0d");
+        assert_eq!(pointer(""), "");
+        assert_eq!(pointer("a..b"), "/a/b");
+    }
+
+    #[test]
+    fn parse_rejects_malformed_rules() {
+        for bad in [
+            "events[eventAction",
+            "events[]",
+            "a..b",
+            "[*]",
+            "order:status[*]",
+        ] {
+            assert!(IgnoreRule::parse(bad).is_err(), "{bad}");
+        }
+        assert!(IgnoreRule::parse("links[href=https://rdap.test/a.b].title").is_ok());
+    }
+
+    #[test]
+    fn removes_fields_and_filtered_elements() {
+        let v = json!({
+            "port43": "whois.test",
+            "events": [
+                {"eventAction": "registration", "eventDate": "2020"},
+                {"eventAction": "last changed", "eventDate": "2024"},
+            ],
+        });
+        assert_eq!(apply("port43", v.clone()).get("port43"), None);
+        assert_eq!(
+            apply("events[eventAction=last changed]", v.clone())["events"],
+            json!([{"eventAction": "registration", "eventDate": "2020"}])
+        );
+        assert_eq!(
+            apply("events.eventDate", v)["events"],
+            json!([{"eventAction": "registration"}, {"eventAction": "last changed"}])
+        );
+    }
+
+    #[test]
+    fn filters_match_non_string_values_and_dotted_values() {
+        let v = json!({"links": [
+            {"href": "https://rdap.test/a.b", "title": "x", "n": 1},
+            {"href": "https://rdap.test/c", "title": "y", "n": 2},
+        ]});
+        let out = apply("links[href=https://rdap.test/a.b].title", v.clone());
+        assert_eq!(out["links"][0].get("title"), None);
+        assert_eq!(out["links"][1]["title"], "y");
+        let out = apply("links[n=2]", v);
+        assert_eq!(out["links"].as_array().map(Vec::len), Some(1));
+    }
+
+    #[test]
+    fn order_sorts_arrays() {
+        let v = json!({"status": ["client hold", "active"]});
+        assert_eq!(
+            apply("order:status", v)["status"],
+            json!(["active", "client hold"])
+        );
+    }
+
+    #[test]
+    fn envelope_rules_reach_annotations_and_the_response() {
+        let v = json!({
+            "rdap": {"port43": "whois.test", "ldhName": "example.test"},
+            "_rdapx": {"tags": {"case": "1"}},
+            "_meta": {"schema_version": 2},
+        });
+        let out = apply("port43", v.clone());
+        assert_eq!(out["rdap"].get("port43"), None);
+        assert_eq!(out["rdap"]["ldhName"], "example.test");
+        let out = apply("_rdapx.tags", v);
+        assert_eq!(out["_rdapx"], json!({}));
+        assert_eq!(out["rdap"]["port43"], "whois.test");
+    }
+}
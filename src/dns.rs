@@ -0,0 +1,83 @@
+//! In-process DNS cache for the HTTP client. Bulk runs talk to a handful of
+//! registry hosts thousands of times; this resolves each once per
+//! [`TTL`] and hands hyper an RFC 8305 interleaved address list (IPv6, IPv4,
+//! IPv6, ...) so its Happy Eyeballs connector falls back to IPv4 quickly when
+//! a registry's IPv6 is broken.
+//!
+//! Record lookups the system resolver can't do (SOA and NS for `--fallback
+//! dns`) go through one shared [`records`] resolver.
+
+use hickory_resolver::TokioAsyncResolver;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// How long a lookup is reused (the system resolver doesn't expose record TTLs).
+const TTL: Duration = Duration::from_secs(300);
+
+type Cache = Mutex<HashMap<String, (Instant, Vec<SocketAddr>)>>;
+
+#[derive(Default)]
+pub struct CachingResolver {
+    cache: Arc<Cache>,
+}
+
+/// Process-wide resolver shared by every client.
+pub fn resolver() -> Arc<CachingResolver> {
+    static RESOLVER: OnceLock<Arc<CachingResolver>> = OnceLock::new();
+    RESOLVER.get_or_init(Arc::default).clone()
+}
+
+/// Process-wide resolver for record lookups, built from the system
+/// configuration on first use.
+pub fn records() -> Result<&'static TokioAsyncResolver, String> {
+    static RECORDS: OnceLock<Result<TokioAsyncResolver, String>> = OnceLock::new();
+    RECORDS
+        .get_or_init(|| TokioAsyncResolver::tokio_from_system_conf().map_err(|e| e.to_string()))
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+/// Alternate address families, IPv6 first (RFC 8305 section 4).
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<_>, Vec<_>) = addrs.into_iter().partition(SocketAddr::is_ipv6);
+    let mut out = Vec::with_capacity(v6.len() + v4.len());
+    let (mut a, mut b) = (v6.into_iter(), v4.into_iter());
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => break,
+            (x, y) => out.extend(x.into_iter().chain(y)),
+        }
+    }
+    out
+}
+
+fn lock(cache: &Cache) -> std::sync::MutexGuard<'_, HashMap<String, (Instant, Vec<SocketAddr>)>> {
+    cache
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let cache = Arc::clone(&self.cache);
+        Box::pin(async move {
+            let hit = lock(&cache)
+                .get(&host)
+                .filter(|(at, _)| at.elapsed() < TTL)
+                .map(|(_, addrs)| addrs.clone());
+            let addrs = if let Some(addrs) = hit {
+                addrs
+            } else {
+                let addrs =
+                    interleave(tokio::net::lookup_host((host.as_str(), 0)).await?.collect());
+                lock(&cache).insert(host, (Instant::now(), addrs.clone()));
+                addrs
+            };
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
@@ -0,0 +1,114 @@
+//! `--fallback`: secondary sources for domains whose RDAP lookup failed
+//! (unreachable server, 5xx, no RDAP service) so bulk pipelines still get an
+//! answer per input. An RDAP 404 is authoritative and never falls back.
+//!
+//! `whois` asks the registry's port-43 server (and the registrar's, for thin
+//! registries) and maps the text onto an RDAP domain object
+//! ([`crate::whois::parse`]). `dns` builds a minimal, registrar-agnostic
+//! record from the zone's SOA and NS records. Fallback records carry
+//! `_meta.source` naming where they came from and `_meta.rdap_error` with
+//! the original failure.
+
+use clap::ValueEnum;
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::proto::op::ResponseCode;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::OnceLock;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fallback {
+    /// Registrar, dates, statuses and nameservers parsed from WHOIS
+    Whois,
+    /// Existence and nameservers from DNS (SOA/NS)
+    Dns,
+}
+
+static CHAIN: OnceLock<Vec<Fallback>> = OnceLock::new();
+
+pub fn init(chain: &[Fallback]) {
+    if !chain.is_empty() {
+        let _ = CHAIN.set(chain.to_vec());
+    }
+}
+
+pub fn enabled() -> bool {
+    CHAIN.get().is_some()
+}
+
+const fn no_records(kind: &ResolveErrorKind) -> Option<ResponseCode> {
+    match kind {
+        ResolveErrorKind::NoRecordsFound { response_code, .. } => Some(*response_code),
+        _ => None,
+    }
+}
+
+/// Minimal domain record from DNS: whether the name has an SOA and which
+/// nameservers it delegates to. Missing records aren't an error; a failed
+/// DNS lookup is.
+async fn dns(domain: &str) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    let resolver = crate::dns::records()?;
+    let name = format!("{}.", domain.trim_end_matches('.'));
+    let (soa, nxdomain) = match resolver.soa_lookup(name.as_str()).await {
+        Ok(soa) => (soa.iter().next().map(|s| s.mname().to_utf8()), false),
+        Err(e) => match no_records(e.kind()) {
+            Some(code) => (None, code == ResponseCode::NXDomain),
+            None => return Err(e.into()),
+        },
+    };
+    let mut nameservers: Vec<String> = match resolver.ns_lookup(name.as_str()).await {
+        Ok(ns) => ns
+            .iter()
+            .map(|n| n.0.to_utf8().trim_end_matches('.').to_lowercase())
+            .collect(),
+        Err(e) if no_records(e.kind()).is_some() => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    nameservers.sort();
+    Ok(json!({
+        "objectClassName": "domain",
+        "ldhName": domain.trim_end_matches('.').to_lowercase(),
+        "nameservers": nameservers
+            .iter()
+            .map(|ns| json!({"objectClassName": "nameserver", "ldhName": ns}))
+            .collect::<Vec<_>>(),
+        "_meta": {
+            "dns": {
+                "exists": !nxdomain,
+                "soa": soa.map(|m| m.trim_end_matches('.').to_lowercase()),
+                "delegated": !nameservers.is_empty(),
+            },
+        },
+    }))
+}
+
+async fn whois(domain: &str) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    let hops = crate::whois::lookup(domain, None, true).await?;
+    Ok(crate::whois::parse_chain(&hops, domain)
+        .ok_or_else(|| format!("no WHOIS record for {domain}"))?)
+}
+
+/// Try each configured source in order after `rdap_error`; returns the
+/// original error if none produces a record.
+pub async fn fetch(
+    domain: &str,
+    rdap_error: Box<dyn Error + Send + Sync>,
+) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    for source in CHAIN.get().into_iter().flatten() {
+        let record = match source {
+            Fallback::Whois => whois(domain).await,
+            Fallback::Dns => dns(domain).await,
+        };
+        if let Ok(mut record) = record {
+            let name = source.to_possible_value().map(|v| v.get_name().to_string());
+            crate::attach_meta(&mut record, "source", json!(name));
+            crate::attach_meta(
+                &mut record,
+                "rdap_error",
+                Value::from(rdap_error.to_string()),
+            );
+            return Ok(record);
+        }
+    }
+    Err(rdap_error)
+}
@@ -0,0 +1,220 @@
+//! `--fan-out`: ask every plausible server for an ambiguous query at once and
+//! keep the first authoritative answer. Entity handles and IP/ASN queries go
+//! to all five RIRs; domains to the registry and the rdap.org redirector.
+//! The other requests are dropped (cancelled) as soon as one wins, and every
+//! attempt is listed under `_meta.fan_out` in candidate order.
+//!
+//! Every answer names the server that gave it in `_meta.server`.
+//!
+//! Without `--fan-out`, a server that times out or answers 5xx is retried at
+//! the other base URLs its bootstrap entry lists, and the answer is tagged
+//! with `_meta.server_fallback`. IP and ASN lookups also reach the other
+//! RIRs: when the routed RIR doesn't know a resource (it was transferred
+//! after the bootstrap data was published) they are asked in turn, after any
+//! HTTP redirect between RIRs has been followed.
+
+use crate::{
+    attach_meta, fetch_url, is_not_found, is_server_failure, normalize, url_host, FetchOpts,
+    HttpStatusError, Kind,
+};
+use futures::stream::{self, StreamExt};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::time::Instant;
+
+/// RDAP base URLs (with trailing slash) of the five RIRs.
+pub const RIRS: [&str; 5] = [
+    "https://rdap.arin.net/registry/",
+    "https://rdap.db.ripe.net/",
+    "https://rdap.apnic.net/",
+    "https://rdap.lacnic.net/rdap/",
+    "https://rdap.afrinic.net/rdap/",
+];
+
+/// Entity handle object tags of the RIRs, in [`RIRS`] order (`ABC123-ARIN`).
+pub const RIR_TAGS: [&str; 5] = ["ARIN", "RIPE", "AP", "LACNIC", "AFRINIC"];
+
+/// Candidate URLs for `q`, most likely first.
+pub fn candidates(q: &str) -> Vec<String> {
+    let (kind, norm) = normalize(q);
+    match kind {
+        Kind::Entity => RIRS.iter().map(|b| format!("{b}entity/{norm}")).collect(),
+        Kind::Domain => {
+            let mut urls = vec![crate::classify_to_url(Kind::Domain, &norm)];
+            let redirector = format!("https://rdap.org/domain/{norm}");
+            if urls[0] != redirector {
+                urls.push(redirector);
+            }
+            urls
+        }
+        Kind::Ip => RIRS.iter().map(|b| format!("{b}ip/{norm}")).collect(),
+        Kind::Asn => RIRS.iter().map(|b| format!("{b}autnum/{norm}")).collect(),
+    }
+}
+
+/// An answer counts as authoritative when it is an RDAP object.
+fn authoritative(json: &Value) -> bool {
+    json.get("objectClassName")
+        .and_then(Value::as_str)
+        .is_some()
+}
+
+pub async fn fetch(
+    client: &reqwest::Client,
+    q: &str,
+    opts: &FetchOpts<'_>,
+) -> Result<Value, Box<dyn Error>> {
+    let urls = candidates(q);
+    let started = Instant::now();
+    // one at a time in candidate order under --deterministic, so the same
+    // server wins every run
+    let pending = stream::iter(0..urls.len())
+        .map(|i| {
+            let url = &urls[i];
+            async move { (url, fetch_url(client, url, q, opts).await) }
+        })
+        .buffer_unordered(crate::deterministic::concurrency(urls.len()));
+    let mut pending = Box::pin(pending);
+
+    let mut attempts = Vec::new();
+    let mut winner = None;
+    // errors are kept as text (not held across awaits); all-404 stays a 404
+    let mut last_err: Option<String> = None;
+    let mut all_not_found = true;
+    while let Some((url, res)) = pending.next().await {
+        let elapsed = crate::deterministic::elapsed(started);
+        let elapsed_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        let mut attempt = json!({ "server": url_host(url), "url": url, "elapsed_ms": elapsed_ms });
+        match res {
+            Ok(v) if authoritative(&v) => {
+                attempt["outcome"] = "ok".into();
+                attempts.push(attempt);
+                winner = Some((v, url));
+                break;
+            }
+            Ok(_) => {
+                attempt["outcome"] = "not authoritative".into();
+                all_not_found = false;
+            }
+            Err(e) => {
+                all_not_found &= is_not_found(e.as_ref());
+                attempt["outcome"] = e.to_string().into();
+                last_err = Some(e.to_string());
+            }
+        }
+        attempts.push(attempt);
+    }
+    drop(pending);
+    for url in &urls {
+        if !attempts.iter().any(|a| a["url"] == url.as_str()) {
+            attempts.push(json!({ "server": url_host(url), "url": url, "outcome": "cancelled" }));
+        }
+    }
+    // listed in candidate order, not completion order
+    attempts.sort_by_key(|a| urls.iter().position(|u| a["url"] == u.as_str()));
+
+    let Some((mut json, won_at)) = winner else {
+        if all_not_found {
+            return Err(Box::new(HttpStatusError {
+                url: urls.first().cloned().unwrap_or_default(),
+                status: reqwest::StatusCode::NOT_FOUND,
+                body: format!("{q}: not found at any of {} servers", urls.len()),
+                attempts: urls.len(),
+            }));
+        }
+        return Err(last_err
+            .unwrap_or_else(|| format!("{q}: no authoritative answer"))
+            .into());
+    };
+    attach_meta(&mut json, "fan_out", Value::Array(attempts));
+    Ok(answered_by(json, won_at))
+}
+
+/// Record the host of `url` as `_meta.server`.
+pub fn answered_by(mut json: Value, url: &str) -> Value {
+    attach_meta(&mut json, "server", url_host(url).into());
+    json
+}
+
+/// `fetch_url`, moving on to the service's other bootstrap base URLs while
+/// the server fails (network error, timeout, or 5xx). An answer from one of
+/// them records `_meta.server_fallback`; a definite error from one (a 404)
+/// is returned as is.
+pub async fn with_alternates(
+    client: &reqwest::Client,
+    url: &str,
+    q: &str,
+    opts: &FetchOpts<'_>,
+) -> Result<Value, Box<dyn Error>> {
+    // made Send before the next await, for `serve`
+    let first = match fetch_url(client, url, q, opts).await {
+        Ok(json) => return Ok(answered_by(json, url)),
+        Err(e) if is_server_failure(e.as_ref()) => crate::sendable(e),
+        Err(e) => return Err(e),
+    };
+    for alt in crate::bootstrap::alternates(url) {
+        let res = fetch_url(client, &alt, q, opts)
+            .await
+            .map_err(crate::sendable);
+        match res {
+            Ok(mut json) => {
+                attach_meta(
+                    &mut json,
+                    "server_fallback",
+                    json!({ "from": url, "to": alt, "error": first.to_string() }),
+                );
+                return Ok(answered_by(json, &alt));
+            }
+            Err(e) if is_server_failure(e.as_ref()) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Err(first)
+}
+
+/// IP or ASN lookup at its routed `url`; when that is an RIR answering 404,
+/// the other RIRs are asked one after another and the first answer is
+/// tagged with `_meta.rir_redirect`, listing every RIR asked and how it
+/// answered. Servers routed through `servers.toml` aren't RIRs and get no
+/// second opinion.
+pub async fn across_rirs(
+    client: &reqwest::Client,
+    url: &str,
+    q: &str,
+    opts: &FetchOpts<'_>,
+) -> Result<Value, Box<dyn Error>> {
+    let routed = url_host(url);
+    if !RIRS.iter().any(|b| url_host(b) == routed) {
+        return with_alternates(client, url, q, opts).await;
+    }
+    // made Send before the next await, for `serve`
+    let first = match with_alternates(client, url, q, opts).await {
+        Err(e) if is_not_found(e.as_ref()) => crate::sendable(e),
+        other => return other,
+    };
+    let (kind, norm) = normalize(q);
+    let path = if matches!(kind, Kind::Asn) {
+        "autnum"
+    } else {
+        "ip"
+    };
+    let mut attempts = vec![json!({ "server": routed, "outcome": first.to_string() })];
+    for base in RIRS.iter().filter(|b| url_host(b) != routed) {
+        let alt = format!("{base}{path}/{norm}");
+        match fetch_url(client, &alt, q, opts).await {
+            Ok(mut json) => {
+                attempts.push(json!({ "server": url_host(&alt), "outcome": "ok" }));
+                attach_meta(
+                    &mut json,
+                    "rir_redirect",
+                    json!({ "from": routed, "to": url_host(&alt), "attempts": attempts }),
+                );
+                return Ok(answered_by(json, &alt));
+            }
+            Err(e) => {
+                attempts.push(json!({ "server": url_host(&alt), "outcome": e.to_string() }));
+            }
+        }
+    }
+    Err(first)
+}
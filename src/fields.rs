@@ -0,0 +1,154 @@
+//! Helpers for pulling commonly used values out of RDAP objects.
+
+use serde_json::Value;
+
+fn array<'a>(json: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    json.get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+pub fn statuses(json: &Value) -> Vec<String> {
+    array(json, "status")
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Nameserver host names, lowercased and sorted.
+pub fn nameservers(json: &Value) -> Vec<String> {
+    let mut ns: Vec<String> = array(json, "nameservers")
+        .filter_map(|n| n.get("ldhName").and_then(Value::as_str))
+        .map(|n| n.trim_end_matches('.').to_ascii_lowercase())
+        .collect();
+    ns.sort();
+    ns.dedup();
+    ns
+}
+
+pub fn has_role(entity: &Value, role: &str) -> bool {
+    array(entity, "roles").any(|r| r.as_str() == Some(role))
+}
+
+/// Top-level entities carrying `role`.
+pub fn entities_with_role<'a>(json: &'a Value, role: &'a str) -> impl Iterator<Item = &'a Value> {
+    array(json, "entities").filter(move |e| has_role(e, role))
+}
+
+/// First value of a jCard property (`fn`, `email`, `tel`, ...) on an entity.
+pub fn vcard_prop(entity: &Value, prop: &str) -> Option<String> {
+    entity
+        .get("vcardArray")?
+        .get(1)?
+        .as_array()?
+        .iter()
+        .find(|p| p.get(0).and_then(Value::as_str) == Some(prop))
+        .and_then(|p| p.get(3))
+        .and_then(|v| match v {
+            Value::String(s) => Some(s.trim().to_string()),
+            Value::Array(parts) => Some(
+                parts
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            _ => None,
+        })
+        .filter(|s| !s.is_empty())
+}
+
+/// Registrar name from the `registrar` entity (`fn`, falling back to handle).
+pub fn registrar(json: &Value) -> Option<String> {
+    let e = entities_with_role(json, "registrar").next()?;
+    vcard_prop(e, "fn").or_else(|| e.get("handle").and_then(Value::as_str).map(str::to_string))
+}
+
+/// Email of the first `abuse` entity, searching nested entities too (RIRs
+/// usually hang the abuse contact off the registrant).
+pub fn abuse_email(json: &Value) -> Option<String> {
+    abuse_prop(json, "email")
+}
+
+/// Phone number of the first `abuse` entity that has one, without `tel:`.
+pub fn abuse_phone(json: &Value) -> Option<String> {
+    abuse_prop(json, "tel").map(|t| t.trim_start_matches("tel:").to_string())
+}
+
+fn abuse_prop(json: &Value, prop: &str) -> Option<String> {
+    array(json, "entities").find_map(|e| {
+        if has_role(e, "abuse") {
+            if let Some(value) = vcard_prop(e, prop) {
+                return Some(value);
+            }
+        }
+        abuse_prop(e, prop)
+    })
+}
+
+/// Date of the first event with `action` (e.g. `registration`, `expiration`).
+pub fn event_date(json: &Value, action: &str) -> Option<String> {
+    array(json, "events")
+        .find(|e| e.get("eventAction").and_then(Value::as_str) == Some(action))
+        .and_then(|e| e.get("eventDate").and_then(Value::as_str))
+        .map(str::to_string)
+}
+
+pub fn expiry(json: &Value) -> Option<String> {
+    event_date(json, "expiration")
+}
+
+/// An attribute objects are counted or matched by (`stats`, `report`,
+/// `pivot`), normalized so equal values compare equal.
+// `report` and `pivot`, the only users of some, need the sqlite feature
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+#[derive(Clone, Copy, Debug)]
+pub enum Attribute {
+    Registrar,
+    Status,
+    Nameserver,
+    Tld,
+    Country,
+    CreatedYear,
+    ObjectClass,
+    AbuseEmail,
+    RegistrantEmail,
+}
+
+impl Attribute {
+    /// Values of this attribute on an RDAP object; multi-valued attributes
+    /// (statuses, nameservers, emails) can give several.
+    pub fn values(self, json: &Value) -> Vec<String> {
+        let str_field = |k: &str| json.get(k).and_then(Value::as_str).map(str::to_string);
+        match self {
+            Self::Registrar => registrar(json).into_iter().collect(),
+            Self::Status => statuses(json).iter().map(|s| s.to_lowercase()).collect(),
+            Self::Nameserver => nameservers(json),
+            Self::Tld => str_field("ldhName")
+                .and_then(|n| {
+                    let n = n.trim_end_matches('.').to_lowercase();
+                    n.rsplit('.').next().map(str::to_string)
+                })
+                .into_iter()
+                .collect(),
+            Self::Country => str_field("country")
+                .map(|c| c.to_ascii_uppercase())
+                .into_iter()
+                .collect(),
+            Self::CreatedYear => event_date(json, "registration")
+                .and_then(|d| d.get(..4).map(str::to_string))
+                .into_iter()
+                .collect(),
+            Self::ObjectClass => str_field("objectClassName").into_iter().collect(),
+            Self::AbuseEmail => abuse_email(json)
+                .map(|e| e.to_lowercase())
+                .into_iter()
+                .collect(),
+            Self::RegistrantEmail => entities_with_role(json, "registrant")
+                .filter_map(|e| vcard_prop(e, "email"))
+                .collect(),
+        }
+    }
+}
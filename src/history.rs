@@ -0,0 +1,427 @@
+//! Append-only snapshot history, one NDJSON file per RDAP URL under the data dir.
+//!
+//! Snapshots can be pinned under a name (`rdapx history pin Q --as
+//! baseline-2024`) as an approved state for `rdapx diff --against`; pins are
+//! kept apart from the history files, so retention never removes them.
+
+use crate::config::RetentionPolicy;
+use crate::{fields, CacheEntry};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Event actions whose dates move on every refresh without the object changing.
+const VOLATILE_EVENTS: [&str; 1] = ["last update of RDAP database"];
+
+pub fn history_dir() -> io::Result<PathBuf> {
+    let p = crate::project::data_dir()?.join("history");
+    fs::create_dir_all(&p)?;
+    Ok(p)
+}
+
+fn history_path(url: &str) -> io::Result<PathBuf> {
+    Ok(history_dir()?.join(format!("{}.ndjson", crate::url_key(url))))
+}
+
+/// Hash of the response with rdapx annotations and volatile fields removed, so
+/// it only moves when the registry data itself changes.
+pub fn content_hash(data: &Value) -> String {
+    let mut v = data.clone();
+    if let Some(obj) = v.as_object_mut() {
+        obj.retain(|k, _| !k.starts_with('_'));
+        if let Some(events) = obj.get_mut("events").and_then(Value::as_array_mut) {
+            events.retain(|e| {
+                let action = e.get("eventAction").and_then(Value::as_str);
+                !action.is_some_and(|a| VOLATILE_EVENTS.contains(&a))
+            });
+        }
+    }
+    // serde_json maps are sorted, so this serialization is canonical
+    blake3::hash(v.to_string().as_bytes()).to_hex().to_string()
+}
+
+/// Append a snapshot, then apply the configured retention policy (if any).
+pub fn append(entry: &CacheEntry) -> io::Result<()> {
+    let path = history_path(&entry.url)?;
+    let mut f = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(f, "{}", crate::crypt::seal(&serde_json::to_string(entry)?))?;
+    let policy = crate::config::get().history;
+    if !policy.is_empty() {
+        compact_file(&path, &policy, false)?;
+    }
+    Ok(())
+}
+
+/// All snapshots for `url`, oldest first. Unreadable lines are skipped.
+pub fn load(url: &str) -> io::Result<Vec<CacheEntry>> {
+    let raw = match fs::read_to_string(history_path(url)?) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(raw
+        .lines()
+        .filter_map(|l| serde_json::from_str(&crate::crypt::open(l)?).ok())
+        .collect())
+}
+
+pub fn latest(url: &str) -> Option<CacheEntry> {
+    load(url).ok()?.pop()
+}
+
+/* -------------------------------- Pins ---------------------------------- */
+
+fn pin_dir(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        || name.starts_with('.')
+    {
+        return Err(format!(
+            "invalid pin name '{name}' (letters, digits, '-', '_' and '.' only)"
+        ));
+    }
+    Ok(history_dir()
+        .map_err(|e| e.to_string())?
+        .join("pins")
+        .join(name))
+}
+
+/// Pin the snapshot of `url` whose hash starts with `hash` (default: the
+/// latest, or the cached response when there is no history yet) as `name`,
+/// replacing an earlier pin of that name.
+pub fn pin(url: &str, name: &str, hash: Option<&str>) -> Result<CacheEntry, String> {
+    let dir = pin_dir(name)?;
+    let snaps = load(url).map_err(|e| e.to_string())?;
+    let snap = match hash {
+        Some(h) => {
+            let mut found = snaps.into_iter().filter(|s| s.hash.starts_with(h));
+            match (found.next(), found.next()) {
+                (Some(s), None) => s,
+                (Some(_), Some(_)) => return Err(format!("hash prefix '{h}' is ambiguous")),
+                (None, _) => return Err(format!("no snapshot with hash '{h}'")),
+            }
+        }
+        None => snaps
+            .into_iter()
+            .last()
+            .or_else(|| crate::read_cache_entry(url))
+            .ok_or("nothing recorded yet; fetch it first")?,
+    };
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(format!("{}.json", crate::url_key(url)));
+    let sealed = crate::crypt::seal(&serde_json::to_string(&snap).map_err(|e| e.to_string())?);
+    fs::write(&path, sealed).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(snap)
+}
+
+/// Snapshot of `url` pinned as `name`, if any.
+pub fn pinned(url: &str, name: &str) -> Result<Option<CacheEntry>, String> {
+    let path = pin_dir(name)?.join(format!("{}.json", crate::url_key(url)));
+    let raw = match fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("{}: {e}", path.display())),
+    };
+    let raw = crate::crypt::open(raw.trim())
+        .ok_or_else(|| format!("{}: sealed with another key", path.display()))?;
+    serde_json::from_str(&raw)
+        .map(Some)
+        .map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/* ------------------------------ Retention ------------------------------- */
+
+/// Apply `policy` to snapshots (oldest first). Dedupe runs first; the
+/// `keep_last` and `keep_monthly` rules are then combined as a union.
+pub fn compact(snapshots: Vec<CacheEntry>, policy: &RetentionPolicy) -> Vec<CacheEntry> {
+    let mut snaps = snapshots;
+    if policy.dedupe {
+        snaps.dedup_by(|cur, prev| cur.hash == prev.hash);
+    }
+    if policy.keep_last.is_none() && !policy.keep_monthly {
+        return snaps;
+    }
+
+    let n = snaps.len();
+    let mut keep = vec![false; n];
+    if let Some(last) = policy.keep_last {
+        keep.iter_mut()
+            .skip(n.saturating_sub(last))
+            .for_each(|k| *k = true);
+    }
+    if policy.keep_monthly {
+        let month = |s: &CacheEntry| crate::format_timestamp(s.fetched_at)[..7].to_string();
+        for i in 0..n {
+            let first = i == 0 || month(&snaps[i - 1]) != month(&snaps[i]);
+            let last = i + 1 == n || month(&snaps[i + 1]) != month(&snaps[i]);
+            keep[i] |= first || last;
+        }
+    }
+    snaps
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(s, k)| k.then_some(s))
+        .collect()
+}
+
+/// Compact one history file in place; returns the number of removed snapshots.
+fn compact_file(path: &Path, policy: &RetentionPolicy, dry_run: bool) -> io::Result<usize> {
+    let raw = fs::read_to_string(path)?;
+    let Some(lines) = raw
+        .lines()
+        .map(crate::crypt::open)
+        .collect::<Option<Vec<_>>>()
+    else {
+        // sealed with a key we don't have; rewriting would drop those snapshots
+        return Ok(0);
+    };
+    let snaps: Vec<CacheEntry> = lines
+        .iter()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect();
+    let before = snaps.len();
+    let kept = compact(snaps, policy);
+    let removed = before - kept.len();
+    if removed > 0 && !dry_run {
+        let mut out = String::new();
+        for s in &kept {
+            out.push_str(&crate::crypt::seal(&serde_json::to_string(s)?));
+            out.push('\n');
+        }
+        // an interrupted compaction must never truncate history
+        crate::checkpoint::write_atomic(path, out.as_bytes())?;
+    }
+    Ok(removed)
+}
+
+/// Compact every history file; returns `(files, removed snapshots)`.
+pub fn compact_all(policy: &RetentionPolicy, dry_run: bool) -> io::Result<(usize, usize)> {
+    let (mut files, mut removed) = (0, 0);
+    for e in fs::read_dir(history_dir()?)?.flatten() {
+        let p = e.path();
+        if p.extension().and_then(|s| s.to_str()) == Some("ndjson") {
+            files += 1;
+            removed += compact_file(&p, policy, dry_run)?;
+        }
+    }
+    Ok((files, removed))
+}
+
+/* ------------------------------- Timeline ------------------------------- */
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum TimelineFormat {
+    Csv,
+    Json,
+}
+
+/// One field change between consecutive snapshots (the first snapshot
+/// reports its initial values with an empty `old`).
+#[derive(Serialize, Debug)]
+pub struct Change {
+    pub at: String,
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+    pub hash: String,
+}
+
+/// Fields tracked by the timeline, rendered as comparable strings.
+fn tracked(data: &Value) -> [(&'static str, String); 4] {
+    [
+        ("status", fields::statuses(data).join(", ")),
+        ("nameservers", fields::nameservers(data).join(", ")),
+        ("registrar", fields::registrar(data).unwrap_or_default()),
+        ("expiry", fields::expiry(data).unwrap_or_default()),
+    ]
+}
+
+pub fn timeline(snapshots: &[CacheEntry]) -> Vec<Change> {
+    let mut out = Vec::new();
+    let mut prev: Option<[(&'static str, String); 4]> = None;
+    for snap in snapshots {
+        let cur = tracked(&snap.data);
+        for (i, (field, new)) in cur.iter().enumerate() {
+            let old = prev.as_ref().map(|p| p[i].1.clone()).unwrap_or_default();
+            let unchanged = prev.as_ref().map_or(new.is_empty(), |_| old == *new);
+            if unchanged {
+                continue;
+            }
+            out.push(Change {
+                at: crate::format_timestamp(snap.fetched_at),
+                field,
+                old,
+                new: new.clone(),
+                hash: snap.hash.clone(),
+            });
+        }
+        prev = Some(cur);
+    }
+    out
+}
+
+pub fn print_timeline(changes: &[Change], format: TimelineFormat) -> io::Result<()> {
+    match format {
+        TimelineFormat::Json => println!("{}", serde_json::to_string_pretty(changes)?),
+        TimelineFormat::Csv => crate::table::print_csv(
+            &["at", "field", "old", "new", "hash"],
+            changes.iter().map(|c| {
+                vec![
+                    c.at.clone(),
+                    c.field.to_string(),
+                    c.old.clone(),
+                    c.new.clone(),
+                    c.hash.clone(),
+                ]
+            }),
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compact, timeline};
+    use crate::config::RetentionPolicy;
+    use crate::CacheEntry;
+    use serde_json::{json, Value};
+
+    /// 2024-01-01T00:00:00Z
+    const JAN_1: u64 = 1_704_067_200;
+    const DAY: u64 = 86_400;
+
+    fn snap(fetched_at: u64, hash: &str, data: Value) -> CacheEntry {
+        CacheEntry {
+            query: "example.com".into(),
+            url: "https://rdap.test/domain/example.com".into(),
+            fetched_at,
+            hash: hash.into(),
+            data,
+            not_found: false,
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    fn hashes(snaps: &[CacheEntry]) -> Vec<&str> {
+        snaps.iter().map(|s| s.hash.as_str()).collect()
+    }
+
+    fn days(list: &[(u64, &str)]) -> Vec<CacheEntry> {
+        list.iter()
+            .map(|&(day, hash)| snap(JAN_1 + day * DAY, hash, Value::Null))
+            .collect()
+    }
+
+    #[test]
+    fn an_empty_policy_keeps_everything() {
+        let snaps = days(&[(0, "a"), (1, "a"), (2, "b")]);
+        let kept = compact(snaps, &RetentionPolicy::default());
+        assert_eq!(hashes(&kept), ["a", "a", "b"]);
+    }
+
+    #[test]
+    fn dedupe_drops_only_consecutive_repeats() {
+        let policy = RetentionPolicy {
+            dedupe: true,
+            ..RetentionPolicy::default()
+        };
+        let kept = compact(days(&[(0, "a"), (1, "a"), (2, "b"), (3, "a")]), &policy);
+        assert_eq!(hashes(&kept), ["a", "b", "a"]);
+    }
+
+    #[test]
+    fn keep_last_and_keep_monthly_combine_as_a_union() {
+        // Jan 1, 10, 20, 25 and Feb 5, 6
+        let snaps = || {
+            days(&[
+                (0, "j1"),
+                (9, "j10"),
+                (19, "j20"),
+                (24, "j25"),
+                (35, "f5"),
+                (36, "f6"),
+            ])
+        };
+        let last = RetentionPolicy {
+            keep_last: Some(2),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(hashes(&compact(snaps(), &last)), ["f5", "f6"]);
+        let monthly = RetentionPolicy {
+            keep_monthly: true,
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            hashes(&compact(snaps(), &monthly)),
+            ["j1", "j25", "f5", "f6"]
+        );
+        let both = RetentionPolicy {
+            keep_last: Some(3),
+            keep_monthly: true,
+            dedupe: false,
+        };
+        assert_eq!(hashes(&compact(snaps(), &both)), ["j1", "j25", "f5", "f6"]);
+        let wider = RetentionPolicy {
+            keep_last: Some(4),
+            ..both
+        };
+        assert_eq!(
+            hashes(&compact(snaps(), &wider)),
+            ["j1", "j20", "j25", "f5", "f6"]
+        );
+    }
+
+    #[test]
+    fn timeline_lists_initial_values_then_changes() {
+        let first = json!({
+            "status": ["active"],
+            "nameservers": [{"ldhName": "NS1.EXAMPLE.COM."}],
+            "events": [{"eventAction": "expiration", "eventDate": "2030-01-01T00:00:00Z"}],
+        });
+        let mut renewed = first.clone();
+        renewed["events"][0]["eventDate"] = json!("2031-01-01T00:00:00Z");
+        let snaps = [
+            snap(JAN_1, "a", first.clone()),
+            snap(JAN_1 + DAY, "a", first),
+            snap(JAN_1 + 2 * DAY, "b", renewed),
+        ];
+        let changes: Vec<_> = timeline(&snaps)
+            .into_iter()
+            .map(|c| (c.at, c.field, c.old, c.new, c.hash))
+            .collect();
+        let at = |day: u64| crate::format_timestamp(JAN_1 + day * DAY);
+        assert_eq!(
+            changes,
+            [
+                (at(0), "status", String::new(), "active".into(), "a".into()),
+                (
+                    at(0),
+                    "nameservers",
+                    String::new(),
+                    "ns1.example.com".into(),
+                    "a".into()
+                ),
+                (
+                    at(0),
+                    "expiry",
+                    String::new(),
+                    "2030-01-01T00:00:00Z".into(),
+                    "a".into()
+                ),
+                (
+                    at(2),
+                    "expiry",
+                    "2030-01-01T00:00:00Z".into(),
+                    "2031-01-01T00:00:00Z".into(),
+                    "b".into()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! `--lang`: the `Accept-Language` sent to registries, the language preferred
+//! when picking notices/remarks, and translated table labels.
+
+use serde_json::Value;
+use std::sync::OnceLock;
+
+static LANG: OnceLock<String> = OnceLock::new();
+
+pub fn init(lang: &str) {
+    let _ = LANG.set(lang.trim().to_string());
+}
+
+/// The `--lang` tag, if one was given.
+pub fn lang() -> Option<&'static str> {
+    LANG.get().map(String::as_str)
+}
+
+/// Primary subtag, lowercased (`pt-BR` -> `pt`).
+fn primary(tag: &str) -> String {
+    tag.split(['-', '_']).next().unwrap_or(tag).to_lowercase()
+}
+
+#[derive(Clone, Copy)]
+pub enum Label {
+    Type,
+    Handle,
+    Name,
+    Country,
+    Status,
+    Roles,
+    Remarks,
+    Notices,
+    Redactions,
+}
+
+/// Table label in the `--lang` language; English when untranslated.
+pub fn label(l: Label) -> &'static str {
+    let lang = lang().map(primary).unwrap_or_default();
+    let row: [&'static str; 6] = match l {
+        // en, de, fr, es, pt, it
+        Label::Type => ["Type", "Typ", "Type", "Tipo", "Tipo", "Tipo"],
+        Label::Handle => [
+            "Handle",
+            "Handle",
+            "Identifiant",
+            "Identificador",
+            "Identificador",
+            "Identificativo",
+        ],
+        Label::Name => ["Name", "Name", "Nom", "Nombre", "Nome", "Nome"],
+        Label::Country => ["Country", "Land", "Pays", "País", "País", "Paese"],
+        Label::Status => ["Status", "Status", "Statut", "Estado", "Estado", "Stato"],
+        Label::Roles => ["Roles", "Rollen", "Rôles", "Roles", "Funções", "Ruoli"],
+        Label::Remarks => [
+            "Remarks",
+            "Hinweise",
+            "Remarques",
+            "Observaciones",
+            "Observações",
+            "Note",
+        ],
+        Label::Notices => [
+            "Notices",
+            "Mitteilungen",
+            "Avis",
+            "Avisos",
+            "Avisos",
+            "Avvisi",
+        ],
+        Label::Redactions => [
+            "Redaction summary",
+            "Geschwärzte Felder",
+            "Champs masqués",
+            "Campos ocultados",
+            "Campos ocultados",
+            "Campi oscurati",
+        ],
+    };
+    let idx = match lang.as_str() {
+        "de" => 1,
+        "fr" => 2,
+        "es" => 3,
+        "pt" => 4,
+        "it" => 5,
+        _ => 0,
+    };
+    row[idx]
+}
+
+/// Whether a remark or notice is in the `--lang` language: untagged ones and
+/// those whose RFC 9083 `lang` (or the object's) matches, so registries that
+/// publish the same text in several languages show only the wanted one.
+/// Everything matches without `--lang`.
+pub fn wanted(item: &Value, object_lang: Option<&str>) -> bool {
+    let Some(want) = lang().map(primary) else {
+        return true;
+    };
+    item.get("lang")
+        .and_then(Value::as_str)
+        .or(object_lang)
+        .is_none_or(|l| primary(l) == want)
+}
+
+/// Remarks to show in the table with `--lang` (notices have their own
+/// section, see `notices`). Returns `(title, first description line)` pairs.
+pub fn localized_remarks(json: &Value) -> Vec<(String, String)> {
+    if lang().is_none() {
+        return Vec::new();
+    }
+    let object_lang = json.get("lang").and_then(Value::as_str);
+    json.get("remarks")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|r| wanted(r, object_lang))
+        .map(|r| {
+            let title = r
+                .get("title")
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string();
+            let desc = r
+                .get("description")
+                .and_then(Value::as_array)
+                .and_then(|d| d.first())
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string();
+            (title, desc)
+        })
+        .collect()
+}
@@ -0,0 +1,83 @@
+//! iCalendar (RFC 5545) export for `rdapx expiry --format ical`: one all-day
+//! event per domain expiration, with optional display alarms N days before.
+
+use crate::portfolio::CheckRow;
+use std::fmt::Write;
+
+/// Escape a TEXT value (RFC 5545 section 3.3.11).
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Fold content lines longer than 75 octets (section 3.1) and end each with CRLF.
+fn push_line(out: &mut String, line: &str) {
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// `YYYYMMDD` for days since the Unix epoch.
+fn ical_date(days: i64) -> String {
+    let secs = u64::try_from(days).unwrap_or(0) * 86_400;
+    crate::format_timestamp(secs)[..10].replace('-', "")
+}
+
+/// Calendar with an event for every row that has an expiry date, and one
+/// alarm per entry in `remind` (days before expiry). `now` stamps DTSTAMP.
+pub fn calendar(rows: &[CheckRow], remind: &[u32], now: u64) -> String {
+    let stamp = crate::format_timestamp(now).replace(['-', ':'], "");
+    let mut out = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//rdapx//domain expiry//EN",
+        "CALSCALE:GREGORIAN",
+        "X-WR-CALNAME:Domain expirations",
+    ] {
+        push_line(&mut out, line);
+    }
+    for row in rows {
+        let Some(day) = row.expiry.as_deref().and_then(crate::parse_date_days) else {
+            continue;
+        };
+        let name = escape(&row.query.to_lowercase());
+        let mut description = format!("{} expires", row.query);
+        if let Some(registrar) = &row.registrar {
+            let _ = write!(description, "; renew with {registrar}");
+        }
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(&mut out, &format!("UID:{name}-expiry@rdapx"));
+        push_line(&mut out, &format!("DTSTAMP:{stamp}"));
+        push_line(&mut out, &format!("DTSTART;VALUE=DATE:{}", ical_date(day)));
+        push_line(
+            &mut out,
+            &format!("DTEND;VALUE=DATE:{}", ical_date(day + 1)),
+        );
+        push_line(&mut out, &format!("SUMMARY:Domain expiry: {name}"));
+        push_line(&mut out, &format!("DESCRIPTION:{}", escape(&description)));
+        push_line(&mut out, "TRANSP:TRANSPARENT");
+        for days in remind {
+            push_line(&mut out, "BEGIN:VALARM");
+            push_line(&mut out, "ACTION:DISPLAY");
+            push_line(&mut out, &format!("TRIGGER:-P{days}D"));
+            push_line(
+                &mut out,
+                &format!("DESCRIPTION:{name} expires in {days} day(s)"),
+            );
+            push_line(&mut out, "END:VALARM");
+        }
+        push_line(&mut out, "END:VEVENT");
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
@@ -0,0 +1,145 @@
+//! `rdapx import`: ingest RDAP JSON collected elsewhere (other tools, old
+//! runs) into the history store, and optionally the cache, so it can be
+//! diffed, timelined, and served like rdapx's own fetches.
+//!
+//! Inputs are single JSON documents or NDJSON (bulk output, either output
+//! schema); directories are walked recursively. Each object is filed under
+//! the URL rdapx would query for it (not its `self` link, which may name
+//! another server), so later lookups and `history` find it. Snapshots
+//! identical to the latest one already stored are skipped.
+
+use crate::{history, CacheEntry};
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const CLASSES: [&str; 4] = ["domain", "ip network", "autnum", "entity"];
+
+#[derive(Default, Debug)]
+pub struct Report {
+    pub files: usize,
+    pub imported: usize,
+    pub unchanged: usize,
+    pub skipped: usize,
+}
+
+/// All files under `paths`, directories expanded recursively and sorted.
+fn collect(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut out = Vec::new();
+    for p in paths {
+        if p.is_dir() {
+            let mut children: Vec<PathBuf> = fs::read_dir(p)?.flatten().map(|e| e.path()).collect();
+            children.sort();
+            out.extend(collect(&children)?);
+        } else {
+            out.push(p.clone());
+        }
+    }
+    Ok(out)
+}
+
+/// Query and URL to file an object under, or why it can't be.
+fn locate(obj: &Value) -> Result<(String, String), String> {
+    let class = obj
+        .get("objectClassName")
+        .and_then(Value::as_str)
+        .ok_or("not an RDAP object (no objectClassName)")?;
+    if class == "nameserver" {
+        return Err("nameservers aren't looked up by rdapx".into());
+    }
+    if !CLASSES.contains(&class) {
+        return Err(format!("unknown objectClassName '{class}'"));
+    }
+    let s = |k: &str| obj.get(k).and_then(Value::as_str);
+    let query = match class {
+        "domain" => s("ldhName")
+            .or_else(|| s("unicodeName"))
+            .map(str::to_lowercase),
+        "ip network" => s("startAddress").map(str::to_string),
+        "autnum" => obj
+            .get("startAutnum")
+            .and_then(Value::as_u64)
+            .map(|n| format!("AS{n}")),
+        _ => s("handle").map(str::to_string),
+    }
+    .ok_or_else(|| format!("{class} without a name or handle"))?;
+    // rdapx only looks up entities by a tagged handle (`ABC123-ARIN`)
+    if class == "entity" && !matches!(crate::normalize(&query).0, crate::Kind::Entity) {
+        return Err(format!("entity '{query}' has no handle rdapx can look up"));
+    }
+    let url = crate::query_url(&query);
+    Ok((query, url))
+}
+
+fn documents(raw: &str) -> Vec<Result<Value, String>> {
+    if let Ok(doc) = serde_json::from_str::<Value>(raw) {
+        return vec![Ok(doc)];
+    }
+    raw.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|l| serde_json::from_str(l).map_err(|e| format!("invalid JSON: {e}")))
+        .collect()
+}
+
+/// Import every object under `paths`; `to_cache` also replaces cache entries
+/// (which then count as fresh for the cache TTL).
+pub fn run(paths: &[PathBuf], to_cache: bool) -> io::Result<Report> {
+    let mut report = Report::default();
+    for path in collect(paths)? {
+        report.files += 1;
+        let raw = fs::read_to_string(&path)?;
+        let fetched_at = mtime(&path);
+        for (n, doc) in documents(raw.trim_start_matches('\u{feff}'))
+            .into_iter()
+            .enumerate()
+        {
+            let stored = doc.and_then(|doc| {
+                // schema v2 envelopes carry the object under `rdap`
+                let mut obj = doc.get("rdap").cloned().unwrap_or(doc);
+                if let Some(map) = obj.as_object_mut() {
+                    map.retain(|k, _| !k.starts_with('_'));
+                }
+                let (query, url) = locate(&obj)?;
+                Ok(CacheEntry {
+                    query,
+                    url,
+                    fetched_at,
+                    hash: history::content_hash(&obj),
+                    data: obj,
+                    not_found: false,
+                    etag: None,
+                    last_modified: None,
+                })
+            });
+            let entry = match stored {
+                Ok(entry) => entry,
+                Err(reason) => {
+                    report.skipped += 1;
+                    eprintln!("Skipped {}#{}: {reason}", path.display(), n + 1);
+                    continue;
+                }
+            };
+            if history::latest(&entry.url).is_some_and(|l| l.hash == entry.hash) {
+                report.unchanged += 1;
+            } else {
+                history::append(&entry)?;
+                report.imported += 1;
+            }
+            if to_cache {
+                crate::save_cache(&entry)?;
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn mtime(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map_or_else(crate::now_secs, |d| d.as_secs())
+}
@@ -0,0 +1,181 @@
+//! Per-record integrity for archived output (`--sign-output`).
+//!
+//! Each JSON record gets `_meta.sha256`: the SHA-256 of the record serialized
+//! compactly (keys sorted) *without* the integrity fields. With `--sign-key`,
+//! the digest is also signed with a minisign secret key (Ed25519) and the
+//! signature lands in `_meta.signature` next to `_meta.key_id`.
+//! `rdapx verify` recomputes both.
+
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use blake2::{digest::consts::U32, Blake2b, Digest as _};
+use ed25519_compact::{PublicKey, SecretKey, Signature};
+use serde_json::Value;
+use sha2::Sha256;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const FIELDS: [&str; 3] = ["sha256", "signature", "key_id"];
+
+static SEALER: OnceLock<Sealer> = OnceLock::new();
+
+struct Sealer {
+    key: Option<(SecretKey, [u8; 8])>,
+}
+
+/// Enable record sealing for this process; `key` is a minisign secret key file.
+/// Encrypted keys are unlocked with `$RDAPX_SIGN_PASSWORD`.
+pub fn init(key: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let key = key.map(load_secret_key).transpose()?;
+    let _ = SEALER.set(Sealer { key });
+    Ok(())
+}
+
+/// Add `_meta.sha256` (and a signature, if a key is loaded) when sealing is on.
+pub fn seal(json: &mut Value) {
+    let Some(sealer) = SEALER.get() else {
+        return;
+    };
+    let digest = digest(json);
+    if let Some((sk, key_id)) = &sealer.key {
+        let sig = sk.sign(digest, None);
+        crate::attach_meta(json, "signature", Value::from(B64.encode(*sig)));
+        crate::attach_meta(json, "key_id", Value::from(hex(key_id)));
+    }
+    crate::attach_meta(json, "sha256", Value::from(hex(&digest)));
+}
+
+/// SHA-256 over the canonical record with the integrity fields removed.
+fn digest(json: &Value) -> [u8; 32] {
+    let mut v = json.clone();
+    if let Some(meta) = v.get_mut("_meta").and_then(Value::as_object_mut) {
+        for f in FIELDS {
+            meta.remove(f);
+        }
+        if meta.is_empty() {
+            v.as_object_mut().map(|o| o.remove("_meta"));
+        }
+    }
+    Sha256::digest(serde_json::to_vec(&v).unwrap_or_default()).into()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Ok,
+    Unsealed,
+    DigestMismatch,
+    BadSignature,
+}
+
+/// Check one sealed record; signatures are only checked when `pk` is given.
+pub fn verify(json: &Value, pk: Option<&(PublicKey, [u8; 8])>) -> Verdict {
+    let meta = json.get("_meta");
+    let field = |k: &str| meta.and_then(|m| m.get(k)).and_then(Value::as_str);
+    let Some(stored) = field("sha256") else {
+        return Verdict::Unsealed;
+    };
+    let digest = digest(json);
+    if stored != hex(&digest) {
+        return Verdict::DigestMismatch;
+    }
+    if let Some((pk, key_id)) = pk {
+        let sig = field("signature")
+            .and_then(|s| B64.decode(s).ok())
+            .and_then(|b| Signature::from_slice(&b).ok());
+        let signed_ok = field("key_id") == Some(hex(key_id).as_str())
+            && sig.is_some_and(|sig| pk.verify(digest, &sig).is_ok());
+        if !signed_ok {
+            return Verdict::BadSignature;
+        }
+    }
+    Verdict::Ok
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+/// The base64 payload line of a minisign key file (skips the comment line).
+fn key_payload(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    let raw = fs::read_to_string(path)?;
+    let line = raw
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with("untrusted comment:"))
+        .ok_or("empty key file")?;
+    Ok(B64.decode(line)?)
+}
+
+/// Load a minisign public key (`minisign.pub`).
+pub fn load_public_key(path: &Path) -> Result<(PublicKey, [u8; 8]), Box<dyn Error>> {
+    let bytes = key_payload(path)?;
+    if bytes.len() != 42 || &bytes[..2] != b"Ed" {
+        return Err("not a minisign public key".into());
+    }
+    let mut key_id = [0u8; 8];
+    key_id.copy_from_slice(&bytes[2..10]);
+    let pk = PublicKey::from_slice(&bytes[10..]).map_err(|e| e.to_string())?;
+    Ok((pk, key_id))
+}
+
+/// Load a minisign secret key: `Ed` + kdf + `B2` + salt(32) + opslimit(8) +
+/// memlimit(8) + [key id(8) + secret key(64) + blake2b checksum(32)], the last
+/// part encrypted with a scrypt keystream when the key is password protected.
+fn load_secret_key(path: &Path) -> Result<(SecretKey, [u8; 8]), Box<dyn Error>> {
+    let bytes = key_payload(path)?;
+    if bytes.len() != 158 || &bytes[..2] != b"Ed" || &bytes[4..6] != b"B2" {
+        return Err("not a minisign secret key".into());
+    }
+    let mut keynum = bytes[54..].to_vec();
+    match &bytes[2..4] {
+        b"Sc" => {
+            let password = std::env::var("RDAPX_SIGN_PASSWORD")
+                .map_err(|_| "secret key is encrypted; set RDAPX_SIGN_PASSWORD")?;
+            let ops = u64::from_le_bytes(bytes[38..46].try_into()?);
+            let mem = u64::from_le_bytes(bytes[46..54].try_into()?);
+            let (log_n, r, p) = scrypt_params(ops, mem);
+            let params = scrypt::Params::new(log_n, r, p, scrypt::Params::RECOMMENDED_LEN)
+                .map_err(|e| e.to_string())?;
+            let mut stream = vec![0u8; keynum.len()];
+            scrypt::scrypt(password.as_bytes(), &bytes[6..38], &params, &mut stream)
+                .map_err(|e| e.to_string())?;
+            for (k, s) in keynum.iter_mut().zip(stream) {
+                *k ^= s;
+            }
+        }
+        [0, 0] => {}
+        _ => return Err("unsupported minisign key derivation".into()),
+    }
+    let (key_id, rest) = keynum.split_at(8);
+    let (sk, checksum) = rest.split_at(64);
+    let mut h = Blake2b::<U32>::new();
+    h.update(b"Ed");
+    h.update(key_id);
+    h.update(sk);
+    if h.finalize().as_slice() != checksum {
+        return Err("wrong password or corrupt secret key".into());
+    }
+    let mut id = [0u8; 8];
+    id.copy_from_slice(key_id);
+    let sk = SecretKey::from_slice(sk).map_err(|e| e.to_string())?;
+    Ok((sk, id))
+}
+
+/// libsodium's scryptsalsa208sha256 `opslimit`/`memlimit` to (log2 N, r, p).
+fn scrypt_params(opslimit: u64, memlimit: u64) -> (u8, u32, u32) {
+    let ops = opslimit.max(32_768);
+    let r = 8u64;
+    let log_n_for = |max_n: u64| (1..63u8).find(|&n| (1u64 << n) > max_n / 2).unwrap_or(63);
+    if ops < memlimit / 32 {
+        (log_n_for(ops / (r * 4)), 8, 1)
+    } else {
+        let log_n = log_n_for(memlimit / (r * 128));
+        let max_rp = ((ops / 4) >> log_n).min(0x3fff_ffff);
+        (log_n, 8, u32::try_from(max_rp / r).unwrap_or(1).max(1))
+    }
+}
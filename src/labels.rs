@@ -0,0 +1,223 @@
+//! Domain name checks before a lookup: label length and LDH rules, IDNA 2008
+//! validity, and (with `--homoglyphs`) a warning for labels that mix scripts
+//! or contain characters that pass for Latin letters, as lookalike phishing
+//! domains do. Warnings go to stderr and `_meta.homoglyphs`.
+
+use colored::Colorize;
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static HOMOGLYPHS: AtomicBool = AtomicBool::new(false);
+
+pub fn init(enabled: bool) {
+    HOMOGLYPHS.store(enabled, Ordering::Relaxed);
+}
+
+/// Characters from other scripts (and Latin IPA letters) that render like a
+/// basic Latin letter, with the letter they imitate.
+pub const CONFUSABLES: &[(char, char)] = &[
+    // Cyrillic
+    ('а', 'a'),
+    ('е', 'e'),
+    ('о', 'o'),
+    ('р', 'p'),
+    ('с', 'c'),
+    ('у', 'y'),
+    ('х', 'x'),
+    ('ѕ', 's'),
+    ('і', 'i'),
+    ('ј', 'j'),
+    ('ԁ', 'd'),
+    ('һ', 'h'),
+    ('ԛ', 'q'),
+    ('ԝ', 'w'),
+    ('ӏ', 'l'),
+    // Greek
+    ('α', 'a'),
+    ('ο', 'o'),
+    ('ν', 'v'),
+    ('ι', 'i'),
+    ('κ', 'k'),
+    ('ρ', 'p'),
+    ('υ', 'u'),
+    ('χ', 'x'),
+    ('ϲ', 'c'),
+    ('ϳ', 'j'),
+    // Latin look-alikes
+    ('ɑ', 'a'),
+    ('ı', 'i'),
+    ('ɩ', 'i'),
+    ('ɡ', 'g'),
+];
+
+/// Script of a letter, coarse enough to tell lookalikes apart; `None` for
+/// digits, hyphens, and marks that every script shares.
+fn script(c: char) -> Option<&'static str> {
+    Some(match c {
+        'a'..='z' | 'A'..='Z' | '\u{c0}'..='\u{2af}' | '\u{1e00}'..='\u{1eff}' => "Latin",
+        '\u{370}'..='\u{3ff}' | '\u{1f00}'..='\u{1fff}' => "Greek",
+        '\u{400}'..='\u{52f}' => "Cyrillic",
+        '\u{530}'..='\u{58f}' => "Armenian",
+        '\u{590}'..='\u{5ff}' => "Hebrew",
+        '\u{600}'..='\u{6ff}' | '\u{750}'..='\u{77f}' => "Arabic",
+        '\u{e00}'..='\u{e7f}' => "Thai",
+        // Japanese and Korean names mix these legitimately
+        '\u{3040}'..='\u{30ff}' | '\u{4e00}'..='\u{9fff}' | '\u{ac00}'..='\u{d7af}' => "CJK",
+        c if c.is_alphabetic() => "other",
+        _ => return None,
+    })
+}
+
+/// Reject names that can't be a registered domain: labels over 63 octets or
+/// outside letters, digits, and inner hyphens, names over 253 octets, and
+/// internationalized labels that aren't valid IDNA 2008.
+pub fn validate(name: &str) -> Result<(), String> {
+    let name = name.trim_end_matches('.');
+    if name.len() > 253 {
+        return Err(format!(
+            "{name}: name is {} characters long (max 253)",
+            name.len()
+        ));
+    }
+    for label in name.split('.') {
+        if label.is_empty() {
+            return Err(format!("{name}: empty label"));
+        }
+        if !label.is_ascii() {
+            continue;
+        }
+        if label.len() > 63 {
+            return Err(format!(
+                "{name}: label '{label}' is {} characters long (max 63)",
+                label.len()
+            ));
+        }
+        if let Some(c) = label
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && *c != '-')
+        {
+            return Err(format!(
+                "{name}: label '{label}' contains '{c}' \
+                 (only letters, digits, and hyphens are allowed)"
+            ));
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return Err(format!(
+                "{name}: label '{label}' starts or ends with a hyphen"
+            ));
+        }
+    }
+    idna::domain_to_ascii_strict(name)
+        .map(|_| ())
+        .map_err(|_| format!("{name}: not a valid internationalized domain name (IDNA 2008)"))
+}
+
+/// Lookalike warnings for each label of `name` (A-labels are decoded first).
+pub fn homoglyphs(name: &str) -> Vec<String> {
+    let (unicode, _) = idna::domain_to_unicode(name.trim_end_matches('.'));
+    let mut warnings = Vec::new();
+    for label in unicode.split('.').filter(|l| !l.is_ascii()) {
+        let mut scripts: Vec<&str> = Vec::new();
+        for s in label.chars().filter_map(script) {
+            if !scripts.contains(&s) {
+                scripts.push(s);
+            }
+        }
+        if scripts.len() > 1 {
+            warnings.push(format!(
+                "label '{label}' mixes scripts ({})",
+                scripts.join(", ")
+            ));
+        }
+        let skeleton: String = label
+            .chars()
+            .map(|c| {
+                CONFUSABLES
+                    .iter()
+                    .find(|(from, _)| *from == c)
+                    .map_or(c, |(_, to)| *to)
+            })
+            .collect();
+        if skeleton != label && skeleton.is_ascii() {
+            warnings.push(format!("label '{label}' can pass for '{skeleton}'"));
+        }
+    }
+    warnings
+}
+
+/// Validate a query before it is looked up and, with `--homoglyphs`, return
+/// its lookalike warnings (already printed). Only dotted domain names are
+/// checked; entity handles, IPs, and AS numbers pass through.
+pub fn check(q: &str) -> Result<Vec<String>, String> {
+    let (kind, name) = crate::normalize(q);
+    if !matches!(kind, crate::Kind::Domain) || !name.contains('.') {
+        return Ok(Vec::new());
+    }
+    validate(&name)?;
+    if !HOMOGLYPHS.load(Ordering::Relaxed) {
+        return Ok(Vec::new());
+    }
+    let warnings = homoglyphs(&name);
+    for w in &warnings {
+        eprintln!("{} {name}: {w}", "Warning:".yellow().bold());
+    }
+    Ok(warnings)
+}
+
+/// Record lookalike warnings under `_meta.homoglyphs`.
+pub fn annotate(json: &mut Value, warnings: &[String]) {
+    if !warnings.is_empty() {
+        crate::attach_meta(json, "homoglyphs", Value::from(warnings.to_vec()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{homoglyphs, validate};
+
+    #[test]
+    fn accepts_ldh_and_idna_names() {
+        assert!(validate("example.com").is_ok());
+        assert!(validate("example.com.").is_ok());
+        assert!(validate("a-b.example").is_ok());
+        assert!(validate("münchen.de").is_ok());
+        assert!(validate("xn--mnchen-3ya.de").is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_labels() {
+        let err = validate("ex_ample.com").unwrap_err();
+        assert!(err.contains("contains '_'"), "{err}");
+        assert!(validate("a..com").is_err());
+        assert!(validate("-abc.com").is_err());
+        assert!(validate("abc-.com").is_err());
+        assert!(validate(&format!("{}.com", "a".repeat(64))).is_err());
+        assert!(validate(&format!("{}.com", "a".repeat(63))).is_ok());
+        let long = vec!["a".repeat(60); 5].join(".");
+        assert!(validate(&long).is_err());
+    }
+
+    #[test]
+    fn plain_and_single_script_names_have_no_warnings() {
+        assert!(homoglyphs("example.com").is_empty());
+        assert!(homoglyphs("münchen.de").is_empty());
+        assert!(homoglyphs("xn--mnchen-3ya.de").is_empty());
+    }
+
+    #[test]
+    fn flags_mixed_scripts_and_lookalikes() {
+        // Cyrillic `а` in an otherwise Latin label
+        let warnings = homoglyphs("p\u{430}ypal.com");
+        assert_eq!(warnings.len(), 2, "{warnings:?}");
+        assert!(warnings[0].contains("mixes scripts (Latin, Cyrillic)"));
+        assert!(warnings[1].ends_with("can pass for 'paypal'"));
+    }
+
+    #[test]
+    fn flags_whole_script_lookalikes_from_a_labels() {
+        // all-Cyrillic "аре", as an A-label
+        let ascii = idna::domain_to_ascii("\u{430}\u{440}\u{435}.com").unwrap();
+        let warnings = homoglyphs(&ascii);
+        assert_eq!(warnings, ["label 'аре' can pass for 'ape'"]);
+    }
+}
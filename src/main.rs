@@ -6,32 +6,249 @@
 use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::{
     generate,
-    shells::{Bash, Fish, Zsh},
+    shells::{Bash, Fish, PowerShell, Zsh},
 };
 use colored::Colorize;
-use directories::BaseDirs;
 use futures::stream::{self, StreamExt};
+use i18n::Label;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 use std::fs;
-use std::io::{self, IsTerminal, Read};
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
-use std::time::{Duration, SystemTime};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::time::sleep;
+
+mod abuse;
+mod archive;
+mod auth;
+mod autnum;
+mod available;
+mod bandwidth;
+mod body;
+mod bootstrap;
+mod breaker;
+mod checkpoint;
+mod complete;
+mod config;
+mod contacts;
+mod crypt;
+mod dataset;
+mod dedupe;
+mod deterministic;
+mod diffruns;
+mod dns;
+mod fallback;
+mod fanout;
+mod fields;
+mod history;
+mod i18n;
+mod ical;
+mod import;
+mod integrity;
+mod labels;
+mod model;
+mod network;
+mod notices;
+mod pivot;
+mod plan;
+mod portfolio;
+mod prefilter;
+mod preset;
+mod progress;
+mod project;
+mod projection;
+mod psl;
+mod ratelimit;
+mod redaction;
+mod referral;
+mod related;
+mod report;
+mod sandbox;
+mod search;
+mod serve;
+mod shard;
+mod sink;
+mod squat;
+mod stats;
+mod strict;
+mod table;
+mod whois;
+mod writer;
+
 #[derive(ValueEnum, Clone, Copy, Debug)]
 enum Format {
     Json,
     Pretty,
     Table,
+    /// Header plus one comma-separated row per record (same fields as table)
+    Csv,
+    /// Msgpack records, each prefixed with a 4-byte big-endian length
+    Msgpack,
+    /// CBOR records, each prefixed with a 4-byte big-endian length
+    Cbor,
+    /// iCalendar (.ics) with one all-day event per expiration (`expiry` only)
+    Ical,
+}
+
+/// How `get` reports a failed query.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum ErrorFormat {
+    /// Human-readable message on stderr
+    #[default]
+    Text,
+    /// `{"error": {...}}` document on stdout (kind, query, server, status, attempts)
+    Json,
+}
+
+/// Which RDAP server(s) to ask for domains.
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+enum Source {
+    /// The registry only
+    #[default]
+    Registry,
+    /// Follow the registry's referral and return the registrar's object
+    Registrar,
+    /// Registry object with the registrar's object under `_registrar`
+    Both,
+    /// Registry object with the registrar's contacts in place of its own
+    Merged,
+}
+
+/// What `--follow-registrar` does with the registrar's object.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum FollowRegistrar {
+    /// Merge its contacts into the registry object (same as `--source merged`)
+    Merge,
+    /// Keep it whole under `_registrar` (same as `--source both`)
+    Both,
+}
+
+/// Single value printed by `--short`.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ShortField {
+    Registrar,
+    Expiry,
+    Country,
+    /// Autnum name
+    Asname,
+    AbuseEmail,
+}
+
+impl ShortField {
+    fn extract(self, json: &Value) -> Option<String> {
+        match self {
+            Self::Registrar => fields::registrar(json),
+            Self::Expiry => fields::expiry(json),
+            Self::Country => json
+                .get("country")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            Self::Asname => (json.get("objectClassName").and_then(Value::as_str) == Some("autnum"))
+                .then(|| json.get("name").and_then(Value::as_str).map(str::to_string))
+                .flatten(),
+            Self::AbuseEmail => fields::abuse_email(json),
+        }
+    }
+}
+
+/// Shape of JSON-family output records (see README, "Output schema versions").
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+enum OutputSchema {
+    /// RDAP object at the top level with `_meta`/`_enrichment` merged in
+    #[default]
+    V1,
+    /// Envelope with the RDAP object under `rdap`, next to `_meta`/`_enrichment`
+    V2,
+}
+
+impl OutputSchema {
+    const fn version(self) -> u64 {
+        match self {
+            Self::V1 => 1,
+            Self::V2 => 2,
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
+#[allow(clippy::struct_excessive_bools)] // independent CLI switches
 struct Cli {
     /// Global output format (replaces --pretty / --table)
     #[arg(long, value_enum, default_value_t = Format::Json)]
     format: Format,
 
+    /// Query every candidate server (all RIRs for handles, IPs, and ASNs) at
+    /// once and keep the first authoritative answer; attempts go in `_meta.fan_out`
+    #[arg(long)]
+    fan_out: bool,
+
+    /// How `get` reports failures
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+
+    /// Print only this value, one line per query (bulk prefixes the query);
+    /// `get` exits 1 when the value is missing
+    #[arg(long, value_enum)]
+    short: Option<ShortField>,
+
+    /// Keep only these top-level RDAP members in records, e.g.
+    /// ldhName,status,events; very large responses are then parsed without
+    /// building the rest of the object
+    #[arg(long, value_delimiter = ',', value_name = "MEMBERS")]
+    fields: Vec<String>,
+
+    /// Confine the process once started: writes only under the cache and
+    /// data directories, outgoing TCP only to HTTPS/DNS/WHOIS ports (Linux
+    /// Landlock, macOS sandbox)
+    #[arg(long)]
+    sandbox: bool,
+
+    /// Fail instead of degrading: unqueryable input lines, stale data, and
+    /// skipped unparsable records become errors with distinct exit codes
+    /// (3, 4, 5)
+    #[arg(long)]
+    strict: bool,
+
+    /// Warn when a domain mixes scripts or contains characters that pass for
+    /// Latin letters (lookalike domains); noted on stderr and in
+    /// `_meta.homoglyphs`
+    #[arg(long)]
+    homoglyphs: bool,
+
+    /// Reproducible output for golden-file tests: clock frozen at
+    /// `SOURCE_DATE_EPOCH` (default 0), zero timings, input-order results
+    #[arg(long)]
+    deterministic: bool,
+
+    /// Keep only entities with these roles (comma-separated), e.g.
+    /// registrant,abuse,technical; other contacts are dropped from output
+    #[arg(long, value_enum, value_delimiter = ',')]
+    roles: Vec<projection::Role>,
+
+    /// Column set for CSV and NDJSON output (stdout and .csv/.ndjson sinks)
+    /// matching a common consumer's field names and date format
+    #[arg(long, value_enum, conflicts_with = "fields")]
+    preset: Option<preset::Preset>,
+
+    /// When a domain's RDAP lookup fails (other than 404), build a minimal
+    /// record from these sources in order, tagged with `_meta.source`
+    #[arg(long, value_enum, value_delimiter = ',')]
+    fallback: Vec<fallback::Fallback>,
+
+    /// Print one line per HTTP request to stderr: status, time, and the
+    /// registry's published rate-limit state
+    #[arg(long)]
+    trace: bool,
+
+    /// Output record schema for JSON, NDJSON, msgpack, and CBOR
+    #[arg(long, value_enum, default_value_t = OutputSchema::V1)]
+    output_schema: OutputSchema,
+
     /// Disable ANSI colors (auto-disabled when stdout is not a TTY)
     #[arg(long)]
     no_color: bool,
@@ -44,10 +261,24 @@ struct Cli {
     #[arg(long, default_value_t = 3600)]
     cache_ttl: u64,
 
-    /// Do not read/write cache
+    /// Do not read/write cache (also disables history)
     #[arg(long)]
     no_cache: bool,
 
+    /// Do not append fresh responses to the snapshot history
+    #[arg(long)]
+    no_history: bool,
+
+    /// Send `Authorization: Bearer` with the keyring secret of this name
+    /// (see `rdapx auth set`)
+    #[arg(long, value_name = "SECRET")]
+    auth: Option<String>,
+
+    /// Encrypt cache and history entries at rest with a key from `keychain`
+    /// or a key file (created with a random key if missing)
+    #[arg(long, value_name = "keychain|FILE")]
+    cache_key: Option<String>,
+
     /// Retry count for transient HTTP errors
     #[arg(long, default_value_t = 2)]
     retries: usize,
@@ -56,20 +287,127 @@ struct Cli {
     #[arg(long, default_value_t = 300)]
     retry_delay_ms: u64,
 
+    /// Resolve the IANA Registrar ID into `_enrichment.registrar` (uses the IANA registry)
+    #[arg(long)]
+    enrich_registrar: bool,
+
+    /// Add a SHA-256 of every JSON record under `_meta.sha256` (see `rdapx verify`)
+    #[arg(long)]
+    sign_output: bool,
+
+    /// Domain data source: registry, registrar (via referral), both, or merged
+    #[arg(long, value_enum, default_value_t = Source::Registry)]
+    source: Source,
+
+    /// Follow a gTLD registry's `related` link to the registrar's RDAP
+    /// server for its fuller contact data, merged in (default) or kept whole
+    /// (`--follow-registrar=both`)
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "merge",
+        conflicts_with = "source"
+    )]
+    follow_registrar: Option<FollowRegistrar>,
+
+    /// Retries for registrar referral requests (independent of --retries)
+    #[arg(long, default_value_t = 1)]
+    registrar_retries: usize,
+
+    /// Timeout for registrar referral requests (seconds)
+    #[arg(long, default_value_t = 10)]
+    registrar_timeout: u64,
+
+    /// Emit a 404 as a `{"query", "found": false, "status": 404}` record instead
+    /// of an error
+    #[arg(long, global = true)]
+    emit_not_found: bool,
+
+    /// Preferred response language (Accept-Language), also used for notices,
+    /// remarks, and table labels, e.g. de, fr-CA
+    #[arg(long, value_name = "TAG", global = true)]
+    lang: Option<String>,
+
+    /// Print notices in table output in full instead of title, first line,
+    /// and link
+    #[arg(long, global = true)]
+    full_notices: bool,
+
+    /// Tag every output record (repeatable); stored under `_rdapx.tags`
+    #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_tag, global = true)]
+    tags: Vec<(String, String)>,
+
+    /// Write records to this destination instead of stdout (repeatable; `-` keeps
+    /// stdout): `path.ndjson`, `path.csv`, `path.cbor`, `sqlite:path.db`, `es://host:9200/index`
+    #[arg(long = "output", value_name = "SPEC", global = true)]
+    outputs: Vec<sink::SinkSpec>,
+
+    /// Capture raw registry requests/responses as WARC records (e.g. run.warc.gz)
+    #[arg(long, value_name = "FILE")]
+    archive: Option<PathBuf>,
+
+    /// Also sign each record digest with this minisign secret key (implies --sign-output)
+    #[arg(long, value_name = "FILE")]
+    sign_key: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Command,
 }
 
 #[derive(Subcommand, Debug)]
 enum Command {
-    /// Resolve a single query (domain, IP, or ASN)
+    /// Resolve a single query (domain, IP, or ASN); `rdapx <query>` is shorthand
+    #[command(visible_aliases = ["lookup", "q"])]
     Get {
-        /// Query: example.com | 1.1.1.1 | AS13335
-        query: String,
+        /// Query: example.com | 1.1.1.1 | AS13335 (omit to stream queries from piped stdin)
+        query: Option<String>,
 
-        /// Emit shell completions for <bash|zsh|fish> to stdout
+        /// Emit shell completions for <bash|zsh|fish|powershell> to stdout
         #[arg(long, value_enum)]
         completions: Option<Shell>,
+
+        /// For IP queries, walk up to the RIR top-level block (`_enrichment.parents`)
+        #[arg(long)]
+        parents: bool,
+
+        /// For ASN queries, add the containing block and same-org sibling ASNs
+        /// (`_enrichment.autnum_context`)
+        #[arg(long)]
+        asn_context: bool,
+
+        /// How many ASNs on each side to probe for siblings when the registry
+        /// doesn't list the org's autnums
+        #[arg(long, default_value_t = 5, requires = "asn_context")]
+        neighbors: u32,
+
+        /// Send the query to this RDAP base URL instead of the server it
+        /// routes to, e.g. a registry's staging endpoint; the object path
+        /// (`domain/`, `ip/`, `autnum/`) is still added
+        #[arg(long, value_name = "URL", value_parser = parse_base_url)]
+        server: Option<String>,
+
+        /// Fetch this RDAP URL as is (e.g. from a `links` array) instead of
+        /// routing a query; caching, retries, and output options still apply
+        #[arg(
+            long,
+            value_name = "URL",
+            value_parser = parse_base_url,
+            conflicts_with_all = ["query", "server", "parents", "asn_context"]
+        )]
+        url: Option<String>,
+
+        /// Max concurrent requests when streaming queries from stdin
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+    },
+
+    /// Look up an entity by handle, routed by its object tag
+    /// (`ABC123-ARIN` to ARIN; RFC 8521)
+    Entity {
+        /// Entity handle, e.g. ABC123-ARIN
+        handle: String,
     },
 
     /// Resolve many queries from a file (one per line)
@@ -84,6 +422,93 @@ enum Command {
         /// Emit Newline-Delimited JSON (one JSON per line)
         #[arg(long)]
         ndjson: bool,
+
+        /// Abort the run once more than N queries have failed
+        #[arg(long)]
+        max_failures: Option<usize>,
+
+        /// Abort the run once failures exceed this share of the input (e.g. 5% or 0.05)
+        #[arg(long, value_parser = parse_rate)]
+        max_failure_rate: Option<f64>,
+
+        /// Skip queries not matching a predicate over offline delegation data,
+        /// e.g. 'rir==ripe && country==DE' (fields: kind, rir, country, status, date)
+        #[arg(long, value_parser = prefilter::Prefilter::parse)]
+        prefilter: Option<prefilter::Prefilter>,
+
+        /// Total retries allowed across the whole run (default: unlimited)
+        #[arg(long)]
+        retry_budget: Option<usize>,
+
+        /// Tag records slower than this in `_meta` (e.g. 1500ms, 2s)
+        #[arg(long, value_parser = parse_duration)]
+        slow_threshold: Option<Duration>,
+
+        /// Number of slowest queries listed in the summary (0 to hide)
+        #[arg(long, default_value_t = 5)]
+        slowest: usize,
+
+        /// Also write the run summary as JSON to this file
+        #[arg(long)]
+        summary_file: Option<PathBuf>,
+
+        /// Replace repeated responses and shared blocks with content-hash
+        /// references (JSON formats only)
+        #[arg(long)]
+        dedupe_output: bool,
+
+        /// Abort the run once downloads exceed this many bytes (e.g. 500MB, 2GB)
+        #[arg(long, value_parser = bandwidth::parse_size)]
+        max_bytes: Option<u64>,
+
+        /// Resolve and open a TLS connection to each registry host before
+        /// dispatching, so the first wave isn't stuck in handshakes
+        #[arg(long)]
+        prewarm: bool,
+
+        /// Revalidate every object (conditional requests where the server
+        /// supports them) and emit only those whose content changed since
+        /// the last snapshot in history
+        #[arg(long)]
+        incremental: bool,
+
+        /// With --incremental, write unchanged queries here as NDJSON
+        #[arg(long, value_name = "FILE", requires = "incremental")]
+        manifest: Option<PathBuf>,
+
+        /// Classify the input and print counts per type and registry, cache
+        /// hits, unsupported lines, and an estimated duration, then exit
+        /// without fetching
+        #[arg(long)]
+        plan: bool,
+
+        /// Write lines that can't be queried here, each followed by a tab and
+        /// the reason (default: reported on stderr)
+        #[arg(long, value_name = "FILE")]
+        rejects: Option<PathBuf>,
+
+        /// Process only shard K of N (e.g. 3/8): queries are partitioned by
+        /// hash so N machines can split one input without coordination
+        #[arg(long, value_name = "K/N", value_parser = shard::Shard::parse)]
+        shard: Option<shard::Shard>,
+
+        /// Report progress (processed, ok, failed, rate, ETA) periodically:
+        /// readable lines or NDJSON events
+        #[arg(long, value_enum)]
+        progress_format: Option<progress::ProgressFormat>,
+
+        /// Write progress reports here instead of stderr (e.g. a named pipe)
+        #[arg(long, value_name = "PATH", requires = "progress_format")]
+        progress_file: Option<PathBuf>,
+
+        /// Also write each failure as an `{"error": {...}}` line here (query,
+        /// kind, server, status, attempts); /dev/fd/3 keeps it on its own fd
+        #[arg(long, value_name = "FILE")]
+        json_errors: Option<PathBuf>,
+
+        /// Time between progress reports (e.g. 500ms, 10s)
+        #[arg(long, value_parser = parse_progress_interval, default_value = "5s")]
+        progress_interval: Duration,
     },
 
     /// Inspect or clear cache
@@ -91,402 +516,4166 @@ enum Command {
         #[command(subcommand)]
         action: CacheCmd,
     },
-}
 
-#[derive(ValueEnum, Clone, Copy, Debug)]
-enum Shell {
-    Bash,
-    Zsh,
-    Fish,
-}
+    /// RDAP search (RFC 9082) with automatic page-following, streamed as NDJSON
+    Search {
+        #[command(subcommand)]
+        target: SearchCmd,
+    },
 
-#[derive(Subcommand, Debug)]
-enum CacheCmd {
-    /// List cached JSON files
-    List,
-    /// Remove cached JSON files
-    Clear,
-}
+    /// Check which names are unregistered across a set of TLDs
+    Available {
+        /// File with base names (one per line), e.g. "acme"
+        #[arg(long)]
+        file: PathBuf,
 
-/* ----------------------------- HTTP + RDAP ------------------------------ */
+        /// Comma-separated TLDs to try each name under
+        #[arg(long, value_delimiter = ',', required = true)]
+        tlds: Vec<String>,
 
-fn http_client(timeout_secs: u64) -> Result<reqwest::Client, Box<dyn Error>> {
-    let client = reqwest::Client::builder()
-        .user_agent(concat!("rdapx/", env!("CARGO_PKG_VERSION")))
-        .timeout(Duration::from_secs(timeout_secs))
-        .gzip(true)
-        .brotli(true)
-        .deflate(true)
-        .build()?;
-    Ok(client)
-}
+        /// Max concurrent checks
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
 
-#[derive(Clone, Copy, Debug)]
-enum Kind {
-    Domain,
-    Ip,
-    Asn,
-}
+        /// Max requests per second to any single registry
+        #[arg(long, default_value_t = 5)]
+        rate: u32,
+    },
 
-fn normalize(query: &str) -> (Kind, String) {
-    // quick’n'tidy
-    let s = query.trim();
-    if s.starts_with(|c: char| ['A', 'a', 'S', 's'].contains(&c)) {
-        // AS13335 -> 13335
-        let num = s.trim_start_matches(|c: char| ['A', 'a', 'S', 's'].contains(&c));
-        return (Kind::Asn, num.to_string());
-    }
-    if s.contains(':') || s.split('.').all(|p| p.parse::<u8>().is_ok()) {
-        return (Kind::Ip, s.to_string());
-    }
-    (Kind::Domain, s.to_string())
-}
+    /// Generate lookalike and typo variants of a domain and list the ones
+    /// that are registered
+    Squat {
+        /// Domain to find lookalikes of, e.g. example.com
+        domain: String,
 
-fn classify_to_url(kind: Kind, normalized: &str) -> String {
-    match kind {
-        Kind::Domain => format!("https://rdap.verisign.com/com/v1/domain/{normalized}"),
-        Kind::Ip => format!("https://rdap.apnic.net/ip/{normalized}"),
-        Kind::Asn => format!("https://rdap.arin.net/registry/autnum/{normalized}"),
-    }
-}
+        /// Comma-separated strategies (default: all)
+        #[arg(long, value_enum, value_delimiter = ',')]
+        strategies: Vec<squat::Strategy>,
 
-/* ------------------------------ CACHING --------------------------------- */
-fn cache_dir() -> io::Result<PathBuf> {
-    let base = BaseDirs::new().ok_or_else(|| io::Error::other("no home"))?;
-    let p = base.cache_dir().join("rdapx");
-    if !p.exists() {
-        fs::create_dir_all(&p)?;
-    }
-    Ok(p)
-}
+        /// TLDs for the tld-swap strategy (default: com,net,org,co,io,info,biz,us,app,xyz)
+        #[arg(long, value_delimiter = ',')]
+        tlds: Vec<String>,
 
-fn cache_key(normalized_url: &str) -> String {
-    use std::hash::{Hash, Hasher};
-    let mut h = std::collections::hash_map::DefaultHasher::new();
-    normalized_url.hash(&mut h);
-    format!("{:016x}.json", h.finish())
-}
+        /// Max concurrent checks
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
 
-fn cache_path(url: &str) -> io::Result<PathBuf> {
-    Ok(cache_dir()?.join(cache_key(url)))
-}
+        /// Max requests per second to any single registry
+        #[arg(long, default_value_t = 5)]
+        rate: u32,
 
-fn load_cache(url: &str, ttl: Duration) -> io::Result<Option<Value>> {
-    let p = cache_path(url)?;
-    let meta = fs::metadata(&p)?;
-    let age_ok = meta
-        .modified()
-        .ok()
-        .and_then(|m| SystemTime::now().duration_since(m).ok())
-        .is_some_and(|age| age <= ttl);
-    if age_ok {
-        let raw = fs::read_to_string(p)?;
-        let v: Value = serde_json::from_str(&raw).unwrap_or(Value::Null);
-        return Ok(Some(v));
-    }
-    Ok(None)
-}
+        /// Check at most this many candidates, in strategy order
+        #[arg(long, value_name = "N")]
+        max_candidates: Option<usize>,
 
-fn save_cache(url: &str, json: &Value) -> io::Result<()> {
-    let p = cache_path(url)?;
-    if let Some(parent) = p.parent() {
-        fs::create_dir_all(parent)?;
-    }
-    fs::write(p, serde_json::to_string(json)?)?;
-    Ok(())
-}
+        /// Send at most this many checks to any one registry in this run;
+        /// the rest are left for a later `--resume` run
+        #[arg(long, value_name = "N")]
+        budget: Option<usize>,
 
-fn list_cache(dir: &Path) -> Vec<PathBuf> {
-    let mut out = Vec::new();
-    if let Ok(rd) = fs::read_dir(dir) {
-        for e in rd.flatten() {
-            let p = e.path();
-            if p.extension().and_then(|s| s.to_str()) == Some("json") {
-                out.push(p);
-            }
-        }
-    }
-    out
-}
+        /// Record answers here as they arrive and skip candidates already
+        /// answered when rerun
+        #[arg(long, value_name = "FILE")]
+        resume: Option<PathBuf>,
+    },
 
-fn clear_cache(dir: &Path) -> usize {
-    let mut n = 0;
-    if let Ok(rd) = fs::read_dir(dir) {
-        for e in rd.flatten() {
-            let p = e.path();
-            if p.extension().and_then(|s| s.to_str()) == Some("json") && fs::remove_file(p).is_ok()
-            {
-                n += 1;
-            }
-        }
-    }
-    n
-}
+    /// Manage named target lists for recurring checks
+    Portfolio {
+        #[command(subcommand)]
+        action: PortfolioCmd,
+    },
 
-/* ------------------------------ OUTPUT ---------------------------------- */
-fn output(json: &Value, fmt: Format) {
-    match fmt {
-        Format::Json => {
-            // compact JSON
-            println!("{json}");
-        }
-        Format::Pretty => {
-            // pretty JSON
-            println!("{}", serde_json::to_string_pretty(json).unwrap());
-        }
-        Format::Table => {
-            use colored::Colorize;
-            use serde_json::Value;
-            use std::collections::BTreeSet;
-            use std::io::IsTerminal;
-
-            let use_color = std::io::stdout().is_terminal();
-
-            // Helper to pull a string field from the top-level object
-            let field = |k: &str| -> String {
-                json.get(k)
-                    .and_then(Value::as_str)
-                    .unwrap_or("-")
-                    .to_string()
-            };
+    /// One row per entity contact (role, name, org, email, phone, address)
+    /// with the object it belongs to; honors `--roles`
+    Contacts {
+        /// Query: example.com | 1.1.1.1 | AS13335
+        #[arg(required_unless_present = "bulk")]
+        query: Option<String>,
 
-            let kind = field("objectClassName"); // RDAP's type name
-            let handle = field("handle");
-            let name = field("name");
-            let country = field("country");
+        /// Read queries from a file (one per line, or NDJSON with "query") instead
+        #[arg(long, conflicts_with = "query")]
+        bulk: Option<PathBuf>,
 
-            let status = json.get("status").and_then(Value::as_array).map_or_else(
-                || "-".to_string(),
-                |a| {
-                    if a.is_empty() {
-                        "-".to_string()
-                    } else {
-                        a.iter()
-                            .filter_map(Value::as_str)
-                            .collect::<Vec<_>>()
-                            .join(",")
-                    }
-                },
-            );
+        /// Max concurrent requests with --bulk
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+    },
 
-            if use_color {
-                println!("{} {}", "Type:".blue().bold(), kind);
-                println!("{} {}", "Handle:".blue().bold(), handle);
-                println!("{} {}", "Name:".blue().bold(), name);
-                println!("{} {}", "Country:".blue().bold(), country);
-                println!("{} {}", "Status:".blue().bold(), status);
-            } else {
-                println!("Type: {kind}");
-                println!("Handle: {handle}");
-                println!("Name: {name}");
-                println!("Country: {country}");
-                println!("Status: {status}");
-            }
-
-            // Derive roles from entities (sorted, unique)
-            if let Some(entities) = json.get("entities").and_then(Value::as_array) {
-                let mut roles = BTreeSet::new();
-                for e in entities {
-                    if let Some(rs) = e.get("roles").and_then(Value::as_array) {
-                        for r in rs {
-                            if let Some(s) = r.as_str() {
-                                roles.insert(s.to_string());
-                            }
-                        }
-                    }
-                }
-                if !roles.is_empty() {
-                    let joined = roles.into_iter().collect::<Vec<_>>().join(", ");
-                    if use_color {
-                        println!("{} {}", "Roles:".yellow().bold(), joined);
-                    } else {
-                        println!("Roles: {joined}");
-                    }
-                }
-            }
-        }
-    }
-}
-/* ------------------------------ IO utils -------------------------------- */
+    /// Registrar abuse contacts for a list of domains, one row per distinct
+    /// mailbox with the domains it covers, for preparing abuse reports
+    Abuse {
+        /// Domains, one per line (or NDJSON with "query")
+        #[arg(long)]
+        file: PathBuf,
 
-fn read_lines(path: &Path) -> io::Result<Vec<String>> {
-    let mut buf = String::new();
-    fs::File::open(path)?.read_to_string(&mut buf)?;
-    Ok(buf
-        .lines()
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect())
-}
+        /// Max concurrent requests
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+    },
 
-/* ------------------------------ Fetch ----------------------------------- */
+    /// Upcoming expirations across a portfolio, soonest first, optionally
+    /// as an iCalendar file for team calendars
+    Expiry {
+        /// Portfolio to report on
+        #[arg(long)]
+        portfolio: String,
 
-async fn fetch_for_query(
-    client: &reqwest::Client,
-    q: &str,
-    ttl: Duration,
-    no_cache: bool,
-    retries: usize,
-    retry_delay_ms: u64,
-) -> Result<Value, Box<dyn Error>> {
-    let (kind, norm) = normalize(q);
-    let url = classify_to_url(kind, &norm);
+        /// With `--format ical`, add a reminder alarm this many days before
+        /// each expiration (comma-separated, e.g. 30,7)
+        #[arg(long, value_delimiter = ',', value_name = "DAYS")]
+        remind: Vec<u32>,
 
-    if !no_cache {
-        if let Ok(Some(v)) = load_cache(&url, ttl) {
-            return Ok(v);
-        }
+        /// Max concurrent requests
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+    },
+
+    /// Security-hardening checks across a portfolio
+    Audit {
+        #[command(subcommand)]
+        action: AuditCmd,
+    },
+
+    /// Compare two bulk NDJSON outputs: added, removed, and changed fields per object
+    DiffRuns {
+        /// Earlier run
+        old: PathBuf,
+
+        /// Later run
+        new: PathBuf,
+
+        /// Field joining the runs (dotted path; `query` falls back to the object's name)
+        #[arg(long, default_value = "query")]
+        key: String,
+
+        /// Render the changes as a colored diff with JSON-pointer paths, or
+        /// as JSON change documents (instead of --format)
+        #[arg(long, value_enum)]
+        diff_format: Option<diffruns::DiffFormat>,
+
+        /// Leave out noise before comparing (repeatable; adds to `[diff] ignore`):
+        /// `notices`, `events[eventAction=last update of RDAP database]`, `order:links`
+        #[arg(long, value_name = "RULE", value_parser = diffruns::IgnoreRule::parse)]
+        ignore_path: Vec<diffruns::IgnoreRule>,
+    },
+
+    /// Combine the NDJSON outputs and summaries of `bulk --shard` runs
+    Merge {
+        /// NDJSON outputs, concatenated to stdout in the order given
+        #[arg(required_unless_present = "summaries")]
+        inputs: Vec<PathBuf>,
+
+        /// Summary from a shard's `bulk --summary-file` (repeatable)
+        #[arg(long = "summary", value_name = "FILE")]
+        summaries: Vec<PathBuf>,
+
+        /// Write the combined summary as JSON to this file
+        #[arg(long, requires = "summaries")]
+        summary_file: Option<PathBuf>,
+    },
+
+    /// Aggregate statistics over collected NDJSON: object classes, registrars,
+    /// statuses, creation years, nameservers, RIRs, and countries
+    Stats {
+        /// NDJSON output of `bulk` or `search`
+        file: PathBuf,
+
+        /// Values listed per section (0 for all; years are always complete)
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+
+    /// Ranked counts and shares of an attribute across a `sqlite:` sink
+    /// database, e.g. the top registrars of a collection
+    Report {
+        /// Database written with `--output sqlite:PATH`
+        #[arg(long)]
+        db: PathBuf,
+
+        /// Attribute to group objects by
+        #[arg(long, value_enum)]
+        group_by: report::GroupBy,
+
+        /// Groups listed (0 for all)
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+    },
+
+    /// Find collected objects sharing an attribute in a `sqlite:` sink database
+    Pivot {
+        /// Database written with `--output sqlite:PATH`
+        #[arg(long)]
+        db: PathBuf,
+
+        /// Attribute to pivot on
+        #[arg(long, value_enum)]
+        by: pivot::PivotBy,
+
+        /// Value to match (case-insensitive), e.g. ns1.example.net
+        value: String,
+    },
+
+    /// Known RDAP services per TLD, IP prefix, and ASN range
+    Servers {
+        #[command(subcommand)]
+        action: ServersCmd,
+    },
+
+    /// Inspect how queries are routed to RDAP servers
+    Bootstrap {
+        #[command(subcommand)]
+        action: BootstrapCmd,
+    },
+
+    /// Query legacy WHOIS (port 43) directly, starting from IANA and
+    /// following registry/registrar and RIR referrals
+    Whois {
+        /// Query: example.com | 1.1.1.1 | AS13335
+        query: String,
+
+        /// WHOIS server to ask instead of looking it up via IANA
+        #[arg(long)]
+        server: Option<String>,
+
+        /// Stop at the first server's answer instead of following referrals
+        #[arg(long)]
+        no_follow: bool,
+
+        /// Print the servers' text as-is instead of the parsed RDAP-shaped record
+        #[arg(long)]
+        raw: bool,
+    },
+
+    /// Create a `.rdapx/` project directory with its own config, cache,
+    /// history, and portfolios, used by commands run inside it
+    Init {
+        /// Directory to initialize
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+
+    /// Import RDAP JSON collected elsewhere (files, NDJSON, or directories)
+    /// into history so it can be diffed, pivoted, and served
+    Import {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Also replace cache entries (they count as fresh for the cache TTL)
+        #[arg(long)]
+        cache: bool,
+    },
+
+    /// Check `--sign-output` digests (and signatures) in an NDJSON file
+    Verify {
+        /// NDJSON file written with --sign-output
+        file: PathBuf,
+
+        /// Minisign public key to check record signatures against
+        #[arg(long, value_name = "FILE")]
+        pubkey: Option<PathBuf>,
+    },
+
+    /// Find domains delegated to a nameserver (`domains?nsLdhName=` search)
+    ReverseNs {
+        /// Nameserver host name, e.g. ns1.example.net
+        nameserver: String,
+
+        #[command(flatten)]
+        paging: search::PagingArgs,
+    },
+
+    /// List other domains sharing a domain's registrant or nameservers, via
+    /// the registry's searches, with the pivots that found each one
+    Related {
+        /// Domain to pivot from, e.g. example.com
+        domain: String,
+
+        /// Comma-separated pivots (default: all)
+        #[arg(long, value_enum, value_delimiter = ',')]
+        pivots: Vec<related::Pivot>,
+
+        /// Stop each pivot's search after this many results (0 = no limit)
+        #[arg(long, default_value_t = 1000)]
+        max_results: usize,
+
+        /// RDAP base URL to search (default: the server the domain routes to)
+        #[arg(long, value_name = "URL", value_parser = parse_base_url)]
+        server: Option<String>,
+    },
+
+    /// Fetch fresh data and report whether the object changed since the last
+    /// snapshot. Exits 0 if changed, 1 if unchanged (or first run), 2 on error
+    Changed {
+        /// Query: example.com | 1.1.1.1 | AS13335
+        query: String,
+
+        /// Also show what changed, field by field (json: one document with
+        /// the hashes and the changes)
+        #[arg(long, value_enum)]
+        diff_format: Option<diffruns::DiffFormat>,
+
+        /// Changes that don't count (repeatable; adds to `[diff] ignore`),
+        /// as for `diff-runs --ignore-path`
+        #[arg(long, value_name = "RULE", value_parser = diffruns::IgnoreRule::parse)]
+        ignore_path: Vec<diffruns::IgnoreRule>,
+    },
+
+    /// Compare an object's current data with a pinned snapshot (see
+    /// `history pin`). Exits 0 when nothing changed, 1 when it did
+    Diff {
+        /// Query: example.com | 1.1.1.1 | AS13335
+        query: String,
+
+        /// Name of the pinned snapshot to compare against
+        #[arg(long, value_name = "NAME")]
+        against: String,
+
+        #[arg(long, value_enum, default_value_t = diffruns::DiffFormat::Unified)]
+        diff_format: diffruns::DiffFormat,
+
+        /// Changes that don't count (repeatable; adds to `[diff] ignore`),
+        /// as for `diff-runs --ignore-path`
+        #[arg(long, value_name = "RULE", value_parser = diffruns::IgnoreRule::parse)]
+        ignore_path: Vec<diffruns::IgnoreRule>,
+    },
+
+    /// Work with the snapshot history
+    History {
+        #[command(subcommand)]
+        action: HistoryCmd,
+    },
+
+    /// Print JSON Schema for rdapx output shapes (default: the JSON record)
+    Schema {
+        /// Schema of one bulk NDJSON line
+        #[arg(long, group = "shape")]
+        ndjson: bool,
+        /// Schema of the CSV/table row
+        #[arg(long, group = "shape")]
+        csv: bool,
+        /// Schema of the bulk summary (`bulk --summary-file`)
+        #[arg(long, group = "shape")]
+        summary: bool,
+    },
+
+    /// Manage offline reference datasets
+    Dataset {
+        #[command(subcommand)]
+        action: DatasetCmd,
+    },
+
+    /// Manage secrets stored in the OS keyring
+    Auth {
+        #[command(subcommand)]
+        action: AuthCmd,
+    },
+
+    /// Serve RDAP lookups over HTTP (`/domain/`, `/ip/`, `/autnum/`)
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: std::net::SocketAddr,
+
+        /// Answer only from the local cache and history (404 on misses)
+        #[arg(long)]
+        offline: bool,
+
+        /// Max upstream lookups in flight, across all clients
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+
+        /// Max upstream requests per second to any single registry
+        #[arg(long)]
+        rate: Option<u32>,
+    },
+
+    /// Print completion candidates (used by the generated shell completions)
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[arg(value_enum)]
+        kind: complete::CompleteKind,
+        #[arg(default_value = "")]
+        prefix: String,
+    },
+}
+
+impl Command {
+    /// Files the command writes outside the cache and data directories,
+    /// which `--sandbox` has to leave writable.
+    fn output_files(&self) -> Vec<PathBuf> {
+        let files = match self {
+            Self::Bulk {
+                summary_file,
+                manifest,
+                rejects,
+                progress_file,
+                json_errors,
+                ..
+            } => vec![
+                summary_file.clone(),
+                manifest.clone(),
+                rejects.clone(),
+                progress_file.clone(),
+                json_errors.clone(),
+            ],
+            Self::Search { target } => vec![target.paging().resume.clone()],
+            Self::Squat { resume, .. } => vec![resume.clone()],
+            Self::Merge { summary_file, .. } => vec![summary_file.clone()],
+            Self::Init { dir } => vec![Some(dir.join(project::DIR))],
+            _ => Vec::new(),
+        };
+        files.into_iter().flatten().collect()
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum CacheSort {
+    Query,
+    Kind,
+    Server,
+    Age,
+    Size,
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCmd {
+    /// List cache entries with query, type, server, age, size, and TTL state
+    List {
+        /// Only rows where FIELD=VALUE (repeatable; fields: kind, server, state, query glob)
+        #[arg(long, value_parser = parse_tag)]
+        filter: Vec<(String, String)>,
+
+        /// Sort rows by this column
+        #[arg(long, value_enum, default_value_t = CacheSort::Query)]
+        sort: CacheSort,
+    },
+    /// Remove cached JSON files (asks first on a terminal)
+    Clear {
+        /// Only entries whose query matches this glob (`*` and `?`), e.g. 'example.*'
+        #[arg(long)]
+        query: Option<String>,
+
+        /// Only entries fetched longer ago than this (e.g. 30d)
+        #[arg(long, value_parser = parse_duration)]
+        older_than: Option<Duration>,
+
+        /// Don't ask for confirmation
+        #[arg(long)]
+        force: bool,
+    },
+    /// Pre-populate or refresh cache entries for a target list (e.g. from cron)
+    Warm {
+        /// File containing queries (one per line)
+        file: PathBuf,
+
+        /// Refresh entries that expire within this window (e.g. 12h)
+        #[arg(long, value_parser = parse_duration, default_value = "0s")]
+        refresh_before: Duration,
+
+        /// Max concurrent requests
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SearchCmd {
+    /// Search domains by name pattern or nameserver
+    Domains {
+        /// Domain name pattern, e.g. 'exam*.com'
+        #[arg(long, group = "by", required = true)]
+        name: Option<String>,
+
+        /// Nameserver host name
+        #[arg(long, group = "by")]
+        ns_name: Option<String>,
+
+        /// Nameserver IP address
+        #[arg(long, group = "by")]
+        ns_ip: Option<String>,
+
+        /// File of name patterns (one per line); runs one search each and merges results
+        #[arg(long, group = "by")]
+        name_file: Option<PathBuf>,
+
+        #[command(flatten)]
+        paging: search::PagingArgs,
+    },
+    /// Search nameservers by name pattern or IP
+    Nameservers {
+        /// Nameserver name pattern, e.g. 'ns1.exam*.com'
+        #[arg(long, group = "by", required = true)]
+        name: Option<String>,
+
+        /// Nameserver IP address
+        #[arg(long, group = "by")]
+        ip: Option<String>,
+
+        #[command(flatten)]
+        paging: search::PagingArgs,
+    },
+    /// Search entities by full name or handle pattern
+    Entities {
+        /// Full name (vCard `fn`) pattern
+        #[arg(long = "fn", group = "by", required = true)]
+        full_name: Option<String>,
+
+        /// Handle pattern
+        #[arg(long, group = "by")]
+        handle: Option<String>,
+
+        #[command(flatten)]
+        paging: search::PagingArgs,
+    },
+}
+
+impl SearchCmd {
+    const fn paging(&self) -> &search::PagingArgs {
+        match self {
+            Self::Domains { paging, .. }
+            | Self::Nameservers { paging, .. }
+            | Self::Entities { paging, .. } => paging,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum PortfolioCmd {
+    /// Add targets to a portfolio (created if missing)
+    Add {
+        name: String,
+        #[arg(required = true)]
+        targets: Vec<String>,
+    },
+    /// Remove targets from a portfolio, or the whole portfolio if none are given
+    Remove { name: String, targets: Vec<String> },
+    /// List portfolios, or the targets of one
+    List { name: Option<String> },
+    /// Look up every target and report status, registrar, and days to expiry
+    Check {
+        name: String,
+
+        /// Max concurrent requests
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BootstrapCmd {
+    /// Print the object type, normalized form, server, and URL for a query
+    #[command(visible_alias = "show")]
+    Resolve {
+        /// Query: example.com | 1.1.1.1 | AS13335
+        query: String,
+    },
+
+    /// Load each IANA bootstrap file from the configured `[bootstrap]`
+    /// sources (mirror, pinned copies, then IANA) and report which one served
+    /// it, its checksum, and why earlier sources were skipped
+    Sources,
+
+    /// Download all five bootstrap files now, whatever their age, and store
+    /// them in the cache
+    Refresh,
+
+    /// When each stored bootstrap file was last updated, its IANA
+    /// publication date, and whether it is due for a refresh
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+enum ServersCmd {
+    /// Base URL per TLD, IP prefix, and ASN range from `servers.toml`, the
+    /// bootstrap data, and the built-in defaults, in the order they are
+    /// consulted
+    List {
+        /// Only entries of this kind
+        #[arg(long, value_enum)]
+        kind: Option<bootstrap::ServiceKind>,
+    },
+
+    /// Request `/help` from every known server (each distinct base URL once)
+    /// and report HTTP status, latency, and rdapConformance. Exits 1 when
+    /// any server is unhealthy
+    Check {
+        /// Only servers with entries of this kind
+        #[arg(long, value_enum)]
+        kind: Option<bootstrap::ServiceKind>,
+
+        /// Max servers probed at once
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AuditCmd {
+    /// Verify every domain carries clientTransfer/Delete/UpdateProhibited;
+    /// exits 1 when any domain is missing a lock or can't be looked up
+    Locks {
+        /// Portfolio to audit
+        #[arg(long)]
+        portfolio: String,
+
+        /// Max concurrent requests
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HistoryCmd {
+    /// Chronological timeline of field changes (status, nameservers, registrar, expiry)
+    Export {
+        /// Query: example.com | 1.1.1.1 | AS13335
+        query: String,
+
+        #[arg(long, value_enum, default_value_t = history::TimelineFormat::Csv)]
+        format: history::TimelineFormat,
+    },
+    /// Apply a retention policy to every history file (defaults to `[history]` in config)
+    Compact {
+        /// Keep at most N most recent snapshots per object
+        #[arg(long)]
+        keep_last: Option<usize>,
+
+        /// Also keep the first and last snapshot of each month
+        #[arg(long)]
+        keep_monthly: bool,
+
+        /// Drop snapshots identical to the previous one
+        #[arg(long)]
+        dedupe: bool,
+
+        /// Report what would be removed without rewriting files
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Pin a snapshot under a name, as an approved state to diff against
+    Pin {
+        /// Query: example.com | 1.1.1.1 | AS13335
+        query: String,
+
+        /// Name for the pin, e.g. baseline-2024 (an existing pin is replaced)
+        #[arg(long = "as", value_name = "NAME")]
+        name: String,
+
+        /// Snapshot to pin, by hash prefix (default: the latest)
+        #[arg(long)]
+        hash: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DatasetCmd {
+    /// Download (or re-download) a dataset into the local cache
+    Pull {
+        #[arg(value_enum)]
+        name: dataset::DatasetName,
+    },
+    /// Show locally available datasets and their age
+    List,
+    /// Map an IP or ASN to its RIR, country, and allocation date (offline)
+    Lookup {
+        /// Query: 1.1.1.1 | `2001:db8::/32` | AS13335
+        query: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AuthCmd {
+    /// Store a secret (read from stdin, or prompted on a terminal)
+    Set {
+        /// Secret name, e.g. `arin-token`
+        name: String,
+    },
+    /// Print a stored secret
+    Get { name: String },
+    /// Remove a stored secret
+    Delete { name: String },
+}
+
+/* ----------------------------- HTTP + RDAP ------------------------------ */
+
+fn http_client(timeout_secs: u64) -> Result<reqwest::Client, Box<dyn Error>> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(lang) = i18n::lang() {
+        headers.insert(reqwest::header::ACCEPT_LANGUAGE, lang.parse()?);
+    }
+    if let Some(token) = auth::bearer() {
+        let mut value: reqwest::header::HeaderValue = format!("Bearer {token}").parse()?;
+        value.set_sensitive(true);
+        headers.insert(reqwest::header::AUTHORIZATION, value);
+    }
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("rdapx/", env!("CARGO_PKG_VERSION")))
+        .default_headers(headers)
+        .dns_resolver(dns::resolver())
+        // bound each connection attempt so a dead address family can't eat the whole timeout
+        .connect_timeout(Duration::from_secs(timeout_secs.clamp(1, 10)))
+        .timeout(Duration::from_secs(timeout_secs))
+        .gzip(true)
+        .brotli(true)
+        .deflate(true)
+        .build()?;
+    Ok(client)
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    Domain,
+    Ip,
+    Asn,
+    /// Entity handle with a known object tag (`ABC123-ARIN`)
+    Entity,
+}
+
+impl Kind {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Domain => "domain",
+            Self::Ip => "ip",
+            Self::Asn => "asn",
+            Self::Entity => "entity",
+        }
+    }
+}
+
+fn normalize(query: &str) -> (Kind, String) {
+    // quick’n'tidy
+    let s = query.trim();
+    // AS13335 -> 13335 (only when the rest is numeric, so `sony.com` stays a domain)
+    if let Some(num) = s
+        .get(..2)
+        .filter(|p| p.eq_ignore_ascii_case("as"))
+        .map(|_| &s[2..])
+    {
+        if !num.is_empty() && num.bytes().all(|b| b.is_ascii_digit()) {
+            return (Kind::Asn, num.to_string());
+        }
+    }
+    if s.contains(':') {
+        return (Kind::Ip, normalize_ipv6(s));
+    }
+    // 192.0.2.1 or 192.0.2.0/24
+    let addr = s.split_once('/').map_or(s, |(a, _)| a);
+    if addr.split('.').all(|p| p.parse::<u8>().is_ok()) {
+        return (Kind::Ip, s.to_string());
+    }
+    if is_handle(s) {
+        return (Kind::Entity, s.to_string());
+    }
+    // www.example.co.uk is registered (and answered) as example.co.uk
+    (Kind::Domain, psl::registrable(s).to_string())
+}
+
+/// Whether `s` is shaped like an entity handle with a known object tag
+/// (`ABC123-ARIN`, RFC 8521); other dotless words aren't guessed at.
+fn is_handle(s: &str) -> bool {
+    s.rsplit_once('-').is_some_and(|(id, tag)| {
+        !id.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            && bootstrap::is_object_tag(tag)
+    })
+}
+
+/// Canonical (RFC 5952) form of an IPv6 address or prefix, so equivalent
+/// spellings share a cache key and URL: brackets and `%zone` are dropped,
+/// and IPv4-mapped addresses (`::ffff:1.2.3.4`) become plain IPv4. Input
+/// that doesn't parse is returned trimmed, as before.
+fn normalize_ipv6(s: &str) -> String {
+    let (addr, len) = match s.split_once('/') {
+        Some((a, l)) => (a, Some(l)),
+        None => (s, None),
+    };
+    let addr = addr.trim_start_matches('[').trim_end_matches(']');
+    let addr = addr.split_once('%').map_or(addr, |(a, _)| a);
+    let Ok(ip) = addr.parse::<std::net::Ipv6Addr>() else {
+        return s.to_string();
+    };
+    let len = match len.map(str::parse::<u8>) {
+        None => None,
+        Some(Ok(l)) if l <= 128 => Some(l),
+        Some(_) => return s.to_string(),
+    };
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        match len {
+            None => return v4.to_string(),
+            Some(l) if l >= 96 => return format!("{v4}/{}", l - 96),
+            Some(_) => {}
+        }
+    }
+    len.map_or_else(|| ip.to_string(), |l| format!("{ip}/{l}"))
+}
+
+/// Default RDAP base URL (with trailing slash) for `kind`: the `.com`
+/// registry for domains and entity handles without a known object tag,
+/// APNIC for IPs, ARIN for ASNs.
+const fn base_url(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Domain | Kind::Entity => "https://rdap.verisign.com/com/v1/",
+        Kind::Ip => "https://rdap.apnic.net/",
+        Kind::Asn => "https://rdap.arin.net/registry/",
+    }
+}
+
+fn classify_to_url(kind: Kind, normalized: &str) -> String {
+    let base = bootstrap::base_for(kind, normalized)
+        .unwrap_or_else(|| builtin_base(kind, normalized).to_string());
+    rdap_url(&base, kind, normalized)
+}
+
+/// Server used until the IANA bootstrap data is available or when it has no
+/// entry for a query: Verisign for `.com`/`.net`, the rdap.org redirector
+/// for every other TLD, the RIR named by an entity handle's tag, and
+/// [`base_url`] otherwise.
+fn builtin_base(kind: Kind, normalized: &str) -> &'static str {
+    if matches!(kind, Kind::Entity) {
+        let tag = normalized.rsplit_once('-').map_or("", |(_, t)| t);
+        let rir = fanout::RIR_TAGS
+            .iter()
+            .position(|t| tag.eq_ignore_ascii_case(t));
+        return rir.map_or_else(|| base_url(kind), |i| fanout::RIRS[i]);
+    }
+    if !matches!(kind, Kind::Domain) || !normalized.contains('.') {
+        return base_url(kind);
+    }
+    let tld = normalized
+        .trim_end_matches('.')
+        .rsplit('.')
+        .next()
+        .unwrap_or("");
+    if tld.eq_ignore_ascii_case("com") {
+        "https://rdap.verisign.com/com/v1/"
+    } else if tld.eq_ignore_ascii_case("net") {
+        "https://rdap.verisign.com/net/v1/"
+    } else {
+        "https://rdap.org/"
+    }
+}
+
+/// Every service `rdapx servers` knows of (optionally of one kind): the
+/// `servers.toml` and bootstrap entries, then the built-in defaults.
+fn known_services(kind: Option<bootstrap::ServiceKind>) -> Vec<bootstrap::Service> {
+    use bootstrap::{Service, ServiceKind};
+    let builtin = |kind, entry: &str, url: &str| Service {
+        kind,
+        entry: entry.to_string(),
+        url: url.to_string(),
+        alternates: Vec::new(),
+        source: "built-in",
+    };
+    let mut services = bootstrap::services_list();
+    services.extend([
+        builtin(ServiceKind::Tld, "com", builtin_base(Kind::Domain, "x.com")),
+        builtin(ServiceKind::Tld, "net", builtin_base(Kind::Domain, "x.net")),
+        builtin(
+            ServiceKind::Tld,
+            "*",
+            builtin_base(Kind::Domain, "x.example"),
+        ),
+        builtin(ServiceKind::Ipv4, "*", base_url(Kind::Ip)),
+        builtin(ServiceKind::Ipv6, "*", base_url(Kind::Ip)),
+        builtin(ServiceKind::Asn, "*", base_url(Kind::Asn)),
+        builtin(ServiceKind::Tag, "*", base_url(Kind::Entity)),
+    ]);
+    services.retain(|s| kind.is_none_or(|k| s.kind == k));
+    services
+}
+
+/// Object URL under an RDAP base URL, with or without its trailing slash.
+fn rdap_url(base: &str, kind: Kind, normalized: &str) -> String {
+    let path = match kind {
+        Kind::Domain => "domain",
+        Kind::Ip => "ip",
+        Kind::Asn => "autnum",
+        Kind::Entity => "entity",
+    };
+    format!("{}/{path}/{normalized}", base.trim_end_matches('/'))
+}
+
+/* ------------------------------ CACHING --------------------------------- */
+fn cache_dir() -> io::Result<PathBuf> {
+    let p = project::cache_dir()?;
+    if !p.exists() {
+        fs::create_dir_all(&p)?;
+    }
+    Ok(p)
+}
+
+/// Stable per-URL key shared by the cache and history stores.
+fn url_key(normalized_url: &str) -> String {
+    blake3::hash(normalized_url.as_bytes()).to_hex()[..32].to_string()
+}
+
+fn cache_key(normalized_url: &str) -> String {
+    format!("{}.json", url_key(normalized_url))
+}
+
+/// Format unix seconds as an RFC 3339 UTC timestamp.
+fn format_timestamp(secs: u64) -> String {
+    let days = i64::try_from(secs / 86_400).unwrap_or(0);
+    let rem = secs % 86_400;
+    // civil-from-days (Howard Hinnant), proleptic Gregorian calendar
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Days since the Unix epoch for an RFC 3339 / ISO date (`YYYY-MM-DD...`).
+fn parse_date_days(s: &str) -> Option<i64> {
+    let mut parts = s.get(..10)?.split('-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // days-from-civil (Howard Hinnant), inverse of `format_timestamp`
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some(era * 146_097 + doe - 719_468)
+}
+
+fn now_secs() -> u64 {
+    deterministic::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// On-disk cache record: the RDAP response plus what produced it.
+#[derive(Serialize, Deserialize, Debug)]
+struct CacheEntry {
+    query: String,
+    url: String,
+    /// Unix seconds, always from the wall clock so `--deterministic` runs
+    /// don't stamp the cache and history with the frozen time
+    fetched_at: u64,
+    /// Normalized content hash (see `history::content_hash`)
+    hash: String,
+    data: Value,
+    /// Negative entry for a 404 (`[cache.ttl] notfound`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    not_found: bool,
+    /// Validators for conditional revalidation (`bulk --incremental`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+}
+
+/// Read the cache entry for `url` regardless of age. Pre-envelope cache files
+/// (bare RDAP JSON) are still accepted.
+fn read_cache_entry(url: &str) -> Option<CacheEntry> {
+    let raw = read_cache_file(&cache_path(url).ok()?)?;
+    serde_json::from_str::<CacheEntry>(&raw).ok().or_else(|| {
+        let data: Value = serde_json::from_str(&raw).ok()?;
+        Some(CacheEntry {
+            query: String::new(),
+            url: url.to_string(),
+            fetched_at: 0,
+            hash: history::content_hash(&data),
+            data,
+            not_found: false,
+            etag: None,
+            last_modified: None,
+        })
+    })
+}
+
+fn cache_path(url: &str) -> io::Result<PathBuf> {
+    Ok(cache_dir()?.join(cache_key(url)))
+}
+
+/// Age of the cache entry for `url`, if one exists.
+fn cache_age(url: &str) -> Option<Duration> {
+    let p = cache_path(url).ok()?;
+    let modified = fs::metadata(p).and_then(|m| m.modified()).ok()?;
+    Some(deterministic::age(modified))
+}
+
+fn load_cache(url: &str, ttl: Duration) -> io::Result<Option<Value>> {
+    let p = cache_path(url)?;
+    let meta = fs::metadata(&p)?;
+    let age_ok = meta
+        .modified()
+        .ok()
+        .map(deterministic::age)
+        .is_some_and(|age| age <= ttl);
+    if age_ok {
+        // entries sealed with a key we don't have count as misses
+        return Ok(read_cache_entry(url)
+            .filter(|e| !e.not_found)
+            .map(|e| e.data));
+    }
+    Ok(None)
+}
+
+/// RDAP object class (`domain`, `ip`, `autnum`, ...) an RDAP URL points at.
+fn url_class(url: &str) -> Option<&'static str> {
+    const CLASSES: [&str; 5] = ["domain", "ip", "autnum", "nameserver", "entity"];
+    let u = reqwest::Url::parse(url).ok()?;
+    let mut segments = u.path_segments()?;
+    segments.find_map(|seg| CLASSES.iter().find(|c| **c == seg).copied())
+}
+
+/// Cache TTL for `url`: the `[cache.ttl]` entry for its class, else `default`.
+fn cache_ttl(url: &str, default: Duration) -> Duration {
+    url_class(url)
+        .and_then(|c| config::get().cache.ttl.for_class(c))
+        .map_or(default, Duration::from_secs)
+}
+
+/// Whether `url` has a fresh negative (404) cache entry.
+fn cached_not_found(url: &str) -> bool {
+    let Some(ttl) = config::get().cache.ttl.notfound else {
+        return false;
+    };
+    cache_age(url).is_some_and(|age| age.as_secs() <= ttl)
+        && read_cache_entry(url).is_some_and(|e| e.not_found)
+}
+
+fn save_cache(entry: &CacheEntry) -> io::Result<()> {
+    let p = cache_path(&entry.url)?;
+    if let Some(parent) = p.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(p, crypt::seal(&serde_json::to_string(entry)?))?;
+    Ok(())
+}
+
+/// Contents of a cache file, decrypted if it was sealed with `--cache-key`.
+fn read_cache_file(path: &Path) -> Option<String> {
+    crypt::open(&fs::read_to_string(path).ok()?)
+}
+
+fn list_cache(dir: &Path) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    if let Ok(rd) = fs::read_dir(dir) {
+        for e in rd.flatten() {
+            let p = e.path();
+            if p.extension().and_then(|s| s.to_str()) == Some("json") {
+                out.push(p);
+            }
+        }
+    }
+    out
+}
+
+/// One `cache list` row.
+#[derive(Serialize, Debug)]
+struct CacheRow {
+    query: String,
+    kind: String,
+    server: String,
+    /// Seconds since the entry was fetched
+    age_secs: u64,
+    size: u64,
+    /// `fresh` or `stale` against the TTL for the entry's object class
+    state: &'static str,
+    file: PathBuf,
+}
+
+impl table::Tabular for CacheRow {
+    const COLUMNS: &'static [table::Column] = &[
+        table::Column("QUERY", 32),
+        table::Column("KIND", 6),
+        table::Column("SERVER", 24),
+        table::Column("AGE", -6),
+        table::Column("SIZE", -8),
+        table::Column("STATE", 0),
+    ];
+    const CSV_HEADER: &'static [&'static str] = &[
+        "query", "kind", "server", "age_secs", "size", "state", "file",
+    ];
+
+    fn table_row(&self) -> Vec<table::Cell> {
+        let color = if self.state == "fresh" {
+            colored::Color::Green
+        } else {
+            colored::Color::Yellow
+        };
+        vec![
+            self.query.as_str().into(),
+            self.kind.as_str().into(),
+            self.server.as_str().into(),
+            short_age(self.age_secs).into(),
+            self.size.to_string().into(),
+            table::Cell::colored(self.state, color),
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.query.clone(),
+            self.kind.clone(),
+            self.server.clone(),
+            self.age_secs.to_string(),
+            self.size.to_string(),
+            self.state.to_string(),
+            self.file.display().to_string(),
+        ]
+    }
+}
+
+impl CacheRow {
+    fn read(path: PathBuf, ttl: Duration) -> Self {
+        let size = fs::metadata(&path).map_or(0, |m| m.len());
+        let age_secs = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|m| m.elapsed().ok())
+            .map_or(0, |d| d.as_secs());
+        let entry =
+            read_cache_file(&path).and_then(|raw| serde_json::from_str::<CacheEntry>(&raw).ok());
+        let ttl = match &entry {
+            Some(e) if e.not_found => {
+                Duration::from_secs(config::get().cache.ttl.notfound.unwrap_or(0))
+            }
+            Some(e) => cache_ttl(&e.url, ttl),
+            None => ttl,
+        };
+        let (query, kind, server) = entry.as_ref().map_or_else(
+            || ("-".to_string(), "-".to_string(), "-".to_string()),
+            |e| {
+                let kind = if e.query.is_empty() {
+                    "-"
+                } else {
+                    normalize(&e.query).0.as_str()
+                };
+                (e.query.clone(), kind.to_string(), url_host(&e.url))
+            },
+        );
+        Self {
+            query,
+            kind,
+            server,
+            age_secs,
+            size,
+            state: if age_secs <= ttl.as_secs() {
+                "fresh"
+            } else {
+                "stale"
+            },
+            file: path,
+        }
+    }
+
+    fn matches(&self, (field, value): &(String, String)) -> bool {
+        match field.as_str() {
+            "kind" => self.kind.eq_ignore_ascii_case(value),
+            "server" => self.server.eq_ignore_ascii_case(value),
+            "state" => self.state.eq_ignore_ascii_case(value),
+            "query" => glob_match(&value.to_lowercase(), &self.query.to_lowercase()),
+            _ => false,
+        }
+    }
+}
+
+/// Compact age like `45s`, `12m`, `3h`, `9d`.
+fn short_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86_399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+/// Bootstrap files as loaded by `bootstrap sources` / `refresh`; errors when
+/// any file failed.
+fn print_bootstrap_loaded(
+    loaded: &[bootstrap::Loaded],
+    failed: &[String],
+    fmt: Format,
+) -> Result<(), Box<dyn Error>> {
+    for reason in loaded.iter().flat_map(|l| &l.skipped) {
+        eprintln!("{} skipped {reason}", "Note:".yellow().bold());
+    }
+    table::print(loaded, fmt)?;
+    for e in failed {
+        eprintln!("{} {e}", "Failed".red().bold());
+    }
+    if !failed.is_empty() {
+        return Err(format!("{} bootstrap file(s) could not be loaded", failed.len()).into());
+    }
+    Ok(())
+}
+
+/// Match `text` against a glob where `*` is any run and `?` any one character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (p, t): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Cache files selected by `cache clear` filters. Entries without a stored
+/// query (pre-envelope files) never match `--query`.
+fn select_cache(dir: &Path, query: Option<&str>, older_than: Option<Duration>) -> Vec<PathBuf> {
+    let now = deterministic::wall_secs();
+    list_cache(dir)
+        .into_iter()
+        .filter(|p| {
+            if query.is_none() && older_than.is_none() {
+                return true;
+            }
+            let Some(entry) =
+                read_cache_file(p).and_then(|raw| serde_json::from_str::<CacheEntry>(&raw).ok())
+            else {
+                return query.is_none()
+                    && older_than.is_some_and(|d| {
+                        fs::metadata(p)
+                            .and_then(|m| m.modified())
+                            .ok()
+                            .and_then(|m| m.elapsed().ok())
+                            .is_some_and(|age| age > d)
+                    });
+            };
+            query.is_none_or(|g| glob_match(&g.to_lowercase(), &entry.query.to_lowercase()))
+                && older_than.is_none_or(|d| now.saturating_sub(entry.fetched_at) > d.as_secs())
+        })
+        .collect()
+}
+
+/// Ask a yes/no question on the terminal; anything but y/yes is a no.
+fn confirm(prompt: &str) -> bool {
+    eprint!("{prompt} [y/N] ");
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).is_ok()
+        && matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+fn clear_cache(files: &[PathBuf]) -> usize {
+    files.iter().filter(|p| fs::remove_file(p).is_ok()).count()
+}
+
+/* ------------------------------ OUTPUT ---------------------------------- */
+
+/// Encode one record for the binary formats (`None` for text formats).
+fn encode_binary(json: &Value, fmt: Format) -> Option<Vec<u8>> {
+    match fmt {
+        Format::Msgpack => rmp_serde::to_vec_named(json).ok(),
+        Format::Cbor => {
+            let mut buf = Vec::new();
+            ciborium::into_writer(json, &mut buf).ok()?;
+            Some(buf)
+        }
+        Format::Json | Format::Pretty | Format::Table | Format::Csv | Format::Ical => None,
+    }
+}
+
+/// Write a length-prefixed binary record so consumers can split the stream.
+fn write_binary_record(payload: &[u8]) -> io::Result<()> {
+    use std::io::Write;
+    let len = u32::try_from(payload.len()).map_err(io::Error::other)?;
+    let mut out = io::stdout().lock();
+    out.write_all(&len.to_be_bytes())?;
+    out.write_all(payload)?;
+    out.flush()
+}
+
+/// Pretty-print `v` like `serde_json::to_string_pretty`, with ANSI colors for
+/// keys, strings, numbers, booleans, and null.
+fn highlight_json(v: &Value, indent: usize, out: &mut String) {
+    use std::fmt::Write;
+    let pad = |n: usize| "  ".repeat(n);
+    let quoted = |s: &str| serde_json::to_string(s).unwrap_or_default();
+    match v {
+        Value::Null => out.push_str(&"null".bright_black().to_string()),
+        Value::Bool(b) => out.push_str(&b.to_string().yellow().to_string()),
+        Value::Number(n) => out.push_str(&n.to_string().cyan().to_string()),
+        Value::String(s) => out.push_str(&quoted(s).green().to_string()),
+        Value::Array(items) if items.is_empty() => out.push_str("[]"),
+        Value::Object(map) if map.is_empty() => out.push_str("{}"),
+        Value::Array(items) => {
+            out.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(&pad(indent + 1));
+                highlight_json(item, indent + 1, out);
+                out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+            }
+            let _ = write!(out, "{}]", pad(indent));
+        }
+        Value::Object(map) => {
+            out.push_str("{\n");
+            for (i, (k, item)) in map.iter().enumerate() {
+                let _ = write!(out, "{}{}: ", pad(indent + 1), quoted(k).blue().bold());
+                highlight_json(item, indent + 1, out);
+                out.push_str(if i + 1 < map.len() { ",\n" } else { "\n" });
+            }
+            let _ = write!(out, "{}}}", pad(indent));
+        }
+    }
+}
+
+fn output(json: &Value, fmt: Format) {
+    match fmt {
+        Format::Msgpack | Format::Cbor => {
+            if let Some(bytes) = encode_binary(json, fmt) {
+                if let Err(e) = write_binary_record(&bytes) {
+                    eprintln!("{} {e}", "Write failed:".red().bold());
+                }
+            }
+        }
+        // `run` only lets `expiry` take ical, which prints its own calendar
+        Format::Json | Format::Ical => {
+            // compact JSON, through the bulk writer thread when it's running
+            if !writer::write(json) {
+                println!("{json}");
+            }
+        }
+        Format::Pretty => {
+            // pretty JSON, highlighted when colors are on (TTY and no --no-color)
+            if colored::control::SHOULD_COLORIZE.should_colorize() {
+                let mut buf = String::new();
+                highlight_json(json, 0, &mut buf);
+                println!("{buf}");
+            } else {
+                println!("{}", serde_json::to_string_pretty(json).unwrap());
+            }
+        }
+        Format::Csv => println!(
+            "{}",
+            preset::csv_row(json).unwrap_or_else(|| model::Row::from_rdap(json).to_csv())
+        ),
+        Format::Table => {
+            let row = model::Row::from_rdap(json);
+            let dash = |s: &str| {
+                if s.is_empty() {
+                    "-".to_string()
+                } else {
+                    s.to_string()
+                }
+            };
+            let label = |l| format!("{}:", i18n::label(l));
+            println!("{} {}", label(Label::Type).blue().bold(), dash(&row.kind));
+            println!(
+                "{} {}",
+                label(Label::Handle).blue().bold(),
+                dash(&row.handle)
+            );
+            println!("{} {}", label(Label::Name).blue().bold(), dash(&row.name));
+            println!(
+                "{} {}",
+                label(Label::Country).blue().bold(),
+                dash(&row.country)
+            );
+            println!(
+                "{} {}",
+                label(Label::Status).blue().bold(),
+                dash(&row.status)
+            );
+            if !row.roles.is_empty() {
+                println!("{} {}", label(Label::Roles).yellow().bold(), row.roles);
+            }
+            let remarks = i18n::localized_remarks(json);
+            if !remarks.is_empty() {
+                println!("{}", label(Label::Remarks).yellow().bold());
+                for (title, desc) in remarks {
+                    println!("  {title}: {desc}");
+                }
+            }
+            let notices = notices::collect(json);
+            if !notices.is_empty() {
+                println!("{}", label(Label::Notices).yellow().bold());
+                for n in notices {
+                    let mut lines = n.lines.iter();
+                    match (n.title.is_empty(), lines.next()) {
+                        (false, Some(first)) => println!("  {}: {first}", n.title),
+                        (false, None) => println!("  {}", n.title),
+                        (true, Some(first)) => println!("  {first}"),
+                        (true, None) => {}
+                    }
+                    for line in lines {
+                        println!("    {line}");
+                    }
+                    if let Some(link) = n.link {
+                        println!("    {}", link.dimmed());
+                    }
+                }
+            }
+            let redactions = redaction::summary(json);
+            if !redactions.is_empty() {
+                let policy = redaction::policy(json).unwrap_or("undeclared");
+                println!("{} {policy}", label(Label::Redactions).yellow().bold());
+                for r in redactions {
+                    let reason = r.reason.map(|s| format!(": {s}")).unwrap_or_default();
+                    println!("  {} [{}]{reason}", r.field, r.method);
+                }
+            }
+        }
+    }
+}
+
+/// Look up every target concurrently, returning results in input order.
+async fn lookup_all<'a>(
+    cli: &Cli,
+    targets: &'a [String],
+    concurrency: usize,
+) -> Result<Vec<(&'a str, Result<Value, String>)>, Box<dyn Error>> {
+    let client = http_client(cli.timeout)?;
+    let opts = FetchOpts::from_cli(cli);
+    let mut rows: Vec<(usize, &str, Result<Value, String>)> =
+        stream::iter(targets.iter().enumerate())
+            .map(|(i, q)| {
+                let (client, opts) = (&client, &opts);
+                async move {
+                    let res = fetch_for_query(client, q, opts)
+                        .await
+                        .map_err(|e| e.to_string());
+                    (i, q.as_str(), res)
+                }
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+    rows.sort_by_key(|(i, _, _)| *i);
+    Ok(rows.into_iter().map(|(_, q, res)| (q, res)).collect())
+}
+
+fn print_stats(stats: &stats::Stats, fmt: Format) -> Result<(), Box<dyn Error>> {
+    match fmt {
+        Format::Table => {
+            println!(
+                "{} {} records, {} not found, {} invalid",
+                "Corpus:".blue().bold(),
+                stats.records,
+                stats.not_found,
+                stats.invalid
+            );
+            for (title, counts) in stats.sections() {
+                if counts.is_empty() {
+                    continue;
+                }
+                println!("{}", format!("{title}:").yellow().bold());
+                for c in counts {
+                    println!("  {:>7}  {}", c.count, c.value);
+                }
+            }
+        }
+        Format::Csv => table::print_csv(
+            &["section", "value", "count"],
+            stats.sections().into_iter().flat_map(|(title, counts)| {
+                counts
+                    .iter()
+                    .map(move |c| vec![title.to_string(), c.value.clone(), c.count.to_string()])
+            }),
+        ),
+        _ => output(&serde_json::to_value(stats)?, fmt),
+    }
+    Ok(())
+}
+
+/// Stamp `_meta.schema_version` and wrap the record for the selected schema.
+fn shape_record(mut json: Value, schema: OutputSchema) -> Value {
+    attach_meta(&mut json, "schema_version", Value::from(schema.version()));
+    match (schema, json) {
+        (OutputSchema::V2, Value::Object(mut rdap)) => {
+            let mut env = serde_json::Map::new();
+            for key in ["_meta", "_enrichment", "_rdapx", "_registrar"] {
+                if let Some(v) = rdap.remove(key) {
+                    env.insert(key.to_string(), v);
+                }
+            }
+            env.insert("rdap".to_string(), Value::Object(rdap));
+            Value::Object(env)
+        }
+        (_, json) => json,
+    }
+}
+
+/// One RDAP record as the JSON-family formats write it: projected, in the
+/// schema's envelope, and sealed.
+fn json_record(json: Value, schema: OutputSchema) -> Value {
+    let mut record = shape_record(projection::apply(json), schema);
+    integrity::seal(&mut record);
+    record
+}
+
+/// Emit one RDAP record; table and CSV read the raw object, the JSON-family
+/// formats get the versioned envelope.
+fn emit_record(json: Value, fmt: Format, schema: OutputSchema) {
+    match fmt {
+        Format::Table | Format::Csv => {
+            let json = projection::apply(json);
+            if sink::active() {
+                sink::write(&json_record(json.clone(), schema));
+            }
+            if sink::to_stdout() {
+                output(&json, fmt);
+            }
+        }
+        _ => {
+            let record = json_record(json, schema);
+            sink::write(&record);
+            if sink::to_stdout() {
+                output(preset::record(&record).as_ref().unwrap_or(&record), fmt);
+            }
+        }
+    }
+}
+
+/// Print the per-stream header, if the format has one (CSV).
+fn output_header(fmt: Format) {
+    if matches!(fmt, Format::Csv) && sink::to_stdout() {
+        let header = preset::csv_header();
+        println!("{}", header.as_deref().unwrap_or(model::Row::CSV_HEADER));
+    }
+}
+
+/* ------------------------------ Bulk ------------------------------------ */
+
+#[derive(Clone, Copy, Debug, Default)]
+struct FailureLimit {
+    max_failures: Option<usize>,
+    max_rate: Option<f64>,
+}
+
+impl FailureLimit {
+    /// Returns the abort reason once `failed` crosses either threshold.
+    /// The rate is measured against the whole input so a run can never end
+    /// with a larger failure share than requested.
+    fn exceeded(&self, failed: usize, total: usize) -> Option<String> {
+        if let Some(max) = self.max_failures {
+            if failed > max {
+                return Some(format!("{failed} failures exceeded --max-failures {max}"));
+            }
+        }
+        if let Some(rate) = self.max_rate {
+            #[allow(clippy::cast_precision_loss)]
+            let share = failed as f64 / total.max(1) as f64;
+            if share > rate {
+                return Some(format!(
+                    "{failed}/{total} failures exceeded --max-failure-rate {:.1}%",
+                    rate * 100.0
+                ));
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug)]
+struct QueryTiming {
+    query: String,
+    host: String,
+    elapsed: Duration,
+}
+
+impl QueryTiming {
+    fn elapsed_ms(&self) -> u64 {
+        u64::try_from(self.elapsed.as_millis()).unwrap_or(u64::MAX)
+    }
+}
+
+#[derive(Debug, Default)]
+struct BulkSummary {
+    total: usize,
+    ok: usize,
+    failed: usize,
+    not_found: usize,
+    unchanged: usize,
+    rejected: usize,
+    filtered: usize,
+    aborted: Option<String>,
+    timings: Vec<QueryTiming>,
+    shard: Option<shard::Shard>,
+}
+
+impl BulkSummary {
+    fn report(&mut self, elapsed: Duration, slowest: usize) -> model::SummaryReport {
+        self.timings.sort_by_key(|t| std::cmp::Reverse(t.elapsed));
+        let slowest = self
+            .timings
+            .iter()
+            .take(slowest)
+            .map(|t| model::QueryLatency {
+                query: t.query.clone(),
+                host: t.host.clone(),
+                elapsed_ms: t.elapsed_ms(),
+            })
+            .collect();
+
+        // Per-registry latency: host -> (count, total, max)
+        let mut hosts: BTreeMap<&str, (u32, Duration, Duration)> = BTreeMap::new();
+        for t in &self.timings {
+            let e = hosts.entry(&t.host).or_default();
+            e.0 += 1;
+            e.1 += t.elapsed;
+            e.2 = e.2.max(t.elapsed);
+        }
+        let ms = |d: Duration| u64::try_from(d.as_millis()).unwrap_or(u64::MAX);
+        let registries = hosts
+            .into_iter()
+            .map(|(host, (n, total, max))| model::RegistryLatency {
+                host: host.to_string(),
+                queries: n,
+                avg_ms: ms(total / n),
+                max_ms: ms(max),
+                bytes: bandwidth::by_host(host),
+            })
+            .collect();
+
+        model::SummaryReport {
+            total: self.total,
+            ok: self.ok,
+            failed: self.failed,
+            not_found: self.not_found,
+            unchanged: self.unchanged,
+            rejected: self.rejected,
+            filtered: self.filtered,
+            skipped: self.total
+                - self.ok
+                - self.failed
+                - self.not_found
+                - self.rejected
+                - self.filtered,
+            elapsed_ms: ms(elapsed),
+            bytes: bandwidth::total(),
+            aborted: self.aborted.clone(),
+            slowest,
+            registries,
+            rate_limits: ratelimit::all(),
+            shard: self.shard.map(|s| s.to_string()),
+        }
+    }
+}
+
+fn print_summary(r: &model::SummaryReport) {
+    #[allow(clippy::cast_precision_loss)]
+    let secs = r.elapsed_ms as f64 / 1000.0;
+    let not_found = if r.not_found > 0 {
+        format!(", {} not found", r.not_found)
+    } else {
+        String::new()
+    };
+    let unchanged = if r.unchanged > 0 {
+        format!(", {} unchanged", r.unchanged)
+    } else {
+        String::new()
+    };
+    let rejected = if r.rejected > 0 {
+        format!(", {} rejected", r.rejected)
+    } else {
+        String::new()
+    };
+    eprintln!(
+        "{} {} queries, {} ok{not_found}{unchanged}, {} failed{rejected}, {} filtered, \
+         {} skipped in {secs:.2}s ({} downloaded)",
+        "Summary:".cyan().bold(),
+        r.total,
+        r.ok,
+        r.failed,
+        r.filtered,
+        r.skipped,
+        bandwidth::human(r.bytes),
+    );
+    if !r.slowest.is_empty() {
+        eprintln!("{}", "Slowest:".cyan().bold());
+        for t in &r.slowest {
+            eprintln!("  {:>8}ms  {}  ({})", t.elapsed_ms, t.query, t.host);
+        }
+    }
+    if !r.registries.is_empty() {
+        eprintln!("{}", "Registries:".cyan().bold());
+        for h in &r.registries {
+            eprintln!(
+                "  {}: {} queries, avg {}ms, max {}ms, {}",
+                h.host,
+                h.queries,
+                h.avg_ms,
+                h.max_ms,
+                bandwidth::human(h.bytes)
+            );
+        }
+    }
+    if !r.rate_limits.is_empty() {
+        eprintln!("{}", "Rate limits:".cyan().bold());
+        for (host, q) in &r.rate_limits {
+            let limit = q.limit.map_or_else(String::new, |l| format!("/{l}"));
+            eprintln!(
+                "  {host}: {}{limit} left, resets in {}s, paced {}ms",
+                q.remaining, q.reset_in_secs, q.paced_ms
+            );
+        }
+    }
+    if let Some(reason) = &r.aborted {
+        eprintln!("{} {reason}", "Aborted:".red().bold());
+    }
+}
+
+/// Insert `key` into the record's `_meta` object, creating it if needed.
+fn attach_meta(json: &mut Value, key: &str, value: Value) {
+    attach_under(json, "_meta", key, value);
+}
+
+/// Insert `key` into the top-level `section` object of a record.
+fn attach_under(json: &mut Value, section: &str, key: &str, value: Value) {
+    if let Some(obj) = json.as_object_mut() {
+        let sec = obj
+            .entry(section)
+            .or_insert_with(|| Value::Object(serde_json::Map::new()));
+        if let Some(sec) = sec.as_object_mut() {
+            sec.insert(key.to_string(), value);
+        }
+    }
+}
+
+/* ------------------------------ IO utils -------------------------------- */
+
+/// Create (or truncate) an output file; the error names it.
+fn create_file(path: &Path) -> Result<fs::File, String> {
+    fs::File::create(path).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn read_lines(path: &Path) -> io::Result<Vec<String>> {
+    let mut buf = String::new();
+    fs::File::open(path)?.read_to_string(&mut buf)?;
+    // lines() also drops the \r of CRLF files; strip a leading UTF-8 BOM too
+    Ok(buf
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect())
+}
+
+/// The lines belonging to `shard` (all of them without `--shard`), keyed on
+/// the query so tagged JSON lines land with their plain equivalents.
+fn shard_lines(lines: Vec<String>, shard: Option<shard::Shard>) -> Vec<String> {
+    let Some(shard) = shard else {
+        return lines;
+    };
+    lines
+        .into_iter()
+        .filter(|line| {
+            parse_bulk_line(line).map_or_else(|_| shard.owns(line), |(q, _)| shard.owns(&q))
+        })
+        .collect()
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    let t = s.trim();
+    let rate = t
+        .strip_suffix('%')
+        .map_or_else(
+            || t.parse::<f64>(),
+            |pct| pct.trim().parse::<f64>().map(|p| p / 100.0),
+        )
+        .map_err(|_| format!("invalid rate '{s}' (expected e.g. 5% or 0.05)"))?;
+    if (0.0..=1.0).contains(&rate) {
+        Ok(rate)
+    } else {
+        Err(format!("rate '{s}' must be between 0% and 100%"))
+    }
+}
+
+/// Parse a `--tag KEY=VALUE` pair.
+fn parse_tag(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((k, v)) if !k.trim().is_empty() => Ok((k.trim().to_string(), v.trim().to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{s}'")),
+    }
+}
+
+/// Merge `--tag` pairs and per-query tags (which win) into `_rdapx.tags`.
+fn attach_tags(json: &mut Value, global: &[(String, String)], per_query: &Tags) {
+    if global.is_empty() && per_query.is_empty() {
+        return;
+    }
+    let mut tags: serde_json::Map<String, Value> = global
+        .iter()
+        .map(|(k, v)| (k.clone(), Value::from(v.as_str())))
+        .collect();
+    tags.extend(
+        per_query
+            .iter()
+            .map(|(k, v)| (k.clone(), Value::from(v.as_str()))),
+    );
+    attach_under(json, "_rdapx", "tags", Value::Object(tags));
+}
+
+type Tags = BTreeMap<String, String>;
+
+/// Queries from a bulk-style input file, skipping blank and `#` lines.
+fn read_targets(path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let targets = read_lines(path)?
+        .iter()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| parse_bulk_line(l).map(|(q, _)| q))
+        .collect::<Result<_, _>>()?;
+    Ok(targets)
+}
+
+/// A bulk input line: a bare query, or an NDJSON object such as
+/// `{"query": "example.com", "tags": {"case": "IR-1234"}}`.
+fn parse_bulk_line(line: &str) -> Result<(String, Tags), String> {
+    if !line.starts_with('{') {
+        return Ok((line.to_string(), Tags::new()));
+    }
+    let v: Value = serde_json::from_str(line).map_err(|e| format!("bad input line {line}: {e}"))?;
+    let query = v
+        .get("query")
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("input line has no \"query\": {line}"))?;
+    let tags = v
+        .get("tags")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .map(|(k, v)| {
+            (
+                k.clone(),
+                v.as_str().map_or_else(|| v.to_string(), str::to_string),
+            )
+        })
+        .collect();
+    Ok((query.to_string(), tags))
+}
+
+/// An `http(s)://` RDAP base URL.
+fn parse_base_url(s: &str) -> Result<String, String> {
+    match reqwest::Url::parse(s) {
+        Ok(u) if matches!(u.scheme(), "https" | "http") && u.host().is_some() => Ok(s.to_string()),
+        Ok(_) => Err("expected an http:// or https:// URL".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parse durations like `500ms`, `2s`, `5m`, `12h`, `30d`, or `1w` (bare
+/// numbers are seconds).
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let t = s.trim();
+    let split = t.find(|c: char| !c.is_ascii_digit()).unwrap_or(t.len());
+    let (num, unit) = t.split_at(split);
+    let n: u64 = num
+        .parse()
+        .map_err(|_| format!("invalid duration '{s}' (expected e.g. 500ms, 2s, 12h, 30d)"))?;
+    let secs = |mult: u64| Duration::from_secs(n.saturating_mul(mult));
+    match unit.trim() {
+        "ms" => Ok(Duration::from_millis(n)),
+        "" | "s" => Ok(secs(1)),
+        "m" => Ok(secs(60)),
+        "h" => Ok(secs(3600)),
+        "d" => Ok(secs(86_400)),
+        "w" => Ok(secs(604_800)),
+        other => Err(format!("unknown duration unit '{other}' in '{s}'")),
+    }
+}
+
+/// A `parse_duration` that must be non-zero.
+fn parse_progress_interval(s: &str) -> Result<Duration, String> {
+    match parse_duration(s)? {
+        d if d.is_zero() => Err("the progress interval must be above zero".to_string()),
+        d => Ok(d),
+    }
+}
+
+/* ------------------------------ Fetch ----------------------------------- */
+
+/// Take one retry from the shared budget; `None` means unlimited.
+fn take_retry(budget: Option<&AtomicUsize>) -> bool {
+    budget.is_none_or(|b| {
+        b.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok()
+    })
+}
+
+/// Per-run fetch settings shared by `get` and `bulk`.
+#[derive(Clone, Copy, Debug)]
+#[allow(clippy::struct_excessive_bools)] // independent CLI switches
+struct FetchOpts<'a> {
+    ttl: Duration,
+    timeout: Duration,
+    no_cache: bool,
+    /// Skip the cache read but still store the fresh response
+    refresh: bool,
+    /// Append fresh responses to the snapshot history
+    history: bool,
+    retries: usize,
+    retry_delay_ms: u64,
+    retry_budget: Option<&'a AtomicUsize>,
+    source: Source,
+    registrar_retries: usize,
+    registrar_timeout: Duration,
+    /// Race all candidate servers (`--fan-out`)
+    fan_out: bool,
+    /// Send the stored entry's validators and reuse it on 304 Not Modified
+    revalidate: bool,
+    /// RDAP base URL that replaces routing (`get --server`)
+    server: Option<&'a str>,
+}
+
+impl FetchOpts<'_> {
+    const fn from_cli(cli: &Cli) -> Self {
+        Self {
+            ttl: Duration::from_secs(cli.cache_ttl),
+            timeout: Duration::from_secs(cli.timeout),
+            no_cache: cli.no_cache,
+            refresh: false,
+            history: !cli.no_history,
+            retries: cli.retries,
+            retry_delay_ms: cli.retry_delay_ms,
+            retry_budget: None,
+            source: match cli.follow_registrar {
+                Some(FollowRegistrar::Merge) => Source::Merged,
+                Some(FollowRegistrar::Both) => Source::Both,
+                None => cli.source,
+            },
+            registrar_retries: cli.registrar_retries,
+            registrar_timeout: Duration::from_secs(cli.registrar_timeout),
+            fan_out: cli.fan_out,
+            revalidate: false,
+            server: None,
+        }
+    }
+}
+
+fn query_url(q: &str) -> String {
+    bootstrap::resolve(q).url
+}
+
+fn url_host(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Open a pooled connection to every distinct origin in `urls` (DNS, TCP, and
+/// TLS) with a `HEAD /`; any response at all leaves a warm connection behind.
+async fn prewarm_hosts(client: &reqwest::Client, urls: impl Iterator<Item = String>, conc: usize) {
+    let origins: BTreeSet<String> = urls
+        .filter_map(|u| reqwest::Url::parse(&u).ok())
+        .map(|u| u.origin().ascii_serialization())
+        .collect();
+    let total = origins.len();
+    let warmed = stream::iter(origins)
+        .map(|origin| async move { client.head(&origin).send().await.is_ok() })
+        .buffer_unordered(conc)
+        .filter(|ok| futures::future::ready(*ok))
+        .count()
+        .await;
+    eprintln!(
+        "{} prewarmed {warmed}/{total} registry hosts",
+        "Note:".yellow().bold()
+    );
+}
+
+/// The one search parameter clap let through (the flags share an arg group).
+fn pick_search_param<const N: usize>(
+    params: [(&'static str, &Option<String>); N],
+) -> (&'static str, Vec<String>) {
+    params
+        .into_iter()
+        .find_map(|(p, v)| v.clone().map(|v| (p, vec![v])))
+        .unwrap_or_default()
+}
+
+/// Run searches from `urls`, streaming every result (NDJSON for JSON formats).
+async fn search_and_emit(
+    cli: &Cli,
+    urls: Vec<reqwest::Url>,
+    paging: &search::PagingArgs,
+) -> Result<(), Box<dyn Error>> {
+    let client = http_client(cli.timeout)?;
+    let fmt = match cli.format {
+        Format::Json | Format::Pretty => Format::Json,
+        other => other,
+    };
+    output_header(fmt);
+    let n = search::run(
+        &client,
+        urls,
+        paging,
+        &FetchOpts::from_cli(cli),
+        |mut item| {
+            attach_tags(&mut item, &cli.tags, &Tags::new());
+            emit_record(item, fmt, cli.output_schema);
+        },
+    )
+    .await?;
+    eprintln!("{} {n} results", "Done:".cyan().bold());
+    Ok(())
+}
+
+/// `get` without a query: stream queries from piped stdin as they arrive and
+/// emit records like `bulk` (unordered).
+async fn get_stdin(cli: &Cli, concurrency: usize) -> Result<(), Box<dyn Error>> {
+    use tokio::io::AsyncBufReadExt;
+
+    let client = http_client(cli.timeout)?;
+    let registrars = load_registrar_ids(&client, cli.enrich_registrar).await?;
+    let mut opts = FetchOpts::from_cli(cli);
+    if let Command::Get { server, .. } = &cli.command {
+        opts.server = server.as_deref();
+    }
+    let fmt = match cli.format {
+        Format::Json | Format::Pretty => Format::Json,
+        other => other,
+    };
+    let lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    let queries = stream::unfold(lines, |mut lines| async move {
+        let line = lines.next_line().await.ok().flatten()?;
+        Some((line, lines))
+    })
+    .filter_map(|line| async move {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        parse_bulk_line(line)
+            .map_err(|e| eprintln!("{} {e}", "Skipped".yellow().bold()))
+            .ok()
+    });
+
+    if cli.short.is_none() {
+        output_header(fmt);
+    }
+    let results = queries
+        .map(|(q, tags)| {
+            let client = &client;
+            let opts = &opts;
+            async move {
+                let res = fetch_for_query(client, &q, opts).await;
+                (q, tags, res)
+            }
+        })
+        .buffer_unordered(deterministic::concurrency(concurrency.max(1)));
+    let mut results = std::pin::pin!(results);
+
+    let (mut ok, mut failed) = (0usize, 0usize);
+    while let Some((q, tags, res)) = results.next().await {
+        match res {
+            Ok(mut json) => {
+                ok += 1;
+                if let Some(ids) = &registrars {
+                    dataset::enrich_registrar(&mut json, ids);
+                }
+                attach_tags(&mut json, &cli.tags, &tags);
+                if let Some(field) = cli.short {
+                    println!("{q}\t{}", field.extract(&json).unwrap_or_default());
+                    sink::write(&json_record(json, cli.output_schema));
+                } else {
+                    emit_record(json, fmt, cli.output_schema);
+                }
+            }
+            Err(e) if e.is::<strict::Violation>() => return Err(e),
+            Err(e) if cli.emit_not_found && is_not_found(e.as_ref()) => {
+                ok += 1;
+                emit_not_found(&q, &tags, cli, fmt);
+            }
+            Err(e) if cli.error_format == ErrorFormat::Json => {
+                failed += 1;
+                println!("{}", error_json(&q, e.as_ref()));
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("{} {q}: {e}", "Failed".red().bold());
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{failed} of {} queries failed", ok + failed).into());
+    }
+    Ok(())
+}
+
+async fn fetch_for_query(
+    client: &reqwest::Client,
+    q: &str,
+    opts: &FetchOpts<'_>,
+) -> Result<Value, Box<dyn Error>> {
+    let lookalike = labels::check(q)?;
+    if let (Kind::Domain, registrable) = normalize(q) {
+        let name = q.trim().trim_end_matches('.');
+        if !registrable.eq_ignore_ascii_case(name) {
+            strict::check(strict::Condition::Rewritten, || {
+                format!("{name} would be looked up as {registrable}")
+            })?;
+            eprintln!(
+                "{} {name}: looking up its registrable domain {registrable}",
+                "Note:".yellow().bold()
+            );
+        }
+    }
+    let mut json = fetch_with_fallback(client, q, opts).await?;
+    labels::annotate(&mut json, &lookalike);
+    Ok(json)
+}
+
+async fn fetch_with_fallback(
+    client: &reqwest::Client,
+    q: &str,
+    opts: &FetchOpts<'_>,
+) -> Result<Value, Box<dyn Error>> {
+    // converted inside its own scope so the future stays Send for `serve`
+    let e = {
+        let e = match fetch_rdap(client, q, opts).await {
+            Ok(json) => return Ok(json),
+            Err(e) => e,
+        };
+        if !fallback::enabled()
+            || !matches!(normalize(q).0, Kind::Domain)
+            || is_not_found(e.as_ref())
+            || e.is::<strict::Violation>()
+        {
+            return Err(e);
+        }
+        sendable(e)
+    };
+    fallback::fetch(q.trim(), e)
+        .await
+        .map_err(|e| e as Box<dyn Error>)
+}
+
+async fn fetch_rdap(
+    client: &reqwest::Client,
+    q: &str,
+    opts: &FetchOpts<'_>,
+) -> Result<Value, Box<dyn Error>> {
+    let (kind, normalized) = normalize(q);
+    if let Some(base) = opts.server {
+        let url = rdap_url(base, kind, &normalized);
+        let json = fetch_url(client, &url, q, opts).await?;
+        return Ok(fanout::answered_by(json, &url));
+    }
+    bootstrap::ensure(client).await?;
+    if opts.fan_out {
+        return fanout::fetch(client, q, opts).await;
+    }
+    if matches!(kind, Kind::Ip | Kind::Asn) {
+        return fanout::across_rirs(client, &query_url(q), q, opts).await;
+    }
+    if matches!(opts.source, Source::Registry) || !matches!(kind, Kind::Domain) {
+        return fanout::with_alternates(client, &query_url(q), q, opts).await;
+    }
+    referral::fetch(client, &query_url(q), q, opts).await
+}
+
+/// Fetch an RDAP URL with caching and retries; `q` is recorded with the cache entry.
+async fn fetch_url(
+    client: &reqwest::Client,
+    url: &str,
+    q: &str,
+    opts: &FetchOpts<'_>,
+) -> Result<Value, Box<dyn Error>> {
+    let url = url.to_string();
+
+    if let Some(hit) = cache_hit(&url, opts) {
+        return hit;
+    }
+
+    let stored = opts.revalidate.then(|| revalidatable(&url)).flatten();
+    breaker::check(&url)?;
+
+    // retry loop; a timed-out attempt doubles the next attempt's timeout (up to 4x)
+    let mut per_try = opts.timeout;
+    let mut last_err: Option<reqwest::Error> = None;
+    let mut attempts = 0;
+    for attempt in 0..=opts.retries {
+        attempts = attempt + 1;
+        let req = conditional(client.get(&url).timeout(per_try), stored.as_ref());
+        match send_paced(req, &url).await {
+            Ok(resp) if resp.status() == reqwest::StatusCode::NOT_MODIFIED && stored.is_some() => {
+                let (status, headers) = (resp.status(), resp.headers().clone());
+                archive::record(&url, status, &headers, &[]);
+                let mut entry = stored.unwrap();
+                entry.fetched_at = deterministic::wall_secs();
+                let _ = save_cache(&entry);
+                return Ok(entry.data);
+            }
+            Ok(resp) if resp.status().is_success() => {
+                let (status, headers) = (resp.status(), resp.headers().clone());
+                let body = body::json(resp)
+                    .await
+                    .map_err(|e| -> Box<dyn Error> { e })?;
+                bandwidth::record(&url, body.len);
+                if let Some(raw) = &body.raw {
+                    archive::record(&url, status, &headers, raw);
+                }
+                let v = body.json?;
+                // a body parsed with --fields applied is not the whole response
+                if !opts.no_cache && !body.projected {
+                    let entry = CacheEntry {
+                        query: q.to_string(),
+                        url: url.clone(),
+                        fetched_at: deterministic::wall_secs(),
+                        hash: history::content_hash(&v),
+                        data: v,
+                        not_found: false,
+                        etag: header_string(&headers, reqwest::header::ETAG),
+                        last_modified: header_string(&headers, reqwest::header::LAST_MODIFIED),
+                    };
+                    let _ = save_cache(&entry);
+                    if opts.history {
+                        let _ = history::append(&entry);
+                    }
+                    return Ok(entry.data);
+                }
+                return Ok(v);
+            }
+            Ok(resp) => {
+                let (code, headers) = (resp.status(), resp.headers().clone());
+                let body = resp.bytes().await.unwrap_or_default();
+                bandwidth::record(&url, body.len());
+                archive::record(&url, code, &headers, &body);
+                if code == reqwest::StatusCode::NOT_FOUND
+                    && !opts.no_cache
+                    && config::get().cache.ttl.notfound.is_some()
+                {
+                    let _ = save_cache(&CacheEntry {
+                        query: q.to_string(),
+                        url: url.clone(),
+                        fetched_at: deterministic::wall_secs(),
+                        hash: String::new(),
+                        data: Value::Null,
+                        not_found: true,
+                        etag: None,
+                        last_modified: None,
+                    });
+                }
+                return Err(Box::new(HttpStatusError {
+                    url,
+                    status: code,
+                    body: String::from_utf8_lossy(&body).into_owned(),
+                    attempts,
+                }));
+            }
+            Err(e) => {
+                if e.is_timeout() {
+                    per_try = (per_try * 2).min(opts.timeout * 4);
+                }
+                last_err = Some(e);
+                if attempt == opts.retries || !take_retry(opts.retry_budget) {
+                    break;
+                }
+                sleep(Duration::from_millis(opts.retry_delay_ms)).await;
+            }
+        }
+    }
+
+    Err(Box::new(NetworkError {
+        url,
+        attempts,
+        source: last_err.unwrap(),
+    }))
+}
+
+/// Send one attempt after any rate-limit pacing, then record the outcome for
+/// the circuit breaker, rate-limit tracking, and `--trace`.
+async fn send_paced(req: reqwest::RequestBuilder, url: &str) -> reqwest::Result<reqwest::Response> {
+    ratelimit::pace(url).await;
+    let t0 = Instant::now();
+    let sent = req.send().await;
+    let response = sent.as_ref().ok().map(|r| (r.status(), r.headers()));
+    breaker::record(url, response);
+    if let Some((_, headers)) = response {
+        ratelimit::observe(url, headers);
+    }
+    ratelimit::trace(url, response.map(|(s, _)| s), t0.elapsed());
+    sent
+}
+
+/// Fresh cache answer for `url`: the stored object or a cached 404.
+fn cache_hit(url: &str, opts: &FetchOpts<'_>) -> Option<Result<Value, Box<dyn Error>>> {
+    if opts.no_cache || opts.refresh {
+        return None;
+    }
+    if let Ok(Some(v)) = load_cache(url, cache_ttl(url, opts.ttl)) {
+        return Some(Ok(v));
+    }
+    cached_not_found(url).then(|| {
+        Err(Box::new(HttpStatusError {
+            url: url.to_string(),
+            status: reqwest::StatusCode::NOT_FOUND,
+            body: "not found (cached)".to_string(),
+            attempts: 0,
+        }) as Box<dyn Error>)
+    })
+}
+
+/// Cache entry for `url` (any age) that carries validators to revalidate with.
+fn revalidatable(url: &str) -> Option<CacheEntry> {
+    read_cache_entry(url)
+        .filter(|e| !e.not_found && (e.etag.is_some() || e.last_modified.is_some()))
+}
+
+/// Add `If-None-Match`/`If-Modified-Since` from a stored entry's validators.
+fn conditional(
+    mut req: reqwest::RequestBuilder,
+    stored: Option<&CacheEntry>,
+) -> reqwest::RequestBuilder {
+    if let Some(e) = stored {
+        if let Some(etag) = &e.etag {
+            req = req.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(lm) = &e.last_modified {
+            req = req.header(reqwest::header::IF_MODIFIED_SINCE, lm);
+        }
+    }
+    req
+}
+
+fn header_string(
+    headers: &reqwest::header::HeaderMap,
+    name: reqwest::header::HeaderName,
+) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Request that never got a response after all retries.
+#[derive(Debug)]
+struct NetworkError {
+    url: String,
+    attempts: usize,
+    source: reqwest::Error,
+}
+
+impl std::fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "network error for {}: {}", self.url, self.source)
+    }
+}
+
+impl Error for NetworkError {}
+
+/// Structured `--error-format json` (and bulk `--json-errors`) document for
+/// a failed query. `server` and `url` name the server that failed when the
+/// error knows it (fallbacks may have moved on from the routed one).
+fn error_json(query: &str, e: &(dyn Error + 'static)) -> Value {
+    let url = if query.starts_with("https://") || query.starts_with("http://") {
+        query.to_string() // `get --url`
+    } else {
+        query_url(query)
+    };
+    let mut err = serde_json::json!({
+        "kind": "other",
+        "query": query,
+        "server": url_host(&url),
+        "url": url,
+        "message": e.to_string(),
+    });
+    if let Some(h) = e.downcast_ref::<HttpStatusError>() {
+        err["kind"] = "http".into();
+        err["url"] = h.url.as_str().into();
+        err["server"] = url_host(&h.url).into();
+        err["status"] = h.status.as_u16().into();
+        err["attempts"] = h.attempts.into();
+    } else if let Some(n) = e.downcast_ref::<NetworkError>() {
+        err["kind"] = if n.source.is_timeout() {
+            "timeout"
+        } else {
+            "network"
+        }
+        .into();
+        err["url"] = n.url.as_str().into();
+        err["server"] = url_host(&n.url).into();
+        err["attempts"] = n.attempts.into();
+    } else if let Some(o) = e.downcast_ref::<breaker::Open>() {
+        err["server"] = o.host.as_str().into();
+    } else if e.downcast_ref::<serde_json::Error>().is_some() {
+        err["kind"] = "parse".into();
+    }
+    serde_json::json!({ "error": err })
+}
+
+/// Non-2xx response from a registry.
+#[derive(Debug)]
+struct HttpStatusError {
+    /// URL that answered
+    url: String,
+    status: reqwest::StatusCode,
+    body: String,
+    /// Requests sent (0 for a cached not-found)
+    attempts: usize,
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {}: {}", self.status, self.body)
+    }
+}
+
+impl Error for HttpStatusError {}
+
+/// Exit with `code` once the run has cleaned up (sinks finished); the
+/// command has already said why.
+#[derive(Debug)]
+struct Exit(u8);
+
+impl std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "exit status {}", self.0)
+    }
+}
+
+impl Error for Exit {}
+
+/// Move a fetch error into a `Send` box so it can be held across an await,
+/// keeping the concrete types `error_json` inspects.
+fn sendable(e: Box<dyn Error>) -> Box<dyn Error + Send + Sync> {
+    let e = match e.downcast::<NetworkError>() {
+        Ok(n) => return n,
+        Err(e) => e,
+    };
+    let e = match e.downcast::<HttpStatusError>() {
+        Ok(h) => return h,
+        Err(e) => e,
+    };
+    let e = match e.downcast::<breaker::Open>() {
+        Ok(o) => return o,
+        Err(e) => e,
+    };
+    match e.downcast::<strict::Violation>() {
+        Ok(v) => v,
+        Err(e) => e.to_string().into(),
+    }
+}
+
+fn is_not_found(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<HttpStatusError>()
+        .is_some_and(|e| e.status == reqwest::StatusCode::NOT_FOUND)
+}
+
+/// The server itself failed (unreachable, timed out, 5xx, or skipped while
+/// its circuit breaker is open), as opposed to giving an answer.
+fn is_server_failure(e: &(dyn Error + 'static)) -> bool {
+    e.is::<NetworkError>()
+        || e.is::<breaker::Open>()
+        || e.downcast_ref::<HttpStatusError>()
+            .is_some_and(|e| e.status.is_server_error())
+}
+
+/// Write a record that isn't an RDAP object (no schema envelope) to stdout and sinks.
+fn emit_raw(json: &Value, fmt: Format) {
+    sink::write(json);
+    if sink::to_stdout() {
+        output(
+            json,
+            if matches!(fmt, Format::Pretty) {
+                Format::Json
+            } else {
+                fmt
+            },
+        );
+    }
+}
+
+/// Version and seal an rdapx record that isn't an RDAP object (a not-found
+/// marker, a dedupe blob), so it has no place in the v2 envelope.
+fn seal_plain(mut json: Value, schema: OutputSchema) -> Value {
+    attach_meta(&mut json, "schema_version", Value::from(schema.version()));
+    integrity::seal(&mut json);
+    json
+}
+
+/// `--emit-not-found`: a 404 as a first-class record instead of an error,
+/// tagged and sealed like the records around it.
+fn emit_not_found(query: &str, tags: &Tags, cli: &Cli, fmt: Format) {
+    let mut json = serde_json::json!({ "query": query, "found": false, "status": 404 });
+    attach_tags(&mut json, &cli.tags, tags);
+    let json = seal_plain(json, cli.output_schema);
+    match fmt {
+        Format::Csv | Format::Table => {
+            sink::write(&json);
+            if !sink::to_stdout() {
+                return;
+            }
+            let row = model::Row {
+                kind: "not found".into(),
+                handle: query.to_string(),
+                status: "404".into(),
+                ..model::Row::default()
+            };
+            if matches!(fmt, Format::Csv) {
+                println!("{}", row.to_csv());
+            } else {
+                println!("{} {query}", "Not found:".yellow().bold());
+            }
+        }
+        _ => emit_raw(&json, fmt),
+    }
+}
+
+/// Load the registrar dataset when enrichment is on; failures only warn,
+/// except a `--strict` violation.
+async fn load_registrar_ids(
+    client: &reqwest::Client,
+    enabled: bool,
+) -> Result<Option<dataset::RegistrarIds>, Box<dyn Error>> {
+    if !enabled {
+        return Ok(None);
+    }
+    match dataset::registrar_ids(client).await {
+        Ok(ids) => Ok(Some(ids)),
+        Err(e) if e.is::<strict::Violation>() => Err(e),
+        Err(e) => {
+            eprintln!(
+                "{} registrar enrichment disabled: {e}",
+                "Warning:".yellow().bold()
+            );
+            Ok(None)
+        }
+    }
+}
+
+const DELEGATION_KEYS: [&str; 6] = ["query", "rir", "country", "allocated", "status", "range"];
+
+/* --------------------------------- MAIN ---------------------------------- */
+
+/// Whether a bare word looks like a query rather than a subcommand: it has
+/// a dot or colon (a domain or IP) or is an AS number.
+fn looks_like_query(word: &str) -> bool {
+    word.contains(['.', ':'])
+        || word
+            .get(..2)
+            .filter(|p| p.eq_ignore_ascii_case("as"))
+            .is_some_and(|_| word.len() > 2 && word[2..].bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Parse the command line, treating an unknown subcommand that looks like a
+/// query as one (`rdapx example.com` runs `rdapx get example.com`).
+fn parse_cli() -> Cli {
+    let args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    match Cli::try_parse_from(&args) {
+        Ok(cli) => cli,
+        // `audit.com` is a query even though clap suggests `audit`; "chache"
+        // is a typo, not a query
+        Err(e) if e.kind() == clap::error::ErrorKind::InvalidSubcommand => {
+            let bare = match e.get(clap::error::ContextKind::InvalidSubcommand) {
+                Some(clap::error::ContextValue::String(q)) if looks_like_query(q) => q.clone(),
+                _ => e.exit(),
+            };
+            let Some(at) = args.iter().skip(1).position(|a| *a == *bare) else {
+                e.exit()
+            };
+            let mut args = args;
+            args.insert(at + 1, "get".into());
+            Cli::try_parse_from(args).unwrap_or_else(|_| e.exit())
+        }
+        Err(e) => e.exit(),
+    }
+}
+
+fn main() -> std::process::ExitCode {
+    // setup (and `--sandbox`) runs before the runtime starts its threads:
+    // Landlock only confines the calling thread and the ones it spawns later
+    let result = setup(parse_cli()).and_then(|ready| {
+        let Some(Ready { cli, sinks }) = ready else {
+            return Ok(());
+        };
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?
+            .block_on(run(cli, sinks))
+    });
+    match result {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            if let Some(Exit(code)) = e.downcast_ref::<Exit>() {
+                return (*code).into();
+            }
+            eprintln!("{} {e}", "Error:".red().bold());
+            e.downcast_ref::<strict::Violation>()
+                .map_or(std::process::ExitCode::FAILURE, |v| {
+                    v.condition.exit_code().into()
+                })
+        }
+    }
+}
+
+/// A parsed command line with global state in place, ready to run.
+struct Ready {
+    cli: Cli,
+    /// Finished when dropped, which has to happen inside the runtime
+    sinks: Option<sink::Finish>,
+}
+
+/// Everything before the first await: shell completions (which end the run
+/// here), global state, the output sinks, and the sandbox.
+fn setup(cli: Cli) -> Result<Option<Ready>, Box<dyn Error>> {
+    // Completions (only for `get --completions <shell>`)
+    if let Command::Get {
+        completions: Some(sh),
+        ..
+    } = &cli.command
+    {
+        // the generators split command paths on `__`, so rename the hidden hook
+        let mut cmd = Cli::command().mut_subcommand("__complete", |c| c.name("complete"));
+        match sh {
+            Shell::Bash => generate(Bash, &mut cmd, "rdapx", &mut io::stdout()),
+            Shell::Zsh => generate(Zsh, &mut cmd, "rdapx", &mut io::stdout()),
+            Shell::Fish => generate(Fish, &mut cmd, "rdapx", &mut io::stdout()),
+            Shell::Powershell => generate(PowerShell, &mut cmd, "rdapx", &mut io::stdout()),
+        }
+        print!("{}", complete::hook(*sh));
+        return Ok(None);
+    }
+
+    if matches!(cli.format, Format::Ical) && !matches!(cli.command, Command::Expiry { .. }) {
+        return Err("--format ical is only for `rdapx expiry`".into());
+    }
+    config::init()?;
+    bootstrap::init()?;
+    deterministic::init(cli.deterministic)?;
+    strict::init(cli.strict);
+    if let Some(lang) = &cli.lang {
+        i18n::init(lang);
+    }
+    notices::init(cli.full_notices);
+    labels::init(cli.homoglyphs);
+    // before the sinks, which write the CSV header
+    preset::init(cli.preset);
+    let sinks = if cli.outputs.is_empty() {
+        None
+    } else {
+        Some(sink::init(&cli.outputs)?)
+    };
+    projection::init(&cli.fields, &cli.roles);
+    ratelimit::init(cli.trace);
+    fallback::init(&cli.fallback);
+    if let Some(path) = &cli.archive {
+        archive::init(path)?;
+    }
+    if cli.sign_output || cli.sign_key.is_some() {
+        integrity::init(cli.sign_key.as_deref())?;
+    }
+    crypt::init(cli.cache_key.as_deref())?;
+    if let Some(name) = &cli.auth {
+        auth::init(name)?;
+    }
+    if cli.sandbox {
+        sandbox::apply(&cli.command.output_files())?;
+    }
+    Ok(Some(Ready { cli, sinks }))
+}
+
+#[allow(clippy::too_many_lines, clippy::future_not_send)]
+async fn run(mut cli: Cli, _sinks: Option<sink::Finish>) -> Result<(), Box<dyn Error>> {
+    // Windows consoles only render ANSI colors with virtual terminal processing on
+    #[cfg(windows)]
+    let _ = colored::control::set_virtual_terminal(true);
+
+    // Auto-disable color if piped
+    if !io::stdout().is_terminal() {
+        cli.no_color = true;
+        colored::control::set_override(false);
+    } else if cli.no_color {
+        colored::control::set_override(false);
     }
 
-    // retry loop
-    let mut last_err: Option<reqwest::Error> = None;
-    for attempt in 0..=retries {
-        match client.get(&url).send().await {
-            Ok(resp) if resp.status().is_success() => {
-                let v: Value = resp.json().await?;
-                if !no_cache {
-                    let _ = save_cache(&url, &v);
+    match &cli.command {
+        Command::Get {
+            query,
+            parents,
+            asn_context,
+            neighbors,
+            server,
+            url,
+            concurrency,
+            ..
+        } => {
+            let Some(query) = query.as_ref().or(url.as_ref()) else {
+                if io::stdin().is_terminal() {
+                    return Err("missing query (or pipe queries on stdin)".into());
+                }
+                if *parents || *asn_context {
+                    return Err("--parents and --asn-context need a query argument".into());
+                }
+                get_stdin(&cli, *concurrency).await?;
+                return Ok(());
+            };
+            let client = http_client(cli.timeout)?;
+            let registrars = load_registrar_ids(&client, cli.enrich_registrar).await?;
+            let opts = FetchOpts {
+                server: server.as_deref(),
+                ..FetchOpts::from_cli(&cli)
+            };
+            let fetched = match url {
+                Some(url) => fetch_url(&client, url, url, &opts).await,
+                None => fetch_for_query(&client, query, &opts).await,
+            };
+            let mut json = match fetched {
+                Err(e) if cli.emit_not_found && is_not_found(e.as_ref()) => {
+                    output_header(cli.format);
+                    emit_not_found(query, &Tags::new(), &cli, cli.format);
+                    return Ok(());
+                }
+                Err(e) if cli.error_format == ErrorFormat::Json => {
+                    println!("{}", error_json(query, e.as_ref()));
+                    return Err(Box::new(Exit(1)));
+                }
+                res => res?,
+            };
+            if let Some(ids) = &registrars {
+                dataset::enrich_registrar(&mut json, ids);
+            }
+            let mut tree = None;
+            if *parents {
+                if matches!(normalize(query).0, Kind::Ip) {
+                    let chain = network::parents(&client, &query_url(query), &json, &opts).await;
+                    attach_under(
+                        &mut json,
+                        "_enrichment",
+                        "parents",
+                        serde_json::to_value(&chain)?,
+                    );
+                    tree = Some(chain);
+                } else {
+                    eprintln!(
+                        "{} --parents only applies to IP queries",
+                        "Note:".yellow().bold()
+                    );
+                }
+            }
+            let mut asn_ctx = None;
+            if *asn_context {
+                let (kind, norm) = normalize(query);
+                if let (Kind::Asn, Ok(asn)) = (kind, norm.parse::<u32>()) {
+                    let ctx =
+                        autnum::context(&client, &query_url(query), &json, asn, *neighbors, &opts)
+                            .await;
+                    attach_under(
+                        &mut json,
+                        "_enrichment",
+                        "autnum_context",
+                        serde_json::to_value(&ctx)?,
+                    );
+                    asn_ctx = Some(ctx);
+                } else {
+                    eprintln!(
+                        "{} --asn-context only applies to ASN queries",
+                        "Note:".yellow().bold()
+                    );
+                }
+            }
+            if let Some(field) = cli.short {
+                let value = field
+                    .extract(&json)
+                    .ok_or_else(|| format!("{query}: no value for --short"))?;
+                println!("{value}");
+                return Ok(());
+            }
+            attach_tags(&mut json, &cli.tags, &Tags::new());
+            output_header(cli.format);
+            if let (Some(chain), Format::Table) = (&tree, cli.format) {
+                println!("{}", "Allocation hierarchy:".yellow().bold());
+                network::print_tree(&model::NetworkSummary::from_rdap(&json), chain);
+                println!();
+            }
+            if let (Some(ctx), Format::Table) = (&asn_ctx, cli.format) {
+                autnum::print_context(ctx);
+                println!();
+            }
+            emit_record(json, cli.format, cli.output_schema);
+        }
+
+        Command::Entity { handle } => {
+            let handle = handle.trim();
+            if handle.is_empty()
+                || handle.contains(['/', '?', '#'])
+                || handle.contains(char::is_whitespace)
+            {
+                return Err(format!("'{handle}' is not an entity handle").into());
+            }
+            let client = http_client(cli.timeout)?;
+            bootstrap::ensure(&client).await?;
+            // routed as an entity even without a known tag, which `get` won't guess
+            let url = classify_to_url(Kind::Entity, handle);
+            let opts = FetchOpts::from_cli(&cli);
+            let mut json = fanout::with_alternates(&client, &url, handle, &opts).await?;
+            attach_tags(&mut json, &cli.tags, &Tags::new());
+            output_header(cli.format);
+            emit_record(json, cli.format, cli.output_schema);
+        }
+
+        Command::Bulk {
+            file,
+            concurrency,
+            ndjson,
+            max_failures,
+            max_failure_rate,
+            retry_budget,
+            slow_threshold,
+            slowest,
+            prefilter,
+            summary_file,
+            dedupe_output,
+            max_bytes,
+            prewarm,
+            incremental,
+            manifest,
+            plan,
+            rejects,
+            shard,
+            progress_format,
+            progress_file,
+            progress_interval,
+            json_errors,
+        } => {
+            if *plan {
+                let db = match prefilter {
+                    Some(pred) if pred.needs_delegations() => Some(dataset::Delegations::load()?),
+                    _ => None,
+                };
+                let keep = |q: &str| {
+                    prefilter.as_ref().is_none_or(|pred| {
+                        pred.matches(
+                            normalize(q).0.as_str(),
+                            db.as_ref().and_then(|db| db.lookup(q)),
+                        )
+                    })
+                };
+                let lines = shard_lines(read_lines(file)?, *shard);
+                let ttl = Duration::from_secs(cli.cache_ttl);
+                let plan = plan::build(&lines, (*concurrency).max(1), ttl, cli.no_cache, keep);
+                plan::print(&plan, cli.format);
+                return Ok(());
+            }
+            if *incremental && cli.no_history {
+                return Err(
+                    "--incremental compares against snapshot history; drop --no-history".into(),
+                );
+            }
+            let mut manifest = manifest
+                .as_ref()
+                .map(|p| create_file(p).map(io::BufWriter::new))
+                .transpose()?;
+            let client = http_client(cli.timeout)?;
+            let registrars = load_registrar_ids(&client, cli.enrich_registrar).await?;
+            let lines = shard_lines(read_lines(file)?, *shard);
+            if lines.is_empty() {
+                eprintln!("{} no queries found in file", "Note:".yellow().bold());
+                return Ok(());
+            }
+            let total = lines.len();
+            let mut rejects = rejects
+                .as_ref()
+                .map(|p| create_file(p).map(io::BufWriter::new))
+                .transpose()?;
+            let mut json_errors = json_errors
+                .as_ref()
+                .map(|p| create_file(p).map(io::LineWriter::new))
+                .transpose()?;
+            let mut rejected = 0;
+            let mut items = Vec::with_capacity(total);
+            for (i, line) in lines.iter().enumerate() {
+                match plan::validate(line) {
+                    Ok(item) => items.push(item),
+                    Err(reason) => {
+                        rejected += 1;
+                        match rejects.as_mut() {
+                            Some(w) => writeln!(w, "{line}\t{reason}")?,
+                            None => eprintln!(
+                                "{} entry {}: {line} ({reason})",
+                                "Rejected".yellow().bold(),
+                                i + 1
+                            ),
+                        }
+                    }
+                }
+            }
+            if let Some(w) = rejects.as_mut() {
+                w.flush()?;
+            }
+            if rejected > 0 {
+                strict::check(strict::Condition::Unclassifiable, || {
+                    format!("{rejected} input line(s) can't be queried")
+                })?;
+            }
+            let valid = items.len();
+            if let Some(pred) = prefilter {
+                let db = if pred.needs_delegations() {
+                    Some(dataset::Delegations::load()?)
+                } else {
+                    None
+                };
+                items.retain(|(q, _)| {
+                    let kind = normalize(q).0;
+                    pred.matches(kind.as_str(), db.as_ref().and_then(|db| db.lookup(q)))
+                });
+            }
+
+            // Prefer NDJSON for JSON formats
+            let ndjson_mode: bool = matches!(cli.format, Format::Json | Format::Pretty) && *ndjson;
+
+            // Copy Format once for the async closures
+            let fmt: Format = cli.format;
+
+            let conc: usize = (*concurrency).max(1);
+            let budget = retry_budget.map(AtomicUsize::new);
+            let opts = FetchOpts {
+                retry_budget: budget.as_ref(),
+                refresh: *incremental,
+                revalidate: *incremental,
+                ..FetchOpts::from_cli(&cli)
+            };
+            let incremental = *incremental;
+            let limit = FailureLimit {
+                max_failures: *max_failures,
+                max_rate: *max_failure_rate,
+            };
+            let mut summary = BulkSummary {
+                total,
+                rejected,
+                filtered: valid - items.len(),
+                shard: *shard,
+                ..BulkSummary::default()
+            };
+            let mut dedupe = (*dedupe_output && !matches!(fmt, Format::Table | Format::Csv))
+                .then(dedupe::Deduper::default);
+            if *prewarm {
+                let urls = items.iter().map(|(q, _)| query_url(q));
+                prewarm_hosts(&client, urls, conc).await;
+            }
+            let mut progress = progress_format
+                .map(|f| progress::Progress::new(f, progress_file.as_deref(), items.len()))
+                .transpose()?;
+            let mut ticker = tokio::time::interval_at(
+                tokio::time::Instant::now() + *progress_interval,
+                *progress_interval,
+            );
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            let started = Instant::now();
+            if cli.short.is_none() {
+                output_header(fmt);
+            }
+            let _writer = (cli.short.is_none() && ndjson_mode && matches!(fmt, Format::Json))
+                .then(writer::start);
+
+            let mut results = stream::iter(items)
+                .map(|(q, tags): (String, Tags)| {
+                    let client = &client;
+                    let opts = &opts;
+                    async move {
+                        let t0 = Instant::now();
+                        let url = query_url(&q);
+                        let before = incremental.then(|| history::latest(&url)).flatten();
+                        let res = fetch_for_query(client, &q, opts).await;
+                        // unchanged when no newer snapshot with a different hash was recorded
+                        let unchanged = before.filter(|b| {
+                            res.is_ok() && history::latest(&url).is_some_and(|a| a.hash == b.hash)
+                        });
+                        let timing = QueryTiming {
+                            host: url_host(&url),
+                            elapsed: deterministic::elapsed(t0),
+                            query: q,
+                        };
+                        (timing, tags, res, unchanged)
+                    }
+                })
+                .buffer_unordered(deterministic::concurrency(conc));
+
+            loop {
+                // reports go out on their own schedule, even while every
+                // query is stuck on a slow server
+                let next = tokio::select! {
+                    next = results.next() => next,
+                    _ = ticker.tick(), if progress.is_some() => {
+                        if let Some(p) = progress.as_mut() {
+                            let ok = summary.ok + summary.not_found;
+                            p.tick(summary.timings.len(), ok, summary.failed);
+                        }
+                        continue;
+                    }
+                };
+                let Some((timing, tags, res, unchanged)) = next else {
+                    break;
+                };
+                let slow = slow_threshold.is_some_and(|t| timing.elapsed >= t);
+                let elapsed_ms = timing.elapsed_ms();
+                let q = timing.query.clone();
+                summary.timings.push(timing);
+                match res {
+                    Ok(_) if unchanged.is_some() => {
+                        summary.ok += 1;
+                        summary.unchanged += 1;
+                        if let (Some(w), Some(snap)) = (manifest.as_mut(), unchanged) {
+                            let line = serde_json::json!({
+                                "query": q,
+                                "hash": snap.hash,
+                                "unchanged_since": format_timestamp(snap.fetched_at),
+                            });
+                            writeln!(w, "{line}")?;
+                        }
+                    }
+                    Ok(mut json) => {
+                        summary.ok += 1;
+                        if let Some(ids) = &registrars {
+                            dataset::enrich_registrar(&mut json, ids);
+                        }
+                        attach_tags(&mut json, &cli.tags, &tags);
+                        if slow {
+                            attach_meta(&mut json, "query", Value::from(q.as_str()));
+                            attach_meta(&mut json, "elapsed_ms", Value::from(elapsed_ms));
+                            attach_meta(&mut json, "slow", Value::Bool(true));
+                        }
+                        if let Some(field) = cli.short {
+                            // stdout gets the value; --output sinks still get the record
+                            println!("{q}\t{}", field.extract(&json).unwrap_or_default());
+                            sink::write(&json_record(json, cli.output_schema));
+                        } else {
+                            let fmt = if ndjson_mode { Format::Json } else { fmt };
+                            if let Some(dedupe) = dedupe.as_mut() {
+                                let (blobs, record) = dedupe.process(&q, json);
+                                for blob in blobs {
+                                    emit_raw(&seal_plain(blob, cli.output_schema), fmt);
+                                }
+                                json = record;
+                            }
+                            emit_record(json, fmt, cli.output_schema);
+                        }
+                    }
+                    Err(e) if e.is::<strict::Violation>() => return Err(e),
+                    Err(e) if cli.emit_not_found && is_not_found(e.as_ref()) => {
+                        summary.not_found += 1;
+                        let fmt = if ndjson_mode { Format::Json } else { fmt };
+                        emit_not_found(&q, &tags, &cli, fmt);
+                    }
+                    Err(e) => {
+                        summary.failed += 1;
+                        eprintln!("{} {q}: {e}", "Failed".red().bold());
+                        if let Some(w) = json_errors.as_mut() {
+                            writeln!(w, "{}", error_json(&q, e.as_ref()))?;
+                        }
+                        if let Some(reason) =
+                            limit.exceeded(summary.failed, summary.total - summary.filtered)
+                        {
+                            summary.aborted = Some(reason);
+                            break;
+                        }
+                    }
+                }
+                if let Some(max) = max_bytes.filter(|max| bandwidth::total() > *max) {
+                    summary.aborted = Some(format!(
+                        "downloaded {} exceeded --max-bytes {}",
+                        bandwidth::human(bandwidth::total()),
+                        bandwidth::human(max)
+                    ));
+                    break;
+                }
+            }
+            // Dropping the stream cancels any requests still in flight.
+            drop(results);
+            if let Some(p) = progress.as_mut() {
+                let ok = summary.ok + summary.not_found;
+                p.finish(summary.timings.len(), ok, summary.failed);
+            }
+            writer::finish()?;
+
+            let report = summary.report(deterministic::elapsed(started), *slowest);
+            print_summary(&report);
+            if let Some(path) = summary_file {
+                fs::write(path, serde_json::to_string_pretty(&report)?)
+                    .map_err(|e| format!("{}: {e}", path.display()))?;
+            }
+            if let Some(reason) = report.aborted {
+                return Err(format!("bulk run aborted: {reason}").into());
+            }
+        }
+
+        Command::Cache { action } => match action {
+            CacheCmd::List { filter, sort } => {
+                let dir = cache_dir().unwrap_or_else(|_| PathBuf::from("./.cache/rdapx"));
+                if let Some((field, _)) = filter
+                    .iter()
+                    .find(|(f, _)| !matches!(f.as_str(), "kind" | "server" | "state" | "query"))
+                {
+                    return Err(format!("unknown filter field '{field}'").into());
+                }
+                let ttl = Duration::from_secs(cli.cache_ttl);
+                let mut rows: Vec<CacheRow> = list_cache(&dir)
+                    .into_iter()
+                    .map(|p| CacheRow::read(p, ttl))
+                    .filter(|r| filter.iter().all(|f| r.matches(f)))
+                    .collect();
+                match sort {
+                    CacheSort::Query => rows.sort_by(|a, b| a.query.cmp(&b.query)),
+                    CacheSort::Kind => rows.sort_by(|a, b| a.kind.cmp(&b.kind)),
+                    CacheSort::Server => rows.sort_by(|a, b| a.server.cmp(&b.server)),
+                    CacheSort::Age => rows.sort_by_key(|r| r.age_secs),
+                    CacheSort::Size => rows.sort_by_key(|r| std::cmp::Reverse(r.size)),
+                }
+                if rows.is_empty() && matches!(cli.format, Format::Table) {
+                    println!("(empty)");
+                } else {
+                    table::print(&rows, cli.format)?;
+                }
+            }
+            CacheCmd::Clear {
+                query,
+                older_than,
+                force,
+            } => {
+                let dir = cache_dir().unwrap_or_else(|_| PathBuf::from("./.cache/rdapx"));
+                let files = select_cache(&dir, query.as_deref(), *older_than);
+                if files.is_empty() {
+                    println!("Nothing to clear");
+                    return Ok(());
+                }
+                if !force
+                    && io::stdin().is_terminal()
+                    && !confirm(&format!("Delete {} cached files?", files.len()))
+                {
+                    println!("Aborted");
+                    return Ok(());
+                }
+                let n = clear_cache(&files);
+                println!("Cleared {n} cached files");
+            }
+            CacheCmd::Warm {
+                file,
+                refresh_before,
+                concurrency,
+            } => {
+                let client = http_client(cli.timeout)?;
+                let opts = FetchOpts {
+                    refresh: true,
+                    ..FetchOpts::from_cli(&cli)
+                };
+                let (mut fresh, mut warmed, mut refreshed, mut failed) = (0, 0, 0, 0);
+                let mut rejected = 0;
+                let mut due = Vec::new();
+                for q in read_targets(file)? {
+                    if let Err(reason) = plan::validate(&q) {
+                        eprintln!("{} {q} ({reason})", "Rejected".yellow().bold());
+                        rejected += 1;
+                        continue;
+                    }
+                    let url = query_url(&q);
+                    match cache_age(&url) {
+                        Some(age) if age + *refresh_before < cache_ttl(&url, opts.ttl) => {
+                            fresh += 1;
+                        }
+                        age => due.push((q, age.is_some())),
+                    }
+                }
+
+                let mut results = stream::iter(due)
+                    .map(|(q, existed)| {
+                        let (client, opts) = (&client, &opts);
+                        async move { (fetch_for_query(client, &q, opts).await, q, existed) }
+                    })
+                    .buffer_unordered((*concurrency).max(1));
+                while let Some((res, q, existed)) = results.next().await {
+                    match res {
+                        Ok(_) if existed => refreshed += 1,
+                        Ok(_) => warmed += 1,
+                        Err(e) => {
+                            failed += 1;
+                            eprintln!("{} {q}: {e}", "Failed".red().bold());
+                        }
+                    }
+                }
+                println!(
+                    "Warmed {warmed}, refreshed {refreshed}, still fresh {fresh}, \
+                     failed {failed}, rejected {rejected}"
+                );
+            }
+        },
+
+        Command::Search { target } => {
+            let (path, param, values, paging) = match target {
+                SearchCmd::Domains {
+                    name,
+                    ns_name,
+                    ns_ip,
+                    name_file,
+                    paging,
+                } => {
+                    let (param, values) = if let Some(file) = name_file {
+                        ("name", read_lines(file)?)
+                    } else {
+                        pick_search_param([("name", name), ("nsLdhName", ns_name), ("nsIp", ns_ip)])
+                    };
+                    if param == "name" {
+                        for p in &values {
+                            search::validate_pattern(p).map_err(|e| format!("{p}: {e}"))?;
+                        }
+                    }
+                    ("domains", param, values, paging)
+                }
+                SearchCmd::Nameservers { name, ip, paging } => {
+                    let (param, values) = pick_search_param([("name", name), ("ip", ip)]);
+                    ("nameservers", param, values, paging)
+                }
+                SearchCmd::Entities {
+                    full_name,
+                    handle,
+                    paging,
+                } => {
+                    let (param, values) =
+                        pick_search_param([("fn", full_name), ("handle", handle)]);
+                    ("entities", param, values, paging)
+                }
+            };
+            let urls = values
+                .iter()
+                .map(|v| search::search_url(paging, path, param, v))
+                .collect::<Result<Vec<_>, _>>()?;
+            search_and_emit(&cli, urls, paging).await?;
+        }
+
+        Command::Available {
+            file,
+            tlds,
+            concurrency,
+            rate,
+        } => {
+            let client = http_client(cli.timeout)?;
+            let names = read_lines(file)?;
+            let tlds: Vec<String> = tlds
+                .iter()
+                .map(|t| t.trim().trim_start_matches('.').to_lowercase())
+                .filter(|t| !t.is_empty())
+                .collect();
+            let rows = available::sweep(&client, &names, &tlds, *concurrency, *rate).await;
+            available::print_matrix(&rows, &tlds, cli.format);
+        }
+
+        Command::Squat {
+            domain,
+            strategies,
+            tlds,
+            concurrency,
+            rate,
+            max_candidates,
+            budget,
+            resume,
+        } => {
+            labels::validate(domain)?;
+            let strategies = if strategies.is_empty() {
+                squat::Strategy::ALL.to_vec()
+            } else {
+                strategies.clone()
+            };
+            let tlds: Vec<String> = if tlds.is_empty() {
+                squat::DEFAULT_TLDS
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect()
+            } else {
+                tlds.iter()
+                    .map(|t| t.trim().trim_start_matches('.').to_lowercase())
+                    .filter(|t| !t.is_empty())
+                    .collect()
+            };
+            let mut candidates = squat::generate(domain, &strategies, &tlds);
+            if let Some(max) = max_candidates.filter(|&m| m < candidates.len()) {
+                eprintln!(
+                    "{} {} candidate(s) beyond --max-candidates left out",
+                    "Note:".yellow().bold(),
+                    candidates.len() - max
+                );
+                candidates.truncate(max);
+            }
+            let client = http_client(cli.timeout)?;
+            let limits = squat::Limits {
+                concurrency: *concurrency,
+                rate: *rate,
+                budget: *budget,
+                resume: resume.clone(),
+            };
+            let statuses = squat::check(&client, domain, &candidates, &limits).await?;
+            let registered: Vec<&squat::Candidate> = candidates
+                .iter()
+                .zip(&statuses)
+                .filter(|(_, s)| **s == Some(available::Status::Registered))
+                .map(|(c, _)| c)
+                .collect();
+            let unknown = statuses
+                .iter()
+                .filter(|s| **s == Some(available::Status::Unknown))
+                .count();
+            let over_budget = statuses.iter().filter(|s| s.is_none()).count();
+            // one record per line, also for `--format pretty`
+            let fmt = if matches!(cli.format, Format::Pretty) {
+                Format::Json
+            } else {
+                cli.format
+            };
+            table::print(&registered, fmt)?;
+            eprintln!(
+                "{} {} of {} candidate(s) registered{}",
+                "Done:".cyan().bold(),
+                registered.len(),
+                candidates.len(),
+                if unknown > 0 {
+                    format!(" ({unknown} could not be checked)")
+                } else {
+                    String::new()
                 }
-                return Ok(v);
+            );
+            if over_budget > 0 {
+                eprintln!(
+                    "{} {over_budget} candidate(s) not checked: registry budget spent{}",
+                    "Note:".yellow().bold(),
+                    if resume.is_some() {
+                        "; rerun with the same --resume file to continue"
+                    } else {
+                        ""
+                    }
+                );
             }
-            Ok(resp) => {
-                let code = resp.status();
-                let body = resp.text().await.unwrap_or_default();
-                return Err(format!("HTTP {code}: {body}").into());
+        }
+
+        Command::Portfolio { action } => match action {
+            PortfolioCmd::Add { name, targets } => {
+                let added = portfolio::add(name, targets)?;
+                println!("{} {added} target(s) to {name}", "Added".green().bold());
             }
-            Err(e) => {
-                last_err = Some(e);
-                if attempt < retries {
-                    sleep(Duration::from_millis(retry_delay_ms)).await;
+            PortfolioCmd::Remove { name, targets } => {
+                let removed = portfolio::remove(name, targets)?;
+                println!(
+                    "{} {removed} target(s) from {name}",
+                    "Removed".green().bold()
+                );
+            }
+            PortfolioCmd::List { name: Some(name) } => {
+                for t in portfolio::targets(name)? {
+                    println!("{t}");
+                }
+            }
+            PortfolioCmd::List { name: None } => {
+                for (name, targets) in portfolio::load()? {
+                    println!("{name} ({} targets)", targets.len());
+                }
+            }
+            PortfolioCmd::Check { name, concurrency } => {
+                let targets = portfolio::targets(name)?;
+                let today = i64::try_from(now_secs() / 86_400).unwrap_or(0);
+                let rows: Vec<portfolio::CheckRow> = lookup_all(&cli, &targets, *concurrency)
+                    .await?
+                    .into_iter()
+                    .map(|(q, res)| match res {
+                        Ok(json) => portfolio::CheckRow::from_rdap(q, &json, today),
+                        Err(e) => portfolio::CheckRow::failed(q, e),
+                    })
+                    .collect();
+                table::print(&rows, cli.format)?;
+            }
+        },
+
+        Command::Contacts {
+            query,
+            bulk,
+            concurrency,
+        } => {
+            let targets: Vec<String> = match bulk {
+                Some(path) => read_targets(path)?,
+                None => query.iter().cloned().collect(),
+            };
+            let mut rows = Vec::new();
+            let mut failed = 0usize;
+            for (q, res) in lookup_all(&cli, &targets, *concurrency).await? {
+                match res {
+                    Ok(json) => rows.extend(contacts::extract(q, &projection::apply(json))),
+                    Err(e) => {
+                        eprintln!("{} {q}: {e}", "Failed".red().bold());
+                        failed += 1;
+                    }
                 }
             }
+            table::print(&rows, cli.format)?;
+            if failed > 0 {
+                return Err(Box::new(Exit(1)));
+            }
         }
-    }
 
-    Err(format!("network error for {url}: {}", last_err.unwrap()).into())
-}
+        Command::Abuse { file, concurrency } => {
+            let targets = read_targets(file)?;
+            let mut found = Vec::new();
+            let mut failed = 0usize;
+            for (q, res) in lookup_all(&cli, &targets, *concurrency).await? {
+                match res {
+                    Ok(json) => found.push((q, json)),
+                    Err(e) => {
+                        eprintln!("{} {q}: {e}", "Failed".red().bold());
+                        failed += 1;
+                    }
+                }
+            }
+            let boxes = abuse::consolidate(found);
+            table::print(&boxes, cli.format)?;
+            let missing: usize = boxes
+                .iter()
+                .filter(|b| b.abuse_email.is_none())
+                .map(|b| b.count)
+                .sum();
+            eprintln!(
+                "{} {} domain(s), {} mailbox(es), {missing} without an abuse email, \
+                 {failed} failed",
+                "Done:".cyan().bold(),
+                targets.len(),
+                boxes.iter().filter(|b| b.abuse_email.is_some()).count()
+            );
+        }
 
-/* --------------------------------- MAIN ---------------------------------- */
+        Command::Expiry {
+            portfolio: name,
+            remind,
+            concurrency,
+        } => {
+            let targets = portfolio::targets(name)?;
+            let today = i64::try_from(now_secs() / 86_400).unwrap_or(0);
+            let mut rows: Vec<portfolio::CheckRow> = lookup_all(&cli, &targets, *concurrency)
+                .await?
+                .into_iter()
+                .map(|(q, res)| match res {
+                    Ok(json) => portfolio::CheckRow::from_rdap(q, &json, today),
+                    Err(e) => portfolio::CheckRow::failed(q, e),
+                })
+                .collect();
+            rows.sort_by_key(|r| r.days_left.unwrap_or(i64::MAX));
+            for r in rows.iter().filter(|r| r.ok && r.expiry.is_none()) {
+                eprintln!(
+                    "{} {} has no expiration date",
+                    "Note:".yellow().bold(),
+                    r.query
+                );
+            }
+            match cli.format {
+                Format::Ical => print!("{}", ical::calendar(&rows, remind, now_secs())),
+                fmt => table::print(&rows, fmt)?,
+            }
+        }
+
+        Command::Audit {
+            action:
+                AuditCmd::Locks {
+                    portfolio: name,
+                    concurrency,
+                },
+        } => {
+            let targets = portfolio::targets(name)?;
+            let rows: Vec<portfolio::LockRow> = lookup_all(&cli, &targets, *concurrency)
+                .await?
+                .into_iter()
+                .map(|(q, res)| match res {
+                    Ok(json) => portfolio::LockRow::from_rdap(q, &json),
+                    Err(e) => portfolio::LockRow::failed(q, e),
+                })
+                .collect();
+            let exceptions = rows.iter().filter(|r| !r.ok()).count();
+            table::print(&rows, cli.format)?;
+            if exceptions > 0 {
+                eprintln!(
+                    "{} {exceptions} of {} domain(s) not fully locked",
+                    "Exceptions:".red().bold(),
+                    rows.len()
+                );
+                return Err(Box::new(Exit(1)));
+            }
+        }
 
-#[allow(clippy::too_many_lines)]
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let mut cli = Cli::parse();
+        Command::DiffRuns {
+            old,
+            new,
+            key,
+            diff_format,
+            ignore_path,
+        } => {
+            let rules = diffruns::ignore_rules(ignore_path)?;
+            let (mut before, skipped_old) = diffruns::index(&read_lines(old)?, key);
+            let (mut after, skipped_new) = diffruns::index(&read_lines(new)?, key);
+            for record in before.values_mut().chain(after.values_mut()) {
+                diffruns::strip(record, &rules);
+            }
+            if skipped_old + skipped_new > 0 {
+                strict::check(strict::Condition::Lenient, || {
+                    format!(
+                        "{} record(s) without a '{key}' value",
+                        skipped_old + skipped_new
+                    )
+                })?;
+                eprintln!(
+                    "{} skipped {} record(s) without a '{key}' value",
+                    "Note:".yellow().bold(),
+                    skipped_old + skipped_new
+                );
+            }
+            let changes = diffruns::diff(&before, &after);
+            match diff_format {
+                Some(f) => diffruns::render(&changes, *f)?,
+                None => table::print(&changes, cli.format)?,
+            }
+            let count = |k| changes.iter().filter(|c| c.kind == k).count();
+            let objects: BTreeSet<&str> = changes
+                .iter()
+                .filter(|c| c.kind == diffruns::Kind::Changed)
+                .map(|c| c.key.as_str())
+                .collect();
+            eprintln!(
+                "{} {} added, {} removed, {} changed ({} field(s))",
+                "Done:".cyan().bold(),
+                count(diffruns::Kind::Added),
+                count(diffruns::Kind::Removed),
+                objects.len(),
+                count(diffruns::Kind::Changed)
+            );
+        }
 
-    // Completions (only for `get --completions <shell>`)
-    if let Command::Get {
-        completions: Some(sh),
-        ..
-    } = &cli.command
-    {
-        let mut cmd = Cli::command();
-        match sh {
-            Shell::Bash => generate(Bash, &mut cmd, "rdapx", &mut io::stdout()),
-            Shell::Zsh => generate(Zsh, &mut cmd, "rdapx", &mut io::stdout()),
-            Shell::Fish => generate(Fish, &mut cmd, "rdapx", &mut io::stdout()),
+        Command::Merge {
+            inputs,
+            summaries,
+            summary_file,
+        } => {
+            let mut out = io::BufWriter::new(io::stdout().lock());
+            let mut records = 0;
+            for path in inputs {
+                for (i, line) in read_lines(path)?.iter().enumerate() {
+                    if let Err(e) = serde_json::from_str::<Value>(line) {
+                        return Err(format!("{}:{}: not JSON: {e}", path.display(), i + 1).into());
+                    }
+                    writeln!(out, "{line}")?;
+                    records += 1;
+                }
+            }
+            out.flush()?;
+            if !inputs.is_empty() {
+                eprintln!(
+                    "{} {records} record(s) from {} file(s)",
+                    "Done:".cyan().bold(),
+                    inputs.len()
+                );
+            }
+            if !summaries.is_empty() {
+                let reports = summaries
+                    .iter()
+                    .map(|p| {
+                        let text = fs::read_to_string(p)?;
+                        serde_json::from_str::<model::SummaryReport>(&text)
+                            .map_err(|e| format!("{}: {e}", p.display()).into())
+                    })
+                    .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+                for problem in shard::coverage(&reports) {
+                    eprintln!("{} {problem}", "Warning:".yellow().bold());
+                }
+                let merged = shard::merge_summaries(reports);
+                print_summary(&merged);
+                if let Some(path) = summary_file {
+                    fs::write(path, serde_json::to_string_pretty(&merged)?)
+                        .map_err(|e| format!("{}: {e}", path.display()))?;
+                }
+            }
         }
-        return Ok(());
-    }
 
-    // Auto-disable color if piped
-    if !io::stdout().is_terminal() {
-        cli.no_color = true;
-        colored::control::set_override(false);
-    } else if cli.no_color {
-        colored::control::set_override(false);
-    }
+        Command::Stats { file, top } => {
+            let stats = stats::compute(&read_lines(file)?, *top);
+            if stats.invalid > 0 {
+                strict::check(strict::Condition::Lenient, || {
+                    format!(
+                        "{} line(s) of {} aren't JSON objects",
+                        stats.invalid,
+                        file.display()
+                    )
+                })?;
+            }
+            print_stats(&stats, cli.format)?;
+        }
 
-    match &cli.command {
-        Command::Get { query, .. } => {
+        Command::Pivot { db, by, value } => {
+            let hits = pivot::search(db, *by, value)?;
+            table::print(&hits, cli.format)?;
+            eprintln!(
+                "{} {} object(s) share {value}",
+                "Done:".cyan().bold(),
+                hits.len()
+            );
+        }
+
+        Command::Report { db, group_by, top } => {
+            let (groups, total) = report::build(db, *group_by, *top)?;
+            table::print(&groups, cli.format)?;
+            eprintln!(
+                "{} {} group(s) over {total} object(s)",
+                "Done:".cyan().bold(),
+                groups.len()
+            );
+        }
+
+        Command::Bootstrap {
+            action: BootstrapCmd::Resolve { query },
+        } => {
+            bootstrap::ensure(&http_client(cli.timeout)?).await?;
+            let route = bootstrap::resolve(query);
+            if matches!(cli.format, Format::Table) {
+                println!("{} {}", "Type:".blue().bold(), route.kind);
+                println!("{} {}", "Normalized:".blue().bold(), route.normalized);
+                println!("{} {}", "Server:".blue().bold(), route.server);
+                println!("{} {}", "URL:".blue().bold(), route.url);
+                println!("{} {}", "Source:".blue().bold(), route.source);
+            } else {
+                output(&serde_json::to_value(&route)?, cli.format);
+            }
+        }
+
+        Command::Bootstrap {
+            action: BootstrapCmd::Sources,
+        } => {
             let client = http_client(cli.timeout)?;
-            let ttl = Duration::from_secs(cli.cache_ttl);
-            let json = fetch_for_query(
-                &client,
-                query,
-                ttl,
-                cli.no_cache,
-                cli.retries,
-                cli.retry_delay_ms,
-            )
-            .await?;
-            output(&json, cli.format);
+            let mut loaded = Vec::new();
+            let mut failed = Vec::new();
+            for file in bootstrap::FILES {
+                match bootstrap::load(&client, file).await {
+                    Ok(l) => loaded.push(l),
+                    Err(e) => failed.push(e.to_string()),
+                }
+            }
+            print_bootstrap_loaded(&loaded, &failed, cli.format)?;
         }
 
-        Command::Bulk {
-            file,
-            concurrency,
-            ndjson,
+        Command::Bootstrap {
+            action: BootstrapCmd::Refresh,
         } => {
             let client = http_client(cli.timeout)?;
-            let ttl = Duration::from_secs(cli.cache_ttl);
-            let items = read_lines(file)?;
-            if items.is_empty() {
-                eprintln!("{} no queries found in file", "Note:".yellow().bold());
-                return Ok(());
+            let (loaded, failed) = bootstrap::refresh(&client).await?;
+            print_bootstrap_loaded(&loaded, &failed, cli.format)?;
+        }
+
+        Command::Bootstrap {
+            action: BootstrapCmd::Status,
+        } => {
+            let files = bootstrap::status()?;
+            if matches!(cli.format, Format::Table) {
+                println!(
+                    "{} {}",
+                    "Directory:".blue().bold(),
+                    bootstrap::dir()?.display()
+                );
             }
+            table::print(&files, cli.format)?;
+        }
 
-            // Prefer NDJSON for JSON formats
-            let ndjson_mode: bool = matches!(cli.format, Format::Json | Format::Pretty) && *ndjson;
+        Command::Servers {
+            action: ServersCmd::List { kind },
+        } => {
+            bootstrap::ensure(&http_client(cli.timeout)?).await?;
+            table::print(&known_services(*kind), cli.format)?;
+        }
 
-            // Copy Format once for the async closures
-            let fmt: Format = cli.format;
+        Command::Servers {
+            action: ServersCmd::Check { kind, concurrency },
+        } => {
+            let client = http_client(cli.timeout)?;
+            bootstrap::ensure(&client).await?;
+            let mut bases: Vec<String> = Vec::new();
+            for s in known_services(*kind) {
+                for url in std::iter::once(s.url).chain(s.alternates) {
+                    if !bases.contains(&url) {
+                        bases.push(url);
+                    }
+                }
+            }
+            let mut probes: Vec<bootstrap::Probe> = stream::iter(bases)
+                .map(|base| bootstrap::probe(&client, base))
+                .buffer_unordered((*concurrency).max(1))
+                .collect()
+                .await;
+            probes.sort_by(|a, b| a.url.cmp(&b.url));
+            table::print(&probes, cli.format)?;
+            let unhealthy = probes.iter().filter(|p| !p.healthy).count();
+            eprintln!(
+                "{} {} of {} server(s) healthy",
+                "Done:".cyan().bold(),
+                probes.len() - unhealthy,
+                probes.len()
+            );
+            if unhealthy > 0 {
+                return Err(Box::new(Exit(1)));
+            }
+        }
 
-            let conc: usize = (*concurrency).max(1);
+        Command::Whois {
+            query,
+            server,
+            no_follow,
+            raw,
+        } => {
+            let hops = whois::lookup(query, server.as_deref(), !no_follow)
+                .await
+                .map_err(|e| e.to_string())?;
+            if *raw {
+                for hop in &hops {
+                    eprintln!("{} {}", "Server:".blue().bold(), hop.server);
+                    print!("{}", hop.text);
+                }
+            } else {
+                let record = whois::parse_chain(&hops, query).ok_or_else(|| {
+                    let server = hops.last().map_or(whois::IANA, |h| h.server.as_str());
+                    format!("{server} has no WHOIS record for {query}")
+                })?;
+                output(&record, cli.format);
+            }
+        }
 
-            stream::iter(items.into_iter())
-                .map(|q: String| {
-                    let client = &client;
-                    async move {
-                        match fetch_for_query(
-                            client,
-                            &q,
-                            ttl,
-                            cli.no_cache,
-                            cli.retries,
-                            cli.retry_delay_ms,
-                        )
-                        .await
-                        {
-                            Ok(json) => Ok((q, json)),
-                            Err(e) => Err((q, e)),
+        Command::Init { dir } => {
+            let (root, created) = project::init(dir)?;
+            if created {
+                println!(
+                    "{} project state in {}",
+                    "Initialized".green().bold(),
+                    root.display()
+                );
+            } else {
+                println!(
+                    "{} {} already exists",
+                    "Note:".yellow().bold(),
+                    root.display()
+                );
+            }
+        }
+
+        Command::Import { paths, cache } => {
+            let r = import::run(paths, *cache)?;
+            eprintln!(
+                "{} {} snapshot(s) from {} file(s), {} unchanged, {} skipped",
+                "Imported".green().bold(),
+                r.imported,
+                r.files,
+                r.unchanged,
+                r.skipped
+            );
+        }
+
+        Command::Verify { file, pubkey } => {
+            let pk = pubkey
+                .as_deref()
+                .map(integrity::load_public_key)
+                .transpose()?;
+            let (mut ok, mut bad) = (0usize, 0usize);
+            for (n, line) in read_lines(file)?.iter().enumerate() {
+                let verdict = serde_json::from_str(line)
+                    .map_or(integrity::Verdict::Unsealed, |json| {
+                        integrity::verify(&json, pk.as_ref())
+                    });
+                if verdict == integrity::Verdict::Ok {
+                    ok += 1;
+                } else {
+                    bad += 1;
+                    eprintln!("{} line {}: {verdict:?}", "FAIL".red().bold(), n + 1);
+                }
+            }
+            println!("{} {ok} verified, {bad} failed", "Done:".cyan().bold());
+            if bad > 0 {
+                return Err(Box::new(Exit(1)));
+            }
+        }
+
+        Command::ReverseNs { nameserver, paging } => {
+            let ns = nameserver.trim().trim_end_matches('.').to_lowercase();
+            let url = search::search_url(paging, "domains", "nsLdhName", &ns)?;
+            search_and_emit(&cli, vec![url], paging).await?;
+        }
+
+        Command::Related {
+            domain,
+            pivots,
+            max_results,
+            server,
+        } => {
+            let client = http_client(cli.timeout)?;
+            let opts = FetchOpts::from_cli(&cli);
+            let json = fetch_for_query(&client, domain, &opts).await?;
+            let server = server.clone().or_else(|| {
+                query_url(domain)
+                    .rsplit_once("/domain/")
+                    .map(|(base, _)| format!("{base}/"))
+            });
+            let paging = search::PagingArgs {
+                max_results: *max_results,
+                page_size: None,
+                server,
+                resume: None,
+                delay: None,
+            };
+            let pivots = if pivots.is_empty() {
+                vec![related::Pivot::Registrant, related::Pivot::Nameserver]
+            } else {
+                pivots.clone()
+            };
+            let found = related::discover(&client, domain, &json, &pivots, &paging, &opts).await?;
+            let fmt = if matches!(cli.format, Format::Pretty) {
+                Format::Json
+            } else {
+                cli.format
+            };
+            table::print(&found, fmt)?;
+            eprintln!(
+                "{} {} related domain(s)",
+                "Done:".cyan().bold(),
+                found.len()
+            );
+        }
+
+        Command::Changed {
+            query,
+            diff_format,
+            ignore_path,
+        } => {
+            let rules = diffruns::ignore_rules(ignore_path)?;
+            let client = http_client(cli.timeout)?;
+            let url = query_url(query);
+            let (previous, before) = history::latest(&url)
+                .or_else(|| read_cache_entry(&url))
+                .map(|e| (e.hash, e.data))
+                .unzip();
+            let opts = FetchOpts {
+                no_cache: false,
+                refresh: true,
+                history: true,
+                ..FetchOpts::from_cli(&cli)
+            };
+            let json = match fetch_for_query(&client, query, &opts).await {
+                Ok(json) => json,
+                Err(e) => {
+                    eprintln!("{} {query}: {e}", "Failed".red().bold());
+                    return Err(Box::new(Exit(2)));
+                }
+            };
+            let current = history::content_hash(&json);
+            let changes = before.map(|mut before| {
+                let mut after = json.clone();
+                diffruns::strip(&mut before, &rules);
+                diffruns::strip(&mut after, &rules);
+                diffruns::fields(query, &before, &after)
+            });
+            // with ignore rules only the fields that remain decide
+            let noise = !rules.is_empty() && changes.as_ref().is_some_and(Vec::is_empty);
+            match previous {
+                Some(prev) if prev != current && !noise => {
+                    let changes = changes.unwrap_or_default();
+                    match diff_format {
+                        Some(diffruns::DiffFormat::Json) => {
+                            let changes: Vec<Value> = changes
+                                .iter()
+                                .map(|c| {
+                                    serde_json::json!({
+                                        "path": diffruns::pointer(&c.field),
+                                        "old": c.old,
+                                        "new": c.new,
+                                    })
+                                })
+                                .collect();
+                            let doc = serde_json::json!({
+                                "query": query,
+                                "old_hash": prev,
+                                "new_hash": current,
+                                "changes": changes,
+                            });
+                            println!("{doc}");
                         }
-                    }
-                })
-                .buffer_unordered(conc)
-                .for_each(|res| async {
-                    match res {
-                        Ok((_q, json)) => {
-                            if ndjson_mode {
-                                println!("{}", serde_json::to_string(&json).unwrap());
-                            } else {
-                                output(&json, fmt);
-                            }
+                        Some(f) => {
+                            println!("changed {query} {prev} -> {current}");
+                            diffruns::render(&changes, *f)?;
                         }
-                        Err((q, e)) => eprintln!("{} {q}: {e}", "Failed".red().bold()),
+                        None => println!("changed {query} {prev} -> {current}"),
                     }
-                })
-                .await;
+                }
+                Some(_) => {
+                    println!("unchanged {query} {current}");
+                    return Err(Box::new(Exit(1)));
+                }
+                None => {
+                    println!("baseline {query} {current}");
+                    return Err(Box::new(Exit(1)));
+                }
+            }
         }
 
-        Command::Cache { action } => match action {
-            CacheCmd::List => {
-                let dir = cache_dir().unwrap_or_else(|_| PathBuf::from("./.cache/rdapx"));
-                let entries = list_cache(&dir);
-                if entries.is_empty() {
-                    println!("(empty)");
+        Command::History { action } => match action {
+            HistoryCmd::Export { query, format } => {
+                let snapshots = history::load(&query_url(query))?;
+                if snapshots.is_empty() {
+                    return Err(format!("no history recorded for {query}").into());
+                }
+                history::print_timeline(&history::timeline(&snapshots), *format)?;
+            }
+            HistoryCmd::Compact {
+                keep_last,
+                keep_monthly,
+                dedupe,
+                dry_run,
+            } => {
+                let flags = config::RetentionPolicy {
+                    keep_last: *keep_last,
+                    keep_monthly: *keep_monthly,
+                    dedupe: *dedupe,
+                };
+                let policy = if flags.is_empty() {
+                    config::get().history
                 } else {
-                    for p in entries {
-                        println!("{}", p.display());
-                    }
+                    flags
+                };
+                if policy.is_empty() {
+                    return Err(
+                        "no retention policy: pass --keep-last/--keep-monthly/--dedupe \
+                                or set [history] in the config file"
+                            .into(),
+                    );
                 }
+                let (files, removed) = history::compact_all(&policy, *dry_run)?;
+                let verb = if *dry_run { "Would remove" } else { "Removed" };
+                println!("{verb} {removed} snapshots across {files} history files");
             }
-            CacheCmd::Clear => {
-                let dir = cache_dir().unwrap_or_else(|_| PathBuf::from("./.cache/rdapx"));
-                let n = clear_cache(&dir);
-                println!("Cleared {n} cached files");
+            HistoryCmd::Pin { query, name, hash } => {
+                let snap = history::pin(&query_url(query), name, hash.as_deref())?;
+                println!(
+                    "Pinned {query} as {name}: {} ({})",
+                    snap.hash,
+                    format_timestamp(snap.fetched_at)
+                );
+            }
+        },
+
+        Command::Diff {
+            query,
+            against,
+            diff_format,
+            ignore_path,
+        } => {
+            let rules = diffruns::ignore_rules(ignore_path)?;
+            let url = query_url(query);
+            let pinned = history::pinned(&url, against)?
+                .ok_or_else(|| format!("{query} has no snapshot pinned as '{against}'"))?;
+            let client = http_client(cli.timeout)?;
+            let mut current = fetch_for_query(&client, query, &FetchOpts::from_cli(&cli)).await?;
+            let mut before = pinned.data;
+            diffruns::strip(&mut before, &rules);
+            diffruns::strip(&mut current, &rules);
+            let changes = diffruns::fields(query, &before, &current);
+            diffruns::render(&changes, *diff_format)?;
+            if *diff_format != diffruns::DiffFormat::Json {
+                eprintln!(
+                    "{} {} field(s) changed since {against} ({})",
+                    "Done:".cyan().bold(),
+                    changes.len(),
+                    format_timestamp(pinned.fetched_at)
+                );
+            }
+            if !changes.is_empty() {
+                return Err(Box::new(Exit(1)));
+            }
+        }
+
+        Command::Schema { csv, summary, .. } => {
+            let schema = if *csv {
+                schemars::schema_for!(model::Row)
+            } else if *summary {
+                schemars::schema_for!(model::SummaryReport)
+            } else {
+                // a bulk NDJSON line and a `get` JSON document share one shape
+                match cli.output_schema {
+                    OutputSchema::V1 => schemars::schema_for!(model::OutputRecord),
+                    OutputSchema::V2 => schemars::schema_for!(model::OutputRecordV2),
+                }
+            };
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+        }
+
+        Command::Serve {
+            listen,
+            offline,
+            concurrency,
+            rate,
+        } => {
+            if !*offline {
+                breaker::init()?;
+            }
+            let concurrency = deterministic::concurrency(*concurrency).max(1);
+            let state = serve::State {
+                client: http_client(cli.timeout)?,
+                opts: FetchOpts::from_cli(&cli),
+                offline: *offline,
+                concurrency,
+                slots: tokio::sync::Semaphore::new(concurrency),
+                limiter: rate.map(ratelimit::HostLimiter::new),
+                schema: cli.output_schema,
+                tags: cli.tags.clone(),
+            };
+            serve::run(*listen, state).await?;
+        }
+
+        Command::Complete { kind, prefix } => {
+            for c in complete::candidates(*kind, prefix) {
+                println!("{c}");
+            }
+        }
+
+        Command::Auth { action } => match action {
+            AuthCmd::Set { name } => {
+                if io::stdin().is_terminal() {
+                    eprint!("Secret for {name}: ");
+                }
+                let mut value = String::new();
+                io::stdin().read_line(&mut value)?;
+                let value = value.trim();
+                if value.is_empty() {
+                    return Err("empty secret".into());
+                }
+                auth::set(name, value)?;
+                eprintln!("Stored secret '{name}'");
+            }
+            AuthCmd::Get { name } => {
+                let value = auth::get(name)?.ok_or_else(|| format!("no secret named '{name}'"))?;
+                println!("{value}");
+            }
+            AuthCmd::Delete { name } => {
+                if auth::delete(name)? {
+                    eprintln!("Deleted secret '{name}'");
+                } else {
+                    return Err(format!("no secret named '{name}'").into());
+                }
+            }
+        },
+
+        Command::Dataset { action } => match action {
+            DatasetCmd::Pull { name } => {
+                let client = http_client(cli.timeout)?;
+                for (file, bytes) in dataset::pull(&client, *name).await? {
+                    println!("{file}: {bytes} bytes");
+                }
+            }
+            DatasetCmd::List => {
+                let files = dataset::list()?;
+                if files.is_empty() {
+                    println!("(empty)");
+                }
+                for (file, bytes, age) in files {
+                    println!("{file}  {bytes} bytes  {}h old", age.as_secs() / 3600);
+                }
+            }
+            DatasetCmd::Lookup { query } => {
+                let db = dataset::Delegations::load()?;
+                let Some(hit) = db.lookup(query) else {
+                    return Err(format!("{query}: no delegation record found").into());
+                };
+                let json = hit.to_json(query);
+                match cli.format {
+                    fmt @ (Format::Json
+                    | Format::Pretty
+                    | Format::Msgpack
+                    | Format::Cbor
+                    | Format::Ical) => {
+                        output(&json, fmt);
+                    }
+                    Format::Table => {
+                        for k in DELEGATION_KEYS {
+                            let v = json.get(k).and_then(Value::as_str).unwrap_or("-");
+                            println!("{} {v}", format!("{k}:").blue().bold());
+                        }
+                    }
+                    Format::Csv => table::print_csv(
+                        &DELEGATION_KEYS,
+                        [DELEGATION_KEYS
+                            .iter()
+                            .map(|k| json[k].as_str().unwrap_or("").to_string())
+                            .collect()],
+                    ),
+                }
             }
         },
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize, normalize_ipv6, Kind};
+
+    fn kind(q: &str) -> Kind {
+        normalize(q).0
+    }
+
+    #[test]
+    fn ipv6_is_canonicalized() {
+        assert_eq!(normalize_ipv6("2001:DB8:0:0:0:0:0:1"), "2001:db8::1");
+        assert_eq!(normalize_ipv6("[2001:db8::1]"), "2001:db8::1");
+        assert_eq!(normalize_ipv6("fe80::1%eth0"), "fe80::1");
+        assert_eq!(normalize_ipv6("2001:db8:0::/32"), "2001:db8::/32");
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_become_ipv4() {
+        assert_eq!(normalize_ipv6("::ffff:192.0.2.1"), "192.0.2.1");
+        assert_eq!(normalize_ipv6("::ffff:192.0.2.0/120"), "192.0.2.0/24");
+        // a prefix wider than the mapped range stays IPv6
+        assert_eq!(normalize_ipv6("::ffff:0.0.0.0/95"), "::ffff:0.0.0.0/95");
+    }
+
+    #[test]
+    fn unparsable_ipv6_is_left_alone() {
+        assert_eq!(normalize_ipv6("2001:db8::zz"), "2001:db8::zz");
+        assert_eq!(normalize_ipv6("2001:db8::/129"), "2001:db8::/129");
+    }
+
+    #[test]
+    fn queries_are_classified() {
+        assert!(matches!(normalize("AS13335"), (Kind::Asn, n) if n == "13335"));
+        assert!(matches!(kind("as64496"), Kind::Asn));
+        assert!(matches!(kind("asus.com"), Kind::Domain));
+        assert!(matches!(kind("192.0.2.1"), Kind::Ip));
+        assert!(matches!(normalize("192.0.2.0/24"), (Kind::Ip, n) if n == "192.0.2.0/24"));
+        assert!(matches!(normalize(" 2001:DB8::/32 "), (Kind::Ip, n) if n == "2001:db8::/32"));
+        assert!(matches!(kind("ABC123-ARIN"), Kind::Entity));
+        assert!(matches!(kind("EXAMPLE-RIPE"), Kind::Entity));
+    }
+
+    #[test]
+    fn domains_reduce_to_their_registrable_name() {
+        assert!(
+            matches!(normalize("www.example.co.uk"), (Kind::Domain, n) if n == "example.co.uk")
+        );
+        assert!(matches!(normalize("example.com"), (Kind::Domain, n) if n == "example.com"));
+        // dotless words without a known object tag aren't handles
+        assert!(matches!(kind("my-laptop"), Kind::Domain));
+    }
+}
@@ -0,0 +1,326 @@
+//! Typed output shapes. These are the contracts published by `rdapx schema`;
+//! keep them in sync with what `get`/`bulk` emit.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// One `get`/`bulk` JSON record (`--output-schema v1`): the RDAP response
+/// plus rdapx annotations merged at the top level.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct OutputRecord {
+    /// rdapx run metadata
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// Data derived from reference datasets and follow-up lookups (`--enrich-*`, `--parents`)
+    #[serde(
+        rename = "_enrichment",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub enrichment: Option<Enrichment>,
+    /// rdapx annotations supplied by the user (`--tag`)
+    #[serde(rename = "_rdapx", default, skip_serializing_if = "Option::is_none")]
+    pub rdapx: Option<Annotations>,
+    /// The sponsoring registrar's RDAP object, or `{"error": ...}` (`--source both`)
+    #[serde(
+        rename = "_registrar",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub registrar: Option<Map<String, Value>>,
+    /// The RDAP response object as returned by the server
+    #[serde(flatten)]
+    pub rdap: Map<String, Value>,
+}
+
+/// One `get`/`bulk` JSON record (`--output-schema v2`): the RDAP response is
+/// nested under `rdap` so server fields can never collide with rdapx keys.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct OutputRecordV2 {
+    /// rdapx run metadata
+    #[serde(rename = "_meta")]
+    pub meta: Meta,
+    /// Data derived from reference datasets and follow-up lookups (`--enrich-*`, `--parents`)
+    #[serde(
+        rename = "_enrichment",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub enrichment: Option<Enrichment>,
+    /// rdapx annotations supplied by the user (`--tag`)
+    #[serde(rename = "_rdapx", default, skip_serializing_if = "Option::is_none")]
+    pub rdapx: Option<Annotations>,
+    /// The sponsoring registrar's RDAP object, or `{"error": ...}` (`--source both`)
+    #[serde(
+        rename = "_registrar",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub registrar: Option<Map<String, Value>>,
+    /// The RDAP response object as returned by the server
+    pub rdap: Map<String, Value>,
+}
+
+/// rdapx metadata attached to a record.
+#[derive(Serialize, Deserialize, JsonSchema, Default)]
+pub struct Meta {
+    /// Output schema version of this record (`--output-schema`)
+    pub schema_version: u32,
+    /// Query as given on the command line or input file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// Wall time spent resolving the query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+    /// Set when the query took longer than `--slow-threshold`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow: Option<bool>,
+}
+
+/// User-supplied annotations carried through to every output and sink.
+#[derive(Serialize, Deserialize, JsonSchema, Default)]
+pub struct Annotations {
+    /// `--tag key=value` pairs, merged with per-line tags from bulk input
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default)]
+pub struct Enrichment {
+    /// Row of the IANA registrar registry, keyed by `snake_case` column name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registrar: Option<Map<String, Value>>,
+    /// Covering IP networks (`get --parents`), closest parent first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parents: Option<Vec<NetworkSummary>>,
+    /// Containing block and sibling ASNs (`get --asn-context`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autnum_context: Option<AutnumContext>,
+}
+
+/// Compact view of an RDAP autnum (a single ASN or a block).
+#[derive(Serialize, Deserialize, JsonSchema, Default, Clone)]
+pub struct AutnumSummary {
+    pub start: u32,
+    pub end: u32,
+    pub handle: String,
+    pub name: String,
+}
+
+impl AutnumSummary {
+    pub fn from_rdap(json: &Value) -> Option<Self> {
+        let num = |k: &str| {
+            json.get(k)
+                .and_then(Value::as_u64)
+                .and_then(|n| u32::try_from(n).ok())
+        };
+        let field = |k: &str| {
+            json.get(k)
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string()
+        };
+        Some(Self {
+            start: num("startAutnum")?,
+            end: num("endAutnum")?,
+            handle: field("handle"),
+            name: field("name"),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default)]
+pub struct AutnumContext {
+    /// Registration block holding the ASN, as published by the registry
+    pub block: Option<AutnumSummary>,
+    /// Matching RIR delegation (`rdapx dataset pull rir-delegations`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delegation: Option<Value>,
+    /// Handle of the registrant entity
+    pub org: Option<String>,
+    pub org_name: Option<String>,
+    /// Other ASNs registered to the same org
+    pub siblings: Vec<AutnumSummary>,
+    /// Where siblings came from: `entity` (the org's autnum list) or
+    /// `neighbors` (adjacent ASNs probed)
+    pub source: String,
+}
+
+/// Compact view of an RDAP IP network.
+#[derive(Serialize, Deserialize, JsonSchema, Default, Clone)]
+pub struct NetworkSummary {
+    pub handle: String,
+    pub name: String,
+    pub start_address: String,
+    pub end_address: String,
+    /// Registry allocation type, e.g. `ALLOCATED PORTABLE`
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+impl NetworkSummary {
+    pub fn from_rdap(json: &Value) -> Self {
+        let field = |k: &str| {
+            json.get(k)
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string()
+        };
+        Self {
+            handle: field("handle"),
+            name: field("name"),
+            start_address: field("startAddress"),
+            end_address: field("endAddress"),
+            kind: field("type"),
+        }
+    }
+}
+
+/* --------------------------------- Rows --------------------------------- */
+
+/// Flattened view of an RDAP object used by `--format table` and `--format csv`.
+#[derive(Serialize, Deserialize, JsonSchema, Default)]
+pub struct Row {
+    /// RDAP `objectClassName`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub handle: String,
+    pub name: String,
+    pub country: String,
+    /// Status values joined with `,`
+    pub status: String,
+    /// Sorted, unique entity roles joined with `, `
+    pub roles: String,
+}
+
+impl Row {
+    pub const CSV_HEADER: &'static str = "type,handle,name,country,status,roles";
+
+    pub fn from_rdap(json: &Value) -> Self {
+        let field = |k: &str| {
+            json.get(k)
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string()
+        };
+        let status = json
+            .get("status")
+            .and_then(Value::as_array)
+            .map(|a| {
+                a.iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .unwrap_or_default();
+        let roles: BTreeSet<&str> = json
+            .get("entities")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.get("roles").and_then(Value::as_array))
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        Self {
+            kind: field("objectClassName"),
+            handle: field("handle"),
+            name: field("name"),
+            country: field("country"),
+            status,
+            roles: roles.into_iter().collect::<Vec<_>>().join(", "),
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        [
+            &self.kind,
+            &self.handle,
+            &self.name,
+            &self.country,
+            &self.status,
+            &self.roles,
+        ]
+        .iter()
+        .map(|c| csv_escape(c))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+/// Quote a CSV cell when it contains a delimiter, quote, or line break.
+pub fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/* -------------------------------- Summary ------------------------------- */
+
+/// End-of-run bulk summary (stderr, or JSON via `bulk --summary-file`).
+#[derive(Serialize, Deserialize, JsonSchema, Default)]
+pub struct SummaryReport {
+    pub total: usize,
+    pub ok: usize,
+    pub failed: usize,
+    /// 404 answers emitted as records (`--emit-not-found`)
+    #[serde(default)]
+    pub not_found: usize,
+    /// `--incremental` objects left out because their content didn't change
+    #[serde(default)]
+    pub unchanged: usize,
+    /// Input lines that couldn't be parsed or classified (`--rejects`)
+    #[serde(default)]
+    pub rejected: usize,
+    /// Queries removed by `--prefilter`
+    pub filtered: usize,
+    /// Queries never attempted because the run was aborted
+    pub skipped: usize,
+    pub elapsed_ms: u64,
+    /// Response bytes downloaded during the run
+    #[serde(default)]
+    pub bytes: u64,
+    /// Abort reason when a failure threshold or `--max-bytes` was crossed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aborted: Option<String>,
+    /// Slowest queries, slowest first
+    pub slowest: Vec<QueryLatency>,
+    /// Per-registry latency, sorted by host
+    pub registries: Vec<RegistryLatency>,
+    /// Quotas servers published in rate-limit headers, by host
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rate_limits: BTreeMap<String, RateLimitState>,
+    /// `bulk --shard` this run covered, e.g. `3/8`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct QueryLatency {
+    pub query: String,
+    pub host: String,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct RegistryLatency {
+    pub host: String,
+    pub queries: u32,
+    pub avg_ms: u64,
+    pub max_ms: u64,
+    #[serde(default)]
+    pub bytes: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct RateLimitState {
+    pub limit: Option<u64>,
+    pub remaining: u64,
+    pub reset_in_secs: u64,
+    /// Time spent waiting to stay within the quota
+    pub paced_ms: u64,
+}
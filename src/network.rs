@@ -0,0 +1,120 @@
+//! IP allocation hierarchy for `get --parents`: follow `up` links from a
+//! network towards the RIR top-level block, and when a server publishes none,
+//! re-query ever shorter covering prefixes until a different network answers.
+
+use crate::model::NetworkSummary;
+use crate::{fetch_url, FetchOpts};
+use serde_json::Value;
+use std::net::IpAddr;
+
+/// Upper bound on lookups per walk, so a misbehaving server can't loop us.
+const MAX_STEPS: usize = 32;
+
+fn up_link(json: &Value) -> Option<String> {
+    json.get("links")?
+        .as_array()?
+        .iter()
+        .find(|l| l.get("rel").and_then(Value::as_str) == Some("up"))
+        .and_then(|l| l.get("href").and_then(Value::as_str))
+        .map(str::to_string)
+}
+
+/// First CIDR of the network (RFC 9283 `cidr0_cidrs`) as (address, length).
+fn first_cidr(json: &Value) -> Option<(IpAddr, u8)> {
+    let c = json.get("cidr0_cidrs")?.as_array()?.first()?;
+    let prefix = c
+        .get("v4prefix")
+        .or_else(|| c.get("v6prefix"))
+        .and_then(Value::as_str)?;
+    let len = u8::try_from(c.get("length")?.as_u64()?).ok()?;
+    Some((prefix.parse().ok()?, len))
+}
+
+/// `addr/len` with host bits cleared.
+fn masked(addr: IpAddr, len: u8) -> String {
+    match addr {
+        IpAddr::V4(a) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(len)).unwrap_or(0);
+            format!("{}/{len}", std::net::Ipv4Addr::from(u32::from(a) & mask))
+        }
+        IpAddr::V6(a) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(len)).unwrap_or(0);
+            format!("{}/{len}", std::net::Ipv6Addr::from(u128::from(a) & mask))
+        }
+    }
+}
+
+/// Same server and path as `url`, asking for `cidr` instead.
+fn ip_url(url: &str, cidr: &str) -> Option<String> {
+    let at = url.rfind("/ip/")? + 4;
+    Some(format!("{}{cidr}", &url[..at]))
+}
+
+/// Covering networks of `json` (fetched from `url`), closest parent first.
+pub async fn parents(
+    client: &reqwest::Client,
+    url: &str,
+    json: &Value,
+    opts: &FetchOpts<'_>,
+) -> Vec<NetworkSummary> {
+    let mut chain = Vec::new();
+    let mut seen = vec![NetworkSummary::from_rdap(json).handle];
+    let (mut url, mut current) = (url.to_string(), json.clone());
+    let mut steps = 0;
+
+    'walk: while steps < MAX_STEPS {
+        let mut candidates = Vec::new();
+        if let Some(up) = up_link(&current) {
+            candidates.push(up);
+        } else if let Some((addr, len)) = first_cidr(&current) {
+            candidates.extend(
+                (0..len)
+                    .rev()
+                    .filter_map(|l| ip_url(&url, &masked(addr, l))),
+            );
+        }
+        for next in candidates {
+            steps += 1;
+            if steps > MAX_STEPS {
+                break 'walk;
+            }
+            let Ok(parent) = fetch_url(client, &next, &next, opts).await else {
+                break 'walk; // typically 404 once we step outside the RIR's space
+            };
+            let summary = NetworkSummary::from_rdap(&parent);
+            if seen.contains(&summary.handle) {
+                continue; // server answered with a network we already have
+            }
+            seen.push(summary.handle.clone());
+            chain.push(summary);
+            (url, current) = (next, parent);
+            continue 'walk;
+        }
+        break;
+    }
+    chain
+}
+
+/// Indented tree from the top-level block down to the queried network.
+pub fn print_tree(network: &NetworkSummary, parents: &[NetworkSummary]) {
+    use colored::Colorize;
+    for (depth, n) in parents.iter().rev().chain([network]).enumerate() {
+        let indent = if depth == 0 {
+            String::new()
+        } else {
+            format!("{}└─ ", "   ".repeat(depth - 1))
+        };
+        let kind = if n.kind.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", n.kind.to_lowercase())
+        };
+        println!(
+            "{indent}{} - {}  {} {}{kind}",
+            n.start_address,
+            n.end_address,
+            n.handle.bold(),
+            n.name
+        );
+    }
+}
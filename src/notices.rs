@@ -0,0 +1,80 @@
+//! Notices section of table output. Registries use RFC 9083 `notices` for
+//! operational policy (terms of service, rate limits, redaction, inaccuracy
+//! reporting), so the table shows each one as title, first line, and link;
+//! `--full-notices` prints the complete text instead.
+
+use serde_json::Value;
+use std::sync::OnceLock;
+
+static FULL: OnceLock<bool> = OnceLock::new();
+
+pub fn init(full: bool) {
+    let _ = FULL.set(full);
+}
+
+fn full() -> bool {
+    FULL.get().copied().unwrap_or(false)
+}
+
+pub struct Notice {
+    pub title: String,
+    /// Description lines; only the first unless `--full-notices`
+    pub lines: Vec<String>,
+    /// Policy link: the first `href`, preferring a non-`self` relation
+    pub link: Option<String>,
+}
+
+fn link(notice: &Value) -> Option<String> {
+    let links: Vec<&Value> = notice
+        .get("links")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|l| l.get("href").and_then(Value::as_str).is_some())
+        .collect();
+    links
+        .iter()
+        .find(|l| l.get("rel").and_then(Value::as_str) != Some("self"))
+        .or_else(|| links.first())
+        .and_then(|l| l.get("href"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+/// The response's notices in the `--lang` language (all of them without it).
+pub fn collect(json: &Value) -> Vec<Notice> {
+    let object_lang = json.get("lang").and_then(Value::as_str);
+    let full = full();
+    json.get("notices")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|n| crate::i18n::wanted(n, object_lang))
+        .map(|n| {
+            let mut lines: Vec<String> = n
+                .get("description")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .flat_map(str::lines)
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(str::to_string)
+                .collect();
+            if !full {
+                lines.truncate(1);
+            }
+            Notice {
+                title: n
+                    .get("title")
+                    .and_then(Value::as_str)
+                    .unwrap_or("")
+                    .to_string(),
+                lines,
+                link: link(n),
+            }
+        })
+        .filter(|n| !n.title.is_empty() || !n.lines.is_empty() || n.link.is_some())
+        .collect()
+}
@@ -0,0 +1,137 @@
+//! `rdapx pivot`: find every object in a `sqlite:` sink database that shares
+//! an attribute (nameserver, registrar, registrant email) with a given value.
+//! The stored records are scanned in full, so it works on databases written
+//! by any rdapx version and with either output schema.
+
+#[cfg(feature = "sqlite")]
+use crate::fields::Attribute;
+use crate::table::{Cell, Column, Tabular};
+use serde::Serialize;
+#[cfg(feature = "sqlite")]
+use serde_json::Value;
+use std::path::Path;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum PivotBy {
+    Nameserver,
+    Registrar,
+    RegistrantEmail,
+}
+
+#[cfg(feature = "sqlite")]
+impl PivotBy {
+    const fn attribute(self) -> Attribute {
+        match self {
+            Self::Nameserver => Attribute::Nameserver,
+            Self::Registrar => Attribute::Registrar,
+            Self::RegistrantEmail => Attribute::RegistrantEmail,
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn normalize(v: &str) -> String {
+    v.trim().trim_end_matches('.').to_lowercase()
+}
+
+/// One object sharing the attribute; repeated writes of the same object
+/// keep the most recent.
+#[derive(Serialize, Debug)]
+pub struct Hit {
+    pub kind: String,
+    pub name: String,
+    pub matched: String,
+    pub written_at: String,
+}
+
+impl Tabular for Hit {
+    const COLUMNS: &'static [Column] = &[
+        Column("KIND", 10),
+        Column("OBJECT", 40),
+        Column("WRITTEN", 0),
+    ];
+    const CSV_HEADER: &'static [&'static str] = &["kind", "name", "matched", "written_at"];
+
+    fn table_row(&self) -> Vec<Cell> {
+        vec![
+            self.kind.as_str().into(),
+            self.name.as_str().into(),
+            self.written_at.as_str().into(),
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.kind.clone(),
+            self.name.clone(),
+            self.matched.clone(),
+            self.written_at.clone(),
+        ]
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub fn search(db: &Path, by: PivotBy, value: &str) -> Result<Vec<Hit>, Box<dyn std::error::Error>> {
+    use rusqlite::OpenFlags;
+    use std::collections::BTreeMap;
+
+    let conn = rusqlite::Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("{}: {e}", db.display()))?;
+    let mut stmt = conn
+        .prepare("SELECT kind, name, handle, written_at, record FROM records ORDER BY id")
+        .map_err(|e| format!("{}: {e}", db.display()))?;
+    let want = normalize(value);
+    let mut hits: BTreeMap<String, Hit> = BTreeMap::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let record: String = row.get(4)?;
+        let Ok(record) = serde_json::from_str::<Value>(&record) else {
+            continue;
+        };
+        let raw = record.get("rdap").unwrap_or(&record);
+        let Some(matched) = by
+            .attribute()
+            .values(raw)
+            .into_iter()
+            .find(|v| normalize(v) == want)
+        else {
+            continue;
+        };
+        let kind: Option<String> = row.get(0)?;
+        let name: Option<String> = row.get(1)?;
+        let handle: Option<String> = row.get(2)?;
+        let name = raw
+            .get("ldhName")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .into_iter()
+            .chain(handle)
+            .chain(name)
+            .find(|n| !n.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        hits.insert(
+            name.clone(),
+            Hit {
+                kind: kind.unwrap_or_default(),
+                name,
+                matched,
+                written_at: row.get(3)?,
+            },
+        );
+    }
+    Ok(hits.into_values().collect())
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn search(
+    db: &Path,
+    _by: PivotBy,
+    _value: &str,
+) -> Result<Vec<Hit>, Box<dyn std::error::Error>> {
+    Err(format!(
+        "{}: pivot needs rdapx built with the `sqlite` feature",
+        db.display()
+    )
+    .into())
+}
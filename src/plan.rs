@@ -0,0 +1,173 @@
+//! `bulk --plan`: classify the input without fetching anything and report
+//! what a run would do — object types, destination registries, fresh cache
+//! hits, lines that can't be queried, and a rough duration estimate.
+
+use crate::{Format, Kind};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Assumed round trip per uncached query when estimating run time; registry
+/// responses typically land between 200ms and 1s.
+const ASSUMED_LATENCY: Duration = Duration::from_millis(600);
+
+#[derive(Serialize, Debug)]
+pub struct Invalid {
+    /// 1-based position among the non-blank input lines
+    pub entry: usize,
+    pub line: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Plan {
+    pub total: usize,
+    pub by_type: BTreeMap<&'static str, usize>,
+    pub by_registry: BTreeMap<String, usize>,
+    /// Served from a fresh cache entry, so no request is made
+    pub cached: usize,
+    /// Removed by `--prefilter`
+    pub filtered: usize,
+    pub invalid: Vec<Invalid>,
+    pub concurrency: usize,
+    pub estimated_secs: u64,
+}
+
+/// Parse a bulk input line and reject queries that can't be looked up
+/// (shared with `bulk --rejects`).
+pub fn validate(line: &str) -> Result<(String, crate::Tags), String> {
+    let (q, tags) = crate::parse_bulk_line(line)?;
+    let (kind, norm) = crate::normalize(&q);
+    check(kind, &norm).map_or(Ok((q, tags)), Err)
+}
+
+/// Why `q` can't be looked up, if it can't.
+fn check(kind: Kind, q: &str) -> Option<String> {
+    if q.is_empty() {
+        return Some("empty query".into());
+    }
+    if q.chars().any(char::is_whitespace) {
+        return Some("contains whitespace".into());
+    }
+    match kind {
+        Kind::Asn => None,
+        Kind::Ip => {
+            let (addr, len) = q.split_once('/').map_or((q, None), |(a, l)| (a, Some(l)));
+            let Ok(ip) = addr.parse::<IpAddr>() else {
+                return Some("not a valid IP address".into());
+            };
+            let max = if ip.is_ipv4() { 32 } else { 128 };
+            match len.map(str::parse::<u8>) {
+                Some(Ok(n)) if n > max => Some(format!("prefix length above /{max}")),
+                Some(Err(_)) => Some("bad prefix length".into()),
+                _ => None,
+            }
+        }
+        Kind::Entity => q
+            .chars()
+            .any(|c| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .then(|| "not a domain, IP, AS number, or entity handle".into()),
+        Kind::Domain => {
+            let name = q.trim_end_matches('.');
+            if !name.contains('.') {
+                return Some("not a domain, IP, AS number, or tagged entity handle".into());
+            }
+            let bad = name.split('.').any(|l| {
+                l.is_empty()
+                    || l.len() > 63
+                    || l.starts_with('-')
+                    || l.ends_with('-')
+                    || l.chars().any(|c| {
+                        c.is_ascii() && !(c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                    })
+            });
+            bad.then(|| "malformed domain label".into())
+        }
+    }
+}
+
+/// Build the plan. `keep` applies `--prefilter` and `ttl` is the default
+/// cache TTL (per-class TTLs from config still apply).
+pub fn build(
+    lines: &[String],
+    concurrency: usize,
+    ttl: Duration,
+    no_cache: bool,
+    keep: impl Fn(&str) -> bool,
+) -> Plan {
+    let mut plan = Plan {
+        total: lines.len(),
+        concurrency,
+        ..Plan::default()
+    };
+    for (i, line) in lines.iter().enumerate() {
+        let q = match validate(line) {
+            Ok((q, _)) => q,
+            Err(reason) => {
+                plan.invalid.push(Invalid {
+                    entry: i + 1,
+                    line: line.clone(),
+                    reason,
+                });
+                continue;
+            }
+        };
+        let kind = crate::normalize(&q).0;
+        if !keep(&q) {
+            plan.filtered += 1;
+            continue;
+        }
+        *plan.by_type.entry(kind.as_str()).or_default() += 1;
+        let url = crate::query_url(&q);
+        if !no_cache
+            && matches!(
+                crate::load_cache(&url, crate::cache_ttl(&url, ttl)),
+                Ok(Some(_))
+            )
+        {
+            plan.cached += 1;
+            continue;
+        }
+        *plan.by_registry.entry(crate::url_host(&url)).or_default() += 1;
+    }
+    let fetches: usize = plan.by_registry.values().sum();
+    let waves = fetches.div_ceil(concurrency.max(1));
+    let ms = ASSUMED_LATENCY.as_millis() * waves as u128;
+    plan.estimated_secs = u64::try_from(ms.div_ceil(1000)).unwrap_or(u64::MAX);
+    plan
+}
+
+pub fn print(plan: &Plan, fmt: Format) {
+    if matches!(fmt, Format::Json | Format::Pretty) {
+        crate::output(&serde_json::to_value(plan).unwrap_or_default(), fmt);
+        return;
+    }
+    println!("{} {} input line(s)", "Plan:".cyan().bold(), plan.total);
+    println!("{}", "By type:".blue().bold());
+    for (kind, n) in &plan.by_type {
+        println!("  {kind:<8} {n}");
+    }
+    println!("{}", "By registry (uncached):".blue().bold());
+    for (host, n) in &plan.by_registry {
+        println!("  {host:<32} {n}");
+    }
+    println!("  {:<32} {}", "(fresh cache)", plan.cached);
+    if plan.filtered > 0 {
+        println!("  {:<32} {}", "(prefiltered)", plan.filtered);
+    }
+    if !plan.invalid.is_empty() {
+        println!("{} {}", "Unsupported:".red().bold(), plan.invalid.len());
+        for inv in &plan.invalid {
+            println!("  entry {}: {} ({})", inv.entry, inv.line, inv.reason);
+        }
+    }
+    println!(
+        "{} ~{} at concurrency {} (assuming {}ms per request, no retries)",
+        "Estimate:".cyan().bold(),
+        crate::short_age(plan.estimated_secs),
+        plan.concurrency,
+        ASSUMED_LATENCY.as_millis()
+    );
+}
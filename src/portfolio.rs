@@ -0,0 +1,268 @@
+//! Named target lists ("portfolios") kept in `<data dir>/rdapx/portfolios.json`
+//! (or a project's `.rdapx/`), so recurring checks can reference `corp`
+//! instead of an ad-hoc file.
+
+use crate::table::{Cell, Column, Tabular};
+use colored::Color;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+pub type Portfolios = BTreeMap<String, Vec<String>>;
+
+fn portfolios_path() -> io::Result<PathBuf> {
+    let dir = crate::project::data_dir()?;
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join("portfolios.json"))
+}
+
+pub fn load() -> io::Result<Portfolios> {
+    match fs::read_to_string(portfolios_path()?) {
+        Ok(raw) => serde_json::from_str(&raw).map_err(io::Error::other),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Portfolios::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn save(all: &Portfolios) -> io::Result<()> {
+    let path = portfolios_path()?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(all)?)?;
+    fs::rename(tmp, path)
+}
+
+/// Add targets to `name` (creating it), skipping ones already present.
+/// Returns how many were added.
+pub fn add(name: &str, targets: &[String]) -> io::Result<usize> {
+    let mut all = load()?;
+    let list = all.entry(name.to_string()).or_default();
+    let before = list.len();
+    for t in targets {
+        let t = t.trim().to_lowercase();
+        if !t.is_empty() && !list.contains(&t) {
+            list.push(t);
+        }
+    }
+    let added = list.len() - before;
+    save(&all)?;
+    Ok(added)
+}
+
+/// Remove targets from `name`; with no targets, delete the portfolio.
+/// Returns how many targets were removed.
+pub fn remove(name: &str, targets: &[String]) -> Result<usize, Box<dyn Error>> {
+    let mut all = load()?;
+    let Some(list) = all.get_mut(name) else {
+        return Err(format!("no portfolio named '{name}'").into());
+    };
+    let removed = if targets.is_empty() {
+        let n = list.len();
+        all.remove(name);
+        n
+    } else {
+        let before = list.len();
+        list.retain(|t| !targets.iter().any(|r| r.trim().eq_ignore_ascii_case(t)));
+        before - list.len()
+    };
+    save(&all)?;
+    Ok(removed)
+}
+
+pub fn targets(name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let targets = load()?
+        .remove(name)
+        .ok_or_else(|| format!("no portfolio named '{name}'"))?;
+    Ok(targets)
+}
+
+/// One line of `portfolio check` output.
+#[derive(Serialize, Debug)]
+pub struct CheckRow {
+    pub query: String,
+    pub ok: bool,
+    pub registrar: Option<String>,
+    pub expiry: Option<String>,
+    pub days_left: Option<i64>,
+    pub status: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl Tabular for CheckRow {
+    const COLUMNS: &'static [Column] = &[
+        Column("TARGET", 32),
+        Column("EXPIRY", 10),
+        Column("DAYS LEFT", -9),
+        Column("REGISTRAR", 30),
+        Column("STATUS", 0),
+    ];
+    const CSV_HEADER: &'static [&'static str] = &[
+        "query",
+        "ok",
+        "expiry",
+        "days_left",
+        "registrar",
+        "status",
+        "error",
+    ];
+
+    fn table_row(&self) -> Vec<Cell> {
+        if let Some(err) = &self.error {
+            let dash = || Cell::from("-");
+            return vec![
+                self.query.as_str().into(),
+                dash(),
+                dash(),
+                dash(),
+                Cell::colored(err.as_str(), Color::Red),
+            ];
+        }
+        let days = self
+            .days_left
+            .map_or_else(|| "-".to_string(), |d| d.to_string());
+        let days = match self.days_left {
+            Some(d) if d < 30 => Cell::colored(days, Color::Red),
+            Some(d) if d < 90 => Cell::colored(days, Color::Yellow),
+            _ => days.into(),
+        };
+        vec![
+            self.query.as_str().into(),
+            Cell::or_dash(self.expiry.as_deref().and_then(|e| e.get(..10))),
+            days,
+            Cell::or_dash(self.registrar.as_deref()),
+            self.status.join(" ").into(),
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.query.clone(),
+            self.ok.to_string(),
+            self.expiry.clone().unwrap_or_default(),
+            self.days_left.map(|d| d.to_string()).unwrap_or_default(),
+            self.registrar.clone().unwrap_or_default(),
+            self.status.join(" "),
+            self.error.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+impl CheckRow {
+    pub fn from_rdap(query: &str, json: &Value, today: i64) -> Self {
+        let expiry = crate::fields::expiry(json);
+        Self {
+            query: query.to_string(),
+            ok: true,
+            registrar: crate::fields::registrar(json),
+            days_left: expiry
+                .as_deref()
+                .and_then(crate::parse_date_days)
+                .map(|d| d - today),
+            expiry,
+            status: crate::fields::statuses(json),
+            error: None,
+        }
+    }
+
+    pub fn failed(query: &str, error: String) -> Self {
+        Self {
+            query: query.to_string(),
+            ok: false,
+            registrar: None,
+            expiry: None,
+            days_left: None,
+            status: Vec::new(),
+            error: Some(error),
+        }
+    }
+}
+
+/// Registrar locks `audit locks` expects on every domain (EPP status names).
+pub const LOCKS: [&str; 3] = [
+    "clientTransferProhibited",
+    "clientDeleteProhibited",
+    "clientUpdateProhibited",
+];
+
+/// RDAP spells EPP statuses as words ("client transfer prohibited").
+fn status_key(s: &str) -> String {
+    s.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// One line of `audit locks` output.
+#[derive(Serialize, Debug)]
+pub struct LockRow {
+    pub query: String,
+    pub missing: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl Tabular for LockRow {
+    const COLUMNS: &'static [Column] = &[
+        Column("TARGET", 32),
+        Column("RESULT", 8),
+        Column("MISSING", 0),
+    ];
+    const CSV_HEADER: &'static [&'static str] = &["query", "ok", "missing", "error"];
+
+    fn table_row(&self) -> Vec<Cell> {
+        let (result, detail) = match &self.error {
+            Some(err) => (
+                Cell::colored("ERROR", Color::Red),
+                Cell::colored(err.as_str(), Color::Red),
+            ),
+            None if self.missing.is_empty() => (Cell::colored("LOCKED", Color::Green), "".into()),
+            None => (
+                Cell::colored("EXPOSED", Color::Red),
+                self.missing.join(" ").into(),
+            ),
+        };
+        vec![self.query.as_str().into(), result, detail]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.query.clone(),
+            self.ok().to_string(),
+            self.missing.join(" "),
+            self.error.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+impl LockRow {
+    pub fn from_rdap(query: &str, json: &Value) -> Self {
+        let present: Vec<String> = crate::fields::statuses(json)
+            .iter()
+            .map(|s| status_key(s))
+            .collect();
+        Self {
+            query: query.to_string(),
+            missing: LOCKS
+                .iter()
+                .filter(|l| !present.contains(&status_key(l)))
+                .map(|l| (*l).to_string())
+                .collect(),
+            error: None,
+        }
+    }
+
+    pub fn failed(query: &str, error: String) -> Self {
+        Self {
+            query: query.to_string(),
+            missing: Vec::new(),
+            error: Some(error),
+        }
+    }
+
+    /// Locked as expected; lookups that failed count as exceptions.
+    pub fn ok(&self) -> bool {
+        self.error.is_none() && self.missing.is_empty()
+    }
+}
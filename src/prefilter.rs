@@ -0,0 +1,108 @@
+//! `bulk --prefilter` predicates evaluated against offline delegation data.
+//!
+//! Grammar: `cond (&& cond)* (|| cond (&& cond)*)*` where `cond` is
+//! `field op value`, `op` is one of `== != >= <= > <`, and `field` is one of
+//! `kind`, `rir`, `country`, `status`, `date`. String comparisons ignore case;
+//! dates compare as `YYYY-MM-DD`. Queries without delegation data (domains,
+//! unknown ranges) have empty `rir`/`country`/`status`/`date`.
+
+use crate::dataset::Delegation;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    Kind,
+    Rir,
+    Country,
+    Status,
+    Date,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+}
+
+#[derive(Clone, Debug)]
+struct Cond {
+    field: Field,
+    op: Op,
+    value: String,
+}
+
+/// Disjunction of conjunctions.
+#[derive(Clone, Debug)]
+pub struct Prefilter {
+    any: Vec<Vec<Cond>>,
+}
+
+impl Prefilter {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let any = expr
+            .split("||")
+            .map(|alt| alt.split("&&").map(parse_cond).collect())
+            .collect::<Result<Vec<Vec<Cond>>, String>>()?;
+        Ok(Self { any })
+    }
+
+    /// Whether evaluation requires the RIR delegation dataset.
+    pub fn needs_delegations(&self) -> bool {
+        self.any.iter().flatten().any(|c| c.field != Field::Kind)
+    }
+
+    pub fn matches(&self, kind: &str, d: Option<&Delegation>) -> bool {
+        self.any.iter().any(|all| {
+            all.iter().all(|c| {
+                let actual = match c.field {
+                    Field::Kind => kind,
+                    Field::Rir => d.map_or("", |d| d.rir.as_str()),
+                    Field::Country => d.map_or("", |d| d.country.as_str()),
+                    Field::Status => d.map_or("", |d| d.status.as_str()),
+                    Field::Date => d.map_or("", |d| d.date.as_str()),
+                };
+                let ord = actual.to_ascii_lowercase().cmp(&c.value);
+                match c.op {
+                    Op::Eq => ord.is_eq(),
+                    Op::Ne => ord.is_ne(),
+                    Op::Ge => ord.is_ge(),
+                    Op::Le => ord.is_le(),
+                    Op::Gt => ord.is_gt(),
+                    Op::Lt => ord.is_lt(),
+                }
+            })
+        })
+    }
+}
+
+fn parse_cond(s: &str) -> Result<Cond, String> {
+    const OPS: [(&str, Op); 6] = [
+        ("==", Op::Eq),
+        ("!=", Op::Ne),
+        (">=", Op::Ge),
+        ("<=", Op::Le),
+        (">", Op::Gt),
+        ("<", Op::Lt),
+    ];
+    let (field, op, value) = OPS
+        .iter()
+        .find_map(|(tok, op)| s.split_once(tok).map(|(f, v)| (f, *op, v)))
+        .ok_or_else(|| format!("invalid condition '{}' (expected e.g. rir==ripe)", s.trim()))?;
+    let field = match field.trim().to_ascii_lowercase().as_str() {
+        "kind" => Field::Kind,
+        "rir" => Field::Rir,
+        "country" | "cc" => Field::Country,
+        "status" => Field::Status,
+        "date" | "allocated" => Field::Date,
+        other => return Err(format!("unknown prefilter field '{other}'")),
+    };
+    let value = value.trim().trim_matches(|c| c == '\'' || c == '"');
+    Ok(Cond {
+        field,
+        op,
+        value: value.to_ascii_lowercase(),
+    })
+}
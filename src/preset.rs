@@ -0,0 +1,242 @@
+//! `--preset`: named column sets for CSV and NDJSON output, so records load
+//! into common consumers without per-team glue. A preset fixes which fields
+//! are emitted, what they're called, how dates are written, and how list
+//! values are joined in CSV (NDJSON keeps them as arrays).
+//!
+//! Presets apply to stdout and to `.csv` / `.ndjson` file sinks; other sinks
+//! (sqlite, Elasticsearch, binary formats) still receive full records.
+
+use crate::fields;
+use crate::model::csv_escape;
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+use std::sync::OnceLock;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// `snake_case` fields, dates as epoch seconds, lists joined with `|`
+    Splunk,
+    /// BigQuery-safe column names, `YYYY-MM-DD HH:MM:SS` UTC timestamps
+    Bigquery,
+    /// Title Case columns and plain dates, as in WHOIS-history exports
+    DomaintoolsLike,
+}
+
+/// A value a preset column is built from.
+#[derive(Clone, Copy)]
+enum Col {
+    Query,
+    Type,
+    Name,
+    Handle,
+    Registrar,
+    Created,
+    Updated,
+    Expires,
+    Status,
+    Nameservers,
+    Country,
+    AbuseEmail,
+}
+
+#[derive(Clone, Copy)]
+enum Dates {
+    Epoch,
+    Timestamp,
+    Day,
+}
+
+struct Spec {
+    columns: &'static [(&'static str, Col)],
+    dates: Dates,
+    list_sep: &'static str,
+}
+
+const SPLUNK: Spec = Spec {
+    columns: &[
+        ("query", Col::Query),
+        ("object_type", Col::Type),
+        ("domain", Col::Name),
+        ("handle", Col::Handle),
+        ("registrar", Col::Registrar),
+        ("created", Col::Created),
+        ("updated", Col::Updated),
+        ("expires", Col::Expires),
+        ("status", Col::Status),
+        ("nameservers", Col::Nameservers),
+        ("country", Col::Country),
+        ("abuse_email", Col::AbuseEmail),
+    ],
+    dates: Dates::Epoch,
+    list_sep: "|",
+};
+
+const BIGQUERY: Spec = Spec {
+    columns: &[
+        ("query", Col::Query),
+        ("object_class", Col::Type),
+        ("ldh_name", Col::Name),
+        ("handle", Col::Handle),
+        ("registrar_name", Col::Registrar),
+        ("creation_time", Col::Created),
+        ("last_changed_time", Col::Updated),
+        ("expiration_time", Col::Expires),
+        ("statuses", Col::Status),
+        ("name_servers", Col::Nameservers),
+        ("country_code", Col::Country),
+        ("abuse_email", Col::AbuseEmail),
+    ],
+    dates: Dates::Timestamp,
+    list_sep: "|",
+};
+
+const DOMAINTOOLS_LIKE: Spec = Spec {
+    columns: &[
+        ("Domain Name", Col::Name),
+        ("Registrar", Col::Registrar),
+        ("Create Date", Col::Created),
+        ("Update Date", Col::Updated),
+        ("Expiration Date", Col::Expires),
+        ("Status", Col::Status),
+        ("Name Servers", Col::Nameservers),
+        ("Country", Col::Country),
+        ("Abuse Email", Col::AbuseEmail),
+    ],
+    dates: Dates::Day,
+    list_sep: ", ",
+};
+
+impl Preset {
+    const fn spec(self) -> &'static Spec {
+        match self {
+            Self::Splunk => &SPLUNK,
+            Self::Bigquery => &BIGQUERY,
+            Self::DomaintoolsLike => &DOMAINTOOLS_LIKE,
+        }
+    }
+}
+
+static PRESET: OnceLock<Preset> = OnceLock::new();
+
+pub fn init(preset: Option<Preset>) {
+    if let Some(p) = preset {
+        let _ = PRESET.set(p);
+    }
+}
+
+fn active() -> Option<&'static Spec> {
+    PRESET.get().map(|p| p.spec())
+}
+
+/// Unix seconds for an RFC 3339 date (`YYYY-MM-DDTHH:MM:SS...`).
+fn epoch(date: &str) -> Option<i64> {
+    let days = crate::parse_date_days(date)?;
+    let secs = date.get(11..19).map_or(0, |t| {
+        t.split(':')
+            .filter_map(|n| n.parse::<i64>().ok())
+            .fold(0, |acc, n| acc * 60 + n)
+    });
+    Some(days * 86_400 + secs)
+}
+
+fn date(value: Option<String>, style: Dates) -> Value {
+    let Some(d) = value else {
+        return Value::Null;
+    };
+    match style {
+        Dates::Epoch => epoch(&d).map_or(Value::String(d), Value::from),
+        Dates::Timestamp => {
+            epoch(&d)
+                .and_then(|s| u64::try_from(s).ok())
+                .map_or(Value::String(d), |s| {
+                    Value::from(
+                        crate::format_timestamp(s)
+                            .replace('T', " ")
+                            .replace('Z', ""),
+                    )
+                })
+        }
+        Dates::Day => Value::from(d.get(..10).unwrap_or(&d)),
+    }
+}
+
+fn text(s: Option<String>) -> Value {
+    s.map_or(Value::Null, Value::from)
+}
+
+fn extract(rdap: &Value, meta: Option<&Value>, col: Col, dates: Dates) -> Value {
+    let str_field = |k: &str| rdap.get(k).and_then(Value::as_str).map(str::to_string);
+    match col {
+        Col::Query => text(
+            meta.and_then(|m| m.get("query"))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .or_else(|| str_field("ldhName"))
+                .or_else(|| str_field("handle")),
+        ),
+        Col::Type => text(str_field("objectClassName")),
+        Col::Name => text(
+            str_field("ldhName")
+                .map(|n| n.to_lowercase())
+                .or_else(|| str_field("name")),
+        ),
+        Col::Handle => text(str_field("handle")),
+        Col::Registrar => text(fields::registrar(rdap)),
+        Col::Created => date(fields::event_date(rdap, "registration"), dates),
+        Col::Updated => date(fields::event_date(rdap, "last changed"), dates),
+        Col::Expires => date(fields::expiry(rdap), dates),
+        Col::Status => json!(fields::statuses(rdap)),
+        Col::Nameservers => json!(fields::nameservers(rdap)),
+        Col::Country => text(str_field("country")),
+        Col::AbuseEmail => text(fields::abuse_email(rdap)),
+    }
+}
+
+/// Columns of the active preset for one output record (bare RDAP or schema
+/// v2 envelope).
+fn columns(record: &Value, spec: &Spec) -> Vec<(&'static str, Value)> {
+    let rdap = record.get("rdap").unwrap_or(record);
+    let meta = record.get("_meta");
+    spec.columns
+        .iter()
+        .map(|&(name, col)| (name, extract(rdap, meta, col, spec.dates)))
+        .collect()
+}
+
+/// The record reshaped by the active preset; `None` without `--preset`.
+pub fn record(record: &Value) -> Option<Value> {
+    let spec = active()?;
+    let map: Map<String, Value> = columns(record, spec)
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    Some(Value::Object(map))
+}
+
+/// CSV header line of the active preset.
+pub fn csv_header() -> Option<String> {
+    let spec = active()?;
+    let names: Vec<String> = spec.columns.iter().map(|(n, _)| csv_escape(n)).collect();
+    Some(names.join(","))
+}
+
+/// One CSV line for `record` under the active preset.
+pub fn csv_row(record: &Value) -> Option<String> {
+    let spec = active()?;
+    let cells: Vec<String> = columns(record, spec)
+        .into_iter()
+        .map(|(_, v)| match v {
+            Value::Null => String::new(),
+            Value::String(s) => csv_escape(&s),
+            Value::Array(items) => csv_escape(
+                &items
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(spec.list_sep),
+            ),
+            other => other.to_string(),
+        })
+        .collect();
+    Some(cells.join(","))
+}
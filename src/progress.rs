@@ -0,0 +1,110 @@
+//! `bulk --progress-format`: periodic progress reports for long runs, as
+//! readable lines or NDJSON events for orchestrators, on stderr or a file
+//! (which may be a named pipe; opening one waits for its reader).
+
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Instant;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// One human-readable line per report
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// One progress report; `event` is `done` for the final one.
+#[derive(Serialize, Debug)]
+struct Report {
+    event: &'static str,
+    processed: usize,
+    total: usize,
+    ok: usize,
+    failed: usize,
+    /// Queries finished per second so far
+    rate: f64,
+    elapsed_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    eta_secs: Option<u64>,
+}
+
+pub struct Progress {
+    format: ProgressFormat,
+    out: Box<dyn Write + Send>,
+    total: usize,
+    started: Instant,
+}
+
+impl Progress {
+    pub fn new(format: ProgressFormat, path: Option<&Path>, total: usize) -> io::Result<Self> {
+        let out: Box<dyn Write + Send> = match path {
+            Some(p) => Box::new(
+                fs::File::create(p)
+                    .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", p.display())))?,
+            ),
+            None => Box::new(io::stderr()),
+        };
+        Ok(Self {
+            format,
+            out,
+            total,
+            started: Instant::now(),
+        })
+    }
+
+    /// Periodic report; the caller drives the interval.
+    pub fn tick(&mut self, processed: usize, ok: usize, failed: usize) {
+        self.emit("progress", processed, ok, failed);
+    }
+
+    /// Final report, always written.
+    pub fn finish(&mut self, processed: usize, ok: usize, failed: usize) {
+        self.emit("done", processed, ok, failed);
+    }
+
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn emit(&mut self, event: &'static str, processed: usize, ok: usize, failed: usize) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            processed as f64 / elapsed
+        } else {
+            0.0
+        };
+        let left = self.total.saturating_sub(processed);
+        let eta_secs = (rate > 0.0 && left > 0).then(|| (left as f64 / rate).ceil() as u64);
+        let e = Report {
+            event,
+            processed,
+            total: self.total,
+            ok,
+            failed,
+            rate: (rate * 10.0).round() / 10.0,
+            elapsed_secs: elapsed as u64,
+            eta_secs,
+        };
+        let line = match self.format {
+            ProgressFormat::Json => serde_json::to_string(&e).unwrap_or_default(),
+            ProgressFormat::Text => {
+                let pct = processed * 100 / self.total.max(1);
+                let eta = e
+                    .eta_secs
+                    .map(|s| format!(", ETA {s}s"))
+                    .unwrap_or_default();
+                format!(
+                    "Progress: {processed}/{} ({pct}%), {ok} ok, {failed} failed, {:.1}/s{eta}",
+                    self.total, e.rate
+                )
+            }
+        };
+        // progress is best-effort: a closed pipe must not fail the run
+        let _ = writeln!(self.out, "{line}").and_then(|()| self.out.flush());
+    }
+}
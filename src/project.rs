@@ -0,0 +1,75 @@
+//! Project directories. `rdapx init` creates a `.rdapx/` directory holding
+//! its own `config.toml`, cache, history, and portfolios; any command run in
+//! that directory or below uses it instead of the per-user locations, so
+//! investigations and datasets stay isolated.
+
+use directories::BaseDirs;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub const DIR: &str = ".rdapx";
+
+const CONFIG_TEMPLATE: &str = "\
+# rdapx project configuration; see the README for all settings.
+#
+# [history]
+# keep_last = 50
+#
+# [cache.ttl]
+# domain = 86400
+";
+
+static ROOT: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// The nearest `.rdapx/` directory at or above the working directory.
+pub fn root() -> Option<&'static Path> {
+    ROOT.get_or_init(|| {
+        let cwd = std::env::current_dir().ok()?;
+        cwd.ancestors().map(|d| d.join(DIR)).find(|d| d.is_dir())
+    })
+    .as_deref()
+}
+
+fn base() -> io::Result<BaseDirs> {
+    BaseDirs::new().ok_or_else(|| io::Error::other("no home"))
+}
+
+/// Directory holding `config.toml`.
+pub fn config_dir() -> io::Result<PathBuf> {
+    match root() {
+        Some(r) => Ok(r.to_path_buf()),
+        None => Ok(base()?.config_dir().join("rdapx")),
+    }
+}
+
+pub fn cache_dir() -> io::Result<PathBuf> {
+    match root() {
+        Some(r) => Ok(r.join("cache")),
+        None => Ok(base()?.cache_dir().join("rdapx")),
+    }
+}
+
+/// Directory for history and portfolios.
+pub fn data_dir() -> io::Result<PathBuf> {
+    match root() {
+        Some(r) => Ok(r.to_path_buf()),
+        None => Ok(base()?.data_dir().join("rdapx")),
+    }
+}
+
+/// Create `<dir>/.rdapx/` with a starter config. Returns the project
+/// directory and whether it was newly created.
+pub fn init(dir: &Path) -> io::Result<(PathBuf, bool)> {
+    let root = dir.join(DIR);
+    if root.is_dir() {
+        return Ok((root, false));
+    }
+    fs::create_dir_all(root.join("cache"))?;
+    fs::create_dir_all(root.join("history"))?;
+    fs::write(root.join("config.toml"), CONFIG_TEMPLATE)?;
+    // the cache is disposable; history and portfolios are worth keeping
+    fs::write(root.join(".gitignore"), "cache/\n")?;
+    Ok((root, true))
+}
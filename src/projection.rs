@@ -0,0 +1,191 @@
+//! `--fields`: keep only the listed top-level RDAP members in output.
+//! `--roles`: prune `entities` to contacts with the listed roles.
+//!
+//! Both apply when a record is emitted, so the cache and history see the
+//! complete response. The exception is a response whose `Content-Length`
+//! exceeds [`STREAM_THRESHOLD`]: with `--fields` it is parsed through
+//! [`Projected`], which skips unselected members (typically huge `entities`
+//! arrays from some RIRs) as they stream in without ever allocating them.
+//! Such partial objects bypass the cache and history.
+
+use clap::ValueEnum;
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Bodies larger than this are parsed with `--fields` already applied.
+const STREAM_THRESHOLD: u64 = 4 << 20;
+
+/// Members kept regardless of `--fields`: object identity and the error
+/// shape callers inspect.
+const ALWAYS: [&str; 3] = ["objectClassName", "errorCode", "rdapConformance"];
+
+/// Entity roles (RFC 9083 section 10.2.4).
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Registrant,
+    Technical,
+    Administrative,
+    Abuse,
+    Billing,
+    Registrar,
+    Reseller,
+    Sponsor,
+    Proxy,
+    Notifications,
+    Noc,
+}
+
+static FIELDS: OnceLock<Vec<String>> = OnceLock::new();
+static ROLES: OnceLock<Vec<String>> = OnceLock::new();
+
+pub fn init(fields: &[String], roles: &[Role]) {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty())
+        .collect();
+    if !fields.is_empty() {
+        let _ = FIELDS.set(fields);
+    }
+    let roles: Vec<String> = roles
+        .iter()
+        .filter_map(ValueEnum::to_possible_value)
+        .map(|v| v.get_name().to_string())
+        .collect();
+    if !roles.is_empty() {
+        let _ = ROLES.set(roles);
+    }
+}
+
+fn keep(fields: &[String], key: &str) -> bool {
+    // rdapx annotations (`_meta`, `_enrichment`, ...) always pass through
+    key.starts_with('_') || ALWAYS.contains(&key) || fields.iter().any(|f| f == key)
+}
+
+/// The parse-time projection for a body of `len` bytes, when `--fields` is
+/// set and the body is known to be large.
+pub fn for_body(len: Option<u64>) -> Option<Projected> {
+    FIELDS
+        .get()
+        .filter(|_| len.is_some_and(|n| n > STREAM_THRESHOLD))
+        .map(|fields| Projected(fields))
+}
+
+/// Parses an RDAP object keeping only the members `--fields` selects, plus
+/// `links`, which referral following reads before output is shaped.
+pub struct Projected(&'static [String]);
+
+impl<'de> DeserializeSeed<'de> for Projected {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, de: D) -> Result<Value, D::Error> {
+        de.deserialize_map(self).map(Value::Object)
+    }
+}
+
+impl<'de> Visitor<'de> for Projected {
+    type Value = Map<String, Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an RDAP object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut out = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "links" || keep(self.0, &key) {
+                out.insert(key, map.next_value()?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Drop unselected top-level members and entities; a no-op without
+/// `--fields` or `--roles`.
+pub fn apply(mut json: Value) -> Value {
+    if let (Some(fields), Some(map)) = (FIELDS.get(), json.as_object_mut()) {
+        map.retain(|k, _| keep(fields, k));
+    }
+    if let Some(roles) = ROLES.get() {
+        prune_entities(&mut json, roles);
+        // the registrar's object from `--source both` gets the same treatment
+        if let Some(registrar) = json.get_mut("_registrar") {
+            prune_entities(registrar, roles);
+        }
+    }
+    json
+}
+
+/// Whether `--roles` selects `role` (everything does without it).
+pub fn wants_role(role: &str) -> bool {
+    ROLES
+        .get()
+        .is_none_or(|roles| roles.iter().any(|w| w.eq_ignore_ascii_case(role)))
+}
+
+fn has_any_role(entity: &Value, roles: &[String]) -> bool {
+    entity
+        .get("roles")
+        .and_then(Value::as_array)
+        .is_some_and(|rs| {
+            rs.iter().any(|r| {
+                r.as_str()
+                    .is_some_and(|r| roles.iter().any(|w| w.eq_ignore_ascii_case(r)))
+            })
+        })
+}
+
+/// Keep entities with a wanted role, plus those whose nested entities do
+/// (pruned the same way), so an abuse contact stays under its registrar.
+fn prune_entities(obj: &mut Value, roles: &[String]) {
+    let Some(entities) = obj.get_mut("entities").and_then(Value::as_array_mut) else {
+        return;
+    };
+    for e in entities.iter_mut() {
+        prune_entities(e, roles);
+    }
+    entities.retain(|e| {
+        has_any_role(e, roles)
+            || e.get("entities")
+                .and_then(Value::as_array)
+                .is_some_and(|nested| !nested.is_empty())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Projected;
+    use serde::de::DeserializeSeed;
+    use serde_json::json;
+
+    #[test]
+    fn projected_parse_skips_unselected_members() {
+        let fields = vec!["ldhName".to_string()].leak();
+        let body = br#"{"objectClassName": "domain", "ldhName": "example.com",
+            "entities": [{"handle": "H0"}], "links": [{"rel": "related"}],
+            "_meta": {"server": "rdap.test"}, "port43": "whois.test"}"#;
+        let mut de = serde_json::Deserializer::from_slice(body);
+        let json = Projected(fields).deserialize(&mut de).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "objectClassName": "domain",
+                "ldhName": "example.com",
+                "links": [{"rel": "related"}],
+                "_meta": {"server": "rdap.test"},
+            })
+        );
+    }
+
+    #[test]
+    fn projected_parse_wants_an_object() {
+        let fields = vec!["ldhName".to_string()].leak();
+        let mut de = serde_json::Deserializer::from_slice(b"[1, 2]");
+        assert!(Projected(fields).deserialize(&mut de).is_err());
+    }
+}
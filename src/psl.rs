@@ -0,0 +1,287 @@
+//! Public suffixes with more than one label (`co.uk`, `com.au`), so a
+//! lookup for a host name under one is sent for its registrable domain:
+//! `www.example.co.uk` is registered, and answered by the registry, as
+//! `example.co.uk`. Single-label suffixes (TLDs) need no list.
+//!
+//! This is the commonly used part of the Public Suffix List's ICANN section,
+//! not all of it. Zones in the DNS bootstrap registry and `servers.toml`
+//! count as suffixes too. A name that looks like it sits under a suffix
+//! missing from both (`example.com.xx`) is left as it is.
+
+const MULTI_LABEL: &[&str] = &[
+    // United Kingdom
+    "co.uk",
+    "org.uk",
+    "me.uk",
+    "ltd.uk",
+    "plc.uk",
+    "net.uk",
+    "ac.uk",
+    "gov.uk",
+    "sch.uk",
+    "nhs.uk",
+    "police.uk",
+    // Australia
+    "com.au",
+    "net.au",
+    "org.au",
+    "edu.au",
+    "gov.au",
+    "asn.au",
+    "id.au",
+    // New Zealand
+    "co.nz",
+    "net.nz",
+    "org.nz",
+    "ac.nz",
+    "govt.nz",
+    "geek.nz",
+    "school.nz",
+    // Japan
+    "co.jp",
+    "ne.jp",
+    "or.jp",
+    "ac.jp",
+    "go.jp",
+    "ad.jp",
+    "ed.jp",
+    "gr.jp",
+    "lg.jp",
+    // Korea
+    "co.kr",
+    "ne.kr",
+    "or.kr",
+    "ac.kr",
+    "go.kr",
+    "re.kr",
+    // China, Hong Kong, Taiwan
+    "com.cn",
+    "net.cn",
+    "org.cn",
+    "gov.cn",
+    "edu.cn",
+    "ac.cn",
+    "com.hk",
+    "net.hk",
+    "org.hk",
+    "edu.hk",
+    "gov.hk",
+    "com.tw",
+    "net.tw",
+    "org.tw",
+    "edu.tw",
+    "gov.tw",
+    // South and Southeast Asia
+    "co.in",
+    "net.in",
+    "org.in",
+    "firm.in",
+    "gen.in",
+    "ind.in",
+    "ac.in",
+    "edu.in",
+    "gov.in",
+    "com.sg",
+    "net.sg",
+    "org.sg",
+    "edu.sg",
+    "gov.sg",
+    "com.my",
+    "net.my",
+    "org.my",
+    "edu.my",
+    "gov.my",
+    "co.id",
+    "or.id",
+    "ac.id",
+    "go.id",
+    "web.id",
+    "co.th",
+    "in.th",
+    "ac.th",
+    "go.th",
+    "or.th",
+    "com.ph",
+    "net.ph",
+    "org.ph",
+    "com.vn",
+    "net.vn",
+    "org.vn",
+    "com.pk",
+    "net.pk",
+    "org.pk",
+    "com.bd",
+    // Middle East and Africa
+    "co.il",
+    "org.il",
+    "ac.il",
+    "gov.il",
+    "com.tr",
+    "net.tr",
+    "org.tr",
+    "gen.tr",
+    "com.sa",
+    "net.sa",
+    "org.sa",
+    "com.eg",
+    "co.za",
+    "org.za",
+    "net.za",
+    "gov.za",
+    "ac.za",
+    "co.ke",
+    "or.ke",
+    "ac.ke",
+    "com.ng",
+    "org.ng",
+    "co.tz",
+    "co.ug",
+    // Americas
+    "com.br",
+    "net.br",
+    "org.br",
+    "gov.br",
+    "edu.br",
+    "art.br",
+    "com.ar",
+    "net.ar",
+    "org.ar",
+    "gob.ar",
+    "com.mx",
+    "net.mx",
+    "org.mx",
+    "gob.mx",
+    "edu.mx",
+    "com.co",
+    "net.co",
+    "org.co",
+    "gov.co",
+    "com.pe",
+    "org.pe",
+    "gob.pe",
+    "com.ve",
+    "co.ve",
+    "com.uy",
+    "com.ec",
+    "com.bo",
+    "com.py",
+    // Europe
+    "co.at",
+    "or.at",
+    "ac.at",
+    "gv.at",
+    "com.pl",
+    "net.pl",
+    "org.pl",
+    "com.ua",
+    "net.ua",
+    "org.ua",
+    "co.ua",
+    "com.ru",
+    "net.ru",
+    "org.ru",
+    "com.es",
+    "nom.es",
+    "org.es",
+    "com.pt",
+    "com.gr",
+    "co.hu",
+    "com.cy",
+    "com.mt",
+];
+
+/// Labels registries commonly open as second levels of their own (`com.xx`,
+/// `co.xx`); a name with one right under its TLD is under a suffix this
+/// list doesn't know.
+const GENERIC_SECOND_LEVEL: &[&str] = &[
+    "com", "net", "org", "edu", "gov", "mil", "co", "ac", "or", "ne", "go", "gob", "gv", "nom",
+];
+
+/// Registrable part of `name`: its public suffix plus one label. Reverse
+/// DNS names (`.arpa`), names that already are registrable, and names under
+/// an unknown suffix are returned unchanged, less any trailing dot.
+pub fn registrable(name: &str) -> &str {
+    registrable_with(name, crate::bootstrap::is_dns_suffix)
+}
+
+/// [`registrable`], with `listed` telling which suffixes (lower case) are
+/// zones besides the ones in [`MULTI_LABEL`].
+fn registrable_with(name: &str, listed: impl Fn(&str) -> bool) -> &str {
+    let trimmed = name.trim_end_matches('.');
+    let lower = trimmed.to_ascii_lowercase();
+    let labels: Vec<&str> = lower.split('.').collect();
+    if labels.len() < 3 || labels.last() == Some(&"arpa") {
+        return trimmed;
+    }
+    // the longest known suffix, else the TLD
+    let suffix_labels = (1..labels.len())
+        .map(|i| labels[i..].join("."))
+        .find(|s| MULTI_LABEL.contains(&s.as_str()) || listed(s))
+        .map_or(1, |s| s.split('.').count());
+    if suffix_labels == 1 && GENERIC_SECOND_LEVEL.contains(&labels[labels.len() - 2]) {
+        return trimmed;
+    }
+    if labels.len() <= suffix_labels + 1 {
+        return trimmed;
+    }
+    let skip: usize = labels[..labels.len() - suffix_labels - 1]
+        .iter()
+        .map(|l| l.len() + 1)
+        .sum();
+    &trimmed[skip..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::registrable_with;
+
+    fn registrable(name: &str) -> &str {
+        registrable_with(name, |_| false)
+    }
+
+    #[test]
+    fn strips_hosts_under_multi_label_suffixes() {
+        assert_eq!(registrable("www.example.co.uk"), "example.co.uk");
+        assert_eq!(registrable("a.b.example.com.au"), "example.com.au");
+        assert_eq!(registrable("WWW.Example.CO.UK"), "Example.CO.UK");
+    }
+
+    #[test]
+    fn strips_hosts_under_tlds() {
+        assert_eq!(registrable("www.example.com"), "example.com");
+        assert_eq!(registrable("www.example.com."), "example.com");
+    }
+
+    #[test]
+    fn leaves_registrable_names_alone() {
+        assert_eq!(registrable("example.com"), "example.com");
+        assert_eq!(registrable("example.com."), "example.com");
+        assert_eq!(registrable("Example.COM"), "Example.COM");
+        assert_eq!(registrable("example.co.uk"), "example.co.uk");
+        assert_eq!(registrable("com"), "com");
+    }
+
+    #[test]
+    fn leaves_reverse_dns_and_unknown_suffixes_alone() {
+        assert_eq!(
+            registrable("1.2.0.192.in-addr.arpa"),
+            "1.2.0.192.in-addr.arpa"
+        );
+        // `com.xx` could be a suffix the list doesn't know
+        assert_eq!(registrable("www.example.com.xx"), "www.example.com.xx");
+    }
+
+    #[test]
+    fn bootstrap_zones_count_as_suffixes() {
+        let listed = |s: &str| s == "gov.zz";
+        assert_eq!(
+            registrable_with("www.example.gov.zz", listed),
+            "example.gov.zz"
+        );
+        // `org.zz` isn't listed, but looks like a suffix
+        assert_eq!(
+            registrable_with("www.example.org.zz", listed),
+            "www.example.org.zz"
+        );
+    }
+}
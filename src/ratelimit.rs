@@ -0,0 +1,213 @@
+//! Rate-limit header awareness. Registries that publish their quota
+//! (`X-RateLimit-Remaining` / `-Reset` / `-Limit`, or the unprefixed
+//! `RateLimit-*` draft names) are paced proactively: once the remaining
+//! quota runs low, requests to that host are spread evenly over the time
+//! left until the reset, and with nothing left they wait for the reset.
+//! The state shows up in `--trace` lines and the bulk summary.
+//!
+//! [`HostLimiter`] is the fixed per-registry rate (`--rate`) of the commands
+//! that take one.
+
+use crate::model::RateLimitState;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Start spreading requests out when fewer than this many remain.
+const LOW_WATER: u64 = 10;
+/// Never pace a single request for longer than this.
+const MAX_WAIT: Duration = Duration::from_secs(120);
+
+#[derive(Default, Clone, Copy, Debug)]
+struct Host {
+    limit: Option<u64>,
+    remaining: u64,
+    /// Unix seconds when the quota resets
+    reset_at: u64,
+    paced: Duration,
+}
+
+static HOSTS: Mutex<BTreeMap<String, Host>> = Mutex::new(BTreeMap::new());
+static TRACE: AtomicBool = AtomicBool::new(false);
+
+fn lock() -> MutexGuard<'static, BTreeMap<String, Host>> {
+    HOSTS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// `--trace`: print one line per HTTP request to stderr.
+pub fn init(trace: bool) {
+    TRACE.store(trace, Ordering::Relaxed);
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<u64> {
+    [format!("x-ratelimit-{name}"), format!("ratelimit-{name}")]
+        .iter()
+        .find_map(|h| headers.get(h.as_str()))
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+}
+
+/// Update the host's quota from a response's headers.
+pub fn observe(url: &str, headers: &HeaderMap) {
+    let Some(remaining) = header(headers, "remaining") else {
+        return;
+    };
+    let now = crate::deterministic::wall_secs();
+    // the reset is either seconds from now or a Unix timestamp
+    let reset_at =
+        header(headers, "reset").map_or(now + 60, |r| if r > 1_000_000_000 { r } else { now + r });
+    let limit = header(headers, "limit");
+    let host = crate::url_host(url);
+    let mut hosts = lock();
+    let h = hosts.entry(host).or_default();
+    h.limit = limit.or(h.limit);
+    h.remaining = remaining;
+    h.reset_at = reset_at;
+    drop(hosts);
+}
+
+/// Wait as long as the host's published quota asks for, and count the
+/// request against it.
+pub async fn pace(url: &str) {
+    let wait = reserve(&crate::url_host(url));
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+}
+
+fn reserve(host: &str) -> Duration {
+    let now = crate::deterministic::wall_secs();
+    let mut hosts = lock();
+    let Some(h) = hosts.get_mut(host).filter(|h| h.reset_at > now) else {
+        return Duration::ZERO;
+    };
+    let left = Duration::from_secs(h.reset_at - now);
+    let wait = match h.remaining {
+        0 => left,
+        n if n < LOW_WATER => left / u32::try_from(n).unwrap_or(1),
+        _ => Duration::ZERO,
+    }
+    .min(MAX_WAIT);
+    h.remaining = h.remaining.saturating_sub(1);
+    h.paced += wait;
+    drop(hosts);
+    wait
+}
+
+fn snapshot(h: &Host) -> RateLimitState {
+    RateLimitState {
+        limit: h.limit,
+        remaining: h.remaining,
+        reset_in_secs: h.reset_at.saturating_sub(crate::deterministic::wall_secs()),
+        paced_ms: u64::try_from(h.paced.as_millis()).unwrap_or(u64::MAX),
+    }
+}
+
+fn state(host: &str) -> Option<RateLimitState> {
+    lock().get(host).map(snapshot)
+}
+
+/// Quota state of one host as persisted by resumable harvests.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Saved {
+    pub limit: Option<u64>,
+    pub remaining: u64,
+    /// Unix seconds when the quota resets
+    pub reset_at: u64,
+}
+
+/// Quotas that haven't reset yet, for saving alongside a harvest cursor.
+pub fn save() -> BTreeMap<String, Saved> {
+    let now = crate::deterministic::wall_secs();
+    lock()
+        .iter()
+        .filter(|(_, h)| h.reset_at > now)
+        .map(|(k, h)| {
+            let saved = Saved {
+                limit: h.limit,
+                remaining: h.remaining,
+                reset_at: h.reset_at,
+            };
+            (k.clone(), saved)
+        })
+        .collect()
+}
+
+/// Reinstate saved quotas that are still running, so a resumed harvest
+/// doesn't spend a quota the interrupted run already used up.
+pub fn restore(saved: &BTreeMap<String, Saved>) {
+    let now = crate::deterministic::wall_secs();
+    let mut hosts = lock();
+    for (host, s) in saved.iter().filter(|(_, s)| s.reset_at > now) {
+        hosts.entry(host.clone()).or_insert(Host {
+            limit: s.limit,
+            remaining: s.remaining,
+            reset_at: s.reset_at,
+            paced: Duration::ZERO,
+        });
+    }
+}
+
+/// Quota state of every host that published one, for the bulk summary.
+pub fn all() -> BTreeMap<String, RateLimitState> {
+    lock()
+        .iter()
+        .map(|(k, h)| (k.clone(), snapshot(h)))
+        .collect()
+}
+
+/// `--trace` line for one request.
+pub fn trace(url: &str, status: Option<StatusCode>, elapsed: Duration) {
+    if !TRACE.load(Ordering::Relaxed) {
+        return;
+    }
+    let status = status.map_or_else(|| "error".to_string(), |s| s.as_u16().to_string());
+    let quota = state(&crate::url_host(url)).map_or_else(String::new, |s| {
+        let limit = s.limit.map_or_else(String::new, |l| format!("/{l}"));
+        format!(
+            " ratelimit={}{limit} reset={}s paced={}ms",
+            s.remaining, s.reset_in_secs, s.paced_ms
+        )
+    });
+    eprintln!(
+        "trace: GET {url} -> {status} in {}ms{quota}",
+        elapsed.as_millis()
+    );
+}
+
+/// Spaces requests to the same registry host at least `interval` apart.
+pub struct HostLimiter {
+    interval: Duration,
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl HostLimiter {
+    /// At most `per_second` requests to any one host.
+    pub fn new(per_second: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / per_second.max(1),
+            next: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Claim the next free slot for `host`; returns how long to wait for it.
+    fn reserve(&self, host: &str) -> Duration {
+        let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        let slot = next.get(host).copied().unwrap_or(now).max(now);
+        next.insert(host.to_string(), slot + self.interval);
+        drop(next);
+        slot - now
+    }
+
+    pub async fn wait(&self, host: &str) {
+        let delay = self.reserve(host);
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
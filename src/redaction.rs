@@ -0,0 +1,119 @@
+//! Redaction summary for table output: which contact fields are withheld and
+//! under what policy. RFC 9537 `redacted` members are authoritative; contact
+//! values that carry a registry placeholder ("REDACTED FOR PRIVACY", ...),
+//! on any entity including nested ones, are reported as well, since many
+//! servers redact without declaring it.
+
+use crate::fields;
+use serde_json::Value;
+
+/// Lowercased fragments registries use in place of withheld contact data.
+const PLACEHOLDERS: [&str; 6] = [
+    "redacted",
+    "data protected",
+    "non-public data",
+    "not disclosed",
+    "withheld",
+    "please query the rdds service of the registrar of record",
+];
+
+/// jCard properties that hold personal data.
+const CONTACT_PROPS: [&str; 6] = ["fn", "org", "adr", "email", "tel", "contact-uri"];
+
+pub struct Redaction {
+    /// Field name, e.g. `Registrant Email` or `registrant email`
+    pub field: String,
+    /// RFC 9537 method (`removal`, `emptyValue`, ...) or `placeholder`
+    pub method: String,
+    pub reason: Option<String>,
+}
+
+/// Policy the response claims to follow, from `rdapConformance`.
+pub fn policy(json: &Value) -> Option<&'static str> {
+    let conformance: Vec<&str> = json
+        .get("rdapConformance")?
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_str)
+        .collect();
+    if conformance
+        .iter()
+        .any(|c| c.starts_with("icann_rdap_response_profile"))
+    {
+        Some("ICANN gTLD RDAP Response Profile")
+    } else if conformance.contains(&"redacted") {
+        Some("RFC 9537")
+    } else {
+        None
+    }
+}
+
+pub fn summary(json: &Value) -> Vec<Redaction> {
+    let mut out: Vec<Redaction> = json
+        .get("redacted")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|r| {
+            let name = r.get("name");
+            let field = name
+                .and_then(|n| n.get("type").or_else(|| n.get("description")))
+                .and_then(Value::as_str)
+                .unwrap_or("(unnamed)");
+            Redaction {
+                field: field.to_string(),
+                method: r
+                    .get("method")
+                    .and_then(Value::as_str)
+                    .unwrap_or("removal")
+                    .to_string(),
+                reason: r
+                    .get("reason")
+                    .and_then(|r| r.get("description").or_else(|| r.get("type")))
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+            }
+        })
+        .collect();
+
+    placeholders(json, &mut out);
+    out
+}
+
+/// Contact values carrying a placeholder on the entities of `json` and the
+/// entities nested under them (e.g. a registrar's abuse contact).
+fn placeholders(json: &Value, out: &mut Vec<Redaction>) {
+    for entity in json
+        .get("entities")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let role = entity
+            .get("roles")
+            .and_then(Value::as_array)
+            .and_then(|r| r.first())
+            .and_then(Value::as_str)
+            .unwrap_or("contact");
+        for prop in CONTACT_PROPS {
+            let Some(value) = fields::vcard_prop(entity, prop) else {
+                continue;
+            };
+            let lower = value.to_lowercase();
+            if !PLACEHOLDERS.iter().any(|p| lower.contains(p)) {
+                continue;
+            }
+            let field = format!("{role} {prop}");
+            // already declared via RFC 9537, or seen on another entity
+            if out.iter().any(|r| r.field.eq_ignore_ascii_case(&field)) {
+                continue;
+            }
+            out.push(Redaction {
+                field,
+                method: "placeholder".to_string(),
+                reason: Some(value),
+            });
+        }
+        placeholders(entity, out);
+    }
+}
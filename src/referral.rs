@@ -0,0 +1,211 @@
+//! `--source registrar|both|merged` (or `--follow-registrar`): follow a thin
+//! registry's referral to the sponsoring registrar's RDAP server. The
+//! registrar fetch has its own retry policy (`--registrar-retries`,
+//! `--registrar-timeout`) and, when an earlier response already told us
+//! where the referral points, starts concurrently with the registry fetch
+//! instead of after it.
+
+use crate::{attach_meta, fetch_url, read_cache_entry, FetchOpts, HttpStatusError, Source};
+use serde_json::Value;
+use std::error::Error;
+
+/// The registrar's RDAP URL from a registry response (`related` link of
+/// RDAP media type).
+pub fn registrar_link(json: &Value) -> Option<String> {
+    json.get("links")?
+        .as_array()?
+        .iter()
+        .filter(|l| l.get("rel").and_then(Value::as_str) == Some("related"))
+        .filter(|l| {
+            l.get("type")
+                .and_then(Value::as_str)
+                .is_none_or(|t| t.contains("rdap+json"))
+        })
+        .filter_map(|l| l.get("href").and_then(Value::as_str))
+        .find(|href| href.contains("/domain/"))
+        .map(str::to_string)
+}
+
+pub async fn fetch(
+    client: &reqwest::Client,
+    url: &str,
+    q: &str,
+    opts: &FetchOpts<'_>,
+) -> Result<Value, Box<dyn Error>> {
+    let registry_opts = FetchOpts {
+        source: Source::Registry,
+        ..*opts
+    };
+    let registrar_opts = FetchOpts {
+        source: Source::Registry,
+        retries: opts.registrar_retries,
+        timeout: opts.registrar_timeout,
+        retry_budget: None,
+        ..*opts
+    };
+
+    // Any earlier copy of the registry response (even expired) names the referral.
+    let guess = read_cache_entry(url).and_then(|e| registrar_link(&e.data));
+    let (registry, early) =
+        futures::future::join(fetch_str(client, url, q, &registry_opts), async {
+            match &guess {
+                Some(link) => Some(fetch_str(client, link, q, &registrar_opts).await),
+                None => None,
+            }
+        })
+        .await;
+    let mut registry = registry.map_err(|e| -> Box<dyn Error> { e })?;
+
+    let link = registrar_link(&registry);
+    let registrar = match (&link, early) {
+        (Some(link), Some(res)) if guess.as_deref() == Some(link.as_str()) => Some(res),
+        (Some(link), _) => Some(fetch_str(client, link, q, &registrar_opts).await),
+        (None, _) => None,
+    };
+
+    match (opts.source, registrar) {
+        (Source::Merged, Some(Ok(obj))) => {
+            merge(&mut registry, &obj);
+            attach_meta(&mut registry, "registrar_url", link.into());
+            Ok(registry)
+        }
+        (Source::Merged, Some(Err(e))) => {
+            attach_meta(&mut registry, "registrar_error", e.to_string().into());
+            Ok(registry)
+        }
+        (Source::Registrar, Some(res)) => res.map_err(|e| -> Box<dyn Error> { e }),
+        (Source::Registrar, None) => {
+            Err(format!("{q}: registry response has no registrar referral").into())
+        }
+        (_, Some(Ok(obj))) => {
+            insert(&mut registry, obj);
+            Ok(registry)
+        }
+        (_, Some(Err(e))) => {
+            insert(&mut registry, serde_json::json!({ "error": e.to_string() }));
+            Ok(registry)
+        }
+        (_, None) => Ok(registry),
+    }
+}
+
+/// `fetch_url` tagged with `_meta.server`, with a `Send` error (HTTP status
+/// errors kept intact, anything else flattened to a string), so a finished
+/// result can sit in the join while the other fetch is still pending.
+async fn fetch_str(
+    client: &reqwest::Client,
+    url: &str,
+    q: &str,
+    opts: &FetchOpts<'_>,
+) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    fetch_url(client, url, q, opts)
+        .await
+        .map(|json| crate::fanout::answered_by(json, url))
+        .map_err(|e| -> Box<dyn Error + Send + Sync> {
+            match e.downcast::<HttpStatusError>() {
+                Ok(http) => http,
+                Err(other) => other.to_string().into(),
+            }
+        })
+}
+
+fn insert(registry: &mut Value, registrar: Value) {
+    if let Some(obj) = registry.as_object_mut() {
+        obj.insert("_registrar".into(), registrar);
+    }
+}
+
+fn roles(entity: &Value) -> impl Iterator<Item = &str> {
+    entity
+        .get("roles")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+}
+
+/// Replace the registry's contacts with the registrar's for every role the
+/// registrar supplies. The registry stays authoritative for everything else,
+/// including its `registrar` entity (IANA ID, abuse contact).
+fn merge(registry: &mut Value, registrar: &Value) {
+    let Some(theirs) = registrar.get("entities").and_then(Value::as_array) else {
+        return;
+    };
+    let contacts: Vec<&Value> = theirs
+        .iter()
+        .filter(|e| roles(e).any(|r| r != "registrar"))
+        .collect();
+    if contacts.is_empty() {
+        return;
+    }
+    let supplied: Vec<&str> = contacts.iter().flat_map(|e| roles(e)).collect();
+    let Some(obj) = registry.as_object_mut() else {
+        return;
+    };
+    let entities = obj
+        .entry("entities")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    if let Some(list) = entities.as_array_mut() {
+        list.retain(|e| {
+            roles(e).any(|r| r == "registrar") || !roles(e).any(|r| supplied.contains(&r))
+        });
+        list.extend(contacts.into_iter().cloned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge, registrar_link};
+    use serde_json::json;
+
+    #[test]
+    fn the_referral_is_the_related_rdap_domain_link() {
+        let registry = json!({"links": [
+            {"rel": "self", "href": "https://registry.test/domain/example.com"},
+            {"rel": "related", "type": "text/html", "href": "https://registrar.test/whois"},
+            {"rel": "related", "type": "application/rdap+json",
+             "href": "https://registrar.test/rdap/domain/example.com"},
+        ]});
+        assert_eq!(
+            registrar_link(&registry).as_deref(),
+            Some("https://registrar.test/rdap/domain/example.com")
+        );
+        assert_eq!(registrar_link(&json!({"links": []})), None);
+    }
+
+    #[test]
+    fn registrar_contacts_replace_the_registry_ones_by_role() {
+        let mut registry = json!({
+            "ldhName": "example.com",
+            "entities": [
+                {"handle": "IANA-9999", "roles": ["registrar"]},
+                {"handle": "REDACTED-1", "roles": ["registrant"]},
+                {"handle": "REGISTRY-TECH", "roles": ["technical"]},
+            ],
+        });
+        let registrar = json!({"entities": [
+            {"handle": "REGISTRAR-SELF", "roles": ["registrar"]},
+            {"handle": "C-1", "roles": ["registrant", "administrative"]},
+        ]});
+        merge(&mut registry, &registrar);
+        let handles: Vec<&str> = registry["entities"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|e| e["handle"].as_str())
+            .collect();
+        assert_eq!(handles, ["IANA-9999", "REGISTRY-TECH", "C-1"]);
+        assert_eq!(registry["ldhName"], "example.com");
+    }
+
+    #[test]
+    fn a_registrar_without_contacts_changes_nothing() {
+        let mut registry = json!({"entities": [{"handle": "R-1", "roles": ["registrant"]}]});
+        let before = registry.clone();
+        merge(
+            &mut registry,
+            &json!({"entities": [{"handle": "SELF", "roles": ["registrar"]}]}),
+        );
+        assert_eq!(registry, before);
+    }
+}
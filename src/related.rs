@@ -0,0 +1,123 @@
+//! `rdapx related`: other domains that share a domain's registrant or
+//! nameservers, found with the server's searches: RFC 9536 reverse search
+//! on the registrant handle and `domains?nsLdhName=` per nameserver. Each
+//! result lists the pivots that found it. Servers that don't offer a search
+//! are noted and skipped.
+
+use crate::search::{self, PagingArgs};
+use crate::table::{Cell, Column, Tabular};
+use crate::FetchOpts;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pivot {
+    /// Registrant entity handle (reverse search)
+    Registrant,
+    /// Each nameserver of the domain
+    Nameserver,
+}
+
+/// One related domain and the pivots it was found through, e.g.
+/// `registrant:ABC-123`, `nameserver:ns1.example.net`.
+#[derive(Serialize, Debug)]
+pub struct Related {
+    pub domain: String,
+    pub via: Vec<String>,
+}
+
+impl Tabular for Related {
+    const COLUMNS: &'static [Column] = &[Column("DOMAIN", 40), Column("VIA", 0)];
+    const CSV_HEADER: &'static [&'static str] = &["domain", "via"];
+
+    fn table_row(&self) -> Vec<Cell> {
+        vec![self.domain.as_str().into(), self.via.join(", ").into()]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![self.domain.clone(), self.via.join("|")]
+    }
+}
+
+/// Registrant handles worth searching for; redacted placeholders are skipped.
+fn registrant_handles(json: &Value) -> Vec<String> {
+    crate::fields::entities_with_role(json, "registrant")
+        .filter_map(|e| e.get("handle").and_then(Value::as_str))
+        .filter(|h| !h.is_empty() && !h.to_ascii_lowercase().contains("redacted"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// `(provenance, first search URL)` for each pivot of `json`.
+fn searches(
+    json: &Value,
+    pivots: &[Pivot],
+    paging: &PagingArgs,
+) -> Result<Vec<(String, reqwest::Url)>, Box<dyn std::error::Error>> {
+    let mut out = Vec::new();
+    if pivots.contains(&Pivot::Registrant) {
+        for handle in registrant_handles(json) {
+            let mut url =
+                search::search_url(paging, "domains/reverse_search/entity", "handle", &handle)?;
+            url.query_pairs_mut().append_pair("role", "registrant");
+            out.push((format!("registrant:{handle}"), url));
+        }
+    }
+    if pivots.contains(&Pivot::Nameserver) {
+        for ns in crate::fields::nameservers(json) {
+            let ns = ns.trim_end_matches('.').to_lowercase();
+            let url = search::search_url(paging, "domains", "nsLdhName", &ns)?;
+            out.push((format!("nameserver:{ns}"), url));
+        }
+    }
+    Ok(out)
+}
+
+/// Domains sharing a pivot with `json` (the domain `name` itself left out),
+/// in name order. Pivots whose search fails are reported on stderr.
+pub async fn discover(
+    client: &reqwest::Client,
+    name: &str,
+    json: &Value,
+    pivots: &[Pivot],
+    paging: &PagingArgs,
+    opts: &FetchOpts<'_>,
+) -> Result<Vec<Related>, Box<dyn std::error::Error>> {
+    use colored::Colorize;
+
+    let own = name.trim_end_matches('.').to_lowercase();
+    let mut found: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let searches = searches(json, pivots, paging)?;
+    if searches.is_empty() {
+        eprintln!(
+            "{} {name} has no registrant handle or nameservers to pivot on",
+            "Note:".yellow().bold()
+        );
+    }
+    for (via, url) in searches {
+        let mut hits = Vec::new();
+        let result = search::run(client, vec![url], paging, opts, |item| {
+            if let Some(d) = item.get("ldhName").and_then(Value::as_str) {
+                hits.push(d.trim_end_matches('.').to_lowercase());
+            }
+        })
+        .await
+        .map_err(|e| e.to_string());
+        if let Err(e) = result {
+            eprintln!("{} {via}: search failed ({e})", "Note:".yellow().bold());
+            continue;
+        }
+        for d in hits.into_iter().filter(|d| *d != own) {
+            let entry = found.entry(d).or_default();
+            if !entry.contains(&via) {
+                entry.push(via.clone());
+            }
+        }
+    }
+    Ok(found
+        .into_iter()
+        .map(|(domain, via)| Related { domain, via })
+        .collect())
+}
@@ -0,0 +1,165 @@
+//! `rdapx report`: ranked group-by summaries (counts and shares) over a
+//! `sqlite:` sink database, for quick analytics without an export. Like
+//! `pivot`, the stored records are scanned in full and each object counts
+//! once, with its most recent write.
+
+#[cfg(feature = "sqlite")]
+use crate::fields::Attribute;
+#[cfg(feature = "sqlite")]
+use crate::stats::{Count, Tally};
+use crate::table::{Cell, Column, Tabular};
+use clap::ValueEnum;
+use serde::Serialize;
+#[cfg(feature = "sqlite")]
+use serde_json::Value;
+use std::path::Path;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum GroupBy {
+    Registrar,
+    Status,
+    Nameserver,
+    Tld,
+    Country,
+    CreatedYear,
+    ObjectClass,
+    AbuseEmail,
+}
+
+/// Bucket for objects without a value for the attribute.
+#[cfg(feature = "sqlite")]
+const NONE: &str = "(none)";
+
+#[cfg(feature = "sqlite")]
+impl GroupBy {
+    const fn attribute(self) -> Attribute {
+        match self {
+            Self::Registrar => Attribute::Registrar,
+            Self::Status => Attribute::Status,
+            Self::Nameserver => Attribute::Nameserver,
+            Self::Tld => Attribute::Tld,
+            Self::Country => Attribute::Country,
+            Self::CreatedYear => Attribute::CreatedYear,
+            Self::ObjectClass => Attribute::ObjectClass,
+            Self::AbuseEmail => Attribute::AbuseEmail,
+        }
+    }
+}
+
+/// One group: its value, how many objects fall in it, and their share of
+/// all objects in the database.
+#[derive(Serialize, Debug)]
+pub struct Group {
+    pub rank: usize,
+    pub value: String,
+    pub count: usize,
+    pub percent: f64,
+}
+
+impl Tabular for Group {
+    const COLUMNS: &'static [Column] = &[
+        Column("RANK", -4),
+        Column("VALUE", 48),
+        Column("COUNT", -7),
+        Column("%", -6),
+    ];
+    const CSV_HEADER: &'static [&'static str] = &["rank", "value", "count", "percent"];
+
+    fn table_row(&self) -> Vec<Cell> {
+        vec![
+            self.rank.to_string().into(),
+            self.value.as_str().into(),
+            self.count.to_string().into(),
+            format!("{:.1}%", self.percent).into(),
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.rank.to_string(),
+            self.value.clone(),
+            self.count.to_string(),
+            format!("{:.1}", self.percent),
+        ]
+    }
+}
+
+/// Groups ranked by count (ties alphabetical), at most `top` (0 for all),
+/// plus the number of distinct objects scanned.
+#[cfg(feature = "sqlite")]
+pub fn build(
+    db: &Path,
+    by: GroupBy,
+    top: usize,
+) -> Result<(Vec<Group>, usize), Box<dyn std::error::Error>> {
+    use rusqlite::OpenFlags;
+    use std::collections::HashMap;
+
+    let conn = rusqlite::Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("{}: {e}", db.display()))?;
+    let mut stmt = conn
+        .prepare("SELECT name, handle, record FROM records ORDER BY id")
+        .map_err(|e| format!("{}: {e}", db.display()))?;
+    // object name -> values of its latest write
+    let mut objects: HashMap<String, Vec<String>> = HashMap::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let record: String = row.get(2)?;
+        let Ok(record) = serde_json::from_str::<Value>(&record) else {
+            continue;
+        };
+        let raw = record.get("rdap").unwrap_or(&record);
+        let name: Option<String> = row.get(0)?;
+        let handle: Option<String> = row.get(1)?;
+        let key = raw
+            .get("ldhName")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .into_iter()
+            .chain(handle)
+            .chain(name)
+            .find(|n| !n.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        let mut values = by.attribute().values(raw);
+        values.sort();
+        values.dedup();
+        objects.insert(key, values);
+    }
+    let total = objects.len();
+    let mut tally = Tally::default();
+    for values in objects.into_values() {
+        if values.is_empty() {
+            tally.add(NONE);
+        }
+        for v in values {
+            tally.add(v);
+        }
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let groups = tally
+        .top(top)
+        .into_iter()
+        .enumerate()
+        .map(|(i, Count { value, count })| Group {
+            rank: i + 1,
+            value,
+            count,
+            percent: (count as f64 * 1000.0 / total.max(1) as f64).round() / 10.0,
+        })
+        .collect();
+    Ok((groups, total))
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn build(
+    db: &Path,
+    _by: GroupBy,
+    _top: usize,
+) -> Result<(Vec<Group>, usize), Box<dyn std::error::Error>> {
+    Err(format!(
+        "{}: report needs rdapx built with the `sqlite` feature",
+        db.display()
+    )
+    .into())
+}
@@ -0,0 +1,186 @@
+//! `--sandbox`: confine the process before it touches untrusted input.
+//!
+//! Once applied, files can be read anywhere but only created or written under
+//! the cache and data directories and the directories of the files the
+//! command writes (`bulk --rejects`, `--summary-file`, a `--resume` file,
+//! ...), and outgoing TCP is limited to HTTP(S), DNS and WHOIS. Files opened
+//! earlier (`--output` sinks, `--archive`) stay writable, so confinement
+//! happens after startup and before the command runs. It also has to happen
+//! before the async runtime starts its worker threads: Landlock confines only
+//! the calling thread and the threads it creates afterwards.
+//!
+//! Linux uses Landlock (filesystem since 5.13, TCP ports since 6.7; older
+//! kernels get what they support, with a note); macOS uses the Seatbelt
+//! `sandbox_init` profile language. Other platforms refuse the flag.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// TCP ports the sandbox may connect to: HTTPS, HTTP (servers and
+/// redirects that use it), DNS over TCP, WHOIS.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const PORTS: [u16; 4] = [443, 80, 53, 43];
+
+/// Directories the sandboxed process may write to: the cache and data
+/// directories (created if missing so they can be named in the rules) and
+/// the directory of each of `files`. Files are replaced through a temporary
+/// sibling in places, so the whole directory is needed, not just the file.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn writable(files: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut dirs = vec![crate::project::cache_dir()?, crate::project::data_dir()?];
+    for d in &dirs {
+        std::fs::create_dir_all(d).map_err(|e| format!("sandbox: {}: {e}", d.display()))?;
+    }
+    for file in files {
+        let dir = file.parent().filter(|d| !d.as_os_str().is_empty());
+        let dir = dir.unwrap_or_else(|| Path::new("."));
+        if !dir.is_dir() {
+            return Err(format!("sandbox: {}: directory doesn't exist", dir.display()).into());
+        }
+        dirs.push(dir.to_path_buf());
+    }
+    Ok(dirs)
+}
+
+#[cfg(target_os = "linux")]
+pub fn apply(files: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    use landlock::{
+        Access, AccessFs, AccessNet, NetPort, PathBeneath, PathFd, Ruleset, RulesetAttr,
+        RulesetCreatedAttr, RulesetStatus, ABI,
+    };
+
+    let abi = ABI::V5;
+    let fd = |path: &std::path::Path| {
+        PathFd::new(path).map_err(|e| format!("sandbox: {}: {e}", path.display()))
+    };
+    let mut ruleset = Ruleset::default()
+        .handle_access(AccessFs::from_all(abi))?
+        // connect only: `serve` still binds its listener
+        .handle_access(AccessNet::ConnectTcp)?
+        .create()?
+        .add_rule(PathBeneath::new(
+            fd("/".as_ref())?,
+            AccessFs::from_read(abi),
+        ))?
+        .add_rule(PathBeneath::new(
+            fd("/dev/null".as_ref())?,
+            AccessFs::from_file(abi),
+        ))?;
+    for dir in writable(files)? {
+        ruleset = ruleset.add_rule(PathBeneath::new(fd(&dir)?, AccessFs::from_all(abi)))?;
+    }
+    for port in PORTS {
+        ruleset = ruleset.add_rule(NetPort::new(port, AccessNet::ConnectTcp))?;
+    }
+    match ruleset.restrict_self()?.ruleset {
+        RulesetStatus::FullyEnforced => Ok(()),
+        RulesetStatus::PartiallyEnforced => {
+            eprintln!(
+                "Note: sandbox partially enforced; this kernel's Landlock can't restrict \
+                 everything (network rules need Linux 6.7)"
+            );
+            Ok(())
+        }
+        RulesetStatus::NotEnforced => {
+            Err("--sandbox: Landlock is not available on this kernel".into())
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn apply(files: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    use std::ffi::{c_char, c_int, CStr, CString};
+
+    extern "C" {
+        fn sandbox_init(profile: *const c_char, flags: u64, errorbuf: *mut *mut c_char) -> c_int;
+        fn sandbox_free_error(errorbuf: *mut c_char);
+    }
+
+    let quote = |p: &std::path::Path| {
+        // Seatbelt matches resolved paths (/var is really /private/var)
+        let p = std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+        format!(
+            "(subpath \"{}\")",
+            p.display().to_string().replace('"', "\\\"")
+        )
+    };
+    let dirs: Vec<String> = writable(files)?
+        .iter()
+        .map(|d| quote(d.as_path()))
+        .collect();
+    let ports: Vec<String> = PORTS
+        .iter()
+        .map(|p| format!("(remote tcp \"*:{p}\")"))
+        .collect();
+    let profile = format!(
+        "(version 1)\n(allow default)\n\
+         (deny file-write*)\n\
+         (allow file-write* {} (literal \"/dev/null\") (literal \"/dev/tty\"))\n\
+         (deny network-outbound)\n\
+         (allow network-outbound {} (remote udp \"*:53\") \
+         (literal \"/private/var/run/mDNSResponder\"))\n",
+        dirs.join(" "),
+        ports.join(" ")
+    );
+    let profile = CString::new(profile)?;
+    let mut err: *mut c_char = std::ptr::null_mut();
+    // SAFETY: `profile` is a valid C string; `err` is only read and freed when
+    // sandbox_init reports failure, as its documentation requires.
+    let rc = unsafe { sandbox_init(profile.as_ptr(), 0, &mut err) };
+    if rc == 0 {
+        return Ok(());
+    }
+    let msg = if err.is_null() {
+        "unknown error".to_string()
+    } else {
+        // SAFETY: non-null `err` is a NUL-terminated string owned by libsandbox.
+        let msg = unsafe { CStr::from_ptr(err) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { sandbox_free_error(err) };
+        msg
+    };
+    Err(format!("--sandbox: {msg}").into())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn apply(_files: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    Err("--sandbox is only supported on Linux and macOS".into())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::fs;
+
+    #[test]
+    fn tasks_spawned_after_apply_cannot_write_elsewhere() {
+        let base = std::env::temp_dir().join(format!("rdapx-sandbox-{}", std::process::id()));
+        let (allowed, denied) = (base.join("allowed"), base.join("denied"));
+        fs::create_dir_all(&allowed).unwrap();
+        fs::create_dir_all(&denied).unwrap();
+        // confines this test's thread and the runtime threads started below
+        if super::apply(&[allowed.join("out.ndjson")]).is_err() {
+            eprintln!("skipped: Landlock is not available");
+            return;
+        }
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .build()
+            .unwrap();
+        let (inside, outside) = runtime.block_on(async move {
+            tokio::spawn(async move {
+                (
+                    fs::write(allowed.join("out.ndjson"), "{}"),
+                    fs::write(denied.join("out.ndjson"), "{}"),
+                )
+            })
+            .await
+            .unwrap()
+        });
+        assert!(inside.is_ok(), "{inside:?}");
+        assert_eq!(
+            outside.unwrap_err().kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! RDAP searches (RFC 9082 section 3.2) with page-following for truncated
+//! result sets (RFC 8977 `paging_metadata` and `next` links).
+//!
+//! Long harvests can be made resumable with `--resume FILE`: after every page
+//! the cursor (next page URL), the keys of the results it emitted, and the
+//! servers' rate-limit quotas are appended there, and a later run with the
+//! same searches picks up from that page. Results of a page cut short by the
+//! interruption may be emitted again. Pages to the same server are spaced by
+//! `--delay` or the `[search]` config section.
+
+use crate::checkpoint::Journal;
+use crate::{fetch_url, FetchOpts, Kind};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(Args, Debug, Clone)]
+pub struct PagingArgs {
+    /// Stop after this many results (0 = no limit)
+    #[arg(long, default_value_t = 1000)]
+    pub max_results: usize,
+
+    /// Requested page size, sent as `pageSize`. RFC 8977 defines no page
+    /// size parameter, so this only works on servers that support that
+    /// extension; others ignore it
+    #[arg(long)]
+    pub page_size: Option<usize>,
+
+    /// RDAP base URL to search (default: the domain registry server)
+    #[arg(long)]
+    pub server: Option<String>,
+
+    /// Save the harvest cursor here after every page and resume from it
+    #[arg(long, value_name = "FILE")]
+    pub resume: Option<PathBuf>,
+
+    /// Minimum milliseconds between pages to the same server (default: the
+    /// `[search]` config section, else none)
+    #[arg(long, value_name = "MS")]
+    pub delay: Option<u64>,
+}
+
+/// Header of the `--resume` journal: the first-page URLs of the searches, to
+/// refuse resuming a different harvest.
+#[derive(Serialize, Deserialize, PartialEq, Eq)]
+struct Searches {
+    searches: Vec<String>,
+}
+
+/// One entry of the `--resume` journal.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+enum Step {
+    /// A page was handled (or cut short by `--max-results`).
+    Page {
+        /// Where the current search continues; `None` means its first page
+        next: Option<String>,
+        /// Keys of the results this page emitted
+        emitted: Vec<String>,
+        rate_limits: BTreeMap<String, crate::ratelimit::Saved>,
+    },
+    /// The current search was fully paged through.
+    Done,
+}
+
+/// Progress of a resumable harvest, replayed from the `--resume` journal.
+#[derive(Default)]
+struct Harvest {
+    /// Searches fully paged through
+    done: usize,
+    /// Next page of `searches[done]`; `None` means its first page
+    next: Option<String>,
+    /// Keys of results already emitted
+    emitted: Vec<String>,
+    rate_limits: BTreeMap<String, crate::ratelimit::Saved>,
+}
+
+impl Harvest {
+    fn replay(steps: Vec<Step>) -> Self {
+        let mut state = Self::default();
+        for step in steps {
+            match step {
+                Step::Page {
+                    next,
+                    emitted,
+                    rate_limits,
+                } => {
+                    state.next = next;
+                    state.emitted.extend(emitted);
+                    state.rate_limits = rate_limits;
+                }
+                Step::Done => {
+                    state.done += 1;
+                    state.next = None;
+                }
+            }
+        }
+        state
+    }
+}
+
+/// Spacing of page requests per server (`--delay`, `[search]` config).
+struct Politeness {
+    flag: Option<u64>,
+    last: HashMap<String, Instant>,
+}
+
+impl Politeness {
+    async fn wait(&mut self, url: &str) {
+        let host = crate::url_host(url);
+        let delay = self
+            .flag
+            .or_else(|| crate::config::get().search.delay_for(&host))
+            .map_or(Duration::ZERO, Duration::from_millis);
+        if let Some(last) = self.last.get(&host) {
+            let since = last.elapsed();
+            if let Some(left) = delay.checked_sub(since) {
+                tokio::time::sleep(left).await;
+            }
+        }
+        self.last.insert(host, Instant::now());
+    }
+}
+
+/// Build the first search URL, e.g. `<base>/domains?name=exam*.com`.
+pub fn search_url(
+    paging: &PagingArgs,
+    path: &str,
+    param: &str,
+    value: &str,
+) -> Result<reqwest::Url, Box<dyn Error>> {
+    let base = paging
+        .server
+        .as_deref()
+        .unwrap_or_else(|| crate::base_url(Kind::Domain));
+    let base = if base.ends_with('/') {
+        base.to_string()
+    } else {
+        format!("{base}/")
+    };
+    let mut url = reqwest::Url::parse(&base)?.join(path)?;
+    url.query_pairs_mut().append_pair(param, value);
+    if let Some(size) = paging.page_size {
+        // server-specific: RFC 8977 only standardizes `count` and `cursor`
+        url.query_pairs_mut()
+            .append_pair("pageSize", &size.to_string());
+    }
+    Ok(url)
+}
+
+/// `next` link from `paging_metadata.links` or the top-level `links`.
+fn next_link(page: &Value) -> Option<String> {
+    let from = |links: Option<&Value>| {
+        links?
+            .as_array()?
+            .iter()
+            .find(|l| l.get("rel").and_then(Value::as_str) == Some("next"))
+            .and_then(|l| l.get("href").and_then(Value::as_str))
+            .map(str::to_string)
+    };
+    from(page.get("paging_metadata").and_then(|m| m.get("links")))
+        .or_else(|| from(page.get("links")))
+}
+
+/// Results array of a search response (`domainSearchResults`, ...).
+fn results(page: &mut Value) -> Vec<Value> {
+    let Some(obj) = page.as_object_mut() else {
+        return Vec::new();
+    };
+    let key = obj
+        .keys()
+        .find(|k| k.ends_with("SearchResults"))
+        .cloned()
+        .unwrap_or_default();
+    match obj.remove(&key) {
+        Some(Value::Array(items)) => items,
+        _ => Vec::new(),
+    }
+}
+
+/// Check a domain name pattern against RFC 9082 partial-match rules: no empty
+/// labels, and at most one `*`, which must end a label and follow at least one
+/// character.
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    let labels: Vec<&str> = pattern.trim_end_matches('.').split('.').collect();
+    if labels.iter().any(|l| l.is_empty()) {
+        return Err("empty label".into());
+    }
+    let stars = pattern.matches('*').count();
+    if stars > 1 {
+        return Err("only one '*' is allowed".into());
+    }
+    if stars == 1 {
+        let label = labels
+            .iter()
+            .find(|l| l.contains('*'))
+            .copied()
+            .unwrap_or_default();
+        if !label.ends_with('*') {
+            return Err("'*' must be the last character of a label".into());
+        }
+        if label.len() == 1 {
+            return Err("'*' must follow at least one character".into());
+        }
+    }
+    Ok(())
+}
+
+/// Identity used to merge results from several searches or overlapping pages.
+fn result_key(item: &Value) -> String {
+    ["ldhName", "handle"]
+        .iter()
+        .find_map(|k| item.get(*k).and_then(Value::as_str))
+        .map_or_else(|| item.to_string(), str::to_lowercase)
+}
+
+/// Follow pages from each URL in `searches`, handing each distinct result to
+/// `emit`. Search pages are never cached. Returns the number of results
+/// emitted, counting those of earlier runs when resuming.
+pub async fn run(
+    client: &reqwest::Client,
+    searches: Vec<reqwest::Url>,
+    paging: &PagingArgs,
+    opts: &FetchOpts<'_>,
+    mut emit: impl FnMut(Value),
+) -> Result<usize, Box<dyn Error>> {
+    let opts = FetchOpts {
+        no_cache: true,
+        ..*opts
+    };
+    let limit = if paging.max_results == 0 {
+        usize::MAX
+    } else {
+        paging.max_results
+    };
+    let searches: Vec<String> = searches.iter().map(ToString::to_string).collect();
+    let (mut journal, state) = match &paging.resume {
+        Some(path) => {
+            let header = Searches {
+                searches: searches.clone(),
+            };
+            let (journal, steps) =
+                Journal::open(path, &header, |_| "a different search".to_string())?;
+            let state = Harvest::replay(steps);
+            crate::ratelimit::restore(&state.rate_limits);
+            (Some(journal), state)
+        }
+        None => (None, Harvest::default()),
+    };
+    if state.done == searches.len() && !searches.is_empty() {
+        eprintln!("Note: this harvest is already complete; remove the --resume file to rerun it");
+    } else if state.done > 0 || state.next.is_some() {
+        eprintln!(
+            "Resuming harvest at search {} of {}, {} result(s) so far",
+            state.done + 1,
+            searches.len(),
+            state.emitted.len()
+        );
+    }
+    let mut record = |step: Step| journal.as_mut().map_or(Ok(()), |j| j.append(&step));
+    let mut polite = Politeness {
+        flag: paging.delay,
+        last: HashMap::new(),
+    };
+    let mut visited = HashSet::new();
+    let mut emitted: HashSet<String> = state.emitted.iter().cloned().collect();
+    let mut count = emitted.len();
+
+    let page_done = |next: Option<String>, emitted: Vec<String>| Step::Page {
+        next,
+        emitted,
+        rate_limits: crate::ratelimit::save(),
+    };
+    let mut resume_at = state.next;
+    for first in searches.iter().skip(state.done) {
+        let mut next = Some(resume_at.take().unwrap_or_else(|| first.clone()));
+        while let Some(url) = next.take() {
+            if !visited.insert(url.clone()) {
+                break; // server handed back a page we already visited
+            }
+            polite.wait(&url).await;
+            let mut page = fetch_url(client, &url, &url, &opts).await?;
+            next = next_link(&page);
+            let mut keys = Vec::new();
+            for item in results(&mut page) {
+                if count == limit {
+                    // resume re-reads this page and skips what was emitted
+                    record(page_done(Some(url), keys))?;
+                    return Ok(count);
+                }
+                let key = result_key(&item);
+                if emitted.insert(key.clone()) {
+                    keys.push(key);
+                    emit(item);
+                    count += 1;
+                }
+            }
+            record(page_done(next.clone(), keys))?;
+        }
+        record(Step::Done)?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{result_key, validate_pattern};
+    use serde_json::json;
+
+    #[test]
+    fn partial_match_patterns_follow_rfc_9082() {
+        assert_eq!(validate_pattern("example*.com"), Ok(()));
+        assert_eq!(
+            validate_pattern("ns1.example.*"),
+            Err("'*' must follow at least one character".into())
+        );
+        assert_eq!(
+            validate_pattern("exa*mple.com"),
+            Err("'*' must be the last character of a label".into())
+        );
+        assert_eq!(
+            validate_pattern("ex*.co*"),
+            Err("only one '*' is allowed".into())
+        );
+        assert_eq!(validate_pattern("example..com"), Err("empty label".into()));
+        assert_eq!(validate_pattern("example.com."), Ok(()));
+    }
+
+    #[test]
+    fn results_merge_by_name_or_handle_ignoring_case() {
+        assert_eq!(
+            result_key(&json!({"ldhName": "Example.COM"})),
+            "example.com"
+        );
+        assert_eq!(result_key(&json!({"handle": "ABC-1"})), "abc-1");
+        assert_eq!(result_key(&json!({"port43": "x"})), r#"{"port43":"x"}"#);
+    }
+}
@@ -0,0 +1,338 @@
+//! `rdapx serve`: a small read-only RDAP HTTP endpoint on top of the lookup
+//! path. Routes mirror RDAP (`/domain/<name>`, `/ip/<addr>[/<len>]`,
+//! `/autnum/<n>`). With `--offline` answers come only from the local cache
+//! and snapshot history — nothing is fetched upstream and misses are 404 —
+//! which turns previously collected data into a mirror for air-gapped networks.
+//!
+//! `POST /bulk` takes a JSON array of queries and streams one NDJSON line
+//! per query as it completes: the record `rdapx bulk --format json` writes
+//! (with `_meta.query`) or an `{"error": {...}}` document, fetched through
+//! the same routing, cache, and rate limits.
+//!
+//! Upstream lookups share one `--concurrency` cap across all clients, and
+//! `--rate` spaces the requests to each registry.
+//!
+//! Answers carry a weak `ETag` (the content hash history uses, so it only
+//! changes when the registry data does) and a `Cache-Control: max-age` for
+//! the time the cached copy has left; a matching `If-None-Match` gets a 304.
+
+use crate::ratelimit::HostLimiter;
+use crate::{
+    attach_meta, attach_tags, cache_ttl, error_json, fetch_for_query, history, is_not_found,
+    json_record, now_secs, query_url, read_cache_entry, url_host, FetchOpts, OutputSchema, Tags,
+};
+use futures::stream::{self, StreamExt};
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Full, Limited, StreamBody};
+use hyper::body::{Bytes, Frame, Incoming};
+use hyper::header::{
+    HeaderMap, HeaderValue, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH, RETRY_AFTER,
+};
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+
+type Body = UnsyncBoxBody<Bytes, Infallible>;
+
+/// Largest `POST /bulk` request body and query count accepted.
+const MAX_BULK_BODY: usize = 1 << 20;
+const MAX_BULK_QUERIES: usize = 10_000;
+
+pub struct State {
+    pub client: reqwest::Client,
+    pub opts: FetchOpts<'static>,
+    /// Answer from cache/history only
+    pub offline: bool,
+    /// Upstream lookups allowed in flight (`--concurrency`)
+    pub concurrency: usize,
+    pub slots: Semaphore,
+    /// Per-registry pacing (`--rate`)
+    pub limiter: Option<HostLimiter>,
+    /// Envelope of `/bulk` records
+    pub schema: OutputSchema,
+    /// `--tag` pairs for `/bulk` records
+    pub tags: Vec<(String, String)>,
+}
+
+pub async fn run(addr: SocketAddr, state: State) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(addr).await?;
+    let mode = if state.offline { " (offline)" } else { "" };
+    eprintln!("Listening on http://{}{mode}", listener.local_addr()?);
+    let state = Arc::new(state);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            let svc = service_fn(move |req| {
+                let state = Arc::clone(&state);
+                async move { Ok::<_, Infallible>(dispatch(state, req).await) }
+            });
+            let _ = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), svc)
+                .await;
+        });
+    }
+}
+
+/// Map an RDAP path to an rdapx query (`/autnum/13335` -> `AS13335`).
+fn route(path: &str) -> Option<String> {
+    let (class, rest) = path.trim_start_matches('/').split_once('/')?;
+    let rest = rest.trim_end_matches('/');
+    if rest.is_empty() {
+        return None;
+    }
+    match class {
+        "domain" | "ip" => Some(rest.to_string()),
+        "autnum" if rest.bytes().all(|b| b.is_ascii_digit()) => Some(format!("AS{rest}")),
+        _ => None,
+    }
+}
+
+/// Look `q` up upstream within the shared concurrency cap and rate.
+async fn fetch(state: &State, q: &str) -> Result<Value, Box<dyn Error>> {
+    let _slot = state.slots.acquire().await?;
+    if let Some(limiter) = &state.limiter {
+        limiter.wait(&url_host(&query_url(q))).await;
+    }
+    fetch_for_query(&state.client, q, &state.opts).await
+}
+
+/// Latest stored copy of `q`: the cache entry regardless of age, then history.
+fn stored(q: &str) -> Option<Value> {
+    let url = query_url(q);
+    read_cache_entry(&url)
+        .filter(|e| !e.not_found)
+        .map(|e| e.data)
+        .or_else(|| history::latest(&url).map(|e| e.data))
+}
+
+async fn dispatch(state: Arc<State>, req: Request<Incoming>) -> Response<Body> {
+    if req.uri().path().trim_end_matches('/') == "/bulk" {
+        if req.method() != Method::POST {
+            return error(StatusCode::METHOD_NOT_ALLOWED, "/bulk takes a POST").map(boxed);
+        }
+        return bulk(state, req).await;
+    }
+    handle(&state, &req).await.map(boxed)
+}
+
+fn boxed(body: Full<Bytes>) -> Body {
+    body.boxed_unsync()
+}
+
+/// `POST /bulk`: a JSON array of queries in, NDJSON out in completion order.
+async fn bulk(state: Arc<State>, req: Request<Incoming>) -> Response<Body> {
+    let body = match Limited::new(req.into_body(), MAX_BULK_BODY).collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(e) if e.is::<http_body_util::LengthLimitError>() => {
+            return error(StatusCode::PAYLOAD_TOO_LARGE, "request body over 1 MiB").map(boxed)
+        }
+        Err(e) => return error(StatusCode::BAD_REQUEST, &e.to_string()).map(boxed),
+    };
+    let queries: Vec<String> = match serde_json::from_slice(&body) {
+        Ok(queries) => queries,
+        Err(e) => {
+            let msg = format!("expected a JSON array of query strings: {e}");
+            return error(StatusCode::BAD_REQUEST, &msg).map(boxed);
+        }
+    };
+    if queries.len() > MAX_BULK_QUERIES {
+        let msg = format!("at most {MAX_BULK_QUERIES} queries per request");
+        return error(StatusCode::PAYLOAD_TOO_LARGE, &msg).map(boxed);
+    }
+    // `slots` caps lookups across clients; this bounds one request's share
+    let concurrency = state.concurrency;
+    let lines = stream::iter(queries)
+        .map(move |q| {
+            let state = Arc::clone(&state);
+            async move { bulk_line(&state, &q).await }
+        })
+        .buffer_unordered(concurrency)
+        .map(|line| Ok(Frame::data(Bytes::from(format!("{line}\n")))));
+    let mut resp = Response::new(StreamBody::new(lines).boxed_unsync());
+    resp.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/x-ndjson"),
+    );
+    resp
+}
+
+/// One `/bulk` result line for `q`.
+async fn bulk_line(state: &State, q: &str) -> Value {
+    let q = match crate::plan::validate(q) {
+        Ok((q, _)) => q,
+        Err(reason) => {
+            return json!({ "error": { "kind": "rejected", "query": q, "message": reason } })
+        }
+    };
+    let result = if state.offline {
+        stored(&q).ok_or_else(|| "not in the local store".into())
+    } else {
+        fetch(state, &q).await
+    };
+    match result {
+        Ok(mut v) => {
+            attach_tags(&mut v, &state.tags, &Tags::new());
+            attach_meta(&mut v, "query", Value::from(q.as_str()));
+            json_record(v, state.schema)
+        }
+        Err(e) => error_json(&q, e.as_ref()),
+    }
+}
+
+async fn handle(state: &State, req: &Request<Incoming>) -> Response<Full<Bytes>> {
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        return error(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "only GET and HEAD are supported (and POST /bulk)",
+        );
+    }
+    let Some(q) = route(req.uri().path()) else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "expected /domain/, /ip/, or /autnum/",
+        );
+    };
+    if state.offline {
+        return stored(&q).map_or_else(
+            || error(StatusCode::NOT_FOUND, "not in the local store"),
+            |v| cached(state, req, &q, &v),
+        );
+    }
+    match fetch(state, &q).await {
+        Ok(v) => cached(state, req, &q, &v),
+        Err(e) if is_not_found(e.as_ref()) => error(StatusCode::NOT_FOUND, "not found"),
+        Err(e) if e.is::<crate::breaker::Open>() => {
+            let wait = e
+                .downcast_ref::<crate::breaker::Open>()
+                .map_or(0, |o| o.wait);
+            let mut resp = error(StatusCode::SERVICE_UNAVAILABLE, &e.to_string());
+            resp.headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(wait));
+            resp
+        }
+        Err(e) => error(StatusCode::BAD_GATEWAY, &e.to_string()),
+    }
+}
+
+/// Seconds the cached copy of `q` stays fresh; 0 when there is none.
+fn max_age(state: &State, q: &str) -> u64 {
+    if state.opts.no_cache {
+        return 0;
+    }
+    let url = query_url(q);
+    read_cache_entry(&url).map_or(0, |e| {
+        let age = now_secs().saturating_sub(e.fetched_at);
+        cache_ttl(&url, state.opts.ttl)
+            .as_secs()
+            .saturating_sub(age)
+    })
+}
+
+/// Whether an `If-None-Match` header lists `etag` (weak comparison).
+fn matches_etag(headers: &HeaderMap, etag: &str) -> bool {
+    let Some(header) = headers.get(IF_NONE_MATCH).and_then(|h| h.to_str().ok()) else {
+        return false;
+    };
+    let strip = |t: &str| t.trim().trim_start_matches("W/").to_string();
+    header
+        .split(',')
+        .any(|t| t.trim() == "*" || strip(t) == strip(etag))
+}
+
+/// 200 with validators and freshness, or 304 when the client has it.
+fn cached(state: &State, req: &Request<Incoming>, q: &str, v: &Value) -> Response<Full<Bytes>> {
+    let etag = format!("W/\"{}\"", history::content_hash(v));
+    let mut resp = if matches_etag(req.headers(), &etag) {
+        let mut resp = Response::new(Full::new(Bytes::new()));
+        *resp.status_mut() = StatusCode::NOT_MODIFIED;
+        resp
+    } else {
+        rdap(StatusCode::OK, v)
+    };
+    let control = match max_age(state, q) {
+        0 => "no-cache".to_string(),
+        secs => format!("max-age={secs}"),
+    };
+    let headers = resp.headers_mut();
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        headers.insert(ETAG, etag);
+    }
+    if let Ok(control) = HeaderValue::from_str(&control) {
+        headers.insert(CACHE_CONTROL, control);
+    }
+    resp
+}
+
+fn rdap(status: StatusCode, body: &Value) -> Response<Full<Bytes>> {
+    let mut resp = Response::new(Full::new(Bytes::from(body.to_string())));
+    *resp.status_mut() = status;
+    resp.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/rdap+json"),
+    );
+    resp
+}
+
+/// RDAP error response (RFC 9083 section 6).
+fn error(status: StatusCode, description: &str) -> Response<Full<Bytes>> {
+    let body = json!({
+        "errorCode": status.as_u16(),
+        "title": status.canonical_reason().unwrap_or("Error"),
+        "description": [description],
+    });
+    let mut resp = rdap(status, &body);
+    resp.headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches_etag, route};
+    use hyper::header::{HeaderMap, HeaderValue, IF_NONE_MATCH};
+
+    #[test]
+    fn rdap_paths_map_to_queries() {
+        assert_eq!(route("/domain/example.com").as_deref(), Some("example.com"));
+        assert_eq!(
+            route("/domain/example.com/").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(route("/ip/192.0.2.0/24").as_deref(), Some("192.0.2.0/24"));
+        assert_eq!(route("/autnum/13335").as_deref(), Some("AS13335"));
+    }
+
+    #[test]
+    fn other_paths_are_not_queries() {
+        assert_eq!(route("/autnum/AS13335"), None);
+        assert_eq!(route("/domain/"), None);
+        assert_eq!(route("/entity/ABC123-ARIN"), None);
+        assert_eq!(route("/help"), None);
+        assert_eq!(route("/"), None);
+    }
+
+    fn if_none_match(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn etags_compare_weakly() {
+        let etag = "W/\"abc\"";
+        assert!(matches_etag(&if_none_match("W/\"abc\""), etag));
+        assert!(matches_etag(&if_none_match("\"abc\""), etag));
+        assert!(matches_etag(&if_none_match("\"x\", W/\"abc\""), etag));
+        assert!(matches_etag(&if_none_match("*"), etag));
+        assert!(!matches_etag(&if_none_match("\"abd\""), etag));
+        assert!(!matches_etag(&HeaderMap::new(), etag));
+    }
+}
@@ -0,0 +1,261 @@
+//! `bulk --shard K/N` and `rdapx merge`: split one huge input across N
+//! machines without coordination, then combine what they produced.
+//!
+//! A query belongs to shard `hash(query) mod N`, using BLAKE3 of the
+//! lowercased query so every machine (and every rdapx version) agrees on the
+//! partition regardless of input order or line endings.
+
+use crate::model::{RegistryLatency, SummaryReport};
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    /// 1-based
+    pub index: u64,
+    pub count: u64,
+}
+
+impl Shard {
+    /// Parse `K/N` with `1 <= K <= N`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (k, n) = s
+            .split_once('/')
+            .ok_or_else(|| format!("invalid shard '{s}' (expected K/N, e.g. 3/8)"))?;
+        let num = |v: &str| {
+            v.trim()
+                .parse::<u64>()
+                .map_err(|_| format!("invalid shard '{s}' (expected K/N, e.g. 3/8)"))
+        };
+        let (index, count) = (num(k)?, num(n)?);
+        if count == 0 || index == 0 || index > count {
+            return Err(format!("shard '{s}' must satisfy 1 <= K <= N"));
+        }
+        Ok(Self { index, count })
+    }
+
+    /// Whether `query` falls into this shard.
+    pub fn owns(self, query: &str) -> bool {
+        let hash = blake3::hash(query.trim().to_lowercase().as_bytes());
+        let mut head = [0u8; 8];
+        head.copy_from_slice(&hash.as_bytes()[..8]);
+        u64::from_le_bytes(head) % self.count == self.index - 1
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// Problems with the set of shard summaries being merged: shards missing,
+/// given twice, or from runs with a different N.
+pub fn coverage(reports: &[SummaryReport]) -> Vec<String> {
+    let shards: Vec<Shard> = reports
+        .iter()
+        .filter_map(|r| r.shard.as_deref().and_then(|s| Shard::parse(s).ok()))
+        .collect();
+    let Some(first) = shards.first() else {
+        return Vec::new();
+    };
+    let mut problems = Vec::new();
+    if shards.iter().any(|s| s.count != first.count) {
+        problems.push("summaries come from runs with different shard counts".to_string());
+        return problems;
+    }
+    let mut seen: BTreeMap<u64, usize> = BTreeMap::new();
+    for s in &shards {
+        *seen.entry(s.index).or_default() += 1;
+    }
+    let missing: Vec<String> = (1..=first.count)
+        .filter(|i| !seen.contains_key(i))
+        .map(|i| format!("{i}/{}", first.count))
+        .collect();
+    if !missing.is_empty() {
+        problems.push(format!("missing shard(s) {}", missing.join(", ")));
+    }
+    let twice: Vec<String> = seen
+        .iter()
+        .filter(|(_, n)| **n > 1)
+        .map(|(i, _)| format!("{i}/{}", first.count))
+        .collect();
+    if !twice.is_empty() {
+        problems.push(format!(
+            "shard(s) {} given more than once",
+            twice.join(", ")
+        ));
+    }
+    problems
+}
+
+/// One summary for the whole distributed run: counts and bytes add up,
+/// elapsed is the slowest shard (they ran in parallel), per-registry
+/// latencies are recombined, and the slowest list keeps the overall top.
+pub fn merge_summaries(reports: Vec<SummaryReport>) -> SummaryReport {
+    let keep = reports.iter().map(|r| r.slowest.len()).max().unwrap_or(0);
+    let mut merged = SummaryReport::default();
+    // host -> (queries, total ms, max ms, bytes)
+    let mut hosts: BTreeMap<String, (u32, u64, u64, u64)> = BTreeMap::new();
+    let mut aborted = Vec::new();
+    for r in reports {
+        merged.total += r.total;
+        merged.ok += r.ok;
+        merged.failed += r.failed;
+        merged.not_found += r.not_found;
+        merged.unchanged += r.unchanged;
+        merged.rejected += r.rejected;
+        merged.filtered += r.filtered;
+        merged.skipped += r.skipped;
+        merged.elapsed_ms = merged.elapsed_ms.max(r.elapsed_ms);
+        merged.bytes += r.bytes;
+        if let Some(reason) = r.aborted {
+            let shard = r.shard.map(|s| format!("shard {s}: ")).unwrap_or_default();
+            aborted.push(format!("{shard}{reason}"));
+        }
+        merged.slowest.extend(r.slowest);
+        for h in r.registries {
+            let e = hosts.entry(h.host).or_default();
+            e.0 += h.queries;
+            e.1 += h.avg_ms * u64::from(h.queries);
+            e.2 = e.2.max(h.max_ms);
+            e.3 += h.bytes;
+        }
+        // the most depleted quota is the one worth knowing about
+        for (host, q) in r.rate_limits {
+            match merged.rate_limits.get(&host) {
+                Some(seen) if seen.remaining <= q.remaining => {}
+                _ => {
+                    merged.rate_limits.insert(host, q);
+                }
+            }
+        }
+    }
+    merged
+        .slowest
+        .sort_by_key(|t| std::cmp::Reverse(t.elapsed_ms));
+    merged.slowest.truncate(keep);
+    merged.registries = hosts
+        .into_iter()
+        .map(|(host, (queries, total, max_ms, bytes))| RegistryLatency {
+            host,
+            queries,
+            avg_ms: total / u64::from(queries.max(1)),
+            max_ms,
+            bytes,
+        })
+        .collect();
+    merged.aborted = (!aborted.is_empty()).then(|| aborted.join("; "));
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{coverage, merge_summaries, Shard};
+    use crate::model::{QueryLatency, RegistryLatency, SummaryReport};
+
+    #[test]
+    fn parses_k_of_n() {
+        assert_eq!(Shard::parse("3/8"), Ok(Shard { index: 3, count: 8 }));
+        assert_eq!(Shard::parse(" 1 / 1 "), Ok(Shard { index: 1, count: 1 }));
+        for bad in ["0/8", "9/8", "1/0", "3", "a/8", "3/"] {
+            assert!(Shard::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn every_query_has_exactly_one_owner() {
+        let queries = (0..200).map(|i| format!("example{i}.com"));
+        let shards: Vec<Shard> = (1..=5).map(|index| Shard { index, count: 5 }).collect();
+        let mut used = [false; 5];
+        for q in queries {
+            let owners: Vec<usize> = (0..5).filter(|&i| shards[i].owns(&q)).collect();
+            assert_eq!(owners.len(), 1, "{q}");
+            used[owners[0]] = true;
+        }
+        assert!(used.iter().all(|u| *u));
+    }
+
+    #[test]
+    fn ownership_ignores_case_and_whitespace() {
+        for index in 1..=4 {
+            let shard = Shard { index, count: 4 };
+            assert_eq!(shard.owns("Example.COM \r"), shard.owns("example.com"));
+        }
+    }
+
+    fn report(shard: &str, total: usize, elapsed_ms: u64) -> SummaryReport {
+        SummaryReport {
+            total,
+            ok: total - 1,
+            failed: 1,
+            elapsed_ms,
+            bytes: 100,
+            shard: Some(shard.to_string()),
+            ..SummaryReport::default()
+        }
+    }
+
+    #[test]
+    fn merge_adds_counts_and_keeps_the_slowest_shard() {
+        let mut a = report("1/2", 10, 500);
+        a.registries = vec![RegistryLatency {
+            host: "rdap.test".into(),
+            queries: 2,
+            avg_ms: 100,
+            max_ms: 150,
+            bytes: 10,
+        }];
+        a.slowest = vec![QueryLatency {
+            query: "a.test".into(),
+            host: "rdap.test".into(),
+            elapsed_ms: 150,
+        }];
+        let mut b = report("2/2", 5, 900);
+        b.registries = vec![RegistryLatency {
+            host: "rdap.test".into(),
+            queries: 6,
+            avg_ms: 200,
+            max_ms: 400,
+            bytes: 30,
+        }];
+        b.slowest = vec![QueryLatency {
+            query: "b.test".into(),
+            host: "rdap.test".into(),
+            elapsed_ms: 400,
+        }];
+        b.aborted = Some("too many failures".into());
+
+        let merged = merge_summaries(vec![a, b]);
+        assert_eq!((merged.total, merged.ok, merged.failed), (15, 13, 2));
+        assert_eq!(merged.elapsed_ms, 900);
+        assert_eq!(merged.bytes, 200);
+        assert_eq!(merged.registries.len(), 1);
+        let host = &merged.registries[0];
+        assert_eq!((host.queries, host.avg_ms, host.max_ms), (8, 175, 400));
+        assert_eq!(host.bytes, 40);
+        // each shard kept one, so the merge keeps the overall slowest one
+        assert_eq!(merged.slowest.len(), 1);
+        assert_eq!(merged.slowest[0].query, "b.test");
+        assert_eq!(
+            merged.aborted.as_deref(),
+            Some("shard 2/2: too many failures")
+        );
+    }
+
+    #[test]
+    fn coverage_reports_missing_and_repeated_shards() {
+        let full = [report("1/2", 1, 0), report("2/2", 1, 0)];
+        assert!(coverage(&full).is_empty());
+        let gaps = [report("1/3", 1, 0), report("1/3", 1, 0)];
+        assert_eq!(
+            coverage(&gaps),
+            [
+                "missing shard(s) 2/3, 3/3",
+                "shard(s) 1/3 given more than once"
+            ]
+        );
+        let mixed = [report("1/2", 1, 0), report("2/3", 1, 0)];
+        assert_eq!(coverage(&mixed).len(), 1);
+    }
+}
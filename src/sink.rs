@@ -0,0 +1,489 @@
+//! `--output` sinks. Every record is written to each sink; a sink that fails
+//! is reported once and dropped while the others keep going, so one bad
+//! destination never costs a re-run of the fetches.
+//!
+//! | spec                         | writes                                    |
+//! |------------------------------|-------------------------------------------|
+//! | `-`                          | stdout in `--format`                      |
+//! | `path.ndjson` / `path.jsonl` | one JSON record per line                  |
+//! | `path.csv`                   | table fields as CSV                       |
+//! | `path.msgpack` / `path.cbor` | length-prefixed binary records            |
+//! | `sqlite:path.db`             | `records` table (needs the `sqlite` feature) |
+//! | `es://host:9200/index`       | Elasticsearch `_bulk` API (`ess://` for HTTPS) |
+//!
+//! File sinks ending in `.gz` or `.zst` are compressed one frame per record
+//! (gzip members / zstd frames), so a file cut short by an interrupted run
+//! still decompresses up to the last complete record. Options go after `?`:
+//! `results.ndjson.zst?level=19`, `out.csv?codec=gzip&level=9`.
+
+use crate::model::Row;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+
+/// Records buffered per Elasticsearch `_bulk` request.
+const ES_BATCH: usize = 500;
+
+/// Per-record compression of a file sink.
+#[derive(Clone, Copy, Debug)]
+pub enum Codec {
+    None,
+    Gzip(u32),
+    Zstd(i32),
+}
+
+impl Codec {
+    /// Codec from a file extension plus `codec=`/`level=` options.
+    fn parse(ext: &str, options: &str) -> Result<Self, String> {
+        let mut name = match ext {
+            "gz" => "gzip",
+            "zst" => "zstd",
+            _ => "none",
+        };
+        let mut level = None;
+        for opt in options.split('&').filter(|o| !o.is_empty()) {
+            match opt.split_once('=') {
+                Some(("codec", v)) => name = v,
+                Some(("level", v)) => {
+                    level = Some(v.parse::<i32>().map_err(|_| format!("bad level '{v}'"))?);
+                }
+                _ => {
+                    return Err(format!(
+                        "unknown sink option '{opt}' (expected codec=, level=)"
+                    ))
+                }
+            }
+        }
+        match name {
+            "none" if level.is_none() => Ok(Self::None),
+            "gzip" | "gz" => {
+                let level = level.unwrap_or(6);
+                u32::try_from(level)
+                    .ok()
+                    .filter(|l| *l <= 9)
+                    .map(Self::Gzip)
+                    .ok_or_else(|| format!("gzip level must be 0-9, got {level}"))
+            }
+            "zstd" | "zst" => {
+                let level = level.unwrap_or(3);
+                if zstd::compression_level_range().contains(&level) {
+                    Ok(Self::Zstd(level))
+                } else {
+                    Err(format!("zstd level {level} out of range"))
+                }
+            }
+            "none" => Err("level= needs a compressed sink (.gz, .zst, or codec=)".into()),
+            other => Err(format!("unknown codec '{other}' (gzip, zstd, none)")),
+        }
+    }
+
+    /// Compress `bytes` as one self-contained frame.
+    fn frame(self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::None => Ok(bytes.to_vec()),
+            Self::Gzip(level) => {
+                let mut gz =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level));
+                gz.write_all(bytes)?;
+                gz.finish()
+            }
+            Self::Zstd(level) => zstd::bulk::compress(bytes, level),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SinkSpec {
+    Stdout,
+    File(PathBuf, Codec),
+    Sqlite(PathBuf),
+    Elastic { url: String, index: String },
+}
+
+impl FromStr for SinkSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "-" {
+            return Ok(Self::Stdout);
+        }
+        if let Some(path) = s.strip_prefix("sqlite:") {
+            return Ok(Self::Sqlite(PathBuf::from(path)));
+        }
+        for (scheme, http) in [("es://", "http://"), ("ess://", "https://")] {
+            if let Some(rest) = s.strip_prefix(scheme) {
+                let (host, index) = rest
+                    .split_once('/')
+                    .filter(|(_, i)| !i.is_empty())
+                    .ok_or_else(|| format!("expected {scheme}host:port/index, got '{s}'"))?;
+                return Ok(Self::Elastic {
+                    url: format!("{http}{host}/{}/_bulk", index.trim_end_matches('/')),
+                    index: index.to_string(),
+                });
+            }
+        }
+        let (path, options) = s.split_once('?').unwrap_or((s, ""));
+        let path = PathBuf::from(path);
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        Ok(Self::File(path.clone(), Codec::parse(ext, options)?))
+    }
+}
+
+enum FileFormat {
+    Ndjson,
+    Csv,
+    Binary(crate::Format),
+}
+
+/// File sink writer; each record goes through [`Codec::frame`].
+struct FileOut {
+    w: BufWriter<File>,
+    codec: Codec,
+}
+
+impl FileOut {
+    fn put(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.w.write_all(&self.codec.frame(bytes)?)
+    }
+}
+
+enum Target {
+    Stdout,
+    File(FileOut, FileFormat),
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Connection),
+    Elastic {
+        client: reqwest::Client,
+        url: String,
+        buf: String,
+        count: usize,
+        pending: Vec<tokio::task::JoinHandle<Result<(), String>>>,
+    },
+}
+
+struct Sink {
+    name: String,
+    target: Option<Target>,
+}
+
+static SINKS: OnceLock<Mutex<Vec<Sink>>> = OnceLock::new();
+
+/// Open every sink up front so bad paths fail before any fetching starts.
+/// The sinks are finished when the returned guard is dropped.
+pub fn init(specs: &[SinkSpec]) -> Result<Finish, Box<dyn std::error::Error>> {
+    let mut sinks = Vec::new();
+    for spec in specs {
+        let (name, target) = match spec {
+            SinkSpec::Stdout => ("-".to_string(), Target::Stdout),
+            SinkSpec::File(path, codec) => {
+                // results.ndjson.zst -> ndjson
+                let inner = match codec {
+                    Codec::None => path.as_path(),
+                    _ => path
+                        .file_stem()
+                        .map_or(path.as_path(), std::path::Path::new),
+                };
+                let ext = inner.extension().and_then(|e| e.to_str()).unwrap_or("");
+                let fmt = match ext {
+                    "csv" => FileFormat::Csv,
+                    "msgpack" | "mpk" => FileFormat::Binary(crate::Format::Msgpack),
+                    "cbor" => FileFormat::Binary(crate::Format::Cbor),
+                    _ => FileFormat::Ndjson,
+                };
+                let file = File::create(path).map_err(|e| format!("{}: {e}", path.display()))?;
+                let mut out = FileOut {
+                    w: BufWriter::new(file),
+                    codec: *codec,
+                };
+                if matches!(fmt, FileFormat::Csv) {
+                    let header = crate::preset::csv_header();
+                    let header = header.as_deref().unwrap_or(Row::CSV_HEADER);
+                    out.put(format!("{header}\n").as_bytes())?;
+                }
+                (path.display().to_string(), Target::File(out, fmt))
+            }
+            #[cfg(feature = "sqlite")]
+            SinkSpec::Sqlite(path) => {
+                let conn = rusqlite::Connection::open(path)
+                    .map_err(|e| format!("{}: {e}", path.display()))?;
+                conn.execute_batch(
+                    "CREATE TABLE IF NOT EXISTS records (
+                        id INTEGER PRIMARY KEY,
+                        kind TEXT, handle TEXT, name TEXT, country TEXT, status TEXT,
+                        written_at TEXT NOT NULL,
+                        record TEXT NOT NULL
+                    )",
+                )?;
+                (format!("sqlite:{}", path.display()), Target::Sqlite(conn))
+            }
+            #[cfg(not(feature = "sqlite"))]
+            SinkSpec::Sqlite(path) => {
+                return Err(format!(
+                    "sqlite:{}: sinks need rdapx built with the `sqlite` feature",
+                    path.display()
+                )
+                .into())
+            }
+            SinkSpec::Elastic { url, index } => (
+                format!("es:{index}"),
+                Target::Elastic {
+                    client: crate::http_client(30)?,
+                    url: url.clone(),
+                    buf: String::new(),
+                    count: 0,
+                    pending: Vec::new(),
+                },
+            ),
+        };
+        sinks.push(Sink {
+            name,
+            target: Some(target),
+        });
+    }
+    let _ = SINKS.set(Mutex::new(sinks));
+    Ok(Finish)
+}
+
+/// Finishes the sinks when dropped, so error returns flush them too.
+#[must_use = "the sinks are finished when this is dropped"]
+pub struct Finish;
+
+impl Drop for Finish {
+    fn drop(&mut self) {
+        tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(finish()));
+    }
+}
+
+fn sinks() -> Option<std::sync::MutexGuard<'static, Vec<Sink>>> {
+    SINKS
+        .get()
+        .map(|m| m.lock().unwrap_or_else(std::sync::PoisonError::into_inner))
+}
+
+/// True when `--output` was given.
+pub fn active() -> bool {
+    SINKS.get().is_some()
+}
+
+/// True unless `--output` was given without `-`.
+pub fn to_stdout() -> bool {
+    sinks().is_none_or(|s| s.iter().any(|s| matches!(s.target, Some(Target::Stdout))))
+}
+
+/// Write one shaped record to every sink.
+pub fn write(record: &Value) {
+    let Some(mut sinks) = sinks() else {
+        return;
+    };
+    for sink in sinks.iter_mut() {
+        let Some(target) = sink.target.as_mut() else {
+            continue;
+        };
+        if let Err(e) = write_one(target, record) {
+            eprintln!("output {} failed, disabling it: {e}", sink.name);
+            sink.target = None;
+        }
+    }
+}
+
+fn write_one(target: &mut Target, record: &Value) -> Result<(), String> {
+    // table columns come from the RDAP object, nested under `rdap` in schema v2
+    let raw = record.get("rdap").unwrap_or(record);
+    match target {
+        Target::Stdout => Ok(()), // handled by the caller in --format
+        Target::File(out, FileFormat::Ndjson) => {
+            let line = crate::preset::record(record).unwrap_or_else(|| record.clone());
+            out.put(format!("{line}\n").as_bytes())
+                .map_err(|e| e.to_string())
+        }
+        Target::File(out, FileFormat::Csv) => {
+            let row =
+                crate::preset::csv_row(record).unwrap_or_else(|| Row::from_rdap(raw).to_csv());
+            out.put(format!("{row}\n").as_bytes())
+                .map_err(|e| e.to_string())
+        }
+        Target::File(out, FileFormat::Binary(fmt)) => {
+            let bytes = crate::encode_binary(record, *fmt).ok_or("encoding failed")?;
+            let len = u32::try_from(bytes.len()).map_err(|e| e.to_string())?;
+            let mut framed = len.to_be_bytes().to_vec();
+            framed.extend_from_slice(&bytes);
+            out.put(&framed).map_err(|e| e.to_string())
+        }
+        #[cfg(feature = "sqlite")]
+        Target::Sqlite(conn) => {
+            let row = Row::from_rdap(raw);
+            conn.execute(
+                "INSERT INTO records (kind, handle, name, country, status, written_at, record)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![
+                    row.kind,
+                    row.handle,
+                    row.name,
+                    row.country,
+                    row.status,
+                    crate::format_timestamp(crate::now_secs()),
+                    record.to_string()
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+        }
+        Target::Elastic {
+            client,
+            url,
+            buf,
+            count,
+            pending,
+        } => {
+            buf.push_str("{\"index\":{}}\n");
+            buf.push_str(&record.to_string());
+            buf.push('\n');
+            *count += 1;
+            if *count >= ES_BATCH {
+                reap(pending)?;
+                pending.push(tokio::spawn(post_bulk(
+                    client.clone(),
+                    url.clone(),
+                    std::mem::take(buf),
+                )));
+                *count = 0;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Drop uploads that have finished; the first failed one fails the sink and
+/// cancels the rest.
+fn reap(pending: &mut Vec<tokio::task::JoinHandle<Result<(), String>>>) -> Result<(), String> {
+    use futures::FutureExt;
+    let mut failed = None;
+    pending.retain_mut(|upload| {
+        if failed.is_some() || !upload.is_finished() {
+            return true;
+        }
+        match upload.now_or_never() {
+            Some(Ok(Err(e))) => failed = Some(e),
+            Some(Err(e)) => failed = Some(e.to_string()),
+            Some(Ok(Ok(()))) | None => {}
+        }
+        false
+    });
+    if let Some(e) = failed {
+        pending.drain(..).for_each(|upload| upload.abort());
+        return Err(e);
+    }
+    Ok(())
+}
+
+async fn post_bulk(client: reqwest::Client, url: String, body: String) -> Result<(), String> {
+    let resp = client
+        .post(&url)
+        .header("content-type", "application/x-ndjson")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = resp.status();
+    let json: Value = resp.json().await.unwrap_or(Value::Null);
+    if !status.is_success() || json.get("errors") == Some(&Value::Bool(true)) {
+        return Err(format!("bulk request to {url} failed (HTTP {status})"));
+    }
+    Ok(())
+}
+
+/// Flush buffered records and wait for in-flight uploads.
+async fn finish() {
+    let mut uploads = Vec::new();
+    if let Some(mut sinks) = sinks() {
+        for sink in sinks.iter_mut() {
+            match sink.target.as_mut() {
+                Some(Target::File(out, _)) => {
+                    if let Err(e) = out.w.flush() {
+                        eprintln!("output {} failed: {e}", sink.name);
+                    }
+                }
+                Some(Target::Elastic {
+                    client,
+                    url,
+                    buf,
+                    pending,
+                    ..
+                }) => {
+                    if !buf.is_empty() {
+                        pending.push(tokio::spawn(post_bulk(
+                            client.clone(),
+                            url.clone(),
+                            std::mem::take(buf),
+                        )));
+                    }
+                    uploads.push((sink.name.clone(), std::mem::take(pending)));
+                }
+                _ => {}
+            }
+        }
+    }
+    for (name, pending) in uploads {
+        let mut pending = pending.into_iter();
+        for upload in pending.by_ref() {
+            let failed = match upload.await {
+                Ok(Ok(())) => continue,
+                Ok(Err(e)) => e,
+                Err(e) => e.to_string(),
+            };
+            eprintln!("output {name} failed: {failed}");
+            break;
+        }
+        pending.for_each(|upload| upload.abort());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Codec;
+    use std::io::Read;
+
+    #[test]
+    fn codec_comes_from_the_extension_and_options() {
+        assert!(matches!(Codec::parse("ndjson", ""), Ok(Codec::None)));
+        assert!(matches!(Codec::parse("gz", ""), Ok(Codec::Gzip(6))));
+        assert!(matches!(Codec::parse("zst", ""), Ok(Codec::Zstd(3))));
+        assert!(matches!(
+            Codec::parse("zst", "level=19"),
+            Ok(Codec::Zstd(19))
+        ));
+        assert!(matches!(
+            Codec::parse("csv", "codec=gzip&level=9"),
+            Ok(Codec::Gzip(9))
+        ));
+        assert!(matches!(Codec::parse("gz", "codec=none"), Ok(Codec::None)));
+    }
+
+    #[test]
+    fn bad_codec_options_are_rejected() {
+        assert!(Codec::parse("gz", "level=10").is_err());
+        assert!(Codec::parse("gz", "level=fast").is_err());
+        assert!(Codec::parse("zst", "level=99").is_err());
+        assert!(Codec::parse("ndjson", "level=3").is_err());
+        assert!(Codec::parse("ndjson", "codec=brotli").is_err());
+        assert!(Codec::parse("ndjson", "compress=yes").is_err());
+    }
+
+    #[test]
+    fn frames_decompress_back_to_back() {
+        let mut gz = Codec::Gzip(6).frame(b"one\n").unwrap();
+        gz.extend(Codec::Gzip(6).frame(b"two\n").unwrap());
+        let mut out = String::new();
+        flate2::read::MultiGzDecoder::new(&gz[..])
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "one\ntwo\n");
+
+        let mut zst = Codec::Zstd(3).frame(b"one\n").unwrap();
+        zst.extend(Codec::Zstd(3).frame(b"two\n").unwrap());
+        assert_eq!(zstd::decode_all(&zst[..]).unwrap(), b"one\ntwo\n");
+    }
+}
@@ -0,0 +1,384 @@
+//! `rdapx squat`: lookalike and typo variants of a domain, checked through
+//! the availability pipeline so only the ones someone has registered are
+//! reported.
+//!
+//! Permutation sets grow quickly, so a run can be capped: `--max-candidates`
+//! keeps the first N candidates (in strategy order), and `--budget` limits
+//! the checks sent to any one registry; what is left over is counted and can
+//! be picked up later with `--resume FILE`, which records every definite
+//! answer as it arrives and skips those domains on the next run.
+
+use crate::available::{self, Status};
+use crate::checkpoint::Journal;
+use crate::table::{Cell, Column, Tabular};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::path::PathBuf;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// One character swapped for a lookalike (`o`/`0`, `rn`/`m`, Cyrillic `а`)
+    Homoglyph,
+    /// One character left out
+    Omission,
+    /// One character doubled
+    Repetition,
+    /// Two neighbouring characters swapped
+    Transposition,
+    /// One character replaced by a neighbouring key (QWERTY)
+    Keyboard,
+    /// A hyphen inserted between two characters
+    Hyphenation,
+    /// The same name under other TLDs
+    TldSwap,
+}
+
+impl Strategy {
+    pub const ALL: [Self; 7] = [
+        Self::Homoglyph,
+        Self::Omission,
+        Self::Repetition,
+        Self::Transposition,
+        Self::Keyboard,
+        Self::Hyphenation,
+        Self::TldSwap,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Homoglyph => "homoglyph",
+            Self::Omission => "omission",
+            Self::Repetition => "repetition",
+            Self::Transposition => "transposition",
+            Self::Keyboard => "keyboard",
+            Self::Hyphenation => "hyphenation",
+            Self::TldSwap => "tld-swap",
+        }
+    }
+}
+
+/// TLDs tried by `tld-swap` unless `--tlds` is given.
+pub const DEFAULT_TLDS: [&str; 10] = [
+    "com", "net", "org", "co", "io", "info", "biz", "us", "app", "xyz",
+];
+
+/// ASCII lookalikes, both ways round.
+const ASCII_LOOKALIKES: [(&str, &str); 6] = [
+    ("o", "0"),
+    ("l", "1"),
+    ("i", "l"),
+    ("rn", "m"),
+    ("vv", "w"),
+    ("cl", "d"),
+];
+
+const KEYBOARD: [&str; 3] = ["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+#[derive(Serialize, Debug)]
+pub struct Candidate {
+    /// ASCII (A-label) form, as queried
+    pub domain: String,
+    /// Unicode form for internationalized candidates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unicode: Option<String>,
+    pub strategy: Strategy,
+}
+
+impl Tabular for Candidate {
+    const COLUMNS: &'static [Column] = &[Column("DOMAIN", 40), Column("STRATEGY", 0)];
+    const CSV_HEADER: &'static [&'static str] = &["domain", "unicode", "strategy"];
+
+    fn table_row(&self) -> Vec<Cell> {
+        let name = self
+            .unicode
+            .as_ref()
+            .map_or_else(|| self.domain.clone(), |u| format!("{} ({u})", self.domain));
+        vec![name.into(), self.strategy.as_str().into()]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.domain.clone(),
+            self.unicode.clone().unwrap_or_default(),
+            self.strategy.as_str().to_string(),
+        ]
+    }
+}
+
+/// Keys next to `c` on the same QWERTY row.
+fn neighbours(c: char) -> Vec<char> {
+    KEYBOARD
+        .iter()
+        .filter_map(|row| {
+            let chars: Vec<char> = row.chars().collect();
+            let i = chars.iter().position(|&k| k == c)?;
+            Some(
+                [i.checked_sub(1), Some(i + 1)]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|j| chars.get(j).copied())
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .flatten()
+        .collect()
+}
+
+/// Variants of one label under a label-level strategy.
+fn label_variants(label: &str, strategy: Strategy) -> Vec<String> {
+    let chars: Vec<char> = label.chars().collect();
+    let with = |i: usize, replacement: &str, skip: usize| -> String {
+        let mut s: String = chars[..i].iter().collect();
+        s.push_str(replacement);
+        s.extend(&chars[(i + skip).min(chars.len())..]);
+        s
+    };
+    let mut out = Vec::new();
+    match strategy {
+        Strategy::Omission if chars.len() > 1 => {
+            out.extend((0..chars.len()).map(|i| with(i, "", 1)));
+        }
+        Strategy::Repetition => {
+            out.extend((0..chars.len()).map(|i| with(i, &chars[i].to_string(), 0)));
+        }
+        Strategy::Transposition => {
+            for i in 0..chars.len().saturating_sub(1) {
+                let swapped: String = [chars[i + 1], chars[i]].iter().collect();
+                out.push(with(i, &swapped, 2));
+            }
+        }
+        Strategy::Keyboard => {
+            for (i, &c) in chars.iter().enumerate() {
+                out.extend(
+                    neighbours(c)
+                        .into_iter()
+                        .map(|n| with(i, &n.to_string(), 1)),
+                );
+            }
+        }
+        Strategy::Hyphenation => {
+            out.extend((1..chars.len()).map(|i| with(i, "-", 0)));
+        }
+        Strategy::Homoglyph => {
+            for (a, b) in ASCII_LOOKALIKES {
+                for (from, to) in [(a, b), (b, a)] {
+                    out.extend(
+                        label.match_indices(from).map(|(i, _)| {
+                            format!("{}{to}{}", &label[..i], &label[i + from.len()..])
+                        }),
+                    );
+                }
+            }
+            for (i, &c) in chars.iter().enumerate() {
+                out.extend(
+                    crate::labels::CONFUSABLES
+                        .iter()
+                        .filter(|(_, latin)| *latin == c)
+                        .map(|(glyph, _)| with(i, &glyph.to_string(), 1)),
+                );
+            }
+        }
+        Strategy::Omission | Strategy::TldSwap => {}
+    }
+    out
+}
+
+/// Candidates for `domain` under `strategies`, without the domain itself,
+/// duplicates, or names that aren't valid. Label strategies vary the first
+/// label and keep the rest (`example` in `example.co.uk`); `tld-swap`
+/// replaces the rest with each of `tlds`.
+pub fn generate(domain: &str, strategies: &[Strategy], tlds: &[String]) -> Vec<Candidate> {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    let (label, suffix) = domain.split_once('.').unwrap_or((&domain, "com"));
+    let mut seen = BTreeSet::from([domain.clone()]);
+    let mut out = Vec::new();
+    for &strategy in strategies {
+        let names: Vec<String> = if strategy == Strategy::TldSwap {
+            tlds.iter().map(|t| format!("{label}.{t}")).collect()
+        } else {
+            label_variants(label, strategy)
+                .into_iter()
+                .map(|l| format!("{l}.{suffix}"))
+                .collect()
+        };
+        for name in names {
+            if crate::labels::validate(&name).is_err() {
+                continue;
+            }
+            let Ok(ascii) = idna::domain_to_ascii_strict(&name) else {
+                continue;
+            };
+            if seen.insert(ascii.clone()) {
+                out.push(Candidate {
+                    unicode: (ascii != name).then_some(name),
+                    domain: ascii,
+                    strategy,
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Header of the `--resume` journal: the domain the candidates were
+/// generated from, to refuse resuming another.
+#[derive(Serialize, Deserialize, PartialEq, Eq)]
+struct Run {
+    target: String,
+}
+
+/// One definite answer, appended to the `--resume` journal as it arrives.
+#[derive(Serialize, Deserialize)]
+struct Checked {
+    domain: String,
+    /// `registered` or `available`
+    status: String,
+}
+
+pub struct Limits {
+    pub concurrency: usize,
+    /// Requests per second to any one registry
+    pub rate: u32,
+    /// Checks per registry in this run
+    pub budget: Option<usize>,
+    pub resume: Option<PathBuf>,
+}
+
+/// Status per candidate (in order); `None` for candidates left unchecked
+/// because their registry's budget ran out.
+pub async fn check(
+    client: &reqwest::Client,
+    target: &str,
+    candidates: &[Candidate],
+    limits: &Limits,
+) -> Result<Vec<Option<Status>>, Box<dyn Error>> {
+    let (mut journal, checked) = match &limits.resume {
+        Some(path) => {
+            let run = Run {
+                target: target.to_string(),
+            };
+            let (journal, steps) =
+                Journal::<Checked>::open(path, &run, |r| format!("a squat run for {}", r.target))?;
+            let checked: HashMap<String, String> =
+                steps.into_iter().map(|c| (c.domain, c.status)).collect();
+            (Some(journal), checked)
+        }
+        None => (None, HashMap::new()),
+    };
+    let mut statuses: Vec<Option<Status>> = candidates
+        .iter()
+        .map(|c| checked.get(&c.domain).and_then(|s| Status::parse(s)))
+        .collect();
+    let resumed = statuses.iter().flatten().count();
+    if resumed > 0 {
+        eprintln!("Resuming: {resumed} candidate(s) already checked");
+    }
+
+    let mut spent: HashMap<String, usize> = HashMap::new();
+    let mut pending = Vec::new();
+    for (i, c) in candidates.iter().enumerate() {
+        if statuses[i].is_some() {
+            continue;
+        }
+        let host = crate::url_host(&available::domain_url(&c.domain));
+        let used = spent.entry(host).or_default();
+        if limits.budget.is_some_and(|b| *used >= b) {
+            continue;
+        }
+        *used += 1;
+        pending.push(i);
+    }
+
+    let domains: Vec<String> = pending
+        .iter()
+        .map(|&i| candidates[i].domain.clone())
+        .collect();
+    available::check_each(
+        client,
+        &domains,
+        limits.concurrency,
+        limits.rate,
+        |j, status| -> Result<(), Box<dyn Error>> {
+            let i = pending[j];
+            statuses[i] = Some(status);
+            if let (Some(journal), Status::Registered | Status::Available) = (&mut journal, status)
+            {
+                journal.append(&Checked {
+                    domain: candidates[i].domain.clone(),
+                    status: status.as_str().to_string(),
+                })?;
+            }
+            Ok(())
+        },
+    )
+    .await?;
+    Ok(statuses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, Strategy};
+
+    fn names(domain: &str, strategies: &[Strategy], tlds: &[&str]) -> Vec<String> {
+        let tlds: Vec<String> = tlds.iter().map(|t| (*t).to_string()).collect();
+        generate(domain, strategies, &tlds)
+            .into_iter()
+            .map(|c| c.domain)
+            .collect()
+    }
+
+    #[test]
+    fn label_strategies_vary_the_first_label() {
+        assert_eq!(
+            names("ABC.com.", &[Strategy::Omission], &[]),
+            ["bc.com", "ac.com", "ab.com"]
+        );
+        assert_eq!(
+            names(
+                "abc.com",
+                &[Strategy::Transposition, Strategy::Hyphenation],
+                &[]
+            ),
+            ["bac.com", "acb.com", "a-bc.com", "ab-c.com"]
+        );
+        assert_eq!(
+            names("abc.co.uk", &[Strategy::Repetition], &[]),
+            ["aabc.co.uk", "abbc.co.uk", "abcc.co.uk"]
+        );
+    }
+
+    #[test]
+    fn tld_swap_skips_the_domain_itself() {
+        assert_eq!(
+            names("abc.com", &[Strategy::TldSwap], &["com", "net", "org"]),
+            ["abc.net", "abc.org"]
+        );
+    }
+
+    #[test]
+    fn invalid_and_repeated_candidates_are_dropped() {
+        // `-b` and `a-` aren't valid labels
+        assert_eq!(names("a-b.com", &[Strategy::Omission], &[]), ["ab.com"]);
+        // omitting either `o` gives the same name once
+        assert_eq!(
+            names("foo.com", &[Strategy::Omission], &[]),
+            ["oo.com", "fo.com"]
+        );
+    }
+
+    #[test]
+    fn homoglyphs_include_internationalized_names() {
+        let tlds: Vec<String> = Vec::new();
+        let candidates = generate("google.com", &[Strategy::Homoglyph], &tlds);
+        assert!(candidates.iter().any(|c| c.domain == "g0ogle.com"));
+        assert!(candidates
+            .iter()
+            .any(|c| c.domain.starts_with("xn--") && c.unicode.is_some()));
+        assert!(candidates
+            .iter()
+            .all(|c| c.strategy == Strategy::Homoglyph && c.domain != "google.com"));
+    }
+}
@@ -0,0 +1,212 @@
+//! `rdapx stats`: aggregate counts over a collected NDJSON corpus (bulk or
+//! search output) for a quick picture of what it contains.
+
+use crate::fields::Attribute;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+/// RIRs and the domain their RDAP and WHOIS servers are under.
+const RIRS: [(&str, &str); 5] = [
+    ("afrinic", "afrinic.net"),
+    ("apnic", "apnic.net"),
+    ("arin", "arin.net"),
+    ("lacnic", "lacnic.net"),
+    ("ripe", "ripe.net"),
+];
+
+#[derive(Serialize, Debug)]
+pub struct Count {
+    pub value: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Stats {
+    pub records: usize,
+    /// `found: false` records from `--emit-not-found`
+    pub not_found: usize,
+    /// Lines that weren't JSON objects
+    pub invalid: usize,
+    pub object_classes: Vec<Count>,
+    pub registrars: Vec<Count>,
+    pub statuses: Vec<Count>,
+    /// Registration year -> objects, oldest first
+    pub created_years: Vec<Count>,
+    pub nameservers: Vec<Count>,
+    pub rirs: Vec<Count>,
+    pub countries: Vec<Count>,
+}
+
+impl Stats {
+    /// The sections in display order, with their table titles.
+    pub fn sections(&self) -> [(&'static str, &[Count]); 7] {
+        [
+            ("Object classes", &self.object_classes),
+            ("Registrars", &self.registrars),
+            ("Statuses", &self.statuses),
+            ("Created (year)", &self.created_years),
+            ("Nameservers", &self.nameservers),
+            ("RIRs", &self.rirs),
+            ("Countries", &self.countries),
+        ]
+    }
+}
+
+/// RIR of an IP network or autnum, from the host of its self link or its
+/// WHOIS server; `None` for other objects.
+fn rir(json: &Value) -> Option<&'static str> {
+    let class = json.get("objectClassName").and_then(Value::as_str);
+    if !matches!(class, Some("ip network" | "autnum")) {
+        return None;
+    }
+    let self_host = json
+        .get("links")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .find(|l| l.get("rel").and_then(Value::as_str) == Some("self"))
+        .and_then(|l| l.get("href"))
+        .and_then(Value::as_str)
+        .and_then(|href| reqwest::Url::parse(href).ok())
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+    let port43 = json
+        .get("port43")
+        .and_then(Value::as_str)
+        .map(|h| h.trim().trim_end_matches('.').to_ascii_lowercase());
+    [self_host, port43].into_iter().flatten().find_map(|host| {
+        RIRS.into_iter()
+            .find(|(_, domain)| host == *domain || host.ends_with(&format!(".{domain}")))
+            .map(|(name, _)| name)
+    })
+}
+
+/// Occurrences per value, ranked by [`Tally::top`].
+#[derive(Default)]
+pub struct Tally(HashMap<String, usize>);
+
+impl Tally {
+    pub fn add(&mut self, value: impl Into<String>) {
+        *self.0.entry(value.into()).or_default() += 1;
+    }
+
+    /// Most frequent first (ties alphabetical), at most `top` (0 for all).
+    pub fn top(self, top: usize) -> Vec<Count> {
+        let mut counts: Vec<Count> = self
+            .0
+            .into_iter()
+            .map(|(value, count)| Count { value, count })
+            .collect();
+        counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        if top > 0 {
+            counts.truncate(top);
+        }
+        counts
+    }
+}
+
+/// Statistics over NDJSON `lines`, keeping the `top` values per section
+/// (the creation-year histogram is always complete).
+pub fn compute(lines: &[String], top: usize) -> Stats {
+    let mut stats = Stats::default();
+    let mut classes = Tally::default();
+    let mut registrars = Tally::default();
+    let mut statuses = Tally::default();
+    let mut nameservers = Tally::default();
+    let mut rirs = Tally::default();
+    let mut countries = Tally::default();
+    let mut years: BTreeMap<String, usize> = BTreeMap::new();
+    for line in lines {
+        let record = match serde_json::from_str::<Value>(line) {
+            Ok(v) if v.is_object() => v,
+            _ => {
+                stats.invalid += 1;
+                continue;
+            }
+        };
+        if record.get("found").and_then(Value::as_bool) == Some(false) {
+            stats.not_found += 1;
+            continue;
+        }
+        // schema v2 envelopes carry the response under `rdap`
+        let json = record.get("rdap").unwrap_or(&record);
+        stats.records += 1;
+        let class = Attribute::ObjectClass.values(json).pop();
+        classes.add(class.as_deref().unwrap_or("unknown"));
+        for year in Attribute::CreatedYear.values(json) {
+            *years.entry(year).or_default() += 1;
+        }
+        if let Some(r) = rir(json) {
+            rirs.add(r);
+        }
+        for (attr, tally) in [
+            (Attribute::Registrar, &mut registrars),
+            (Attribute::Status, &mut statuses),
+            (Attribute::Nameserver, &mut nameservers),
+            (Attribute::Country, &mut countries),
+        ] {
+            for value in attr.values(json) {
+                tally.add(value);
+            }
+        }
+    }
+    stats.object_classes = classes.top(0);
+    stats.registrars = registrars.top(top);
+    stats.statuses = statuses.top(top);
+    stats.nameservers = nameservers.top(top);
+    stats.rirs = rirs.top(0);
+    stats.countries = countries.top(top);
+    stats.created_years = years
+        .into_iter()
+        .map(|(value, count)| Count { value, count })
+        .collect();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compute, rir};
+    use serde_json::json;
+
+    #[test]
+    fn rir_comes_from_the_host_of_networks_and_autnums() {
+        let net = json!({
+            "objectClassName": "ip network",
+            "links": [{"rel": "self", "href": "https://rdap.db.ripe.net/ip/192.0.2.0"}],
+        });
+        assert_eq!(rir(&net), Some("ripe"));
+        let asn = json!({"objectClassName": "autnum", "port43": "whois.arin.net"});
+        assert_eq!(rir(&asn), Some("arin"));
+    }
+
+    #[test]
+    fn rir_ignores_names_that_merely_contain_one() {
+        let domain = json!({
+            "objectClassName": "domain",
+            "ldhName": "MARINA.COM",
+            "port43": "whois.marina.com",
+        });
+        assert_eq!(rir(&domain), None);
+        let net = json!({
+            "objectClassName": "ip network",
+            "port43": "whois.tripe.net",
+            "links": [{"rel": "self", "href": "https://rdap.test/ip/1?from=ripe.net"}],
+        });
+        assert_eq!(rir(&net), None);
+    }
+
+    #[test]
+    fn counts_envelopes_not_found_and_invalid_lines() {
+        let lines = [
+            json!({"rdap": {"objectClassName": "domain", "status": ["Active"]}}).to_string(),
+            json!({"objectClassName": "domain", "status": ["active"]}).to_string(),
+            json!({"query": "gone.test", "found": false}).to_string(),
+            "not json".to_string(),
+        ];
+        let stats = compute(&lines, 0);
+        assert_eq!((stats.records, stats.not_found, stats.invalid), (2, 1, 1));
+        assert_eq!(stats.statuses.len(), 1);
+        assert_eq!(stats.statuses[0].value, "active");
+        assert_eq!(stats.statuses[0].count, 2);
+    }
+}
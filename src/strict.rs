@@ -0,0 +1,66 @@
+//! `--strict`: for pipelines that must not silently degrade. Conditions
+//! rdapx normally reports and works around become errors, each with its own
+//! exit code so CI can tell them apart:
+//!
+//! | code | condition |
+//! |------|-----------|
+//! | 3    | input lines that aren't a domain, IP, or AS number (`bulk`) |
+//! | 4    | stale data: an outdated dataset copy or bootstrap data used after a failed download |
+//! | 5    | lenient parsing: unparsable or keyless records skipped (`stats`, `diff-runs`) |
+//! | 6    | host names looked up as their registrable domain (`www.example.co.uk`) |
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+pub fn init(strict: bool) {
+    STRICT.store(strict, Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Condition {
+    Unclassifiable,
+    Stale,
+    Lenient,
+    Rewritten,
+}
+
+impl Condition {
+    pub const fn exit_code(self) -> u8 {
+        match self {
+            Self::Unclassifiable => 3,
+            Self::Stale => 4,
+            Self::Lenient => 5,
+            Self::Rewritten => 6,
+        }
+    }
+}
+
+/// A condition hit under `--strict`; `main` reports it and exits with the
+/// condition's code.
+#[derive(Debug)]
+pub struct Violation {
+    pub condition: Condition,
+    detail: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "--strict: {}", self.detail)
+    }
+}
+
+impl std::error::Error for Violation {}
+
+/// Under `--strict`, fail with `condition`; otherwise return `Ok`, and the
+/// caller warns and carries on as before.
+pub fn check(condition: Condition, detail: impl FnOnce() -> String) -> Result<(), Violation> {
+    if STRICT.load(Ordering::Relaxed) {
+        return Err(Violation {
+            condition,
+            detail: detail(),
+        });
+    }
+    Ok(())
+}
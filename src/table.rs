@@ -0,0 +1,120 @@
+//! Listings (`cache list`, `contacts`, `report`, ...) printed from one
+//! description of their rows: an aligned table, CSV with a header row, or
+//! one record per row for the JSON-like formats.
+
+use crate::model::csv_escape;
+use crate::Format;
+use colored::{Color, Colorize};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt::Write;
+
+/// A table column: its header and width; negative widths right-align.
+pub struct Column(pub &'static str, pub i16);
+
+/// One table cell, colored after padding so colors don't upset alignment.
+pub struct Cell {
+    text: String,
+    color: Option<Color>,
+}
+
+impl Cell {
+    pub fn colored(text: impl Into<String>, color: Color) -> Self {
+        Self {
+            text: text.into(),
+            color: Some(color),
+        }
+    }
+
+    /// The text, or `-` when there is none.
+    pub fn or_dash(text: Option<&str>) -> Self {
+        text.unwrap_or("-").into()
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Self { text, color: None }
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Self {
+        text.to_string().into()
+    }
+}
+
+/// Rows [`print`] can list.
+pub trait Tabular: Serialize {
+    const COLUMNS: &'static [Column];
+    /// CSV column names, one per [`Tabular::csv_row`] value
+    const CSV_HEADER: &'static [&'static str];
+
+    fn table_row(&self) -> Vec<Cell>;
+    fn csv_row(&self) -> Vec<String>;
+}
+
+impl<T: Tabular> Tabular for &T {
+    const COLUMNS: &'static [Column] = T::COLUMNS;
+    const CSV_HEADER: &'static [&'static str] = T::CSV_HEADER;
+
+    fn table_row(&self) -> Vec<Cell> {
+        (*self).table_row()
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        (*self).csv_row()
+    }
+}
+
+/// Print `rows` as a table, as CSV, or one record per row in `fmt`.
+pub fn print<T: Tabular>(rows: &[T], fmt: Format) -> Result<(), Box<dyn Error>> {
+    match fmt {
+        Format::Table => {
+            let header: Vec<Cell> = T::COLUMNS.iter().map(|c| c.0.into()).collect();
+            println!("{}", line(T::COLUMNS, &header).blue().bold());
+            for row in rows {
+                println!("{}", line(T::COLUMNS, &row.table_row()));
+            }
+        }
+        Format::Csv => print_csv(T::CSV_HEADER, rows.iter().map(Tabular::csv_row)),
+        _ => {
+            for row in rows {
+                crate::output(&serde_json::to_value(row)?, fmt);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// CSV with a header row; values are escaped here.
+pub fn print_csv(header: &[&str], rows: impl IntoIterator<Item = Vec<String>>) {
+    println!("{}", header.join(","));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|c| csv_escape(c)).collect();
+        println!("{}", cells.join(","));
+    }
+}
+
+/// One table line; a left-aligned last column isn't padded.
+fn line(columns: &[Column], cells: &[Cell]) -> String {
+    let mut out = String::new();
+    for (i, (col, cell)) in columns.iter().zip(cells).enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        let width = usize::from(col.1.unsigned_abs());
+        let text = if col.1 < 0 {
+            format!("{:>width$}", cell.text)
+        } else if i + 1 == columns.len() {
+            cell.text.clone()
+        } else {
+            format!("{:<width$}", cell.text)
+        };
+        let _ = match cell.color {
+            Some(color) => write!(out, "{}", text.color(color)),
+            None => write!(out, "{text}"),
+        };
+    }
+    out
+}
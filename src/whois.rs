@@ -0,0 +1,551 @@
+//! Legacy WHOIS (port 43) transport and a field parser that maps the common
+//! registry/registrar text formats onto an RDAP-shaped domain object, so
+//! consumers see one schema whichever protocol answered. Parsed records carry
+//! `_meta.source: "whois"`.
+//!
+//! The parser only picks out what RDAP consumers rely on: registrar (name,
+//! IANA id, abuse contact), registration/expiry/update dates, EPP statuses,
+//! nameservers and DNSSEC. Anything else stays in the raw text.
+
+use serde_json::{json, Value};
+use std::error::Error;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Root of the WHOIS referral chain.
+pub const IANA: &str = "whois.iana.org";
+const PORT: u16 = 43;
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Servers stream until they close; anything past this is not a WHOIS record.
+const MAX_RESPONSE: u64 = 1 << 20;
+
+/// Send one query to `server` and read the reply until the server closes.
+pub async fn query(server: &str, q: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let exchange = async {
+        let mut stream = TcpStream::connect((server, PORT)).await?;
+        stream.write_all(format!("{q}\r\n").as_bytes()).await?;
+        let mut body = Vec::new();
+        stream.take(MAX_RESPONSE).read_to_end(&mut body).await?;
+        Ok::<_, std::io::Error>(body)
+    };
+    let body = tokio::time::timeout(TIMEOUT, exchange)
+        .await
+        .map_err(|_| format!("whois {server}: timed out"))?
+        .map_err(|e| format!("whois {server}: {e}"))?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// One server's answer in a referral chain.
+pub struct Hop {
+    pub server: String,
+    pub text: String,
+}
+
+/// Longest chain we follow: IANA, registry or RIR, registrar.
+const MAX_HOPS: usize = 3;
+
+/// Where a response sends us next: IANA's `refer:`, a thin registry's
+/// `Registrar WHOIS Server:`, or ARIN's `ReferralServer: whois://...`.
+/// `rwhois://` referrals aren't port-43 WHOIS and are ignored.
+pub fn referral(text: &str) -> Option<String> {
+    const KEYS: [&str; 4] = ["refer", "whois", "registrar whois server", "referralserver"];
+    let value = fields(text)
+        .into_iter()
+        .find(|(k, v)| KEYS.contains(&k.as_str()) && !v.is_empty())
+        .map(|(_, v)| v.to_lowercase())?;
+    if value.contains("://") && !value.starts_with("whois://") {
+        return None;
+    }
+    let host = value.trim_start_matches("whois://");
+    let host = host.split([':', '/']).next().unwrap_or(host);
+    (!host.is_empty()).then(|| host.to_string())
+}
+
+/// Query `q` starting at `server` (IANA when `None`), following referrals
+/// when `follow` is set. Returns every hop, most specific last.
+pub async fn lookup(
+    q: &str,
+    server: Option<&str>,
+    follow: bool,
+) -> Result<Vec<Hop>, Box<dyn Error + Send + Sync>> {
+    let (kind, _) = crate::normalize(q);
+    let q = q.trim().trim_end_matches('.');
+    let mut hops: Vec<Hop> = Vec::new();
+    let mut next = server.map_or_else(|| IANA.to_string(), str::to_string);
+    loop {
+        // IANA only knows TLDs, not the names under them
+        let ask = if next == IANA && matches!(kind, crate::Kind::Domain) {
+            q.rsplit('.').next().unwrap_or(q)
+        } else {
+            q
+        };
+        let text = query(&next, ask).await?;
+        let refer = referral(&text);
+        let from_iana = next == IANA;
+        hops.push(Hop { server: next, text });
+        match refer {
+            Some(to) if (follow || from_iana) && hops.len() < MAX_HOPS => {
+                if hops.iter().any(|h| h.server == to) {
+                    break;
+                }
+                next = to;
+            }
+            _ => break,
+        }
+    }
+    // the IANA hop only exists to find the registry or RIR
+    if hops.len() > 1 && hops[0].server == IANA {
+        hops.remove(0);
+    }
+    Ok(hops)
+}
+
+/// The most specific hop that parses, tagged with `_meta.whois_server`.
+/// Members it lacks (a registrar rarely repeats the creation date) are taken
+/// from the hops before it.
+pub fn parse_chain(hops: &[Hop], q: &str) -> Option<Value> {
+    let mut parsed = hops
+        .iter()
+        .rev()
+        .filter_map(|hop| Some((hop.server.as_str(), parse(&hop.text, q)?)));
+    let (server, mut record) = parsed.next()?;
+    for (_, earlier) in parsed {
+        if let (Some(obj), Value::Object(earlier)) = (record.as_object_mut(), earlier) {
+            for (k, v) in earlier {
+                obj.entry(k).or_insert(v);
+            }
+        }
+    }
+    crate::attach_meta(&mut record, "whois_server", Value::from(server));
+    Some(record)
+}
+
+/// `key: value` pairs in document order, keys lowercased. A key with an
+/// empty value followed by indented lines (the `.uk` style) yields one pair
+/// per indented line. Stops at the `>>>` trailer ICANN registries append.
+fn fields(text: &str) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    let mut block: Option<String> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with(">>>") {
+            break;
+        }
+        if trimmed.is_empty() {
+            block = None;
+            continue;
+        }
+        if trimmed.starts_with('%') || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(key) = &block {
+            if line.starts_with(char::is_whitespace) && !trimmed.contains(": ") {
+                out.push((key.clone(), trimmed.to_string()));
+                continue;
+            }
+        }
+        let Some((key, value)) = trimmed.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+        if value.is_empty() {
+            block = Some(key);
+        } else {
+            block = None;
+            out.push((key, value.to_string()));
+        }
+    }
+    out
+}
+
+const DOMAIN: &[&str] = &["domain name", "domain"];
+const REGISTRAR: &[&str] = &["registrar", "sponsoring registrar", "registrar name"];
+const CREATED: &[&str] = &[
+    "creation date",
+    "created",
+    "created on",
+    "registered",
+    "registered on",
+    "registration time",
+];
+const UPDATED: &[&str] = &[
+    "updated date",
+    "last updated",
+    "last updated on",
+    "last-update",
+    "last modified",
+    "changed",
+];
+const EXPIRES: &[&str] = &[
+    "registry expiry date",
+    "registrar registration expiration date",
+    "expiration date",
+    "expiry date",
+    "expires",
+    "expires on",
+    "paid-till",
+    "expiration time",
+];
+const STATUS: &[&str] = &["domain status", "status", "state"];
+const NAMESERVER: &[&str] = &[
+    "name server",
+    "name servers",
+    "nserver",
+    "nameserver",
+    "nameservers",
+];
+
+/// WHOIS prints EPP status codes (`clientTransferProhibited https://...`);
+/// RDAP spells them as words (RFC 8056), with `ok` becoming `active`.
+fn rdap_status(raw: &str) -> String {
+    let code = raw.split_whitespace().next().unwrap_or(raw);
+    if code.eq_ignore_ascii_case("ok") {
+        return "active".into();
+    }
+    let mut words = String::new();
+    for c in code.chars() {
+        if c.is_ascii_uppercase() && !words.is_empty() {
+            words.push(' ');
+        }
+        words.push(c.to_ascii_lowercase());
+    }
+    words
+}
+
+/// Normalize the date formats seen in the wild to RFC 3339 where possible
+/// (`2024-08-14T07:01:34Z`, `2024-08-14 07:01:34`, `2024.08.14`,
+/// `14-Aug-2024`); anything else is passed through unchanged.
+fn rdap_date(raw: &str) -> String {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let raw = raw.trim();
+    let iso = raw.get(..10).map(|d| d.replace(['.', '/'], "-"));
+    if let Some(date) = iso.filter(|d| crate::parse_date_days(d).is_some()) {
+        let time = raw
+            .get(10..)
+            .map(|t| t.trim_start_matches(['T', ' ']))
+            .and_then(|t| t.get(..8))
+            .filter(|t| t.len() == 8 && t.as_bytes()[2] == b':')
+            .unwrap_or("00:00:00");
+        return format!("{date}T{time}Z");
+    }
+    let mut parts = raw.split('-');
+    if let (Some(d), Some(m), Some(y)) = (parts.next(), parts.next(), parts.next()) {
+        let month = MONTHS
+            .iter()
+            .position(|name| m.eq_ignore_ascii_case(name))
+            .map(|i| i + 1);
+        let y = y.get(..4).unwrap_or(y);
+        if let (Some(month), Ok(day), Ok(year)) = (month, d.parse::<u32>(), y.parse::<u32>()) {
+            return format!("{year:04}-{month:02}-{day:02}T00:00:00Z");
+        }
+    }
+    raw.to_string()
+}
+
+/// Map WHOIS text for `query` onto the RDAP object of its kind (domain,
+/// ip network, autnum). `None` when the text has none of the fields we know
+/// (typically a "no match" reply) or for entity handles.
+pub fn parse(text: &str, query: &str) -> Option<Value> {
+    let pairs = fields(text);
+    match crate::normalize(query).0 {
+        crate::Kind::Domain => parse_domain(&pairs, query),
+        crate::Kind::Ip => parse_network(&pairs),
+        crate::Kind::Asn => parse_autnum(&pairs),
+        crate::Kind::Entity => None,
+    }
+}
+
+fn first<'a>(pairs: &'a [(String, String)], keys: &[&str]) -> Option<&'a str> {
+    pairs
+        .iter()
+        .find(|(k, _)| keys.contains(&k.as_str()))
+        .map(|(_, v)| v.as_str())
+}
+
+/// `registration` / `last changed` events from RIR-style date fields.
+fn rir_events(pairs: &[(String, String)]) -> Vec<Value> {
+    [
+        ("registration", &["regdate", "created"][..]),
+        ("last changed", &["updated", "last-modified"][..]),
+    ]
+    .into_iter()
+    .filter_map(|(action, keys)| {
+        first(pairs, keys).map(|d| json!({"eventAction": action, "eventDate": rdap_date(d)}))
+    })
+    .collect()
+}
+
+/// Fields shared by RIR network and autnum records; `None` without `range`.
+fn rir_record(pairs: &[(String, String)], class: &str, range: Option<&str>) -> Option<Value> {
+    range?;
+    let name = first(pairs, &["netname", "as-name", "asname"]);
+    let mut record = json!({"objectClassName": class, "_meta": {"source": "whois"}});
+    if let Some(handle) = first(pairs, &["nethandle", "handle"]).or(name) {
+        record["handle"] = json!(handle);
+    }
+    if let Some(name) = name {
+        record["name"] = json!(name);
+    }
+    if let Some(country) = first(pairs, &["country"]) {
+        record["country"] = json!(country.to_uppercase());
+    }
+    let events = rir_events(pairs);
+    if !events.is_empty() {
+        record["events"] = json!(events);
+    }
+    Some(record)
+}
+
+/// RIPE-style `inetnum: a - b` or ARIN `NetRange: a - b`; a bare CIDR
+/// (`inet6num: 2001:db8::/32`) is kept as the start address.
+fn parse_network(pairs: &[(String, String)]) -> Option<Value> {
+    let range = first(pairs, &["inetnum", "inet6num", "netrange", "cidr"]);
+    let mut record = rir_record(pairs, "ip network", range)?;
+    let range = range.unwrap_or_default();
+    let (start, end) = range.split_once(" - ").unwrap_or((range, ""));
+    record["startAddress"] = json!(start.trim());
+    if !end.is_empty() {
+        record["endAddress"] = json!(end.trim());
+    }
+    record["ipVersion"] = json!(if start.contains(':') { "v6" } else { "v4" });
+    Some(record)
+}
+
+/// RIPE `aut-num: AS3333` or ARIN `ASNumber: 13335` / `13335 - 13340`.
+fn parse_autnum(pairs: &[(String, String)]) -> Option<Value> {
+    let range = first(pairs, &["aut-num", "asnumber"]);
+    let mut record = rir_record(pairs, "autnum", range)?;
+    let number = |s: &str| {
+        let s = s.trim();
+        s.get(..2)
+            .filter(|p| p.eq_ignore_ascii_case("as"))
+            .map_or(s, |_| &s[2..])
+            .parse::<u32>()
+            .ok()
+    };
+    let range = range.unwrap_or_default();
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    if let (Some(start), Some(end)) = (number(start), number(end)) {
+        record["startAutnum"] = json!(start);
+        record["endAutnum"] = json!(end);
+    }
+    Some(record)
+}
+
+fn parse_domain(pairs: &[(String, String)], query: &str) -> Option<Value> {
+    let first = |keys: &[&str]| first(pairs, keys);
+    let all = |keys: &[&str]| {
+        pairs
+            .iter()
+            .filter(|(k, _)| keys.contains(&k.as_str()))
+            .map(|(_, v)| v.as_str())
+            .collect::<Vec<_>>()
+    };
+
+    let mut status: Vec<String> = all(STATUS).into_iter().map(rdap_status).collect();
+    status.dedup();
+    let mut nameservers: Vec<String> = all(NAMESERVER)
+        .into_iter()
+        // some registries append glue addresses after the host name
+        .filter_map(|ns| ns.split_whitespace().next())
+        .map(|ns| ns.trim_end_matches('.').to_lowercase())
+        .collect();
+    nameservers.sort();
+    nameservers.dedup();
+    let events: Vec<Value> = [
+        ("registration", CREATED),
+        ("expiration", EXPIRES),
+        ("last changed", UPDATED),
+    ]
+    .into_iter()
+    .filter_map(|(action, keys)| {
+        first(keys).map(|d| json!({"eventAction": action, "eventDate": rdap_date(d)}))
+    })
+    .collect();
+    let registrar = first(REGISTRAR).map(|name| {
+        let mut entity = json!({
+            "objectClassName": "entity",
+            "roles": ["registrar"],
+            "vcardArray": ["vcard", [["version", {}, "text", "4.0"], ["fn", {}, "text", name]]],
+        });
+        if let Some(id) = first(&["registrar iana id"]) {
+            entity["publicIds"] = json!([{"type": "IANA Registrar ID", "identifier": id}]);
+        }
+        if let Some(email) = first(&["registrar abuse contact email"]) {
+            entity["entities"] = json!([{
+                "objectClassName": "entity",
+                "roles": ["abuse"],
+                "vcardArray": ["vcard", [
+                    ["version", {}, "text", "4.0"],
+                    ["email", {}, "text", email],
+                ]],
+            }]);
+        }
+        entity
+    });
+
+    let domain = first(DOMAIN);
+    if domain.is_none() && registrar.is_none() && events.is_empty() && nameservers.is_empty() {
+        return None;
+    }
+    let mut record = json!({
+        "objectClassName": "domain",
+        "ldhName": domain.unwrap_or(query).trim_end_matches('.').to_lowercase(),
+        "_meta": {"source": "whois"},
+    });
+    if !status.is_empty() {
+        record["status"] = json!(status);
+    }
+    if !events.is_empty() {
+        record["events"] = json!(events);
+    }
+    if let Some(registrar) = registrar {
+        record["entities"] = json!([registrar]);
+    }
+    if !nameservers.is_empty() {
+        record["nameservers"] = nameservers
+            .iter()
+            .map(|ns| json!({"objectClassName": "nameserver", "ldhName": ns}))
+            .collect();
+    }
+    if let Some(dnssec) = first(&["dnssec"]) {
+        let signed = dnssec.to_lowercase().starts_with("signed");
+        record["secureDNS"] = json!({"delegationSigned": signed});
+    }
+    Some(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, referral};
+    use serde_json::json;
+
+    #[test]
+    fn referrals_from_iana_registries_and_arin() {
+        assert_eq!(
+            referral("% IANA WHOIS server\nrefer:        whois.verisign-grs.com\n").as_deref(),
+            Some("whois.verisign-grs.com")
+        );
+        assert_eq!(
+            referral("Domain Name: EXAMPLE.COM\nRegistrar WHOIS Server: WHOIS.Example.NET\n")
+                .as_deref(),
+            Some("whois.example.net")
+        );
+        assert_eq!(
+            referral("ReferralServer:  whois://whois.ripe.net:43\n").as_deref(),
+            Some("whois.ripe.net")
+        );
+        assert_eq!(
+            referral("ReferralServer:  rwhois://rwhois.example.net:4321\n"),
+            None
+        );
+        assert_eq!(referral("Registrar WHOIS Server:\n"), None);
+    }
+
+    #[test]
+    fn parses_icann_style_domain_records() {
+        let text = "\
+   Domain Name: EXAMPLE.COM
+   Registrar: Example Registrar, Inc.
+   Registrar IANA ID: 9999
+   Registrar Abuse Contact Email: abuse@registrar.test
+   Creation Date: 1995-08-14T04:00:00Z
+   Registry Expiry Date: 2030-08-13T04:00:00Z
+   Domain Status: clientTransferProhibited https://icann.org/epp#clientTransferProhibited
+   Domain Status: clientTransferProhibited https://icann.org/epp#clientTransferProhibited
+   Name Server: NS2.EXAMPLE.COM
+   Name Server: NS1.EXAMPLE.COM
+   DNSSEC: signedDelegation
+>>> Last update of whois database: 2025-01-01T00:00:00Z <<<
+   Name Server: NS3.EXAMPLE.COM
+";
+        let record = parse(text, "example.com").unwrap();
+        assert_eq!(record["ldhName"], "example.com");
+        assert_eq!(record["status"], json!(["client transfer prohibited"]));
+        assert_eq!(
+            record["events"],
+            json!([
+                {"eventAction": "registration", "eventDate": "1995-08-14T04:00:00Z"},
+                {"eventAction": "expiration", "eventDate": "2030-08-13T04:00:00Z"},
+            ])
+        );
+        assert_eq!(
+            record["nameservers"],
+            json!([
+                {"objectClassName": "nameserver", "ldhName": "ns1.example.com"},
+                {"objectClassName": "nameserver", "ldhName": "ns2.example.com"},
+            ])
+        );
+        let registrar = &record["entities"][0];
+        assert_eq!(registrar["publicIds"][0]["identifier"], "9999");
+        assert_eq!(
+            registrar["entities"][0]["vcardArray"][1][1][3],
+            "abuse@registrar.test"
+        );
+        assert_eq!(record["secureDNS"]["delegationSigned"], true);
+        assert_eq!(record["_meta"]["source"], "whois");
+    }
+
+    #[test]
+    fn parses_indented_blocks_and_other_date_formats() {
+        let text = "\
+    Domain name:
+        example.co.uk
+
+    Registrar:
+        Example Ltd [Tag = EXAMPLE]
+
+    Relevant dates:
+        Registered on: 14-Aug-1996
+        Expiry date:  14-Aug-2030
+
+    Name servers:
+        ns1.example.co.uk.   192.0.2.53
+        ns2.example.co.uk
+";
+        let record = parse(text, "example.co.uk").unwrap();
+        assert_eq!(record["ldhName"], "example.co.uk");
+        assert_eq!(
+            record["entities"][0]["vcardArray"][1][1][3],
+            "Example Ltd [Tag = EXAMPLE]"
+        );
+        assert_eq!(record["events"][0]["eventDate"], "1996-08-14T00:00:00Z");
+        assert_eq!(record["events"][1]["eventDate"], "2030-08-14T00:00:00Z");
+        assert_eq!(record["nameservers"][0]["ldhName"], "ns1.example.co.uk");
+        assert_eq!(record["nameservers"][1]["ldhName"], "ns2.example.co.uk");
+    }
+
+    #[test]
+    fn parses_rir_networks_and_autnums() {
+        let network = parse(
+            "inetnum:        193.0.0.0 - 193.0.7.255\nnetname:        RIPE-NCC\ncountry:        nl\n",
+            "193.0.0.1",
+        )
+        .unwrap();
+        assert_eq!(network["objectClassName"], "ip network");
+        assert_eq!(network["startAddress"], "193.0.0.0");
+        assert_eq!(network["endAddress"], "193.0.7.255");
+        assert_eq!(network["ipVersion"], "v4");
+        assert_eq!(network["country"], "NL");
+
+        let autnum = parse(
+            "ASNumber:       13335\nASName:         CLOUDFLARENET\nRegDate:        2010-07-14\n",
+            "AS13335",
+        )
+        .unwrap();
+        assert_eq!(autnum["objectClassName"], "autnum");
+        assert_eq!(autnum["startAutnum"], 13335);
+        assert_eq!(autnum["endAutnum"], 13335);
+        assert_eq!(autnum["events"][0]["eventDate"], "2010-07-14T00:00:00Z");
+    }
+
+    #[test]
+    fn no_match_replies_and_handles_parse_to_nothing() {
+        assert_eq!(parse("No match for \"NOPE.COM\".\n", "nope.com"), None);
+        assert_eq!(parse("handle: ABC123-ARIN\n", "ABC123-ARIN"), None);
+    }
+}
@@ -0,0 +1,117 @@
+//! Dedicated stdout writer for NDJSON bulk output.
+//!
+//! At high concurrency, formatting each record with `println!` (a fresh
+//! `String` plus a stdout lock per line) dominates. While the writer runs,
+//! `--format json` records are serialized into pooled buffers on the calling
+//! task and handed over a bounded channel to a thread that owns a large
+//! `BufWriter`; emptied buffers go back to the pool, so steady-state output
+//! allocates nothing per record.
+
+use serde_json::Value;
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::thread::JoinHandle;
+
+/// Records in flight between serializers and the writer thread.
+const QUEUE: usize = 4096;
+/// Buffers kept for reuse.
+const POOL_MAX: usize = 256;
+const OUT_CAPACITY: usize = 1 << 20;
+
+struct Writer {
+    tx: SyncSender<Vec<u8>>,
+    handle: JoinHandle<io::Result<()>>,
+}
+
+static WRITER: Mutex<Option<Writer>> = Mutex::new(None);
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static POOL: OnceLock<Mutex<Vec<Vec<u8>>>> = OnceLock::new();
+
+fn pool() -> std::sync::MutexGuard<'static, Vec<Vec<u8>>> {
+    POOL.get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Start the writer thread; JSON records go through it until [`finish`] or
+/// until the returned guard drops, which also covers error returns.
+pub fn start() -> Running {
+    let (tx, rx) = sync_channel::<Vec<u8>>(QUEUE);
+    let handle = std::thread::spawn(move || {
+        let mut out = BufWriter::with_capacity(OUT_CAPACITY, io::stdout());
+        for mut buf in rx {
+            out.write_all(&buf)?;
+            buf.clear();
+            let mut pool = pool();
+            if pool.len() < POOL_MAX {
+                pool.push(buf);
+            }
+        }
+        out.flush()
+    });
+    *WRITER.lock().unwrap_or_else(PoisonError::into_inner) = Some(Writer { tx, handle });
+    ACTIVE.store(true, Ordering::Release);
+    Running
+}
+
+/// Queue one compact JSON line; `false` when the writer isn't running or
+/// has stopped (the caller prints directly).
+pub fn write(json: &Value) -> bool {
+    if !ACTIVE.load(Ordering::Acquire) {
+        return false;
+    }
+    let mut buf = pool().pop().unwrap_or_default();
+    if serde_json::to_writer(&mut buf, json).is_err() {
+        return false;
+    }
+    buf.push(b'\n');
+    // cloned so a full queue doesn't block other writers on the lock
+    let Some(tx) = WRITER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .map(|w| w.tx.clone())
+    else {
+        return false;
+    };
+    let sent = match tx.try_send(buf) {
+        Ok(()) => Ok(()),
+        // the queue is full: wait without stalling the runtime's other tasks
+        Err(TrySendError::Full(buf)) => {
+            tokio::task::block_in_place(|| tx.send(buf)).map_err(|_| ())
+        }
+        Err(TrySendError::Disconnected(_)) => Err(()),
+    };
+    if sent.is_err() && ACTIVE.swap(false, Ordering::AcqRel) {
+        // the thread died on a write error, which `finish` returns
+        eprintln!("output writer stopped; printing records directly");
+    }
+    sent.is_ok()
+}
+
+/// Drain the queue, flush, and stop the thread.
+pub fn finish() -> io::Result<()> {
+    ACTIVE.store(false, Ordering::Release);
+    let Some(Writer { tx, handle }) = WRITER.lock().unwrap_or_else(PoisonError::into_inner).take()
+    else {
+        return Ok(());
+    };
+    drop(tx);
+    handle
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("output writer panicked")))
+}
+
+/// Finishes the writer when dropped unless [`finish`] already ran.
+#[must_use = "the writer is finished when this is dropped"]
+pub struct Running;
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        if let Err(e) = finish() {
+            eprintln!("output writer failed: {e}");
+        }
+    }
+}